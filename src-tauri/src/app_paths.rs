@@ -0,0 +1,392 @@
+use once_cell::sync::{Lazy, OnceCell};
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+/// Directory name used under the platform data directory.
+pub const APP_DIR_NAME: &str = "RosChronoTrace";
+
+/// Environment variable that overrides the data root.
+pub const DATA_DIR_ENV: &str = "CHRONOTRACE_DATA_DIR";
+
+/// Marker file next to the executable that enables portable mode.
+pub const PORTABLE_MARKER: &str = "portable";
+
+const DATABASE_FILE_NAME: &str = "database.db";
+const SCREENSHOTS_DIR_NAME: &str = "screenshots";
+
+static CLI_OVERRIDE: OnceCell<DataRootOverride> = OnceCell::new();
+static DATA_ROOT: OnceCell<PathBuf> = OnceCell::new();
+/// Directory of the selected profile; `None` means the data root itself.
+static PROFILE_ROOT: Lazy<RwLock<Option<PathBuf>>> = Lazy::new(|| RwLock::new(None));
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DataRootOverride {
+    Directory(PathBuf),
+    Portable,
+}
+
+/// Read `--data-dir <path>` / `--data-dir=<path>` and `--portable` from the command line.
+///
+/// Must run before the first call to [`data_root`]; later calls are ignored.
+pub fn init_from_args<I>(args: I)
+where
+    I: IntoIterator<Item = String>,
+{
+    if let Some(parsed) = parse_cli_override(args) {
+        let _ = CLI_OVERRIDE.set(parsed);
+    }
+}
+
+fn parse_cli_override<I>(args: I) -> Option<DataRootOverride>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut args = args.into_iter();
+    let mut result = None;
+
+    while let Some(arg) = args.next() {
+        if arg == "--portable" {
+            result = Some(DataRootOverride::Portable);
+        } else if arg == "--data-dir" {
+            if let Some(path) = args.next() {
+                result = Some(DataRootOverride::Directory(PathBuf::from(path)));
+            }
+        } else if let Some(path) = arg.strip_prefix("--data-dir=") {
+            result = Some(DataRootOverride::Directory(PathBuf::from(path)));
+        }
+    }
+
+    result
+}
+
+/// Pick the data root. Precedence: CLI flag, environment variable,
+/// portable marker next to the executable, platform local data directory.
+fn resolve_data_root(
+    cli: Option<&DataRootOverride>,
+    env: Option<OsString>,
+    exe_dir: Option<&Path>,
+    platform_dir: Option<PathBuf>,
+) -> Result<PathBuf, String> {
+    let portable_dir = || {
+        exe_dir
+            .map(|dir| dir.join("data"))
+            .ok_or_else(|| "Failed to locate executable directory for portable mode".to_string())
+    };
+
+    match cli {
+        Some(DataRootOverride::Directory(path)) => return Ok(path.clone()),
+        Some(DataRootOverride::Portable) => return portable_dir(),
+        None => {}
+    }
+
+    if let Some(path) = env.filter(|v| !v.is_empty()) {
+        return Ok(PathBuf::from(path));
+    }
+
+    if exe_dir.is_some_and(|dir| dir.join(PORTABLE_MARKER).exists()) {
+        return portable_dir();
+    }
+
+    platform_dir
+        .map(|dir| dir.join(APP_DIR_NAME))
+        .ok_or_else(|| "Failed to get local data directory".to_string())
+}
+
+/// Whether the data root comes from the CLI, the environment or portable mode
+/// rather than the platform default.
+fn is_overridden(
+    cli: Option<&DataRootOverride>,
+    env: Option<OsString>,
+    exe_dir: Option<&Path>,
+) -> bool {
+    cli.is_some()
+        || env.is_some_and(|v| !v.is_empty())
+        || exe_dir.is_some_and(|dir| dir.join(PORTABLE_MARKER).exists())
+}
+
+fn current_exe_dir() -> Option<PathBuf> {
+    std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf))
+}
+
+/// Root directory of all app data. The default profile keeps its database,
+/// settings and screenshots here directly; see [`profile_root`].
+pub fn data_root() -> Result<PathBuf, String> {
+    let root = DATA_ROOT.get_or_try_init(|| {
+        let exe_dir = current_exe_dir();
+        resolve_data_root(
+            CLI_OVERRIDE.get(),
+            std::env::var_os(DATA_DIR_ENV),
+            exe_dir.as_deref(),
+            dirs::data_local_dir(),
+        )
+    })?;
+
+    fs::create_dir_all(root).map_err(|e| format!("Failed to create data directory: {}", e))?;
+    Ok(root.clone())
+}
+
+/// Directory of the active profile: its database, settings and default
+/// screenshot and backup storage.
+pub fn profile_root() -> Result<PathBuf, String> {
+    let selected = PROFILE_ROOT
+        .read()
+        .map_err(|e| format!("Failed to lock profile root: {}", e))?
+        .clone();
+    let Some(root) = selected else {
+        return data_root();
+    };
+
+    fs::create_dir_all(&root).map_err(|e| format!("Failed to create profile directory: {}", e))?;
+    Ok(root)
+}
+
+/// Point [`profile_root`] at `root`, or back at the data root with `None`.
+pub fn set_profile_root(root: Option<PathBuf>) -> Result<(), String> {
+    let mut selected = PROFILE_ROOT
+        .write()
+        .map_err(|e| format!("Failed to lock profile root: {}", e))?;
+    *selected = root;
+    Ok(())
+}
+
+/// Where releases before the shared resolver kept the database.
+fn legacy_data_dir() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join("AppData").join("Local").join(APP_DIR_NAME))
+}
+
+/// Move `database.db` (and its WAL/SHM side files) and the screenshots from
+/// the legacy location into `data_root` if the new root does not have a
+/// database yet.
+///
+/// Skipped when the data root is overridden: a `--data-dir`, environment or
+/// portable root starts with its own data rather than a copy of the
+/// installed one.
+///
+/// Returns the legacy path that was migrated, if any.
+pub fn migrate_legacy_database(data_root: &Path) -> Result<Option<PathBuf>, String> {
+    if is_overridden(
+        CLI_OVERRIDE.get(),
+        std::env::var_os(DATA_DIR_ENV),
+        current_exe_dir().as_deref(),
+    ) {
+        return Ok(None);
+    }
+
+    match legacy_data_dir() {
+        Some(legacy_dir) => migrate_database_between(&legacy_dir, data_root),
+        None => Ok(None),
+    }
+}
+
+fn migrate_database_between(
+    legacy_dir: &Path,
+    data_root: &Path,
+) -> Result<Option<PathBuf>, String> {
+    let legacy_db = legacy_dir.join(DATABASE_FILE_NAME);
+    let target_db = data_root.join(DATABASE_FILE_NAME);
+
+    if !legacy_db.exists() || target_db.exists() || same_dir(legacy_dir, data_root) {
+        return Ok(None);
+    }
+
+    fs::create_dir_all(data_root).map_err(|e| format!("Failed to create data directory: {}", e))?;
+
+    // Stored screenshot paths are relative to the root, so they move along.
+    let legacy_screenshots = legacy_dir.join(SCREENSHOTS_DIR_NAME);
+    if legacy_screenshots.is_dir() {
+        move_dir(&legacy_screenshots, &data_root.join(SCREENSHOTS_DIR_NAME))?;
+    }
+
+    // Side files first so the main file only appears once everything is in place.
+    for suffix in ["-wal", "-shm"] {
+        let name = format!("{}{}", DATABASE_FILE_NAME, suffix);
+        let source = legacy_dir.join(&name);
+        if source.exists() {
+            move_file(&source, &data_root.join(&name))?;
+        }
+    }
+    move_file(&legacy_db, &target_db)?;
+
+    Ok(Some(legacy_db))
+}
+
+fn same_dir(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// Rename `source` to `target`, copying and deleting it across file systems.
+fn move_file(source: &Path, target: &Path) -> Result<(), String> {
+    if fs::rename(source, target).is_ok() {
+        return Ok(());
+    }
+
+    fs::copy(source, target).map_err(|e| {
+        format!(
+            "Failed to copy {} to {}: {}",
+            source.display(),
+            target.display(),
+            e
+        )
+    })?;
+    fs::remove_file(source).map_err(|e| format!("Failed to remove {}: {}", source.display(), e))?;
+    Ok(())
+}
+
+/// Move a directory tree into `target`, keeping files that already exist
+/// there. Emptied source directories are removed.
+fn move_dir(source: &Path, target: &Path) -> Result<(), String> {
+    if !target.exists() && fs::rename(source, target).is_ok() {
+        return Ok(());
+    }
+
+    fs::create_dir_all(target)
+        .map_err(|e| format!("Failed to create {}: {}", target.display(), e))?;
+    let entries =
+        fs::read_dir(source).map_err(|e| format!("Failed to read {}: {}", source.display(), e))?;
+    for entry in entries {
+        let entry = entry.map_err(|e| format!("Failed to read {}: {}", source.display(), e))?;
+        let from = entry.path();
+        let to = target.join(entry.file_name());
+        if from.is_dir() {
+            move_dir(&from, &to)?;
+        } else if !to.exists() {
+            move_file(&from, &to)?;
+        }
+    }
+    // Still holds files the target already had
+    let _ = fs::remove_dir(source);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_cli_override() {
+        assert_eq!(parse_cli_override(args(&["app"])), None);
+        assert_eq!(
+            parse_cli_override(args(&["app", "--data-dir", "/tmp/ct"])),
+            Some(DataRootOverride::Directory(PathBuf::from("/tmp/ct")))
+        );
+        assert_eq!(
+            parse_cli_override(args(&["app", "--data-dir=/tmp/ct"])),
+            Some(DataRootOverride::Directory(PathBuf::from("/tmp/ct")))
+        );
+        assert_eq!(
+            parse_cli_override(args(&["app", "--portable"])),
+            Some(DataRootOverride::Portable)
+        );
+    }
+
+    #[test]
+    fn test_resolve_precedence() {
+        let exe_dir = tempfile::TempDir::new().unwrap();
+        let platform = Some(PathBuf::from("/platform"));
+        let cli = DataRootOverride::Directory(PathBuf::from("/cli"));
+
+        let resolved = resolve_data_root(
+            Some(&cli),
+            Some("/env".into()),
+            Some(exe_dir.path()),
+            platform.clone(),
+        );
+        assert_eq!(resolved.unwrap(), PathBuf::from("/cli"));
+
+        let resolved = resolve_data_root(
+            None,
+            Some("/env".into()),
+            Some(exe_dir.path()),
+            platform.clone(),
+        );
+        assert_eq!(resolved.unwrap(), PathBuf::from("/env"));
+
+        let resolved = resolve_data_root(None, None, Some(exe_dir.path()), platform.clone());
+        assert_eq!(
+            resolved.unwrap(),
+            PathBuf::from("/platform").join(APP_DIR_NAME)
+        );
+
+        fs::write(exe_dir.path().join(PORTABLE_MARKER), "").unwrap();
+        let resolved = resolve_data_root(None, None, Some(exe_dir.path()), platform);
+        assert_eq!(resolved.unwrap(), exe_dir.path().join("data"));
+    }
+
+    #[test]
+    fn test_is_overridden() {
+        let exe_dir = tempfile::TempDir::new().unwrap();
+        let cli = DataRootOverride::Directory(PathBuf::from("/cli"));
+
+        assert!(!is_overridden(None, None, Some(exe_dir.path())));
+        assert!(!is_overridden(None, Some("".into()), Some(exe_dir.path())));
+        assert!(is_overridden(Some(&cli), None, Some(exe_dir.path())));
+        assert!(is_overridden(
+            None,
+            Some("/env".into()),
+            Some(exe_dir.path())
+        ));
+
+        fs::write(exe_dir.path().join(PORTABLE_MARKER), "").unwrap();
+        assert!(is_overridden(None, None, Some(exe_dir.path())));
+    }
+
+    #[test]
+    fn test_migrate_legacy_database() {
+        let legacy = tempfile::TempDir::new().unwrap();
+        let root = tempfile::TempDir::new().unwrap();
+        let new_root = root.path().join("data");
+        fs::write(legacy.path().join("database.db"), b"db").unwrap();
+        fs::write(legacy.path().join("database.db-wal"), b"wal").unwrap();
+        let shots = legacy.path().join("screenshots").join("2024-01-01");
+        fs::create_dir_all(&shots).unwrap();
+        fs::write(shots.join("a.png"), b"png").unwrap();
+        // Screenshots taken since the new root was first used are kept
+        let new_shots = new_root.join("screenshots").join("2024-01-01");
+        fs::create_dir_all(&new_shots).unwrap();
+        fs::write(new_shots.join("b.png"), b"new").unwrap();
+
+        let migrated = migrate_database_between(legacy.path(), &new_root).unwrap();
+        assert_eq!(migrated, Some(legacy.path().join("database.db")));
+        assert_eq!(fs::read(new_root.join("database.db")).unwrap(), b"db");
+        assert_eq!(fs::read(new_root.join("database.db-wal")).unwrap(), b"wal");
+        assert_eq!(
+            fs::read(new_root.join("screenshots/2024-01-01/a.png")).unwrap(),
+            b"png"
+        );
+        assert_eq!(fs::read(new_shots.join("b.png")).unwrap(), b"new");
+        assert!(!legacy.path().join("database.db").exists());
+        assert!(!legacy.path().join("screenshots").exists());
+
+        // Second run finds the database already in place and does nothing.
+        assert_eq!(
+            migrate_database_between(legacy.path(), &new_root).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_migrate_keeps_existing_target() {
+        let legacy = tempfile::TempDir::new().unwrap();
+        let root = tempfile::TempDir::new().unwrap();
+        fs::write(legacy.path().join("database.db"), b"old").unwrap();
+        fs::write(root.path().join("database.db"), b"new").unwrap();
+
+        assert_eq!(
+            migrate_database_between(legacy.path(), root.path()).unwrap(),
+            None
+        );
+        assert_eq!(fs::read(root.path().join("database.db")).unwrap(), b"new");
+        assert!(legacy.path().join("database.db").exists());
+    }
+}
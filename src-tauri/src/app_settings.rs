@@ -39,16 +39,238 @@ impl ScreenshotSettings {
     }
 }
 
-fn get_data_directory() -> Result<PathBuf, String> {
-    let local_data =
-        dirs::data_local_dir().ok_or_else(|| "Failed to get local data directory".to_string())?;
-    let data_dir = local_data.join("RosChronoTrace");
-    fs::create_dir_all(&data_dir).map_err(|e| format!("Failed to create data directory: {}", e))?;
-    Ok(data_dir)
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+#[serde(default)]
+pub struct BackupSettings {
+    pub enabled: bool,
+    pub directory: Option<String>,
+    pub keep_count: u32,
+}
+
+impl Default for BackupSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            directory: None,
+            keep_count: 7,
+        }
+    }
+}
+
+impl BackupSettings {
+    pub fn normalized(mut self) -> Self {
+        self.keep_count = self.keep_count.clamp(1, 365);
+        self.directory = self
+            .directory
+            .map(|p| p.trim().to_string())
+            .filter(|p| !p.is_empty());
+        self
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+#[serde(default)]
+pub struct TrashSettings {
+    /// Days a deleted time entry stays restorable; 0 keeps it until purged by hand.
+    pub retention_days: u32,
+}
+
+impl Default for TrashSettings {
+    fn default() -> Self {
+        Self { retention_days: 30 }
+    }
+}
+
+impl TrashSettings {
+    pub fn normalized(mut self) -> Self {
+        self.retention_days = self.retention_days.min(3650);
+        self
+    }
+}
+
+/// How long captured data is kept, in days per kind; 0 keeps it forever.
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+#[serde(default)]
+pub struct RetentionSettings {
+    pub process_segments_days: u32,
+    pub window_activity_days: u32,
+    /// Applies to both the screenshot rows and their image files.
+    pub screenshots_days: u32,
+    pub idle_periods_days: u32,
+}
+
+impl Default for RetentionSettings {
+    fn default() -> Self {
+        Self {
+            process_segments_days: 30,
+            window_activity_days: 0,
+            screenshots_days: 0,
+            idle_periods_days: 0,
+        }
+    }
+}
+
+impl RetentionSettings {
+    pub fn normalized(mut self) -> Self {
+        self.process_segments_days = self.process_segments_days.min(3650);
+        self.window_activity_days = self.window_activity_days.min(3650);
+        self.screenshots_days = self.screenshots_days.min(3650);
+        self.idle_periods_days = self.idle_periods_days.min(3650);
+        self
+    }
+}
+
+/// When the daily optimize + vacuum may run. WAL checkpoints run hourly regardless.
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+#[serde(default)]
+pub struct MaintenanceSettings {
+    pub enabled: bool,
+    /// Local hour (0-23) the idle window starts.
+    pub idle_start_hour: u32,
+    /// Local hour the idle window ends, exclusive; may be before the start to wrap midnight.
+    pub idle_end_hour: u32,
+}
+
+impl Default for MaintenanceSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            idle_start_hour: 2,
+            idle_end_hour: 6,
+        }
+    }
+}
+
+impl MaintenanceSettings {
+    pub fn normalized(mut self) -> Self {
+        self.idle_start_hour = self.idle_start_hour.min(23);
+        self.idle_end_hour = self.idle_end_hour.min(23);
+        self
+    }
+}
+
+/// Local hours that count as working time, e.g. for finding untracked gaps.
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+#[serde(default)]
+pub struct WorkingHoursSettings {
+    /// Local hour (0-23) the working day starts.
+    pub start_hour: u32,
+    /// Local hour (1-24) the working day ends, exclusive.
+    pub end_hour: u32,
+    /// Working weekdays, 1 = Monday through 7 = Sunday.
+    pub days: Vec<u32>,
+}
+
+impl Default for WorkingHoursSettings {
+    fn default() -> Self {
+        Self {
+            start_hour: 9,
+            end_hour: 18,
+            days: vec![1, 2, 3, 4, 5],
+        }
+    }
+}
+
+impl WorkingHoursSettings {
+    pub fn normalized(mut self) -> Self {
+        self.start_hour = self.start_hour.min(23);
+        self.end_hour = self.end_hour.clamp(self.start_hour + 1, 24);
+        self.days.retain(|day| (1..=7).contains(day));
+        self.days.sort_unstable();
+        self.days.dedup();
+        self
+    }
+}
+
+/// What happens to a timer still running when the app starts again.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "snake_case")]
+pub enum TimerStartupPolicy {
+    /// Keep counting; the entry covers the time the app was closed.
+    Resume,
+    /// Close the entry when the app was last seen running it.
+    #[default]
+    Stop,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Type)]
+#[serde(default)]
+pub struct TimerSettings {
+    pub on_startup: TimerStartupPolicy,
+}
+
+/// Passphrase metadata for encryption at rest. Never holds the key itself.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EncryptionSettings {
+    pub enabled: bool,
+    /// Base64 Argon2 salt.
+    pub salt: Option<String>,
+    /// Sealed known value used to check a passphrase.
+    pub check: Option<String>,
+    /// Salt and check of the passphrase being replaced, kept until a
+    /// passphrase change has rekeyed the database. If the change is cut
+    /// short, unlocking accepts either passphrase.
+    pub previous_salt: Option<String>,
+    pub previous_check: Option<String>,
+}
+
+/// On-disk layout of settings.json.
+///
+/// Screenshot settings stay at the top level so files written by earlier
+/// versions still load; newer sections live under their own key.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct SettingsFile {
+    #[serde(flatten)]
+    screenshot: ScreenshotSettings,
+    #[serde(default)]
+    backup: BackupSettings,
+    #[serde(default)]
+    trash: TrashSettings,
+    #[serde(default)]
+    retention: RetentionSettings,
+    #[serde(default)]
+    maintenance: MaintenanceSettings,
+    #[serde(default)]
+    timer: TimerSettings,
+    #[serde(default)]
+    working_hours: WorkingHoursSettings,
+    #[serde(default)]
+    encryption: EncryptionSettings,
 }
 
 fn settings_file_path() -> Result<PathBuf, String> {
-    Ok(get_data_directory()?.join("settings.json"))
+    Ok(crate::app_paths::profile_root()?.join("settings.json"))
+}
+
+fn load_settings_file() -> Result<SettingsFile, String> {
+    let path = settings_file_path()?;
+    if !path.exists() {
+        return Ok(SettingsFile::default());
+    }
+
+    let raw = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read settings file {}: {}", path.display(), e))?;
+    serde_json::from_str(&raw)
+        .map_err(|e| format!("Failed to parse settings file {}: {}", path.display(), e))
+}
+
+fn update_settings_file<F>(update: F) -> Result<(), String>
+where
+    F: FnOnce(&mut SettingsFile),
+{
+    let mut file = load_settings_file()?;
+    update(&mut file);
+
+    let path = settings_file_path()?;
+    let serialized = serde_json::to_string_pretty(&file)
+        .map_err(|e| format!("Failed to serialize settings: {}", e))?;
+    // Write aside and rename so a crash never leaves a half-written file.
+    let partial = path.with_extension("json.partial");
+    fs::write(&partial, serialized)
+        .map_err(|e| format!("Failed to write settings file {}: {}", partial.display(), e))?;
+    fs::rename(&partial, &path)
+        .map_err(|e| format!("Failed to write settings file {}: {}", path.display(), e))
 }
 
 pub fn resolve_screenshot_storage_dir(configured_storage_dir: Option<String>) -> Result<PathBuf, String> {
@@ -62,7 +284,7 @@ pub fn resolve_screenshot_storage_dir(configured_storage_dir: Option<String>) ->
         return Ok(path);
     }
 
-    Ok(get_data_directory()?.join("screenshots"))
+    Ok(crate::app_paths::profile_root()?.join("screenshots"))
 }
 
 pub fn resolve_screenshot_file_path(stored_path: &str) -> Result<PathBuf, String> {
@@ -71,7 +293,7 @@ pub fn resolve_screenshot_file_path(stored_path: &str) -> Result<PathBuf, String
         return Ok(candidate.to_path_buf());
     }
 
-    let mut absolute = get_data_directory()?;
+    let mut absolute = crate::app_paths::profile_root()?;
     for segment in stored_path.split('/') {
         if segment.is_empty() || segment == "." {
             continue;
@@ -82,28 +304,96 @@ pub fn resolve_screenshot_file_path(stored_path: &str) -> Result<PathBuf, String
 }
 
 pub fn load_screenshot_settings() -> Result<ScreenshotSettings, String> {
-    let path = settings_file_path()?;
-    if !path.exists() {
-        return Ok(ScreenshotSettings::default());
-    }
-
-    let raw = fs::read_to_string(&path)
-        .map_err(|e| format!("Failed to read settings file {}: {}", path.display(), e))?;
-    let settings: ScreenshotSettings = serde_json::from_str(&raw)
-        .map_err(|e| format!("Failed to parse settings file {}: {}", path.display(), e))?;
-    Ok(settings.normalized())
+    Ok(load_settings_file()?.screenshot.normalized())
 }
 
 pub fn save_screenshot_settings(settings: ScreenshotSettings) -> Result<ScreenshotSettings, String> {
     let normalized = settings.normalized();
-    let path = settings_file_path()?;
-    let serialized = serde_json::to_string_pretty(&normalized)
-        .map_err(|e| format!("Failed to serialize settings: {}", e))?;
-    fs::write(&path, serialized)
-        .map_err(|e| format!("Failed to write settings file {}: {}", path.display(), e))?;
+    update_settings_file(|file| file.screenshot = normalized.clone())?;
+    Ok(normalized)
+}
+
+pub fn load_backup_settings() -> Result<BackupSettings, String> {
+    Ok(load_settings_file()?.backup.normalized())
+}
+
+pub fn save_backup_settings(settings: BackupSettings) -> Result<BackupSettings, String> {
+    let normalized = settings.normalized();
+    update_settings_file(|file| file.backup = normalized.clone())?;
+    Ok(normalized)
+}
+
+pub fn load_trash_settings() -> Result<TrashSettings, String> {
+    Ok(load_settings_file()?.trash.normalized())
+}
+
+pub fn save_trash_settings(settings: TrashSettings) -> Result<TrashSettings, String> {
+    let normalized = settings.normalized();
+    update_settings_file(|file| file.trash = normalized.clone())?;
+    Ok(normalized)
+}
+
+pub fn load_retention_settings() -> Result<RetentionSettings, String> {
+    Ok(load_settings_file()?.retention.normalized())
+}
+
+pub fn save_retention_settings(
+    settings: RetentionSettings,
+) -> Result<RetentionSettings, String> {
+    let normalized = settings.normalized();
+    update_settings_file(|file| file.retention = normalized.clone())?;
+    Ok(normalized)
+}
+
+pub fn load_maintenance_settings() -> Result<MaintenanceSettings, String> {
+    Ok(load_settings_file()?.maintenance.normalized())
+}
+
+pub fn save_maintenance_settings(
+    settings: MaintenanceSettings,
+) -> Result<MaintenanceSettings, String> {
+    let normalized = settings.normalized();
+    update_settings_file(|file| file.maintenance = normalized.clone())?;
     Ok(normalized)
 }
 
+pub fn load_timer_settings() -> Result<TimerSettings, String> {
+    Ok(load_settings_file()?.timer)
+}
+
+pub fn save_timer_settings(settings: TimerSettings) -> Result<TimerSettings, String> {
+    update_settings_file(|file| file.timer = settings.clone())?;
+    Ok(settings)
+}
+
+pub fn load_working_hours_settings() -> Result<WorkingHoursSettings, String> {
+    Ok(load_settings_file()?.working_hours.normalized())
+}
+
+pub fn save_working_hours_settings(
+    settings: WorkingHoursSettings,
+) -> Result<WorkingHoursSettings, String> {
+    let normalized = settings.normalized();
+    update_settings_file(|file| file.working_hours = normalized.clone())?;
+    Ok(normalized)
+}
+
+pub fn load_encryption_settings() -> Result<EncryptionSettings, String> {
+    Ok(load_settings_file()?.encryption)
+}
+
+pub fn save_encryption_settings(settings: EncryptionSettings) -> Result<(), String> {
+    update_settings_file(|file| file.encryption = settings)
+}
+
+/// Backup folder from settings, defaulting to `<profile root>/backups`.
+pub fn resolve_backup_dir(settings: &BackupSettings) -> Result<PathBuf, String> {
+    match settings.directory.as_deref() {
+        Some(dir) => Ok(PathBuf::from(dir)),
+        None => Ok(crate::app_paths::profile_root()?.join("backups")),
+    }
+}
+
 #[tauri::command]
 #[specta::specta]
 pub async fn get_screenshot_settings_cmd() -> Result<ScreenshotSettings, String> {
@@ -134,3 +424,82 @@ pub async fn resolve_screenshot_file_path_cmd(stored_path: String) -> Result<Str
     Ok(resolved.to_string_lossy().to_string())
 }
 
+#[tauri::command]
+#[specta::specta]
+pub async fn get_backup_settings_cmd() -> Result<BackupSettings, String> {
+    load_backup_settings()
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn update_backup_settings_cmd(
+    settings: BackupSettings,
+) -> Result<BackupSettings, String> {
+    save_backup_settings(settings)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn get_trash_settings_cmd() -> Result<TrashSettings, String> {
+    load_trash_settings()
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn update_trash_settings_cmd(settings: TrashSettings) -> Result<TrashSettings, String> {
+    save_trash_settings(settings)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn get_retention_settings_cmd() -> Result<RetentionSettings, String> {
+    load_retention_settings()
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn update_retention_settings_cmd(
+    settings: RetentionSettings,
+) -> Result<RetentionSettings, String> {
+    save_retention_settings(settings)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn get_maintenance_settings_cmd() -> Result<MaintenanceSettings, String> {
+    load_maintenance_settings()
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn update_maintenance_settings_cmd(
+    settings: MaintenanceSettings,
+) -> Result<MaintenanceSettings, String> {
+    save_maintenance_settings(settings)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn get_timer_settings_cmd() -> Result<TimerSettings, String> {
+    load_timer_settings()
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn update_timer_settings_cmd(settings: TimerSettings) -> Result<TimerSettings, String> {
+    save_timer_settings(settings)
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn get_working_hours_settings_cmd() -> Result<WorkingHoursSettings, String> {
+    load_working_hours_settings()
+}
+
+#[tauri::command]
+#[specta::specta]
+pub async fn update_working_hours_settings_cmd(
+    settings: WorkingHoursSettings,
+) -> Result<WorkingHoursSettings, String> {
+    save_working_hours_settings(settings)
+}
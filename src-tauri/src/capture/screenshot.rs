@@ -11,12 +11,14 @@ use std::path::PathBuf;
 /// Returns the relative file path on success
 pub async fn capture_screenshot() -> Result<String, String> {
     let start_time = std::time::Instant::now();
+    // Never write a plaintext screenshot while encrypted data is locked.
+    crate::crypto::current_keys()?;
     let screenshot_settings = crate::app_settings::load_screenshot_settings()
         .unwrap_or_default()
         .normalized();
 
     // Get data directory
-    let data_dir = get_data_directory()?;
+    let data_dir = crate::app_paths::profile_root()?;
 
     // Check disk space before capture (skip if less than 100MB free)
     if !has_sufficient_disk_space(&data_dir)? {
@@ -83,9 +85,9 @@ pub async fn capture_screenshot() -> Result<String, String> {
     }
 
     // Insert screenshot record into database
-    if let Err(e) =
-        crate::data::with_db(|conn| crate::data::insert_screenshot(conn, timestamp, &stored_path))
-    {
+    if let Err(e) = crate::data::with_db_write(|conn| {
+        crate::data::insert_screenshot(conn, timestamp, &stored_path)
+    }) {
         eprintln!("Failed to insert screenshot record: {}", e);
     }
 
@@ -161,7 +163,8 @@ fn capture_windows_screenshot(
                     self.quality as f32,
                 )?;
 
-                fs::write(&self.file_path, &webp_bytes)
+                let file_bytes = crate::crypto::protect_file_bytes(webp_bytes)?;
+                fs::write(&self.file_path, &file_bytes)
                     .map_err(|e| format!("Failed to write screenshot: {}", e))?;
 
                 self.captured = true;
@@ -202,18 +205,6 @@ fn capture_windows_screenshot(
     Ok(())
 }
 
-/// Get the data directory path
-fn get_data_directory() -> Result<PathBuf, String> {
-    let local_data =
-        dirs::data_local_dir().ok_or_else(|| "Failed to get local data directory".to_string())?;
-
-    let data_dir = local_data.join("RosChronoTrace");
-
-    fs::create_dir_all(&data_dir).map_err(|e| format!("Failed to create data directory: {}", e))?;
-
-    Ok(data_dir)
-}
-
 /// Check if there is sufficient disk space (at least 100MB free)
 fn has_sufficient_disk_space(path: &PathBuf) -> Result<bool, String> {
     #[cfg(target_os = "windows")]
@@ -240,14 +231,18 @@ fn has_sufficient_disk_space(path: &PathBuf) -> Result<bool, String> {
 #[specta::specta]
 pub async fn get_screenshot_for_time(
     timestamp: i64,
-) -> Result<crate::types::ScreenshotInfo, String> {
-    crate::data::with_db(|conn| {
+) -> crate::data::AppResult<crate::types::ScreenshotInfo> {
+    crate::data::with_db_read(|conn| {
         let file_path = crate::data::get_screenshot_near_time(conn, timestamp, 300000)?; // 5 minutes tolerance
         let data_url = file_path
             .as_ref()
             .and_then(|stored_path| {
-                let absolute_path = resolve_absolute_screenshot_path(stored_path)?;
-                match fs::read(&absolute_path) {
+                let absolute_path =
+                    crate::app_settings::resolve_screenshot_file_path(stored_path).ok()?;
+                let bytes = fs::read(&absolute_path)
+                    .map_err(|e| e.to_string())
+                    .and_then(crate::crypto::reveal_file_bytes);
+                match bytes {
                     Ok(bytes) => Some(format!(
                         "data:{};base64,{}",
                         infer_mime_type_from_path(stored_path),
@@ -292,15 +287,6 @@ fn infer_mime_type_from_path(relative_path: &str) -> &'static str {
     }
 }
 
-fn resolve_absolute_screenshot_path(stored_path: &str) -> Option<PathBuf> {
-    let path = PathBuf::from(stored_path);
-    if path.is_absolute() {
-        return Some(path);
-    }
-    let local_data = dirs::data_local_dir()?;
-    Some(local_data.join("RosChronoTrace").join(stored_path))
-}
-
 #[cfg(target_os = "windows")]
 fn encode_webp_with_size_target(
     rgba: Vec<u8>,
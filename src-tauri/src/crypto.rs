@@ -0,0 +1,254 @@
+// Passphrase-based encryption at rest.
+//
+// One Argon2id derivation yields two independent 256-bit keys: a raw
+// SQLCipher key for `database.db` and an AES-256-GCM key for screenshot
+// files. Keys only ever live in memory; settings.json stores the salt and a
+// small sealed check value used to verify the passphrase.
+
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use argon2::Argon2;
+use base64::Engine;
+use once_cell::sync::Lazy;
+use rusqlite::Connection;
+use std::sync::{Arc, RwLock};
+
+/// Prefix of every encrypted screenshot file. Plain WebP files start with `RIFF`.
+const FILE_MAGIC: &[u8] = b"CTENC1";
+const NONCE_LEN: usize = 12;
+const SALT_LEN: usize = 16;
+
+/// Plaintext sealed into settings.json to check a passphrase before use.
+const CHECK_PLAINTEXT: &[u8] = b"chronotrace-passphrase-check";
+
+/// Keys derived from the passphrase.
+pub struct KeyMaterial {
+    database: [u8; 32],
+    files: [u8; 32],
+}
+
+impl KeyMaterial {
+    /// Derive keys from `passphrase` and a base64 `salt`.
+    pub fn derive(passphrase: &str, salt: &str) -> Result<Self, String> {
+        let salt = base64::engine::general_purpose::STANDARD
+            .decode(salt)
+            .map_err(|e| format!("Invalid encryption salt: {}", e))?;
+
+        let mut output = [0u8; 64];
+        Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), &salt, &mut output)
+            .map_err(|e| format!("Failed to derive encryption key: {}", e))?;
+
+        let mut keys = Self {
+            database: [0u8; 32],
+            files: [0u8; 32],
+        };
+        keys.database.copy_from_slice(&output[..32]);
+        keys.files.copy_from_slice(&output[32..]);
+        Ok(keys)
+    }
+
+    /// Value for `PRAGMA key` / `PRAGMA rekey`, using the raw key so SQLCipher
+    /// skips its own key derivation.
+    pub fn sqlcipher_key(&self) -> String {
+        let hex: String = self.database.iter().map(|b| format!("{:02x}", b)).collect();
+        format!("x'{}'", hex)
+    }
+
+    /// Encrypt `plaintext` into the screenshot file format.
+    pub fn seal(&self, plaintext: &[u8]) -> Result<Vec<u8>, String> {
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&self.files));
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = cipher
+            .encrypt(&nonce, plaintext)
+            .map_err(|_| "Failed to encrypt file".to_string())?;
+
+        let mut sealed = Vec::with_capacity(FILE_MAGIC.len() + NONCE_LEN + ciphertext.len());
+        sealed.extend_from_slice(FILE_MAGIC);
+        sealed.extend_from_slice(&nonce);
+        sealed.extend_from_slice(&ciphertext);
+        Ok(sealed)
+    }
+
+    /// Decrypt bytes produced by [`KeyMaterial::seal`].
+    pub fn open(&self, sealed: &[u8]) -> Result<Vec<u8>, String> {
+        if !is_sealed(sealed) || sealed.len() < FILE_MAGIC.len() + NONCE_LEN {
+            return Err("File is not encrypted".to_string());
+        }
+        let (nonce, ciphertext) = sealed[FILE_MAGIC.len()..].split_at(NONCE_LEN);
+        let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&self.files));
+        cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| "Failed to decrypt file: wrong passphrase or damaged file".to_string())
+    }
+
+    /// Sealed check value to store next to the salt.
+    pub fn check_value(&self) -> Result<String, String> {
+        Ok(base64::engine::general_purpose::STANDARD.encode(self.seal(CHECK_PLAINTEXT)?))
+    }
+
+    /// Whether these keys were derived from the passphrase that produced `check`.
+    pub fn matches(&self, check: &str) -> bool {
+        base64::engine::general_purpose::STANDARD
+            .decode(check)
+            .ok()
+            .and_then(|sealed| self.open(&sealed).ok())
+            .is_some_and(|plaintext| plaintext == CHECK_PLAINTEXT)
+    }
+}
+
+/// Fresh random salt, base64 encoded.
+pub fn generate_salt() -> String {
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    base64::engine::general_purpose::STANDARD.encode(salt)
+}
+
+/// Whether `bytes` are in the encrypted file format.
+pub fn is_sealed(bytes: &[u8]) -> bool {
+    bytes.starts_with(FILE_MAGIC)
+}
+
+/// Where the app stands with respect to encryption.
+enum KeyState {
+    Disabled,
+    Locked,
+    Unlocked(Arc<KeyMaterial>),
+}
+
+static KEY_STATE: Lazy<RwLock<KeyState>> = Lazy::new(|| RwLock::new(KeyState::Disabled));
+
+/// Load the encryption mode from settings. Encrypted installs start locked.
+pub fn init_from_settings() -> Result<(), String> {
+    let settings = crate::app_settings::load_encryption_settings()?;
+    let mut state = KEY_STATE
+        .write()
+        .map_err(|e| format!("Failed to lock key state: {}", e))?;
+    *state = if settings.enabled {
+        KeyState::Locked
+    } else {
+        KeyState::Disabled
+    };
+    Ok(())
+}
+
+/// Keep `keys` in memory for the rest of the session; `None` turns encryption off.
+pub fn set_keys(keys: Option<Arc<KeyMaterial>>) -> Result<(), String> {
+    let mut state = KEY_STATE
+        .write()
+        .map_err(|e| format!("Failed to lock key state: {}", e))?;
+    *state = match keys {
+        Some(keys) => KeyState::Unlocked(keys),
+        None => KeyState::Disabled,
+    };
+    Ok(())
+}
+
+/// Keys of the unlocked session, `None` when encryption is off.
+///
+/// Fails while encryption is on but the passphrase has not been entered yet.
+pub fn current_keys() -> Result<Option<Arc<KeyMaterial>>, String> {
+    let state = KEY_STATE
+        .read()
+        .map_err(|e| format!("Failed to lock key state: {}", e))?;
+    match &*state {
+        KeyState::Disabled => Ok(None),
+        KeyState::Locked => Err("Encrypted data is locked; enter the passphrase first".to_string()),
+        KeyState::Unlocked(keys) => Ok(Some(keys.clone())),
+    }
+}
+
+pub fn is_locked() -> bool {
+    KEY_STATE
+        .read()
+        .map(|state| matches!(*state, KeyState::Locked))
+        .unwrap_or(false)
+}
+
+/// Key a freshly opened connection to the main database. Must run before
+/// any other statement; a no-op when encryption is off.
+pub fn apply_database_key(conn: &Connection) -> Result<(), String> {
+    if let Some(keys) = current_keys()? {
+        key_connection(conn, &keys)?;
+    }
+    Ok(())
+}
+
+pub fn key_connection(conn: &Connection, keys: &KeyMaterial) -> Result<(), String> {
+    conn.pragma_update(None, "key", keys.sqlcipher_key())
+        .map_err(|e| format!("Failed to apply database key: {}", e))
+}
+
+/// Encrypt screenshot bytes for writing if encryption is on.
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+pub fn protect_file_bytes(bytes: Vec<u8>) -> Result<Vec<u8>, String> {
+    match current_keys()? {
+        Some(keys) => keys.seal(&bytes),
+        None => Ok(bytes),
+    }
+}
+
+/// Decrypt screenshot bytes read from disk; plain files pass through.
+pub fn reveal_file_bytes(bytes: Vec<u8>) -> Result<Vec<u8>, String> {
+    if !is_sealed(&bytes) {
+        return Ok(bytes);
+    }
+    match current_keys()? {
+        Some(keys) => keys.open(&bytes),
+        None => Err("Screenshot is encrypted but encryption is disabled".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_round_trip() {
+        let keys = KeyMaterial::derive("correct horse", &generate_salt()).unwrap();
+        let sealed = keys.seal(b"RIFF....WEBP").unwrap();
+
+        assert!(is_sealed(&sealed));
+        assert!(!is_sealed(b"RIFF....WEBP"));
+        assert_eq!(keys.open(&sealed).unwrap(), b"RIFF....WEBP");
+    }
+
+    #[test]
+    fn test_wrong_passphrase_is_rejected() {
+        let salt = generate_salt();
+        let keys = KeyMaterial::derive("correct horse", &salt).unwrap();
+        let other = KeyMaterial::derive("battery staple", &salt).unwrap();
+        let check = keys.check_value().unwrap();
+
+        assert!(keys.matches(&check));
+        assert!(!other.matches(&check));
+        assert!(other.open(&keys.seal(b"secret").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_database_key_is_required() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("encrypted.db");
+        let keys = KeyMaterial::derive("correct horse", &generate_salt()).unwrap();
+
+        {
+            let conn = Connection::open(&path).unwrap();
+            key_connection(&conn, &keys).unwrap();
+            conn.execute_batch("CREATE TABLE t (v TEXT); INSERT INTO t VALUES ('hidden');")
+                .unwrap();
+        }
+
+        let unkeyed = Connection::open(&path).unwrap();
+        assert!(unkeyed
+            .query_row("SELECT COUNT(*) FROM t", [], |row| row.get::<_, i64>(0))
+            .is_err());
+
+        let keyed = Connection::open(&path).unwrap();
+        key_connection(&keyed, &keys).unwrap();
+        let value: String = keyed
+            .query_row("SELECT v FROM t", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(value, "hidden");
+    }
+}
@@ -0,0 +1,487 @@
+use crate::crypto::KeyMaterial;
+use crate::data::database::{latest_schema_version, MIGRATIONS};
+use crate::data::encryption;
+use crate::data::error::{db_err, io_err};
+use crate::data::{AppError, AppResult};
+use crate::types::BackupInfo;
+use chrono::{Local, TimeZone};
+use rusqlite::backup::Backup;
+use rusqlite::{Connection, OpenFlags, OptionalExtension};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Prefix of rotated backup files: `database-YYYYMMDD-HHMMSS.db`.
+const BACKUP_PREFIX: &str = "database-";
+const BACKUP_EXTENSION: &str = "db";
+
+/// Safety copy written right before a restore replaces the live database.
+const PRE_RESTORE_PREFIX: &str = "pre-restore-";
+/// Safety copies kept besides the rotated backups; older ones are deleted.
+const PRE_RESTORE_KEEP_COUNT: u32 = 3;
+
+/// Pages copied per backup step; the pause between steps lets writers in.
+const PAGES_PER_STEP: i32 = 256;
+const STEP_PAUSE: Duration = Duration::from_millis(10);
+
+/// Copy the database behind `conn` into `dest` using SQLite's online backup API.
+///
+/// Safe to run while other connections keep writing: the backup restarts
+/// transparently if the source changes mid-copy.
+pub fn backup_to_file(conn: &Connection, dest: &Path) -> AppResult<BackupInfo> {
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent).map_err(io_err("Failed to create backup directory"))?;
+    }
+
+    // Write next to the target first so a half-written file is never mistaken for a backup.
+    let partial = dest.with_extension("partial");
+    {
+        let mut target =
+            Connection::open(&partial).map_err(db_err("Failed to create backup file"))?;
+        // Backups of an encrypted database stay encrypted with the same key.
+        crate::crypto::apply_database_key(&target)?;
+        let backup = Backup::new(conn, &mut target).map_err(db_err("Failed to start backup"))?;
+        backup
+            .run_to_completion(PAGES_PER_STEP, STEP_PAUSE, None)
+            .map_err(db_err("Failed to copy database"))?;
+    }
+    fs::rename(&partial, dest).map_err(io_err("Failed to finalize backup"))?;
+
+    backup_info(dest)
+}
+
+/// Write a timestamped backup into `dir`, then delete all but the newest `keep_count`.
+pub fn create_rotated_backup(
+    conn: &Connection,
+    dir: &Path,
+    keep_count: u32,
+) -> AppResult<BackupInfo> {
+    let file_name = format!(
+        "{}{}.{}",
+        BACKUP_PREFIX,
+        Local::now().format("%Y%m%d-%H%M%S"),
+        BACKUP_EXTENSION
+    );
+    let info = backup_to_file(conn, &dir.join(file_name))?;
+    rotate_backups(dir, keep_count)?;
+    Ok(info)
+}
+
+/// Rotated backups in `dir`, newest first.
+pub fn list_backups(dir: &Path) -> AppResult<Vec<BackupInfo>> {
+    let mut backups = rotated_backup_paths(dir, BACKUP_PREFIX)?
+        .iter()
+        .map(|path| backup_info(path))
+        .collect::<AppResult<Vec<_>>>()?;
+    backups.sort_by(|a, b| b.path.cmp(&a.path));
+    Ok(backups)
+}
+
+/// Delete rotated backups beyond the newest `keep_count`. Returns how many were removed.
+pub fn rotate_backups(dir: &Path, keep_count: u32) -> AppResult<usize> {
+    remove_all_but_newest(dir, BACKUP_PREFIX, keep_count)
+}
+
+fn remove_all_but_newest(dir: &Path, prefix: &str, keep_count: u32) -> AppResult<usize> {
+    let mut paths = rotated_backup_paths(dir, prefix)?;
+    // Timestamped names sort chronologically.
+    paths.sort();
+    paths.reverse();
+
+    let mut removed = 0;
+    for stale in paths.iter().skip(keep_count as usize) {
+        fs::remove_file(stale).map_err(|e| {
+            AppError::from(e).context(format!("Failed to remove old backup {}", stale.display()))
+        })?;
+        removed += 1;
+    }
+    Ok(removed)
+}
+
+/// Whether the newest rotated backup in `dir` is older than `max_age`.
+pub fn is_backup_due(dir: &Path, now_ms: i64, max_age: Duration) -> AppResult<bool> {
+    let newest = list_backups(dir)?.into_iter().map(|b| b.created_at).max();
+    Ok(match newest {
+        Some(created_at) => now_ms - created_at >= max_age.as_millis() as i64,
+        None => true,
+    })
+}
+
+/// A backup that passed [`validate_backup`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ValidatedBackup {
+    /// Schema version recorded in the backup.
+    pub schema_version: i64,
+    /// Whether it opened with the current key rather than unencrypted.
+    pub encrypted: bool,
+}
+
+/// Check that `path` is an intact ChronoTrace database this build can open.
+///
+/// The backup is tried unencrypted first, then with `keys`, so copies taken
+/// before encryption was turned on still restore.
+pub fn validate_backup(path: &Path, keys: Option<&KeyMaterial>) -> AppResult<ValidatedBackup> {
+    if !path.is_file() {
+        return Err(invalid_backup(format!(
+            "Backup file {} does not exist",
+            path.display()
+        )));
+    }
+
+    let (conn, encrypted) = match open_backup(path, None) {
+        Ok(conn) => (conn, false),
+        Err(plain) => match keys {
+            Some(keys) => {
+                let conn = open_backup(path, Some(keys)).map_err(|keyed| {
+                    invalid_backup(format!(
+                        "Backup could not be read unencrypted ({}) or with the current \
+                         passphrase ({}); it may be encrypted under an older passphrase",
+                        plain, keyed
+                    ))
+                })?;
+                (conn, true)
+            }
+            None => {
+                return Err(invalid_backup(format!(
+                    "Backup could not be read unencrypted: {}",
+                    plain
+                )))
+            }
+        },
+    };
+
+    let has_table = |name: &str| -> AppResult<bool> {
+        let count: i64 = conn
+            .query_row(
+                "SELECT count(*) FROM sqlite_master WHERE type='table' AND name=?1",
+                [name],
+                |row| row.get(0),
+            )
+            .map_err(db_err("Failed to inspect backup schema"))?;
+        Ok(count == 1)
+    };
+
+    if !has_table("time_entries")? {
+        return Err(invalid_backup("Backup is not a ChronoTrace database"));
+    }
+
+    let version = if has_table("schema_migrations")? {
+        conn.query_row("SELECT MAX(version) FROM schema_migrations", [], |row| {
+            row.get::<_, Option<i64>>(0)
+        })
+        .optional()
+        .map_err(db_err("Failed to read backup schema version"))?
+        .flatten()
+        .unwrap_or(0)
+    } else {
+        // Pre-runner databases are upgraded by run_migrations after the swap.
+        MIGRATIONS.first().map(|m| m.version).unwrap_or(0)
+    };
+
+    if version > latest_schema_version() {
+        return Err(invalid_backup(format!(
+            "Backup schema version {} is newer than this build supports ({})",
+            version,
+            latest_schema_version()
+        )));
+    }
+
+    Ok(ValidatedBackup {
+        schema_version: version,
+        encrypted,
+    })
+}
+
+/// Open `path` read-only, keyed with `keys` if given, and run SQLite's integrity check.
+fn open_backup(path: &Path, keys: Option<&KeyMaterial>) -> Result<Connection, String> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| format!("failed to open: {}", e))?;
+    if let Some(keys) = keys {
+        crate::crypto::key_connection(&conn, keys)?;
+    }
+
+    let integrity: String = conn
+        .query_row("PRAGMA integrity_check", [], |row| row.get(0))
+        .map_err(|e| format!("not a readable database: {}", e))?;
+    if integrity != "ok" {
+        return Err(format!("integrity check failed: {}", integrity));
+    }
+    Ok(conn)
+}
+
+/// Write a copy of the unencrypted backup at `path` to `dest`, encrypted with `keys`.
+///
+/// The export attaches `dest` through its connection, which therefore has to be
+/// writable; it runs against a scratch copy so the backup itself is never opened
+/// for writing and gains no `-wal`/`-shm` files.
+pub fn encrypted_copy(path: &Path, dest: &Path, keys: &KeyMaterial) -> AppResult<()> {
+    let scratch = tempfile::TempDir::new().map_err(io_err("Failed to create scratch directory"))?;
+    let source = scratch.path().join("backup.db");
+    fs::copy(path, &source).map_err(io_err("Failed to copy backup"))?;
+    let conn = Connection::open(&source)
+        .map_err(|e| invalid_backup(format!("Failed to open backup: {}", e)))?;
+    encryption::export_database(&conn, dest, Some(keys))
+}
+
+/// Replace the database file at `db_path` with a copy of `backup_path`.
+///
+/// All connections to `db_path` must be closed. Stale WAL/SHM files are
+/// removed so SQLite does not replay them onto the restored file.
+pub fn replace_database_file(backup_path: &Path, db_path: &Path) -> AppResult<()> {
+    let staged = db_path.with_extension("restoring");
+    fs::copy(backup_path, &staged).map_err(io_err("Failed to stage backup"))?;
+
+    for suffix in ["-wal", "-shm"] {
+        let side_file = PathBuf::from(format!("{}{}", db_path.display(), suffix));
+        if side_file.exists() {
+            fs::remove_file(&side_file).map_err(|e| {
+                AppError::from(e).context(format!("Failed to remove {}", side_file.display()))
+            })?;
+        }
+    }
+
+    fs::rename(&staged, db_path).map_err(io_err("Failed to swap in backup"))?;
+    Ok(())
+}
+
+/// Write the safety copy taken before a restore into `dir`, keeping only the
+/// newest [`PRE_RESTORE_KEEP_COUNT`] of them.
+pub fn create_pre_restore_backup(conn: &Connection, dir: &Path) -> AppResult<BackupInfo> {
+    let path = dir.join(format!(
+        "{}{}.{}",
+        PRE_RESTORE_PREFIX,
+        Local::now().format("%Y%m%d-%H%M%S"),
+        BACKUP_EXTENSION
+    ));
+    let info = backup_to_file(conn, &path)?;
+    remove_all_but_newest(dir, PRE_RESTORE_PREFIX, PRE_RESTORE_KEEP_COUNT)?;
+    Ok(info)
+}
+
+fn rotated_backup_paths(dir: &Path, prefix: &str) -> AppResult<Vec<PathBuf>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let entries = fs::read_dir(dir).map_err(io_err("Failed to read backup directory"))?;
+
+    let mut paths = Vec::new();
+    for entry in entries {
+        let path = entry
+            .map_err(io_err("Failed to read backup directory"))?
+            .path();
+        let is_backup = path.file_name().and_then(|n| n.to_str()).is_some_and(|n| {
+            n.starts_with(prefix) && n.ends_with(&format!(".{}", BACKUP_EXTENSION))
+        });
+        if is_backup && path.is_file() {
+            paths.push(path);
+        }
+    }
+    Ok(paths)
+}
+
+fn backup_info(path: &Path) -> AppResult<BackupInfo> {
+    let metadata = fs::metadata(path).map_err(|e| {
+        AppError::from(e).context(format!("Failed to read backup metadata {}", path.display()))
+    })?;
+
+    Ok(BackupInfo {
+        path: path.to_string_lossy().to_string(),
+        created_at: parse_backup_timestamp(path).unwrap_or_else(|| {
+            metadata
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .map(|d| d.as_millis() as i64)
+                .unwrap_or(0)
+        }),
+        size_bytes: metadata.len() as i64,
+    })
+}
+
+/// Timestamp encoded in a rotated backup's file name, in local time.
+fn parse_backup_timestamp(path: &Path) -> Option<i64> {
+    let stem = path.file_stem()?.to_str()?;
+    let raw = stem.strip_prefix(BACKUP_PREFIX)?;
+    let naive = chrono::NaiveDateTime::parse_from_str(raw, "%Y%m%d-%H%M%S").ok()?;
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|dt| dt.timestamp_millis())
+}
+
+fn invalid_backup(message: impl Into<String>) -> AppError {
+    AppError::validation("invalid_backup", message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::database::run_migrations;
+
+    fn seeded_db(dir: &Path) -> Connection {
+        let conn = Connection::open(dir.join("live.db")).unwrap();
+        run_migrations(&conn).unwrap();
+        conn.pragma_update(None, "journal_mode", "WAL").unwrap();
+        conn.execute(
+            "INSERT INTO time_entries (start_time, end_time, label) VALUES (?1, ?2, ?3)",
+            rusqlite::params![1000, 2000, "Backed up"],
+        )
+        .unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_backup_copies_live_database() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let conn = seeded_db(dir.path());
+
+        let dest = dir.path().join("backups").join("copy.db");
+        let info = backup_to_file(&conn, &dest).unwrap();
+        assert!(info.size_bytes > 0);
+
+        let copy = Connection::open(&dest).unwrap();
+        let label: String = copy
+            .query_row("SELECT label FROM time_entries", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(label, "Backed up");
+        assert_eq!(
+            validate_backup(&dest, None).unwrap().schema_version,
+            latest_schema_version()
+        );
+    }
+
+    #[test]
+    fn test_rotation_keeps_newest() {
+        let dir = tempfile::TempDir::new().unwrap();
+        for stamp in ["20260101-080000", "20260102-080000", "20260103-080000"] {
+            fs::write(dir.path().join(format!("database-{}.db", stamp)), b"x").unwrap();
+        }
+        fs::write(dir.path().join("pre-restore-20250101-000000.db"), b"x").unwrap();
+
+        assert_eq!(rotate_backups(dir.path(), 2).unwrap(), 1);
+
+        let remaining = list_backups(dir.path()).unwrap();
+        assert_eq!(remaining.len(), 2);
+        assert!(remaining[0].path.ends_with("database-20260103-080000.db"));
+        assert!(dir.path().join("pre-restore-20250101-000000.db").exists());
+    }
+
+    #[test]
+    fn test_pre_restore_copies_are_capped() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let conn = seeded_db(dir.path());
+        let backups = dir.path().join("b");
+        fs::create_dir_all(&backups).unwrap();
+        for stamp in ["20250101-000000", "20250102-000000", "20250103-000000"] {
+            fs::write(backups.join(format!("pre-restore-{}.db", stamp)), b"x").unwrap();
+        }
+        fs::write(backups.join("database-20250101-000000.db"), b"x").unwrap();
+
+        let info = create_pre_restore_backup(&conn, &backups).unwrap();
+
+        let remaining = rotated_backup_paths(&backups, PRE_RESTORE_PREFIX).unwrap();
+        assert_eq!(remaining.len(), PRE_RESTORE_KEEP_COUNT as usize);
+        assert!(Path::new(&info.path).exists());
+        assert!(!backups.join("pre-restore-20250101-000000.db").exists());
+        assert!(backups.join("database-20250101-000000.db").exists());
+    }
+
+    #[test]
+    fn test_backup_due() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let day = Duration::from_secs(24 * 3600);
+        assert!(is_backup_due(dir.path(), 0, day).unwrap());
+
+        let conn = seeded_db(dir.path());
+        let info = create_rotated_backup(&conn, &dir.path().join("b"), 3).unwrap();
+        let backups = dir.path().join("b");
+        assert!(!is_backup_due(&backups, info.created_at + 1000, day).unwrap());
+        assert!(is_backup_due(&backups, info.created_at + day.as_millis() as i64, day).unwrap());
+    }
+
+    #[test]
+    fn test_validate_rejects_garbage_and_newer_schema() {
+        let dir = tempfile::TempDir::new().unwrap();
+
+        let garbage = dir.path().join("garbage.db");
+        fs::write(&garbage, b"definitely not sqlite").unwrap();
+        assert_eq!(
+            validate_backup(&garbage, None).unwrap_err().code,
+            "invalid_backup"
+        );
+
+        let conn = seeded_db(dir.path());
+        conn.execute(
+            "INSERT INTO schema_migrations (version, name, applied_at) VALUES (?1, 'future', 0)",
+            [latest_schema_version() + 1],
+        )
+        .unwrap();
+        let future = dir.path().join("future.db");
+        backup_to_file(&conn, &future).unwrap();
+        assert!(validate_backup(&future, None)
+            .unwrap_err()
+            .message
+            .contains("newer than this build supports"));
+    }
+
+    #[test]
+    fn test_validate_tries_plain_and_current_key() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let conn = seeded_db(dir.path());
+        let keys = KeyMaterial::derive("current", &crate::crypto::generate_salt()).unwrap();
+        let other = KeyMaterial::derive("older", &crate::crypto::generate_salt()).unwrap();
+
+        // Taken before encryption was turned on
+        let plain = dir.path().join("plain.db");
+        backup_to_file(&conn, &plain).unwrap();
+        assert!(!validate_backup(&plain, Some(&keys)).unwrap().encrypted);
+
+        let keyed = dir.path().join("keyed.db");
+        encryption::export_database(&conn, &keyed, Some(&keys)).unwrap();
+        assert!(validate_backup(&keyed, Some(&keys)).unwrap().encrypted);
+
+        let error = validate_backup(&keyed, Some(&other)).unwrap_err();
+        assert_eq!(error.code, "invalid_backup");
+        assert!(error.message.contains("current passphrase"));
+        assert!(validate_backup(&keyed, None)
+            .unwrap_err()
+            .message
+            .contains("unencrypted"));
+
+        // A plain backup restored into an encrypted profile gets encrypted first,
+        // leaving the backup itself and its folder as they were, even in WAL mode
+        Connection::open(&plain)
+            .unwrap()
+            .pragma_update(None, "journal_mode", "WAL")
+            .unwrap();
+        let original = fs::read(&plain).unwrap();
+        let staged = dir.path().join("staged.db");
+        encrypted_copy(&plain, &staged, &keys).unwrap();
+        assert!(validate_backup(&staged, Some(&keys)).unwrap().encrypted);
+        assert_eq!(fs::read(&plain).unwrap(), original);
+        for suffix in ["-wal", "-shm"] {
+            assert!(!PathBuf::from(format!("{}{}", plain.display(), suffix)).exists());
+        }
+    }
+
+    #[test]
+    fn test_replace_database_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let conn = seeded_db(dir.path());
+        let backup = dir.path().join("snapshot.db");
+        backup_to_file(&conn, &backup).unwrap();
+
+        conn.execute("DELETE FROM time_entries", []).unwrap();
+        drop(conn);
+
+        let live = dir.path().join("live.db");
+        replace_database_file(&backup, &live).unwrap();
+
+        let restored = Connection::open(&live).unwrap();
+        let count: i64 = restored
+            .query_row("SELECT COUNT(*) FROM time_entries", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 1);
+    }
+}
@@ -0,0 +1,305 @@
+use crate::data::error::db_err;
+use crate::data::journal;
+use crate::data::time_entries::update_time_entry_impl;
+use crate::data::{AppError, AppResult};
+use crate::types::{BulkEditChange, TimeEntry, TimeEntryFilter, TimeEntryPatch, TimeEntryUpdate};
+use rusqlite::Connection;
+
+/// Entries matching `filter`, ordered by start time.
+pub fn find_matching_entries(
+    conn: &Connection,
+    filter: &TimeEntryFilter,
+) -> AppResult<Vec<TimeEntry>> {
+    let mut conditions = vec!["e.deleted_at IS NULL"];
+    let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+
+    if let Some(start_time) = filter.start_time {
+        conditions.push("e.end_time > ?");
+        params.push(Box::new(start_time));
+    }
+    if let Some(end_time) = filter.end_time {
+        conditions.push("e.start_time < ?");
+        params.push(Box::new(end_time));
+    }
+    if let Some(ref label) = filter.label_contains {
+        conditions.push("instr(lower(e.label), lower(?)) > 0");
+        params.push(Box::new(label.clone()));
+    }
+    if let Some(category_id) = filter.category_id {
+        conditions.push("e.category_id = ?");
+        params.push(Box::new(category_id));
+    }
+    if let Some(ref process_name) = filter.process_name {
+        conditions.push(
+            "EXISTS (SELECT 1 FROM process_segments p
+                     WHERE p.process_name = ? AND p.start_time < e.end_time AND p.end_time > e.start_time)",
+        );
+        params.push(Box::new(process_name.clone()));
+    }
+
+    if params.is_empty() {
+        return Err(AppError::validation(
+            "empty_filter",
+            "A bulk edit needs at least one filter condition",
+        )
+        .with_field("filter"));
+    }
+
+    let sql = format!(
+        "SELECT e.id, e.start_time, e.end_time, e.label, e.color, e.category_id, e.notes, e.project_id
+         FROM time_entries e
+         WHERE {}
+         ORDER BY e.start_time",
+        conditions.join(" AND ")
+    );
+    let mut stmt = conn
+        .prepare(&sql)
+        .map_err(db_err("Failed to prepare bulk edit query"))?;
+
+    let entries = stmt
+        .query_map(
+            rusqlite::params_from_iter(params.iter().map(|p| p.as_ref())),
+            |row| {
+                Ok(TimeEntry {
+                    id: row.get(0)?,
+                    start_time: row.get(1)?,
+                    end_time: row.get(2)?,
+                    label: row.get(3)?,
+                    color: row.get(4)?,
+                    category_id: row.get(5)?,
+                    notes: row.get(6)?,
+                    project_id: row.get(7)?,
+                })
+            },
+        )
+        .map_err(db_err("Failed to query entries for bulk edit"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db_err("Failed to map row"))?;
+
+    Ok(entries)
+}
+
+/// What `apply_bulk_edit` would change, without changing anything.
+///
+/// Runs the edit and rolls it back, so a preview fails exactly when applying would.
+pub fn preview_bulk_edit(
+    conn: &Connection,
+    filter: &TimeEntryFilter,
+    patch: &TimeEntryPatch,
+) -> AppResult<Vec<BulkEditChange>> {
+    let tx = conn
+        .unchecked_transaction()
+        .map_err(db_err("Failed to start transaction"))?;
+    let changes = edit_matching(&tx, filter, patch)?;
+    tx.rollback()
+        .map_err(db_err("Failed to roll back bulk edit preview"))?;
+    Ok(changes)
+}
+
+/// Apply `patch` to every entry matching `filter`, all or nothing, as one undo step.
+pub fn apply_bulk_edit(
+    conn: &Connection,
+    filter: &TimeEntryFilter,
+    patch: &TimeEntryPatch,
+) -> AppResult<Vec<BulkEditChange>> {
+    let tx = conn
+        .unchecked_transaction()
+        .map_err(db_err("Failed to start transaction"))?;
+    let changes = edit_matching(&tx, filter, patch)?;
+    tx.commit().map_err(db_err("Failed to commit bulk edit"))?;
+    Ok(changes)
+}
+
+fn edit_matching(
+    conn: &Connection,
+    filter: &TimeEntryFilter,
+    patch: &TimeEntryPatch,
+) -> AppResult<Vec<BulkEditChange>> {
+    let shift_ms = patch.shift_ms.filter(|shift| *shift != 0);
+    if patch.label.is_none()
+        && patch.color.is_none()
+        && patch.category_id.is_none()
+        && shift_ms.is_none()
+    {
+        return Err(AppError::validation("no_updates", "No updates provided"));
+    }
+
+    let mut entries = find_matching_entries(conn, filter)?;
+    // Move the entry furthest along first so shifted entries never collide with each other
+    if shift_ms.is_some_and(|shift| shift > 0) {
+        entries.reverse();
+    }
+
+    let mut changes = journal::grouped(conn, || {
+        let mut changes = Vec::with_capacity(entries.len());
+        for before in entries {
+            let after = update_time_entry_impl(
+                conn,
+                before.id,
+                &TimeEntryUpdate {
+                    start_time: shift_ms.map(|shift| before.start_time + shift),
+                    end_time: shift_ms.map(|shift| before.end_time + shift),
+                    label: patch.label.clone(),
+                    color: patch.color.clone(),
+                    category_id: patch.category_id,
+                    notes: None,
+                    project_id: None,
+                },
+            )?;
+            changes.push(BulkEditChange { before, after });
+        }
+        Ok(changes)
+    })?;
+
+    changes.sort_by_key(|change| change.before.start_time);
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::database::{enable_foreign_keys, run_migrations};
+    use crate::data::time_entries::{create_time_entry_impl, get_time_entries_by_range_impl};
+    use crate::types::TimeEntryInput;
+    use rusqlite::params;
+
+    fn setup_test_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        enable_foreign_keys(&conn).unwrap();
+        run_migrations(&conn).unwrap();
+        conn
+    }
+
+    fn create(conn: &Connection, start_time: i64, end_time: i64, label: &str) -> TimeEntry {
+        create_time_entry_impl(
+            conn,
+            &TimeEntryInput {
+                start_time,
+                end_time,
+                label: label.to_string(),
+                color: None,
+                category_id: None,
+                notes: None,
+                project_id: None,
+            },
+        )
+        .unwrap()
+    }
+
+    fn labels(conn: &Connection) -> Vec<String> {
+        get_time_entries_by_range_impl(conn, 0, i64::MAX)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.label)
+            .collect()
+    }
+
+    #[test]
+    fn test_filters() {
+        let conn = setup_test_db();
+        create(&conn, 1000, 2000, "Standup");
+        create(&conn, 3000, 4000, "Code review");
+        create(&conn, 5000, 6000, "standup notes");
+        conn.execute(
+            "INSERT INTO process_segments (start_time, end_time, process_name)
+             VALUES (?1, ?2, 'code.exe')",
+            params![3500, 5500],
+        )
+        .unwrap();
+
+        let matching = |filter: TimeEntryFilter| -> Vec<String> {
+            find_matching_entries(&conn, &filter)
+                .unwrap()
+                .into_iter()
+                .map(|e| e.label)
+                .collect()
+        };
+
+        assert_eq!(
+            matching(TimeEntryFilter {
+                label_contains: Some("STANDUP".to_string()),
+                ..Default::default()
+            }),
+            vec!["Standup", "standup notes"]
+        );
+        assert_eq!(
+            matching(TimeEntryFilter {
+                start_time: Some(1500),
+                end_time: Some(3500),
+                ..Default::default()
+            }),
+            vec!["Standup", "Code review"]
+        );
+        assert_eq!(
+            matching(TimeEntryFilter {
+                process_name: Some("code.exe".to_string()),
+                ..Default::default()
+            }),
+            vec!["Code review", "standup notes"]
+        );
+
+        let error = find_matching_entries(&conn, &TimeEntryFilter::default()).unwrap_err();
+        assert_eq!(error.code, "empty_filter");
+    }
+
+    #[test]
+    fn test_preview_then_apply_is_one_undo_step() {
+        let conn = setup_test_db();
+        create(&conn, 1000, 2000, "Standup");
+        create(&conn, 3000, 4000, "standup");
+        let filter = TimeEntryFilter {
+            label_contains: Some("standup".to_string()),
+            ..Default::default()
+        };
+        let patch = TimeEntryPatch {
+            label: Some("Daily".to_string()),
+            ..Default::default()
+        };
+
+        let preview = preview_bulk_edit(&conn, &filter, &patch).unwrap();
+        assert_eq!(preview.len(), 2);
+        assert_eq!(preview[0].after.label, "Daily");
+        assert_eq!(labels(&conn), vec!["Standup", "standup"]);
+
+        apply_bulk_edit(&conn, &filter, &patch).unwrap();
+        assert_eq!(labels(&conn), vec!["Daily", "Daily"]);
+
+        journal::undo(&conn).unwrap();
+        assert_eq!(labels(&conn), vec!["Standup", "standup"]);
+    }
+
+    #[test]
+    fn test_shift_moves_adjacent_entries_together() {
+        let conn = setup_test_db();
+        create(&conn, 1000, 2000, "A");
+        create(&conn, 2000, 3000, "B");
+        let blocker = create(&conn, 5000, 6000, "Blocker");
+        let filter = TimeEntryFilter {
+            end_time: Some(4000),
+            ..Default::default()
+        };
+
+        for shift_ms in [1000, -500] {
+            let patch = TimeEntryPatch {
+                shift_ms: Some(shift_ms),
+                ..Default::default()
+            };
+            apply_bulk_edit(&conn, &filter, &patch).unwrap();
+        }
+        let entries = get_time_entries_by_range_impl(&conn, 0, i64::MAX).unwrap();
+        assert_eq!((entries[0].start_time, entries[1].end_time), (1500, 3500));
+
+        // Running into an entry outside the filter rolls back the whole edit
+        let patch = TimeEntryPatch {
+            shift_ms: Some(2000),
+            ..Default::default()
+        };
+        let error = apply_bulk_edit(&conn, &filter, &patch).unwrap_err();
+        assert_eq!(
+            error.details.unwrap().conflicting_entry_ids,
+            vec![blocker.id]
+        );
+        let unchanged = get_time_entries_by_range_impl(&conn, 0, i64::MAX).unwrap();
+        assert_eq!(unchanged[0].start_time, 1500);
+    }
+}
@@ -1,11 +1,13 @@
-use crate::data::AppResult;
+use crate::data::error::db_err;
+use crate::data::journal;
+use crate::data::{AppError, AppResult};
 use crate::types::{Category, CategoryInput};
 use rusqlite::{params, Connection};
 
 pub fn get_categories_impl(conn: &Connection) -> AppResult<Vec<Category>> {
     let mut stmt = conn
         .prepare("SELECT id, name, color FROM categories ORDER BY name")
-        .map_err(|e| format!("Failed to prepare categories query: {}", e))?;
+        .map_err(db_err("Failed to prepare categories query"))?;
 
     let iter = stmt
         .query_map([], |row| {
@@ -15,11 +17,11 @@ pub fn get_categories_impl(conn: &Connection) -> AppResult<Vec<Category>> {
                 color: row.get(2)?,
             })
         })
-        .map_err(|e| format!("Failed to query categories: {}", e))?;
+        .map_err(db_err("Failed to query categories"))?;
 
     let mut categories = Vec::new();
     for category in iter {
-        categories.push(category.map_err(|e| format!("Failed to map category: {}", e))?);
+        categories.push(category.map_err(db_err("Failed to map category"))?);
     }
 
     Ok(categories)
@@ -27,16 +29,23 @@ pub fn get_categories_impl(conn: &Connection) -> AppResult<Vec<Category>> {
 
 pub fn create_category_impl(conn: &Connection, category: &CategoryInput) -> AppResult<Category> {
     if category.name.trim().is_empty() {
-        return Err("Category name cannot be empty".to_string());
+        return Err(empty_category_name());
     }
 
     conn.execute(
         "INSERT INTO categories (name, color) VALUES (?, ?)",
         params![category.name, category.color],
     )
-    .map_err(|e| format!("Failed to insert category: {}", e))?;
+    .map_err(db_err("Failed to insert category"))?;
 
     let id = conn.last_insert_rowid();
+    journal::record(
+        conn,
+        journal::CATEGORIES,
+        id,
+        None,
+        journal::snapshot(conn, journal::CATEGORIES, id)?,
+    )?;
 
     Ok(Category {
         id,
@@ -51,19 +60,23 @@ pub fn update_category_impl(
     category: &CategoryInput,
 ) -> AppResult<Category> {
     if category.name.trim().is_empty() {
-        return Err("Category name cannot be empty".to_string());
+        return Err(empty_category_name());
     }
 
-    let rows_affected = conn
-        .execute(
-            "UPDATE categories SET name = ?1, color = ?2 WHERE id = ?3",
-            params![category.name, category.color, id],
-        )
-        .map_err(|e| format!("Failed to update category: {}", e))?;
+    journal::track(conn, journal::CATEGORIES, id, || {
+        let rows_affected = conn
+            .execute(
+                "UPDATE categories SET name = ?1, color = ?2 WHERE id = ?3",
+                params![category.name, category.color, id],
+            )
+            .map_err(db_err("Failed to update category"))?;
 
-    if rows_affected == 0 {
-        return Err("Category not found".to_string());
-    }
+        if rows_affected == 0 {
+            return Err(category_not_found(id));
+        }
+
+        Ok(())
+    })?;
 
     Ok(Category {
         id,
@@ -72,21 +85,83 @@ pub fn update_category_impl(
     })
 }
 
+/// Time entries and entry templates using the category have `category_id`
+/// cleared by the `ON DELETE SET NULL` foreign keys.
 pub fn delete_category_impl(conn: &Connection, id: i64) -> AppResult<()> {
-    // First, nullify category_id in time_entries that use this category
-    conn.execute(
-        "UPDATE time_entries SET category_id = NULL WHERE category_id = ?",
-        params![id],
-    )
-    .map_err(|e| format!("Failed to clear category from time entries: {}", e))?;
+    let tx = conn
+        .unchecked_transaction()
+        .map_err(db_err("Failed to start transaction"))?;
+    journal::grouped(&tx, || {
+        let category_before = journal::snapshot(&tx, journal::CATEGORIES, id)?;
+
+        // Journal the cleared assignments too so undo can put them back.
+        let mut users = Vec::new();
+        for table in [journal::TIME_ENTRIES, journal::ENTRY_TEMPLATES] {
+            for user_id in ids_using_category(&tx, table, id)? {
+                users.push((table, user_id, journal::snapshot(&tx, table, user_id)?));
+            }
+        }
+
+        let rows_affected = tx
+            .execute("DELETE FROM categories WHERE id = ?", params![id])
+            .map_err(db_err("Failed to delete category"))?;
+
+        if rows_affected == 0 {
+            return Err(category_not_found(id));
+        }
+
+        // Recorded before the category itself so undo recreates it first.
+        for (table, user_id, before) in users {
+            let after = journal::snapshot(&tx, table, user_id)?;
+            journal::record(&tx, table, user_id, before, after)?;
+        }
+        journal::record(&tx, journal::CATEGORIES, id, category_before, None)
+    })?;
+    tx.commit()
+        .map_err(db_err("Failed to commit category deletion"))?;
+    Ok(())
+}
 
-    let rows_affected = conn
-        .execute("DELETE FROM categories WHERE id = ?", params![id])
-        .map_err(|e| format!("Failed to delete category: {}", e))?;
+fn ids_using_category(conn: &Connection, table: &str, id: i64) -> AppResult<Vec<i64>> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT id FROM \"{}\" WHERE category_id = ?",
+            table
+        ))
+        .map_err(db_err("Failed to prepare category usage query"))?;
+    let ids = stmt
+        .query_map(params![id], |row| row.get::<_, i64>(0))
+        .map_err(db_err("Failed to query category usage"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db_err("Failed to query category usage"))?;
+    Ok(ids)
+}
+
+/// Reject references to categories that do not exist.
+pub fn ensure_category_exists(conn: &Connection, id: i64) -> AppResult<()> {
+    let exists: bool = conn
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM categories WHERE id = ?)",
+            params![id],
+            |row| row.get(0),
+        )
+        .map_err(db_err("Failed to look up category"))?;
 
-    if rows_affected == 0 {
-        return Err("Category not found".to_string());
+    if !exists {
+        return Err(AppError::validation(
+            "unknown_category",
+            format!("Category {} does not exist", id),
+        )
+        .with_field("category_id"));
     }
 
     Ok(())
 }
+
+fn empty_category_name() -> AppError {
+    AppError::validation("empty_category_name", "Category name cannot be empty").with_field("name")
+}
+
+fn category_not_found(id: i64) -> AppError {
+    AppError::not_found("category_not_found", format!("Category {} not found", id))
+}
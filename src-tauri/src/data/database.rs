@@ -1,83 +1,736 @@
-﻿use dirs::home_dir;
-use rusqlite::{Connection, Result};
+﻿use crate::data::error::db_err;
+use crate::data::{AppError, AppResult};
+use rusqlite::{Connection, OptionalExtension, Result};
 use std::path::PathBuf;
 
 pub fn get_database_path() -> Result<PathBuf, String> {
-    let data_dir = home_dir()
-        .ok_or("Failed to get home directory")?
-        .join("AppData")
-        .join("Local")
-        .join("RosChronoTrace");
+    Ok(crate::app_paths::profile_root()?.join("database.db"))
+}
+
+/// A numbered schema migration.
+///
+/// Migrations are applied in ascending `version` order, each inside its own
+/// transaction, and recorded in the `schema_migrations` table.
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    pub up: MigrationStep,
+    pub down: Option<MigrationStep>,
+}
+
+/// What a migration executes: a SQL batch or a Rust data migration.
+pub enum MigrationStep {
+    Sql(&'static str),
+    /// Rust step that cannot run inside a transaction, such as `VACUUM`.
+    /// It runs before the version is recorded, so it must be safe to repeat.
+    NonTransactional(fn(&Connection) -> AppResult<()>),
+}
+
+impl MigrationStep {
+    fn apply(&self, conn: &Connection) -> AppResult<()> {
+        match self {
+            MigrationStep::Sql(sql) => conn.execute_batch(sql).map_err(AppError::from),
+            MigrationStep::NonTransactional(f) => f(conn),
+        }
+    }
+
+    fn is_transactional(&self) -> bool {
+        !matches!(self, MigrationStep::NonTransactional(_))
+    }
+}
+
+/// All known migrations, ordered by version. Append new entries at the end.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial_schema",
+        up: MigrationStep::Sql(include_str!("migrations/V1__initial_schema.sql")),
+        down: None,
+    },
+    Migration {
+        version: 2,
+        name: "categories",
+        up: MigrationStep::Sql(include_str!("migrations/V2__categories.sql")),
+        down: Some(MigrationStep::Sql(
+            "ALTER TABLE time_entries DROP COLUMN category_id;
+             DROP TABLE IF EXISTS categories;",
+        )),
+    },
+    Migration {
+        version: 3,
+        name: "process_samples",
+        up: MigrationStep::Sql(include_str!("migrations/V3__process_samples.sql")),
+        down: Some(MigrationStep::Sql("DROP TABLE IF EXISTS process_samples;")),
+    },
+    Migration {
+        version: 4,
+        name: "quarantine",
+        up: MigrationStep::Sql(include_str!("migrations/V4__quarantine.sql")),
+        down: Some(MigrationStep::Sql("DROP TABLE IF EXISTS quarantined_rows;")),
+    },
+    Migration {
+        version: 5,
+        name: "time_entries_category_fk",
+        up: MigrationStep::Sql(include_str!("migrations/V5__time_entries_category_fk.sql")),
+        down: Some(MigrationStep::Sql(include_str!(
+            "migrations/V5__time_entries_category_fk.down.sql"
+        ))),
+    },
+    Migration {
+        version: 6,
+        name: "time_entries_trash",
+        up: MigrationStep::Sql(include_str!("migrations/V6__time_entries_trash.sql")),
+        down: Some(MigrationStep::Sql(
+            "DROP INDEX IF EXISTS idx_time_entries_deleted_at;
+             ALTER TABLE time_entries DROP COLUMN deleted_at;",
+        )),
+    },
+    Migration {
+        version: 7,
+        name: "operation_journal",
+        up: MigrationStep::Sql(include_str!("migrations/V7__operation_journal.sql")),
+        down: Some(MigrationStep::Sql(
+            "DROP TABLE IF EXISTS operation_journal;",
+        )),
+    },
+    Migration {
+        version: 8,
+        name: "time_entry_revisions",
+        up: MigrationStep::Sql(include_str!("migrations/V8__time_entry_revisions.sql")),
+        down: Some(MigrationStep::Sql(
+            "DROP TABLE IF EXISTS time_entry_revisions;",
+        )),
+    },
+    Migration {
+        version: 9,
+        name: "process_segments",
+        up: MigrationStep::Sql(include_str!("migrations/V9__process_segments.sql")),
+        down: Some(MigrationStep::Sql(include_str!(
+            "migrations/V9__process_segments.down.sql"
+        ))),
+    },
+    Migration {
+        version: 10,
+        name: "maintenance_log",
+        up: MigrationStep::Sql(include_str!("migrations/V10__maintenance_log.sql")),
+        down: Some(MigrationStep::Sql("DROP TABLE IF EXISTS maintenance_log;")),
+    },
+    Migration {
+        version: 11,
+        name: "incremental_auto_vacuum",
+        up: MigrationStep::NonTransactional(
+            crate::data::maintenance::enable_incremental_auto_vacuum,
+        ),
+        down: Some(MigrationStep::NonTransactional(
+            crate::data::maintenance::disable_auto_vacuum,
+        )),
+    },
+    Migration {
+        version: 12,
+        name: "running_timer",
+        up: MigrationStep::Sql(include_str!("migrations/V12__running_timer.sql")),
+        down: Some(MigrationStep::Sql("DROP TABLE IF EXISTS running_timer;")),
+    },
+    Migration {
+        version: 13,
+        name: "recurring_entries",
+        up: MigrationStep::Sql(include_str!("migrations/V13__recurring_entries.sql")),
+        down: Some(MigrationStep::Sql(
+            "DROP TABLE IF EXISTS recurring_occurrences;
+             DROP TABLE IF EXISTS recurring_series;
+             DROP TABLE IF EXISTS entry_templates;",
+        )),
+    },
+    Migration {
+        version: 14,
+        name: "tags",
+        up: MigrationStep::Sql(include_str!("migrations/V14__tags.sql")),
+        down: Some(MigrationStep::Sql(
+            "DROP TABLE IF EXISTS time_entry_tags;
+             DROP TABLE IF EXISTS tags;",
+        )),
+    },
+    Migration {
+        version: 15,
+        name: "time_entry_notes",
+        up: MigrationStep::Sql(include_str!("migrations/V15__time_entry_notes.sql")),
+        down: Some(MigrationStep::Sql(
+            "ALTER TABLE time_entries DROP COLUMN notes;",
+        )),
+    },
+    Migration {
+        version: 16,
+        name: "clients_projects",
+        up: MigrationStep::Sql(include_str!("migrations/V16__clients_projects.sql")),
+        down: Some(MigrationStep::Sql(
+            "DROP INDEX IF EXISTS idx_time_entries_project;
+             ALTER TABLE time_entries DROP COLUMN project_id;
+             DROP TABLE IF EXISTS projects;
+             DROP TABLE IF EXISTS clients;",
+        )),
+    },
+];
+
+/// Highest schema version this build knows how to create.
+pub fn latest_schema_version() -> i64 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+fn ensure_migrations_table(conn: &Connection) -> AppResult<()> {
+    let has_table = table_exists(conn, "schema_migrations")?;
+    if has_table {
+        return Ok(());
+    }
 
-    std::fs::create_dir_all(&data_dir)
-        .map_err(|e| format!("Failed to create data directory: {}", e))?;
+    conn.execute_batch(
+        "CREATE TABLE schema_migrations (
+            version INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            applied_at INTEGER NOT NULL
+        );",
+    )
+    .map_err(db_err("Failed to create schema_migrations table"))?;
 
-    Ok(data_dir.join("database.db"))
+    baseline_legacy_schema(conn)
 }
 
-pub fn run_migrations(conn: &Connection) -> Result<(), String> {
-    conn.execute_batch(include_str!("migrations/V1__initial_schema.sql"))
-        .map_err(|e| format!("Failed to run V1 migrations: {}", e))?;
+/// Record migrations that were applied by the pre-runner startup code.
+///
+/// Databases created before `schema_migrations` existed have no version
+/// history, so the applied versions are inferred from the schema itself.
+fn baseline_legacy_schema(conn: &Connection) -> AppResult<()> {
+    let mut applied = Vec::new();
+
+    if table_exists(conn, "time_entries")? {
+        applied.push(1);
 
-    // Check if category_id exists in time_entries
-    let category_count: i32 = conn
+        if column_exists(conn, "time_entries", "category_id")? {
+            applied.push(2);
+        }
+    }
+
+    if table_exists(conn, "process_samples")? {
+        applied.push(3);
+    }
+
+    for version in applied {
+        if let Some(migration) = MIGRATIONS.iter().find(|m| m.version == version) {
+            record_migration(conn, migration)?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn table_exists(conn: &Connection, table: &str) -> AppResult<bool> {
+    let count: i32 = conn
         .query_row(
-            "SELECT count(*) FROM pragma_table_info('time_entries') WHERE name='category_id'",
-            [],
+            "SELECT count(*) FROM sqlite_master WHERE type='table' AND name=?1",
+            [table],
             |row| row.get(0),
         )
-        .map_err(|e| format!("Failed to check for category_id: {}", e))?;
-    let has_category_id = category_count == 1;
-
-    if !has_category_id {
-        conn.execute_batch(include_str!("migrations/V2__categories.sql"))
-            .map_err(|e| format!("Failed to run V2 migrations: {}", e))?;
-    }
+        .map_err(|e| AppError::from(e).context(format!("Failed to check for {} table", table)))?;
+    Ok(count == 1)
+}
 
-    let process_samples_table_count: i32 = conn
+pub fn column_exists(conn: &Connection, table: &str, column: &str) -> AppResult<bool> {
+    let count: i32 = conn
         .query_row(
-            "SELECT count(*) FROM sqlite_master WHERE type='table' AND name='process_samples'",
-            [],
+            "SELECT count(*) FROM pragma_table_info(?1) WHERE name=?2",
+            [table, column],
             |row| row.get(0),
         )
-        .map_err(|e| format!("Failed to check for process_samples table: {}", e))?;
-    let has_process_samples = process_samples_table_count == 1;
+        .map_err(|e| {
+            AppError::from(e).context(format!("Failed to check for {}.{}", table, column))
+        })?;
+    Ok(count == 1)
+}
+
+/// Column names of `table` in the attached database `schema` (usually "main").
+pub fn table_columns(conn: &Connection, schema: &str, table: &str) -> AppResult<Vec<String>> {
+    let mut stmt = conn
+        .prepare(&format!("PRAGMA {}.table_info(\"{}\")", schema, table))
+        .map_err(|e| AppError::from(e).context(format!("Failed to read columns of {}", table)))?;
+    let columns = stmt
+        .query_map([], |row| row.get::<_, String>(1))
+        .map_err(|e| AppError::from(e).context(format!("Failed to read columns of {}", table)))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| AppError::from(e).context(format!("Failed to read columns of {}", table)))?;
+    Ok(columns)
+}
+
+/// Row `id` of `table` as a JSON object keyed by column name, if it exists.
+pub fn row_as_json(conn: &Connection, table: &str, id: i64) -> AppResult<Option<String>> {
+    let pairs = table_columns(conn, "main", table)?
+        .iter()
+        .map(|c| format!("'{}', \"{}\"", c, c))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    conn.query_row(
+        &format!(
+            "SELECT json_object({}) FROM \"{}\" WHERE id = ?1",
+            pairs, table
+        ),
+        [id],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(|e| AppError::from(e).context(format!("Failed to read {} row {}", table, id)))
+}
+
+fn record_migration(conn: &Connection, migration: &Migration) -> AppResult<()> {
+    conn.execute(
+        "INSERT INTO schema_migrations (version, name, applied_at) VALUES (?1, ?2, ?3)",
+        rusqlite::params![
+            migration.version,
+            migration.name,
+            chrono::Utc::now().timestamp_millis()
+        ],
+    )
+    .map_err(|e| {
+        AppError::from(e).context(format!("Failed to record migration V{}", migration.version))
+    })?;
+    Ok(())
+}
+
+/// Versions recorded in `schema_migrations`, ascending.
+pub fn applied_migrations(conn: &Connection) -> AppResult<Vec<i64>> {
+    let mut stmt = conn
+        .prepare("SELECT version FROM schema_migrations ORDER BY version")
+        .map_err(db_err("Failed to prepare schema_migrations query"))?;
+
+    let versions = stmt
+        .query_map([], |row| row.get::<_, i64>(0))
+        .map_err(db_err("Failed to query schema_migrations"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db_err("Failed to collect schema_migrations"))?;
+
+    Ok(versions)
+}
+
+pub fn run_migrations(conn: &Connection) -> AppResult<()> {
+    ensure_migrations_table(conn)?;
+
+    let applied = applied_migrations(conn)?;
+    let latest = latest_schema_version();
+    if let Some(&newest) = applied.last() {
+        if newest > latest {
+            return Err(format!(
+                "Database schema version {} is newer than this build supports ({})",
+                newest, latest
+            )
+            .into());
+        }
+    }
+
+    for migration in MIGRATIONS.iter().filter(|m| !applied.contains(&m.version)) {
+        let run_failed =
+            |e: AppError| e.context(format!("Failed to run V{} migrations", migration.version));
+        if !migration.up.is_transactional() {
+            migration.up.apply(conn).map_err(run_failed)?;
+        }
+
+        let tx = conn
+            .unchecked_transaction()
+            .map_err(db_err("Failed to start transaction"))?;
+
+        if migration.up.is_transactional() {
+            migration.up.apply(&tx).map_err(run_failed)?;
+        }
+        record_migration(&tx, migration)?;
+
+        tx.commit().map_err(|e| {
+            AppError::from(e).context(format!(
+                "Failed to commit V{} migrations",
+                migration.version
+            ))
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Roll the schema back to `target_version` by running down-migrations
+/// newest-first. Fails without changes if any step has no down-migration.
+pub fn revert_migrations(conn: &Connection, target_version: i64) -> AppResult<()> {
+    ensure_migrations_table(conn)?;
+
+    let applied = applied_migrations(conn)?;
+    let to_revert: Vec<&Migration> = MIGRATIONS
+        .iter()
+        .rev()
+        .filter(|m| m.version > target_version && applied.contains(&m.version))
+        .collect();
+
+    if let Some(missing) = to_revert.iter().find(|m| m.down.is_none()) {
+        return Err(format!(
+            "Migration V{} ({}) cannot be reverted",
+            missing.version, missing.name
+        )
+        .into());
+    }
+
+    for migration in to_revert {
+        let revert_failed = |e: AppError| {
+            e.context(format!(
+                "Failed to revert V{} migrations",
+                migration.version
+            ))
+        };
+        let down = migration.down.as_ref().expect("checked above");
+        if !down.is_transactional() {
+            down.apply(conn).map_err(revert_failed)?;
+        }
+
+        let tx = conn
+            .unchecked_transaction()
+            .map_err(db_err("Failed to start transaction"))?;
+
+        if down.is_transactional() {
+            down.apply(&tx).map_err(revert_failed)?;
+        }
+        tx.execute(
+            "DELETE FROM schema_migrations WHERE version = ?1",
+            [migration.version],
+        )
+        .map_err(|e| {
+            AppError::from(e).context(format!(
+                "Failed to unrecord migration V{}",
+                migration.version
+            ))
+        })?;
 
-    if !has_process_samples {
-        conn.execute_batch(include_str!("migrations/V3__process_samples.sql"))
-            .map_err(|e| format!("Failed to run V3 migrations: {}", e))?;
+        tx.commit().map_err(|e| {
+            AppError::from(e).context(format!("Failed to commit V{} revert", migration.version))
+        })?;
     }
 
     Ok(())
 }
 
-pub fn init_database() -> Result<Connection, String> {
+/// Read `--downgrade-schema <version>` / `--downgrade-schema=<version>` from the command line.
+pub fn parse_downgrade_target<I>(args: I) -> Option<String>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut args = args.into_iter();
+    let mut result = None;
+
+    while let Some(arg) = args.next() {
+        if arg == "--downgrade-schema" {
+            result = args.next();
+        } else if let Some(version) = arg.strip_prefix("--downgrade-schema=") {
+            result = Some(version.to_string());
+        }
+    }
+
+    result
+}
+
+/// Roll the database back to schema `target` so an older build can open it.
+pub fn downgrade_database(target: &str) -> AppResult<i64> {
+    let target_version: i64 = target
+        .parse()
+        .map_err(|_| format!("Invalid schema version: {}", target))?;
+    if crate::crypto::is_locked() {
+        return Err("An encrypted database cannot be downgraded from the command line".into());
+    }
+
+    let conn = Connection::open(get_database_path()?).map_err(db_err("Failed to open database"))?;
+    crate::crypto::apply_database_key(&conn)?;
+    enable_foreign_keys(&conn)?;
+    revert_migrations(&conn, target_version)?;
+    Ok(target_version)
+}
+
+pub fn init_database() -> AppResult<Connection> {
+    let data_root = crate::app_paths::data_root()?;
+    if let Some(legacy) = crate::app_paths::migrate_legacy_database(&data_root)? {
+        println!(
+            "Moved database from legacy location {} to {}",
+            legacy.display(),
+            data_root.display()
+        );
+    }
+
     let db_path = get_database_path()?;
-    let conn = Connection::open(&db_path).map_err(|e| format!("Failed to open database: {}", e))?;
+    let conn = Connection::open(&db_path).map_err(db_err("Failed to open database"))?;
 
+    crate::crypto::apply_database_key(&conn)?;
+    enable_foreign_keys(&conn)?;
     run_migrations(&conn)?;
 
     conn.pragma_update(None, "journal_mode", "WAL")
-        .map_err(|e| format!("Failed to enable WAL mode: {}", e))?;
+        .map_err(db_err("Failed to enable WAL mode"))?;
 
     conn.busy_timeout(std::time::Duration::from_secs(5))
-        .map_err(|e| format!("Failed to set busy timeout: {}", e))?;
+        .map_err(db_err("Failed to set busy timeout"))?;
 
     Ok(conn)
 }
 
+/// Turn on foreign key enforcement. SQLite keeps this per connection and
+/// ignores it inside a transaction, so call it right after opening.
+pub fn enable_foreign_keys(conn: &Connection) -> AppResult<()> {
+    conn.pragma_update(None, "foreign_keys", true)
+        .map_err(db_err("Failed to enable foreign keys"))
+}
+
 #[allow(dead_code)]
-pub fn initialize_database(db_path: &std::path::Path) -> Result<(), String> {
-    let conn = Connection::open(db_path).map_err(|e| format!("Failed to open database: {}", e))?;
+pub fn initialize_database(db_path: &std::path::Path) -> AppResult<()> {
+    let conn = Connection::open(db_path).map_err(db_err("Failed to open database"))?;
 
+    enable_foreign_keys(&conn)?;
     run_migrations(&conn)?;
 
     conn.pragma_update(None, "journal_mode", "WAL")
-        .map_err(|e| format!("Failed to enable WAL mode: {}", e))?;
+        .map_err(db_err("Failed to enable WAL mode"))?;
 
     conn.busy_timeout(std::time::Duration::from_secs(5))
-        .map_err(|e| format!("Failed to set busy timeout: {}", e))?;
+        .map_err(db_err("Failed to set busy timeout"))?;
 
     Ok(())
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn current_schema_version(conn: &Connection) -> AppResult<i64> {
+        Ok(applied_migrations(conn)?.last().copied().unwrap_or(0))
+    }
+
+    fn v1_only_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(include_str!("migrations/V1__initial_schema.sql"))
+            .unwrap();
+        conn.execute(
+            "INSERT INTO time_entries (start_time, end_time, label) VALUES (?1, ?2, ?3)",
+            rusqlite::params![1000, 2000, "Legacy entry"],
+        )
+        .unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_fresh_database_reaches_latest_version() {
+        let conn = Connection::open_in_memory().unwrap();
+        run_migrations(&conn).unwrap();
+
+        assert_eq!(
+            current_schema_version(&conn).unwrap(),
+            latest_schema_version()
+        );
+        let expected: Vec<i64> = MIGRATIONS.iter().map(|m| m.version).collect();
+        assert_eq!(applied_migrations(&conn).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_v1_database_upgrades_to_latest() {
+        let conn = v1_only_db();
+        run_migrations(&conn).unwrap();
+
+        assert_eq!(
+            current_schema_version(&conn).unwrap(),
+            latest_schema_version()
+        );
+        assert!(column_exists(&conn, "time_entries", "category_id").unwrap());
+        assert!(table_exists(&conn, "categories").unwrap());
+        assert!(table_exists(&conn, "process_segments").unwrap());
+        assert!(!table_exists(&conn, "process_samples").unwrap());
+
+        let label: String = conn
+            .query_row("SELECT label FROM time_entries WHERE id = 1", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(label, "Legacy entry");
+    }
+
+    #[test]
+    fn test_run_migrations_is_idempotent() {
+        let conn = v1_only_db();
+        run_migrations(&conn).unwrap();
+        run_migrations(&conn).unwrap();
+
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM schema_migrations", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(count, MIGRATIONS.len() as i64);
+    }
+
+    #[test]
+    fn test_rejects_database_newer_than_binary() {
+        let conn = Connection::open_in_memory().unwrap();
+        run_migrations(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO schema_migrations (version, name, applied_at) VALUES (?1, 'future', 0)",
+            [latest_schema_version() + 1],
+        )
+        .unwrap();
+
+        let result = run_migrations(&conn);
+        assert!(result
+            .unwrap_err()
+            .message
+            .contains("newer than this build supports"));
+    }
+
+    #[test]
+    fn test_failed_migration_rolls_back() {
+        let conn = v1_only_db();
+        // A pre-existing category_id column makes V2's ALTER TABLE fail after
+        // its CREATE TABLE has already run.
+        conn.execute_batch(
+            "CREATE TABLE schema_migrations (
+                version INTEGER PRIMARY KEY,
+                name TEXT NOT NULL,
+                applied_at INTEGER NOT NULL
+            );
+            INSERT INTO schema_migrations VALUES (1, 'initial_schema', 0);
+            ALTER TABLE time_entries ADD COLUMN category_id INTEGER;",
+        )
+        .unwrap();
+
+        let result = run_migrations(&conn);
+        assert!(result
+            .unwrap_err()
+            .message
+            .contains("Failed to run V2 migrations"));
+        assert!(!table_exists(&conn, "categories").unwrap());
+        assert_eq!(applied_migrations(&conn).unwrap(), vec![1]);
+    }
+
+    #[test]
+    fn test_revert_migrations() {
+        let conn = Connection::open_in_memory().unwrap();
+        run_migrations(&conn).unwrap();
+
+        revert_migrations(&conn, 1).unwrap();
+        assert_eq!(current_schema_version(&conn).unwrap(), 1);
+        assert!(!table_exists(&conn, "process_samples").unwrap());
+        assert!(!table_exists(&conn, "process_segments").unwrap());
+        assert!(!column_exists(&conn, "time_entries", "category_id").unwrap());
+
+        run_migrations(&conn).unwrap();
+        assert_eq!(
+            current_schema_version(&conn).unwrap(),
+            latest_schema_version()
+        );
+
+        assert!(revert_migrations(&conn, 0)
+            .unwrap_err()
+            .message
+            .contains("cannot be reverted"));
+    }
+
+    #[test]
+    fn test_parse_downgrade_target() {
+        let args = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(parse_downgrade_target(args(&["app"])), None);
+        assert_eq!(
+            parse_downgrade_target(args(&["app", "--downgrade-schema", "12"])),
+            Some("12".to_string())
+        );
+        assert_eq!(
+            parse_downgrade_target(args(&["app", "--portable", "--downgrade-schema=9"])),
+            Some("9".to_string())
+        );
+    }
+
+    #[test]
+    fn test_process_samples_convert_to_segments() {
+        let conn = Connection::open_in_memory().unwrap();
+        run_migrations(&conn).unwrap();
+        revert_migrations(&conn, 8).unwrap();
+
+        let samples = [
+            (1000, "code.exe"),
+            (2000, "code.exe"),
+            (3000, "code.exe"),
+            (4000, "chrome.exe"),
+            (5000, "code.exe"),
+            // Sampler paused
+            (60_000, "code.exe"),
+        ];
+        for (timestamp, process_name) in samples {
+            conn.execute(
+                "INSERT INTO process_samples (timestamp, process_name, day_id) VALUES (?1, ?2, 19700101)",
+                rusqlite::params![timestamp, process_name],
+            )
+            .unwrap();
+        }
+
+        run_migrations(&conn).unwrap();
+        assert!(!table_exists(&conn, "process_samples").unwrap());
+
+        let mut stmt = conn
+            .prepare("SELECT start_time, end_time, process_name FROM process_segments ORDER BY start_time")
+            .unwrap();
+        let segments: Vec<(i64, i64, String)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            segments,
+            vec![
+                (1000, 4000, "code.exe".to_string()),
+                (4000, 5000, "chrome.exe".to_string()),
+                (5000, 6000, "code.exe".to_string()),
+                (60_000, 61_000, "code.exe".to_string()),
+            ]
+        );
+
+        // Reverting expands the segments back into per-second samples
+        revert_migrations(&conn, 8).unwrap();
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM process_samples", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 6);
+    }
+
+    #[test]
+    fn test_category_fk_migration_clears_dangling_references() {
+        let conn = v1_only_db();
+        conn.execute_batch(include_str!("migrations/V2__categories.sql"))
+            .unwrap();
+        // Legacy builds never enforced the reference
+        conn.pragma_update(None, "foreign_keys", false).unwrap();
+        conn.execute("UPDATE time_entries SET category_id = 99", [])
+            .unwrap();
+
+        enable_foreign_keys(&conn).unwrap();
+        run_migrations(&conn).unwrap();
+
+        let category: Option<i64> = conn
+            .query_row(
+                "SELECT category_id FROM time_entries WHERE id = 1",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(category, None);
+
+        conn.execute(
+            "INSERT INTO categories (id, name, color) VALUES (1, 'Work', '#fff')",
+            [],
+        )
+        .unwrap();
+        conn.execute("UPDATE time_entries SET category_id = 1", [])
+            .unwrap();
+        conn.execute("DELETE FROM categories WHERE id = 1", [])
+            .unwrap();
+        let category: Option<i64> = conn
+            .query_row(
+                "SELECT category_id FROM time_entries WHERE id = 1",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(category, None);
+    }
+}
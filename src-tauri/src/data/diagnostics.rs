@@ -0,0 +1,680 @@
+use crate::data::database::{
+    column_exists, enable_foreign_keys, row_as_json, run_migrations, table_columns, table_exists,
+};
+use crate::data::error::{db_err, io_err};
+use crate::data::{AppError, AppResult};
+use crate::types::{IntegrityIssue, IntegrityReport};
+use chrono::Local;
+use rusqlite::{params, Connection, ErrorCode, OpenFlags};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const QUARANTINE_SQL: &str = include_str!("migrations/V4__quarantine.sql");
+
+/// Check the database for physical corruption and logical inconsistencies.
+///
+/// With `repair` set, logical problems are fixed inside one transaction:
+/// invalid and fully shadowed entries are quarantined, partially overlapping
+/// entries are trimmed, dangling categories are cleared and screenshot rows
+/// whose file is gone are quarantined. `screenshot_exists` decides whether a
+/// stored screenshot path still points at a file.
+pub fn run_diagnostics(
+    conn: &Connection,
+    repair: bool,
+    screenshot_exists: &dyn Fn(&str) -> bool,
+) -> AppResult<IntegrityReport> {
+    let sqlite_messages = sqlite_integrity_messages(conn)?;
+    let mut report = IntegrityReport {
+        sqlite_ok: sqlite_messages.is_empty(),
+        sqlite_messages,
+        issues: Vec::new(),
+        repaired: false,
+        corrupt_copy_path: None,
+    };
+
+    // Logical checks on a physically broken file would just surface more noise.
+    if !report.sqlite_ok {
+        return Ok(report);
+    }
+
+    let tx = conn
+        .unchecked_transaction()
+        .map_err(db_err("Failed to start transaction"))?;
+    if repair {
+        tx.execute_batch(QUARANTINE_SQL)
+            .map_err(db_err("Failed to create quarantine table"))?;
+    }
+
+    report.issues.extend(foreign_key_issues(&tx)?);
+    if table_exists(&tx, "time_entries")? {
+        report.issues.extend(check_invalid_ranges(&tx, repair)?);
+        report.issues.extend(check_overlaps(&tx, repair)?);
+        if column_exists(&tx, "time_entries", "category_id")? {
+            report
+                .issues
+                .extend(check_dangling_categories(&tx, repair)?);
+        }
+    }
+    if table_exists(&tx, "screenshots")? {
+        report
+            .issues
+            .extend(check_screenshot_files(&tx, repair, screenshot_exists)?);
+    }
+
+    if repair {
+        tx.commit().map_err(db_err("Failed to commit repairs"))?;
+        report.repaired = true;
+    }
+
+    Ok(report)
+}
+
+/// Last-resort repair of the database file at `db_path`, used when startup fails.
+///
+/// Rebuilds indexes if SQLite reports corruption; if that is not enough, every
+/// readable row is copied into a fresh database and the damaged file is kept
+/// next to it as `database.corrupt-<timestamp>.db`. Logical repairs run last.
+pub fn repair_database_file(
+    db_path: &Path,
+    screenshot_exists: &dyn Fn(&str) -> bool,
+) -> AppResult<IntegrityReport> {
+    let mut corrupt_copy_path = None;
+
+    if db_path.exists() {
+        let conn =
+            Connection::open(db_path).map_err(db_err("Failed to open database for repair"))?;
+        crate::crypto::apply_database_key(&conn)?;
+
+        // A file SQLite cannot even read counts as corrupt too.
+        let check =
+            |conn: &Connection| sqlite_integrity_messages(conn).unwrap_or_else(|e| vec![e.message]);
+
+        let mut messages = check(&conn);
+        if !messages.is_empty() {
+            // Index corruption is the common case and REINDEX fixes it in place.
+            let _ = conn.execute_batch("REINDEX;");
+            messages = check(&conn);
+        }
+
+        if !messages.is_empty() {
+            let salvaged = db_path.with_extension("salvaged");
+            if salvaged.exists() {
+                fs::remove_file(&salvaged)
+                    .map_err(io_err("Failed to remove stale salvage file"))?;
+            }
+            salvage_into(&conn, &salvaged)?;
+            drop(conn);
+
+            let corrupt = set_aside_corrupt_file(db_path)?;
+            fs::rename(&salvaged, db_path)
+                .map_err(io_err("Failed to move salvaged database into place"))?;
+            corrupt_copy_path = Some(corrupt.to_string_lossy().to_string());
+        }
+    }
+
+    let conn =
+        Connection::open(db_path).map_err(db_err("Failed to reopen database after repair"))?;
+    crate::crypto::apply_database_key(&conn)?;
+    enable_foreign_keys(&conn)?;
+    let mut report = run_diagnostics(&conn, true, screenshot_exists)?;
+    report.corrupt_copy_path = corrupt_copy_path;
+    Ok(report)
+}
+
+/// Whether SQLite reports the file at `db_path` as corrupt or not a database,
+/// the only failures [`repair_database_file`] is meant for. Opened without a
+/// key, so an encrypted file also reads as not a database.
+pub fn is_corrupt_database_file(db_path: &Path) -> bool {
+    let Ok(conn) = Connection::open_with_flags(db_path, OpenFlags::SQLITE_OPEN_READ_ONLY) else {
+        return false;
+    };
+    match conn.query_row("PRAGMA quick_check", [], |row| row.get::<_, String>(0)) {
+        Ok(result) => result != "ok",
+        Err(rusqlite::Error::SqliteFailure(e, _)) => {
+            matches!(e.code, ErrorCode::DatabaseCorrupt | ErrorCode::NotADatabase)
+        }
+        Err(_) => false,
+    }
+}
+
+/// Messages from `PRAGMA integrity_check`, empty when the file is healthy.
+fn sqlite_integrity_messages(conn: &Connection) -> AppResult<Vec<String>> {
+    let mut stmt = conn
+        .prepare("PRAGMA integrity_check")
+        .map_err(db_err("Failed to run integrity check"))?;
+    let rows = stmt
+        .query_map([], |row| row.get::<_, String>(0))
+        .map_err(db_err("Failed to run integrity check"))?;
+
+    let mut messages = Vec::new();
+    for row in rows {
+        let message = row.map_err(db_err("Failed to read integrity check"))?;
+        if message != "ok" {
+            messages.push(message);
+        }
+    }
+    Ok(messages)
+}
+
+fn foreign_key_issues(conn: &Connection) -> AppResult<Vec<IntegrityIssue>> {
+    let mut stmt = conn
+        .prepare("PRAGMA foreign_key_check")
+        .map_err(db_err("Failed to run foreign key check"))?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, Option<i64>>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })
+        .map_err(db_err("Failed to run foreign key check"))?;
+
+    let mut issues = Vec::new();
+    for row in rows {
+        let (table, row_id, parent) = row.map_err(db_err("Failed to read foreign key check"))?;
+        // Reported (and repaired) as dangling_category below.
+        if table == "time_entries" && parent == "categories" {
+            continue;
+        }
+        issues.push(issue(
+            "foreign_key",
+            &table,
+            row_id,
+            format!("References a missing row in {}", parent),
+            None,
+        ));
+    }
+    Ok(issues)
+}
+
+fn check_invalid_ranges(conn: &Connection, repair: bool) -> AppResult<Vec<IntegrityIssue>> {
+    let ids = query_ids(
+        conn,
+        "SELECT id FROM time_entries WHERE end_time <= start_time ORDER BY id",
+    )?;
+
+    let mut issues = Vec::new();
+    for id in ids {
+        let action = if repair {
+            quarantine_time_entry(conn, id, "invalid_range")?;
+            Some("quarantined")
+        } else {
+            None
+        };
+        issues.push(issue(
+            "invalid_range",
+            "time_entries",
+            Some(id),
+            "End time is not after start time".to_string(),
+            action,
+        ));
+    }
+    Ok(issues)
+}
+
+/// Single pass over entries sorted by start time, comparing each entry with
+/// the one that currently reaches furthest.
+fn check_overlaps(conn: &Connection, repair: bool) -> AppResult<Vec<IntegrityIssue>> {
+    // Trashed entries may legitimately sit under live ones.
+    let live_only = if column_exists(conn, "time_entries", "deleted_at")? {
+        "AND deleted_at IS NULL"
+    } else {
+        ""
+    };
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT id, start_time, end_time FROM time_entries
+             WHERE end_time > start_time {}
+             ORDER BY start_time, id",
+            live_only
+        ))
+        .map_err(db_err("Failed to prepare overlap check"))?;
+    let entries = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, i64>(2)?,
+            ))
+        })
+        .map_err(db_err("Failed to run overlap check"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db_err("Failed to read time entry"))?;
+
+    let mut issues = Vec::new();
+    let mut reach: Option<(i64, i64)> = None;
+
+    for (id, start, end) in entries {
+        let Some((reach_id, reach_end)) = reach else {
+            reach = Some((id, end));
+            continue;
+        };
+
+        if start >= reach_end {
+            reach = Some((id, end));
+            continue;
+        }
+
+        let message = format!("Overlaps time entry {}", reach_id);
+        if end > reach_end {
+            let action = if repair {
+                conn.execute(
+                    "UPDATE time_entries SET start_time = ?1 WHERE id = ?2",
+                    params![reach_end, id],
+                )
+                .map_err(|e| {
+                    AppError::from(e).context(format!("Failed to trim time entry {}", id))
+                })?;
+                Some("trimmed")
+            } else {
+                None
+            };
+            issues.push(issue("overlap", "time_entries", Some(id), message, action));
+            reach = Some((id, end));
+        } else {
+            let action = if repair {
+                quarantine_time_entry(conn, id, "overlap")?;
+                Some("quarantined")
+            } else {
+                None
+            };
+            issues.push(issue("overlap", "time_entries", Some(id), message, action));
+        }
+    }
+    Ok(issues)
+}
+
+fn check_dangling_categories(conn: &Connection, repair: bool) -> AppResult<Vec<IntegrityIssue>> {
+    let has_categories = table_exists(conn, "categories")?;
+    let sql = if has_categories {
+        "SELECT id FROM time_entries
+         WHERE category_id IS NOT NULL
+           AND category_id NOT IN (SELECT id FROM categories)
+         ORDER BY id"
+    } else {
+        "SELECT id FROM time_entries WHERE category_id IS NOT NULL ORDER BY id"
+    };
+
+    let mut issues = Vec::new();
+    for id in query_ids(conn, sql)? {
+        let action = if repair {
+            conn.execute(
+                "UPDATE time_entries SET category_id = NULL WHERE id = ?1",
+                [id],
+            )
+            .map_err(|e| {
+                AppError::from(e).context(format!("Failed to clear category of time entry {}", id))
+            })?;
+            Some("cleared_category")
+        } else {
+            None
+        };
+        issues.push(issue(
+            "dangling_category",
+            "time_entries",
+            Some(id),
+            "References a category that does not exist".to_string(),
+            action,
+        ));
+    }
+    Ok(issues)
+}
+
+fn check_screenshot_files(
+    conn: &Connection,
+    repair: bool,
+    screenshot_exists: &dyn Fn(&str) -> bool,
+) -> AppResult<Vec<IntegrityIssue>> {
+    let mut stmt = conn
+        .prepare("SELECT id, file_path FROM screenshots ORDER BY id")
+        .map_err(db_err("Failed to prepare screenshot check"))?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+        })
+        .map_err(db_err("Failed to run screenshot check"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db_err("Failed to read screenshot row"))?;
+
+    let mut issues = Vec::new();
+    for (id, file_path) in rows {
+        if screenshot_exists(&file_path) {
+            continue;
+        }
+
+        let action = if repair {
+            quarantine_row(conn, "screenshots", id, "missing_screenshot_file")?;
+            Some("quarantined")
+        } else {
+            None
+        };
+        issues.push(issue(
+            "missing_screenshot_file",
+            "screenshots",
+            Some(id),
+            format!("Screenshot file {} is missing", file_path),
+            action,
+        ));
+    }
+    Ok(issues)
+}
+
+fn quarantine_time_entry(conn: &Connection, id: i64, reason: &str) -> AppResult<()> {
+    quarantine_row(conn, "time_entries", id, reason)
+}
+
+/// Copy a row into `quarantined_rows` as JSON, then delete it.
+fn quarantine_row(conn: &Connection, table: &str, id: i64, reason: &str) -> AppResult<()> {
+    let payload = row_as_json(conn, table, id)?
+        .ok_or_else(|| format!("{} row {} does not exist", table, id))?;
+    conn.execute(
+        "INSERT INTO quarantined_rows (source_table, source_id, reason, payload, quarantined_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            table,
+            id,
+            reason,
+            payload,
+            chrono::Utc::now().timestamp_millis()
+        ],
+    )
+    .map_err(|e| AppError::from(e).context(format!("Failed to quarantine {} row {}", table, id)))?;
+
+    conn.execute(&format!("DELETE FROM {} WHERE id = ?1", table), [id])
+        .map_err(|e| AppError::from(e).context(format!("Failed to remove {} row {}", table, id)))?;
+    Ok(())
+}
+
+fn query_ids(conn: &Connection, sql: &str) -> AppResult<Vec<i64>> {
+    let mut stmt = conn
+        .prepare(sql)
+        .map_err(db_err("Failed to prepare integrity query"))?;
+    let ids = stmt
+        .query_map([], |row| row.get::<_, i64>(0))
+        .map_err(db_err("Failed to run integrity query"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db_err("Failed to read integrity query"))?;
+    Ok(ids)
+}
+
+/// Copy every readable row from `corrupt` into a freshly migrated database at `target`.
+fn salvage_into(corrupt: &Connection, target: &Path) -> AppResult<()> {
+    {
+        let fresh =
+            Connection::open(target).map_err(db_err("Failed to create salvage database"))?;
+        crate::crypto::apply_database_key(&fresh)?;
+        run_migrations(&fresh)?;
+    }
+
+    // If not even the schema is readable, the fresh database is all we get.
+    if let Err(e) = corrupt.execute(
+        "ATTACH DATABASE ?1 AS salvage",
+        [target.to_string_lossy().to_string()],
+    ) {
+        eprintln!("Nothing could be salvaged from the damaged database: {}", e);
+        return Ok(());
+    }
+
+    let tables = {
+        let mut stmt = corrupt
+            .prepare(
+                "SELECT name FROM salvage.sqlite_master
+                 WHERE type = 'table' AND name NOT LIKE 'sqlite_%' AND name != 'schema_migrations'",
+            )
+            .map_err(db_err("Failed to list salvage tables"))?;
+        let names = stmt
+            .query_map([], |row| row.get::<_, String>(0))
+            .map_err(db_err("Failed to list salvage tables"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(db_err("Failed to list salvage tables"))?;
+        names
+    };
+
+    for table in tables {
+        // Unreadable tables are skipped; whatever could be copied is kept.
+        let Ok(source_columns) = table_columns(corrupt, "main", &table) else {
+            continue;
+        };
+        let columns = table_columns(corrupt, "salvage", &table)?
+            .into_iter()
+            .filter(|c| source_columns.contains(c))
+            .map(|c| format!("\"{}\"", c))
+            .collect::<Vec<_>>()
+            .join(", ");
+        if columns.is_empty() {
+            continue;
+        }
+
+        if let Err(e) = corrupt.execute(
+            &format!(
+                "INSERT OR IGNORE INTO salvage.\"{table}\" ({columns}) SELECT {columns} FROM main.\"{table}\""
+            ),
+            [],
+        ) {
+            eprintln!("Failed to salvage table {}: {}", table, e);
+        }
+    }
+
+    corrupt
+        .execute("DETACH DATABASE salvage", [])
+        .map_err(db_err("Failed to detach salvage database"))?;
+    Ok(())
+}
+
+/// Rename the damaged database (and WAL/SHM files) to `database.corrupt-<timestamp>.db`.
+fn set_aside_corrupt_file(db_path: &Path) -> AppResult<PathBuf> {
+    let stem = db_path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("database");
+    let corrupt = db_path.with_file_name(format!(
+        "{}.corrupt-{}.db",
+        stem,
+        Local::now().format("%Y%m%d-%H%M%S")
+    ));
+
+    for suffix in ["-wal", "-shm"] {
+        let side_file = PathBuf::from(format!("{}{}", db_path.display(), suffix));
+        if side_file.exists() {
+            let target = PathBuf::from(format!("{}{}", corrupt.display(), suffix));
+            fs::rename(&side_file, &target).map_err(|e| {
+                AppError::from(e).context(format!("Failed to move {}", side_file.display()))
+            })?;
+        }
+    }
+    fs::rename(db_path, &corrupt).map_err(io_err("Failed to move corrupt database aside"))?;
+    Ok(corrupt)
+}
+
+fn issue(
+    kind: &str,
+    table: &str,
+    row_id: Option<i64>,
+    message: String,
+    action: Option<&str>,
+) -> IntegrityIssue {
+    IntegrityIssue {
+        kind: kind.to_string(),
+        table: table.to_string(),
+        row_id,
+        message,
+        action: action.map(str::to_string),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup_test_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        run_migrations(&conn).unwrap();
+        // The checks exist for data written without constraint enforcement.
+        conn.pragma_update(None, "foreign_keys", false).unwrap();
+        conn
+    }
+
+    fn insert_entry(conn: &Connection, start: i64, end: i64, category_id: Option<i64>) -> i64 {
+        conn.execute(
+            "INSERT INTO time_entries (start_time, end_time, label, category_id) VALUES (?1, ?2, 'x', ?3)",
+            params![start, end, category_id],
+        )
+        .unwrap();
+        conn.last_insert_rowid()
+    }
+
+    fn all_present(_: &str) -> bool {
+        true
+    }
+
+    fn kinds(report: &IntegrityReport) -> Vec<&str> {
+        report.issues.iter().map(|i| i.kind.as_str()).collect()
+    }
+
+    #[test]
+    fn test_healthy_database_has_no_issues() {
+        let conn = setup_test_db();
+        insert_entry(&conn, 0, 1000, None);
+        insert_entry(&conn, 1000, 2000, None);
+
+        let report = run_diagnostics(&conn, false, &all_present).unwrap();
+        assert!(report.sqlite_ok);
+        assert!(report.issues.is_empty());
+    }
+
+    #[test]
+    fn test_check_reports_without_changing_data() {
+        let conn = setup_test_db();
+        insert_entry(&conn, 0, 1000, None);
+        insert_entry(&conn, 500, 1500, None);
+        insert_entry(&conn, 3000, 3000, None);
+        insert_entry(&conn, 4000, 5000, Some(99));
+
+        let report = run_diagnostics(&conn, false, &all_present).unwrap();
+        assert_eq!(
+            kinds(&report),
+            vec!["invalid_range", "overlap", "dangling_category"]
+        );
+        assert!(report.issues.iter().all(|i| i.action.is_none()));
+
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM time_entries", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 4);
+    }
+
+    #[test]
+    fn test_repair_trims_quarantines_and_clears() {
+        let conn = setup_test_db();
+        insert_entry(&conn, 0, 1000, None);
+        let trimmed = insert_entry(&conn, 500, 1500, None);
+        let shadowed = insert_entry(&conn, 600, 900, None);
+        let invalid = insert_entry(&conn, 3000, 2000, None);
+        let dangling = insert_entry(&conn, 4000, 5000, Some(99));
+
+        let report = run_diagnostics(&conn, true, &all_present).unwrap();
+        assert!(report.repaired);
+        assert_eq!(report.issues.len(), 4);
+
+        let start: i64 = conn
+            .query_row(
+                "SELECT start_time FROM time_entries WHERE id = ?1",
+                [trimmed],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(start, 1000);
+
+        let quarantined: Vec<i64> = {
+            let mut stmt = conn
+                .prepare("SELECT source_id FROM quarantined_rows ORDER BY source_id")
+                .unwrap();
+            stmt.query_map([], |row| row.get(0))
+                .unwrap()
+                .map(|r| r.unwrap())
+                .collect()
+        };
+        assert_eq!(quarantined, vec![shadowed, invalid]);
+
+        let category: Option<i64> = conn
+            .query_row(
+                "SELECT category_id FROM time_entries WHERE id = ?1",
+                [dangling],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(category, None);
+
+        let again = run_diagnostics(&conn, false, &all_present).unwrap();
+        assert!(again.issues.is_empty());
+    }
+
+    #[test]
+    fn test_missing_screenshot_files_are_quarantined() {
+        let conn = setup_test_db();
+        conn.execute(
+            "INSERT INTO screenshots (timestamp, file_path, day_id) VALUES (1, 'screenshots/a.webp', 0), (2, 'screenshots/gone.webp', 0)",
+            [],
+        )
+        .unwrap();
+
+        let exists = |path: &str| path != "screenshots/gone.webp";
+        let report = run_diagnostics(&conn, true, &exists).unwrap();
+        assert_eq!(kinds(&report), vec!["missing_screenshot_file"]);
+
+        let payload: String = conn
+            .query_row(
+                "SELECT payload FROM quarantined_rows WHERE source_table = 'screenshots'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert!(payload.contains("gone.webp"));
+    }
+
+    #[test]
+    fn test_repair_database_file_sets_aside_unreadable_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db_path = dir.path().join("database.db");
+        fs::write(&db_path, b"not a database at all").unwrap();
+
+        let report = repair_database_file(&db_path, &all_present).unwrap();
+        let corrupt = PathBuf::from(report.corrupt_copy_path.unwrap());
+        assert_eq!(fs::read(&corrupt).unwrap(), b"not a database at all");
+
+        let conn = Connection::open(&db_path).unwrap();
+        assert!(table_exists(&conn, "time_entries").unwrap());
+    }
+
+    #[test]
+    fn test_is_corrupt_database_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db_path = dir.path().join("database.db");
+        assert!(!is_corrupt_database_file(&db_path));
+
+        {
+            let conn = Connection::open(&db_path).unwrap();
+            run_migrations(&conn).unwrap();
+        }
+        assert!(!is_corrupt_database_file(&db_path));
+
+        fs::write(&db_path, b"not a database at all").unwrap();
+        assert!(is_corrupt_database_file(&db_path));
+    }
+
+    #[test]
+    fn test_repair_database_file_fixes_healthy_file_in_place() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db_path = dir.path().join("database.db");
+        {
+            let conn = Connection::open(&db_path).unwrap();
+            run_migrations(&conn).unwrap();
+            insert_entry(&conn, 0, 1000, None);
+            insert_entry(&conn, 0, 1000, None);
+        }
+        let report = repair_database_file(&db_path, &all_present).unwrap();
+        assert!(report.corrupt_copy_path.is_none());
+        assert_eq!(kinds(&report), vec!["overlap"]);
+    }
+}
@@ -0,0 +1,267 @@
+use crate::crypto::{is_sealed, KeyMaterial};
+use crate::data::error::{db_err, io_err};
+use crate::data::AppResult;
+use crate::types::EncryptionReport;
+use rusqlite::Connection;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Copy the database behind `conn` into a new file at `dest`, encrypted with
+/// `keys` or in plaintext when `None`.
+///
+/// SQLCipher cannot encrypt or decrypt a database in place, so switching
+/// encryption on or off goes through `sqlcipher_export` into a fresh file.
+pub fn export_database(
+    conn: &Connection,
+    dest: &Path,
+    keys: Option<&KeyMaterial>,
+) -> AppResult<()> {
+    if dest.exists() {
+        fs::remove_file(dest).map_err(io_err("Failed to remove stale export"))?;
+    }
+
+    let key = keys.map(KeyMaterial::sqlcipher_key).unwrap_or_default();
+    conn.execute(
+        "ATTACH DATABASE ?1 AS converted KEY ?2",
+        [dest.to_string_lossy().to_string(), key],
+    )
+    .map_err(db_err("Failed to create converted database"))?;
+
+    let exported = conn
+        .query_row("SELECT sqlcipher_export('converted')", [], |_| Ok(()))
+        .map_err(db_err("Failed to export database"));
+    let detached = conn
+        .execute("DETACH DATABASE converted", [])
+        .map_err(db_err("Failed to detach converted database"));
+
+    exported?;
+    detached?;
+    Ok(())
+}
+
+/// Change the key of an already encrypted database in place.
+pub fn rekey_database(conn: &Connection, keys: &KeyMaterial) -> AppResult<()> {
+    conn.pragma_update(None, "rekey", keys.sqlcipher_key())
+        .map_err(db_err("Failed to rekey database"))?;
+    Ok(())
+}
+
+/// Rewrite every screenshot file referenced by the database from `old` to
+/// `new` keys. `None` on either side means plaintext.
+///
+/// Files that are missing or fail to convert are reported, not fatal, so one
+/// damaged image cannot block a passphrase change.
+pub fn reencrypt_screenshots(
+    conn: &Connection,
+    old: Option<&KeyMaterial>,
+    new: Option<&KeyMaterial>,
+    resolve: &dyn Fn(&str) -> Result<PathBuf, String>,
+) -> AppResult<EncryptionReport> {
+    let mut stmt = conn
+        .prepare("SELECT file_path FROM screenshots ORDER BY timestamp")
+        .map_err(db_err("Failed to prepare screenshots query"))?;
+    let stored_paths = stmt
+        .query_map([], |row| row.get::<_, String>(0))
+        .map_err(db_err("Failed to query screenshots"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db_err("Failed to collect screenshots"))?;
+
+    let mut report = EncryptionReport {
+        screenshots_converted: 0,
+        screenshots_failed: Vec::new(),
+    };
+
+    for stored_path in stored_paths {
+        let converted = resolve(&stored_path).and_then(|path| {
+            if !path.exists() {
+                return Ok(false);
+            }
+            convert_file(&path, old, new)
+        });
+        match converted {
+            Ok(true) => report.screenshots_converted += 1,
+            Ok(false) => {}
+            Err(e) => {
+                eprintln!("Failed to convert screenshot {}: {}", stored_path, e);
+                report.screenshots_failed.push(stored_path);
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+/// Returns whether the file was rewritten.
+fn convert_file(
+    path: &Path,
+    old: Option<&KeyMaterial>,
+    new: Option<&KeyMaterial>,
+) -> Result<bool, String> {
+    let bytes = fs::read(path).map_err(|e| format!("Failed to read file: {}", e))?;
+
+    if is_sealed(&bytes) && new.is_some_and(|keys| keys.open(&bytes).is_ok()) {
+        // Written with the new keys already, e.g. captured mid-conversion.
+        return Ok(false);
+    }
+
+    let plaintext = if is_sealed(&bytes) {
+        match old {
+            Some(keys) => keys.open(&bytes)?,
+            None => return Err("File is encrypted with an unknown key".to_string()),
+        }
+    } else if new.is_none() {
+        // Already plaintext and staying that way.
+        return Ok(false);
+    } else {
+        bytes
+    };
+
+    let output = match new {
+        Some(keys) => keys.seal(&plaintext)?,
+        None => plaintext,
+    };
+
+    let staged = path.with_extension("converting");
+    fs::write(&staged, output).map_err(|e| format!("Failed to write file: {}", e))?;
+    fs::rename(&staged, path).map_err(|e| format!("Failed to replace file: {}", e))?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::{generate_salt, key_connection};
+    use tempfile::TempDir;
+
+    fn plain_database(dir: &TempDir) -> (Connection, PathBuf) {
+        let path = dir.path().join("database.db");
+        let conn = Connection::open(&path).unwrap();
+        crate::data::database::run_migrations(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO time_entries (start_time, end_time, label) VALUES (1000, 2000, 'Private')",
+            [],
+        )
+        .unwrap();
+        (conn, path)
+    }
+
+    fn label(conn: &Connection) -> rusqlite::Result<String> {
+        conn.query_row("SELECT label FROM time_entries", [], |row| row.get(0))
+    }
+
+    #[test]
+    fn test_export_encrypts_and_decrypts() {
+        let dir = TempDir::new().unwrap();
+        let (conn, _) = plain_database(&dir);
+        let keys = KeyMaterial::derive("passphrase", &generate_salt()).unwrap();
+
+        let encrypted_path = dir.path().join("encrypted.db");
+        export_database(&conn, &encrypted_path, Some(&keys)).unwrap();
+
+        let unkeyed = Connection::open(&encrypted_path).unwrap();
+        assert!(label(&unkeyed).is_err());
+
+        let encrypted = Connection::open(&encrypted_path).unwrap();
+        key_connection(&encrypted, &keys).unwrap();
+        assert_eq!(label(&encrypted).unwrap(), "Private");
+
+        let plain_path = dir.path().join("plain.db");
+        export_database(&encrypted, &plain_path, None).unwrap();
+        let plain = Connection::open(&plain_path).unwrap();
+        assert_eq!(label(&plain).unwrap(), "Private");
+    }
+
+    #[test]
+    fn test_rekey_database() {
+        let dir = TempDir::new().unwrap();
+        let (conn, _) = plain_database(&dir);
+        let old_keys = KeyMaterial::derive("old", &generate_salt()).unwrap();
+        let new_keys = KeyMaterial::derive("new", &generate_salt()).unwrap();
+
+        let path = dir.path().join("encrypted.db");
+        export_database(&conn, &path, Some(&old_keys)).unwrap();
+        {
+            let encrypted = Connection::open(&path).unwrap();
+            key_connection(&encrypted, &old_keys).unwrap();
+            rekey_database(&encrypted, &new_keys).unwrap();
+        }
+
+        let stale = Connection::open(&path).unwrap();
+        key_connection(&stale, &old_keys).unwrap();
+        assert!(label(&stale).is_err());
+
+        let fresh = Connection::open(&path).unwrap();
+        key_connection(&fresh, &new_keys).unwrap();
+        assert_eq!(label(&fresh).unwrap(), "Private");
+    }
+
+    #[test]
+    fn test_reencrypt_screenshots() {
+        let dir = TempDir::new().unwrap();
+        let (conn, _) = plain_database(&dir);
+        let shot = dir.path().join("shot.webp");
+        fs::write(&shot, b"RIFF-image").unwrap();
+        conn.execute(
+            "INSERT INTO screenshots (timestamp, file_path, day_id) VALUES (1500, ?1, 19700101)",
+            [shot.to_string_lossy().to_string()],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO screenshots (timestamp, file_path, day_id) VALUES (1600, 'gone.webp', 19700101)",
+            [],
+        )
+        .unwrap();
+
+        let resolve = |stored: &str| -> Result<PathBuf, String> { Ok(PathBuf::from(stored)) };
+        let old_keys = KeyMaterial::derive("old", &generate_salt()).unwrap();
+        let new_keys = KeyMaterial::derive("new", &generate_salt()).unwrap();
+
+        let report = reencrypt_screenshots(&conn, None, Some(&old_keys), &resolve).unwrap();
+        assert_eq!(report.screenshots_converted, 1);
+        assert!(report.screenshots_failed.is_empty());
+        assert!(is_sealed(&fs::read(&shot).unwrap()));
+
+        reencrypt_screenshots(&conn, Some(&old_keys), Some(&new_keys), &resolve).unwrap();
+        assert_eq!(
+            new_keys.open(&fs::read(&shot).unwrap()).unwrap(),
+            b"RIFF-image"
+        );
+
+        // Wrong key: reported, file left untouched
+        let report = reencrypt_screenshots(&conn, Some(&old_keys), None, &resolve).unwrap();
+        assert_eq!(
+            report.screenshots_failed,
+            vec![shot.to_string_lossy().to_string()]
+        );
+
+        reencrypt_screenshots(&conn, Some(&new_keys), None, &resolve).unwrap();
+        assert_eq!(fs::read(&shot).unwrap(), b"RIFF-image");
+    }
+
+    #[test]
+    fn test_retry_converts_only_what_is_left() {
+        let dir = TempDir::new().unwrap();
+        let (conn, _) = plain_database(&dir);
+        for (timestamp, name) in [(1500, "done.webp"), (1600, "left.webp")] {
+            let path = dir.path().join(name);
+            fs::write(&path, b"RIFF-image").unwrap();
+            conn.execute(
+                "INSERT INTO screenshots (timestamp, file_path, day_id) VALUES (?1, ?2, 19700101)",
+                rusqlite::params![timestamp, path.to_string_lossy().to_string()],
+            )
+            .unwrap();
+        }
+
+        let resolve = |stored: &str| -> Result<PathBuf, String> { Ok(PathBuf::from(stored)) };
+        let keys = KeyMaterial::derive("secret", &generate_salt()).unwrap();
+        let done = dir.path().join("done.webp");
+        fs::write(&done, keys.seal(b"RIFF-image").unwrap()).unwrap();
+
+        let report = reencrypt_screenshots(&conn, None, Some(&keys), &resolve).unwrap();
+        assert_eq!(report.screenshots_converted, 1);
+        for name in ["done.webp", "left.webp"] {
+            let bytes = fs::read(dir.path().join(name)).unwrap();
+            assert_eq!(keys.open(&bytes).unwrap(), b"RIFF-image");
+        }
+    }
+}
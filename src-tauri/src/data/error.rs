@@ -0,0 +1,205 @@
+use serde::{Deserialize, Serialize};
+use specta::Type;
+use std::fmt;
+
+/// Broad category of a failure, stable across releases so the UI can branch on it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    /// Input rejected before touching the database.
+    Validation,
+    /// The referenced row does not exist.
+    NotFound,
+    /// The change collides with existing data, e.g. overlapping time entries.
+    Conflict,
+    /// SQLite reported an error.
+    Database,
+    /// Filesystem access failed.
+    Io,
+    /// Anything else.
+    Internal,
+}
+
+/// Structured context attached to an error.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, Type)]
+pub struct ErrorDetails {
+    /// Entries the rejected change would collide with.
+    pub conflicting_entry_ids: Vec<i64>,
+    /// Input field that failed validation.
+    pub field: Option<String>,
+}
+
+/// Error returned by every `data::*` function and Tauri command.
+///
+/// Serializes as `{ kind, code, message, details }`. `code` is a fine-grained
+/// snake_case identifier such as `time_entry_overlap`; `message` is for humans.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub struct AppError {
+    pub kind: ErrorKind,
+    pub code: String,
+    pub message: String,
+    pub details: Option<ErrorDetails>,
+}
+
+impl AppError {
+    pub fn new(kind: ErrorKind, code: &str, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            code: code.to_string(),
+            message: message.into(),
+            details: None,
+        }
+    }
+
+    pub fn validation(code: &str, message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Validation, code, message)
+    }
+
+    pub fn not_found(code: &str, message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::NotFound, code, message)
+    }
+
+    /// Overlap with the given existing time entries.
+    pub fn overlap(conflicting_entry_ids: Vec<i64>) -> Self {
+        Self::new(
+            ErrorKind::Conflict,
+            "time_entry_overlap",
+            "Time entry overlaps with an existing entry",
+        )
+        .with_details(ErrorDetails {
+            conflicting_entry_ids,
+            ..Default::default()
+        })
+    }
+
+    pub fn with_field(self, field: &str) -> Self {
+        let mut details = self.details.clone().unwrap_or_default();
+        details.field = Some(field.to_string());
+        self.with_details(details)
+    }
+
+    pub fn with_details(mut self, details: ErrorDetails) -> Self {
+        self.details = Some(details);
+        self
+    }
+
+    /// Prefix the message with what was being done, keeping kind and code.
+    pub fn context(mut self, context: impl fmt::Display) -> Self {
+        self.message = format!("{}: {}", context, self.message);
+        self
+    }
+}
+
+/// `map_err` adapter for SQLite failures that keeps [`ErrorKind::Database`].
+///
+/// `.map_err(db_err("Failed to read entries"))` reports
+/// `Failed to read entries: <sqlite message>`.
+pub fn db_err(context: &str) -> impl FnOnce(rusqlite::Error) -> AppError + '_ {
+    move |error| AppError::from(error).context(context)
+}
+
+/// `map_err` adapter for filesystem failures that keeps [`ErrorKind::Io`].
+pub fn io_err(context: &str) -> impl FnOnce(std::io::Error) -> AppError + '_ {
+    move |error| AppError::from(error).context(context)
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for AppError {}
+
+/// Plain messages for failures that are neither SQLite nor filesystem errors.
+impl From<String> for AppError {
+    fn from(message: String) -> Self {
+        Self::new(ErrorKind::Internal, "internal", message)
+    }
+}
+
+impl From<&str> for AppError {
+    fn from(message: &str) -> Self {
+        Self::from(message.to_string())
+    }
+}
+
+impl From<rusqlite::Error> for AppError {
+    fn from(error: rusqlite::Error) -> Self {
+        match error {
+            rusqlite::Error::QueryReturnedNoRows => {
+                Self::not_found("not_found", "Record not found")
+            }
+            other => Self::new(ErrorKind::Database, "database", other.to_string()),
+        }
+    }
+}
+
+impl From<std::io::Error> for AppError {
+    fn from(error: std::io::Error) -> Self {
+        Self::new(ErrorKind::Io, "io", error.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overlap_serializes_to_stable_shape() {
+        let json = serde_json::to_value(AppError::overlap(vec![3, 7])).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "kind": "conflict",
+                "code": "time_entry_overlap",
+                "message": "Time entry overlaps with an existing entry",
+                "details": { "conflicting_entry_ids": [3, 7], "field": null }
+            })
+        );
+    }
+
+    #[test]
+    fn test_string_errors_become_internal() {
+        let error: AppError = "Failed to lock database".into();
+        assert_eq!(error.kind, ErrorKind::Internal);
+        assert_eq!(error.to_string(), "Failed to lock database");
+        assert!(error.details.is_none());
+    }
+
+    #[test]
+    fn test_no_rows_maps_to_not_found() {
+        let error: AppError = rusqlite::Error::QueryReturnedNoRows.into();
+        assert_eq!(error.kind, ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_failing_query_keeps_database_kind() {
+        // No migrations, so the categories table is missing
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        let error = crate::data::categories::get_categories_impl(&conn).unwrap_err();
+
+        let json = serde_json::to_value(&error).unwrap();
+        assert_eq!(json["kind"], "database");
+        assert_eq!(json["code"], "database");
+        assert!(error.message.contains("no such table: categories"));
+
+        // Lookups that map a missing row to not_found keep other failures too
+        for error in [
+            crate::data::time_entries::get_time_entry_by_id(&conn, 1).unwrap_err(),
+            crate::data::time_entries::restore_time_entry_impl(&conn, 1).unwrap_err(),
+        ] {
+            assert_eq!(error.kind, ErrorKind::Database);
+            assert!(error.message.contains("no such table: time_entries"));
+        }
+    }
+
+    #[test]
+    fn test_io_errors_keep_io_kind() {
+        let error = std::fs::read("/nonexistent/chronotrace")
+            .map_err(io_err("Failed to read file"))
+            .unwrap_err();
+        assert_eq!(error.kind, ErrorKind::Io);
+        assert!(error.message.starts_with("Failed to read file: "));
+    }
+}
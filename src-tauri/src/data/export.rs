@@ -1,29 +1,21 @@
+use crate::data::error::{db_err, io_err};
+use crate::data::tags;
+use crate::data::AppResult;
 use chrono::Utc;
 use rusqlite::Connection;
 use std::path::Path;
 
 /// Export all data to JSON
-pub fn export_data_impl(conn: &Connection) -> Result<crate::types::ExportData, String> {
-    // Check if category_id column exists
-    let has_category_id: bool = conn
-        .query_row(
-            "SELECT count(*) FROM pragma_table_info('time_entries') WHERE name='category_id'",
-            [],
-            |row| row.get(0),
-        )
-        .unwrap_or(0)
-        == 1;
-
-    let query = if has_category_id {
-        "SELECT id, start_time, end_time, label, color, category_id FROM time_entries ORDER BY start_time"
-    } else {
-        "SELECT id, start_time, end_time, label, color, NULL as category_id FROM time_entries ORDER BY start_time"
-    };
+pub fn export_data_impl(conn: &Connection) -> AppResult<crate::types::ExportData> {
+    let query = "SELECT id, start_time, end_time, label, color, category_id, notes, project_id
+         FROM time_entries
+         WHERE deleted_at IS NULL
+         ORDER BY start_time";
 
     // Get all time entries
     let mut stmt = conn
         .prepare(query)
-        .map_err(|e| format!("Failed to prepare time entries query: {}", e))?;
+        .map_err(db_err("Failed to prepare time entries query"))?;
 
     let time_entries: Vec<crate::types::TimeEntry> = stmt
         .query_map([], |row| {
@@ -34,16 +26,18 @@ pub fn export_data_impl(conn: &Connection) -> Result<crate::types::ExportData, S
                 label: row.get(3)?,
                 color: row.get(4)?,
                 category_id: row.get(5)?,
+                notes: row.get(6)?,
+                project_id: row.get(7)?,
             })
         })
-        .map_err(|e| format!("Failed to query time entries: {}", e))?
+        .map_err(db_err("Failed to query time entries"))?
         .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| format!("Failed to collect time entries: {}", e))?;
+        .map_err(db_err("Failed to collect time entries"))?;
 
     // Get all screenshots
     let mut stmt = conn
         .prepare("SELECT timestamp, file_path FROM screenshots ORDER BY timestamp")
-        .map_err(|e| format!("Failed to prepare screenshots query: {}", e))?;
+        .map_err(db_err("Failed to prepare screenshots query"))?;
 
     let screenshots: Vec<crate::types::ScreenshotRef> = stmt
         .query_map([], |row| {
@@ -52,13 +46,13 @@ pub fn export_data_impl(conn: &Connection) -> Result<crate::types::ExportData, S
                 file_path: row.get(1)?,
             })
         })
-        .map_err(|e| format!("Failed to query screenshots: {}", e))?
+        .map_err(db_err("Failed to query screenshots"))?
         .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| format!("Failed to collect screenshots: {}", e))?;
+        .map_err(db_err("Failed to collect screenshots"))?;
 
     // Get all window activities
     let mut stmt = conn.prepare("SELECT id, timestamp, window_title, process_name FROM window_activity ORDER BY timestamp")
-        .map_err(|e| format!("Failed to prepare window activity query: {}", e))?;
+        .map_err(db_err("Failed to prepare window activity query"))?;
 
     let window_activities: Vec<crate::types::WindowActivity> = stmt
         .query_map([], |row| {
@@ -69,16 +63,16 @@ pub fn export_data_impl(conn: &Connection) -> Result<crate::types::ExportData, S
                 process_name: row.get(3)?,
             })
         })
-        .map_err(|e| format!("Failed to query window activities: {}", e))?
+        .map_err(db_err("Failed to query window activities"))?
         .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| format!("Failed to collect window activities: {}", e))?;
+        .map_err(db_err("Failed to collect window activities"))?;
 
     // Get all idle periods
     let mut stmt = conn
         .prepare(
             "SELECT id, start_time, end_time, resolution FROM idle_periods ORDER BY start_time",
         )
-        .map_err(|e| format!("Failed to prepare idle periods query: {}", e))?;
+        .map_err(db_err("Failed to prepare idle periods query"))?;
 
     let idle_periods: Vec<crate::types::IdlePeriod> = stmt
         .query_map([], |row| {
@@ -89,9 +83,12 @@ pub fn export_data_impl(conn: &Connection) -> Result<crate::types::ExportData, S
                 resolution: row.get(3)?,
             })
         })
-        .map_err(|e| format!("Failed to query idle periods: {}", e))?
+        .map_err(db_err("Failed to query idle periods"))?
         .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| format!("Failed to collect idle periods: {}", e))?;
+        .map_err(db_err("Failed to collect idle periods"))?;
+
+    let tags = tags::get_tags_impl(conn)?;
+    let time_entry_tags = tags::get_time_entry_tags_by_range_impl(conn, i64::MIN, i64::MAX)?;
 
     Ok(crate::types::ExportData {
         version: "1.0".to_string(),
@@ -100,6 +97,8 @@ pub fn export_data_impl(conn: &Connection) -> Result<crate::types::ExportData, S
         screenshots,
         window_activities,
         idle_periods,
+        tags,
+        time_entry_tags,
     })
 }
 
@@ -109,37 +108,20 @@ pub fn export_to_csv_impl(
     conn: &Connection,
     export_path: &Path,
     options: &crate::types::ExportOptions,
-) -> Result<(), String> {
-    // Check if category_id column exists
-    let has_category_id: bool = conn
-        .query_row(
-            "SELECT count(*) FROM pragma_table_info('time_entries') WHERE name='category_id'",
-            [],
-            |row| row.get(0),
-        )
-        .unwrap_or(0)
-        == 1;
-
-    let select_part = if has_category_id {
-        "SELECT id, start_time, end_time, label, color, category_id FROM time_entries"
-    } else {
-        "SELECT id, start_time, end_time, label, color, NULL as category_id FROM time_entries"
-    };
-
-    let mut query = select_part.to_string();
+) -> AppResult<()> {
+    let mut query = "SELECT id, start_time, end_time, label, color, category_id, notes, project_id
+         FROM time_entries
+         WHERE deleted_at IS NULL"
+        .to_string();
     let mut params: Vec<String> = Vec::new();
 
     if let Some(start_date) = options.start_date {
-        query.push_str(" WHERE start_time >= ?");
+        query.push_str(" AND start_time >= ?");
         params.push(start_date.to_string());
     }
 
     if let Some(end_date) = options.end_date {
-        if params.is_empty() {
-            query.push_str(" WHERE end_time <= ?");
-        } else {
-            query.push_str(" AND end_time <= ?");
-        }
+        query.push_str(" AND end_time <= ?");
         params.push(end_date.to_string());
     }
 
@@ -147,7 +129,7 @@ pub fn export_to_csv_impl(
 
     let mut stmt = conn
         .prepare(&query)
-        .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        .map_err(db_err("Failed to prepare query"))?;
 
     let entries: Vec<crate::types::TimeEntry> = stmt
         .query_map(rusqlite::params_from_iter(params.iter()), |row| {
@@ -158,42 +140,51 @@ pub fn export_to_csv_impl(
                 label: row.get(3)?,
                 color: row.get(4)?,
                 category_id: row.get(5)?,
+                notes: row.get(6)?,
+                project_id: row.get(7)?,
             })
         })
-        .map_err(|e| format!("Failed to query time entries: {}", e))?
+        .map_err(db_err("Failed to query time entries"))?
         .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| format!("Failed to collect time entries: {}", e))?;
+        .map_err(db_err("Failed to collect time entries"))?;
 
-    let mut csv_content = String::from("id,start_time,end_time,label,color,category_id\n");
+    let mut csv_content =
+        String::from("id,start_time,end_time,label,color,category_id,notes,project_id\n");
     for entry in entries {
         csv_content.push_str(&format!(
-            "{},{},{},{},{},{}\n",
+            "{},{},{},{},{},{},{},{}\n",
             entry.id,
             entry.start_time,
             entry.end_time,
-            entry.label,
-            entry.color.as_deref().unwrap_or(""),
-            entry.category_id.map(|id| id.to_string()).unwrap_or_default()
+            csv_field(&entry.label),
+            csv_field(entry.color.as_deref().unwrap_or("")),
+            entry.category_id.map(|id| id.to_string()).unwrap_or_default(),
+            csv_field(entry.notes.as_deref().unwrap_or("")),
+            entry.project_id.map(|id| id.to_string()).unwrap_or_default()
         ));
     }
 
-    std::fs::write(export_path, csv_content)
-        .map_err(|e| format!("Failed to write CSV file: {}", e))?;
+    std::fs::write(export_path, csv_content).map_err(io_err("Failed to write CSV file"))?;
 
     Ok(())
 }
 
+/// Quote a CSV field when it contains a separator, quote or line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     fn setup_test_db() -> Connection {
         let conn = Connection::open_in_memory().unwrap();
-        conn.execute_batch(include_str!("migrations/V1__initial_schema.sql"))
-            .unwrap();
-        // Manually add category_id for tests since V1 doesn't have it
-        conn.execute("ALTER TABLE time_entries ADD COLUMN category_id INTEGER", [])
-            .unwrap();
+        crate::data::database::run_migrations(&conn).unwrap();
         conn
     }
 
@@ -207,9 +198,42 @@ mod tests {
         assert!(result.screenshots.is_empty());
         assert!(result.window_activities.is_empty());
         assert!(result.idle_periods.is_empty());
+        assert!(result.tags.is_empty());
+        assert!(result.time_entry_tags.is_empty());
         assert!(!result.exported_at.is_empty());
     }
 
+    #[test]
+    fn test_export_data_with_tags() {
+        let conn = setup_test_db();
+
+        conn.execute(
+            "INSERT INTO time_entries (start_time, end_time, label) VALUES (?1, ?2, ?3)",
+            rusqlite::params![1000, 2000, "Work"],
+        )
+        .unwrap();
+        let billable = tags::create_tag_impl(
+            &conn,
+            &crate::types::TagInput {
+                name: "Billable".to_string(),
+                color: None,
+            },
+        )
+        .unwrap();
+        tags::set_time_entry_tags_impl(&conn, 1, &[billable.id]).unwrap();
+
+        let result = export_data_impl(&conn).unwrap();
+
+        assert_eq!(result.tags, vec![billable.clone()]);
+        assert_eq!(
+            result.time_entry_tags,
+            vec![crate::types::TimeEntryTag {
+                entry_id: 1,
+                tag_id: billable.id,
+            }]
+        );
+    }
+
     #[test]
     fn test_export_data_with_entries() {
         let conn = setup_test_db();
@@ -301,10 +325,66 @@ mod tests {
         export_to_csv_impl(&conn, &csv_path, &options).unwrap();
 
         let content = std::fs::read_to_string(&csv_path).unwrap();
-        assert!(content.starts_with("id,start_time,end_time,label,color,category_id\n"));
+        assert!(content
+            .starts_with("id,start_time,end_time,label,color,category_id,notes,project_id\n"));
         assert!(content.contains("Work"));
         assert!(content.contains("#FF0000"));
 
         let _ = std::fs::remove_dir_all(&tmp_dir);
     }
+
+    #[test]
+    fn test_export_includes_notes() {
+        let conn = setup_test_db();
+
+        conn.execute(
+            "INSERT INTO time_entries (start_time, end_time, label, notes) VALUES (?1, ?2, ?3, ?4)",
+            rusqlite::params![1000, 2000, "Review, part 2", "Went through \"auth\"\n- merged"],
+        )
+        .unwrap();
+
+        let result = export_data_impl(&conn).unwrap();
+        assert_eq!(
+            result.time_entries[0].notes.as_deref(),
+            Some("Went through \"auth\"\n- merged")
+        );
+
+        let tmp_dir = std::env::temp_dir().join("digital_diary_test_csv_notes");
+        std::fs::create_dir_all(&tmp_dir).unwrap();
+        let csv_path = tmp_dir.join("notes.csv");
+        let options = crate::types::ExportOptions {
+            start_date: None,
+            end_date: None,
+            include_screenshots: false,
+        };
+        export_to_csv_impl(&conn, &csv_path, &options).unwrap();
+
+        let content = std::fs::read_to_string(&csv_path).unwrap();
+        assert!(content.ends_with(
+            "1,1000,2000,\"Review, part 2\",,,\"Went through \"\"auth\"\"\n- merged\",\n"
+        ));
+
+        let _ = std::fs::remove_dir_all(&tmp_dir);
+    }
+
+    #[test]
+    fn test_export_skips_trashed_entries() {
+        let conn = setup_test_db();
+
+        conn.execute(
+            "INSERT INTO time_entries (start_time, end_time, label) VALUES (?1, ?2, ?3)",
+            rusqlite::params![1000, 2000, "Kept"],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO time_entries (start_time, end_time, label, deleted_at) VALUES (?1, ?2, ?3, ?4)",
+            rusqlite::params![3000, 4000, "Trashed", 5000],
+        )
+        .unwrap();
+
+        let result = export_data_impl(&conn).unwrap();
+
+        assert_eq!(result.time_entries.len(), 1);
+        assert_eq!(result.time_entries[0].label, "Kept");
+    }
 }
@@ -1,5 +1,8 @@
-use crate::data::AppResult;
-use crate::types::{IdlePeriod, IdlePeriodResolution};
+use crate::data::error::db_err;
+use crate::data::journal;
+use crate::data::revisions::with_source;
+use crate::data::{AppError, AppResult};
+use crate::types::{IdlePeriod, IdlePeriodResolution, RevisionSource};
 use rusqlite::Connection;
 
 /// Update idle period resolution
@@ -13,7 +16,7 @@ pub fn update_idle_period_resolution(
         "UPDATE idle_periods SET resolution = ?1 WHERE id = ?2",
         rusqlite::params![resolution, id],
     )
-    .map_err(|e| format!("Failed to update idle period: {}", e))?;
+    .map_err(db_err("Failed to update idle period"))?;
 
     Ok(())
 }
@@ -23,7 +26,7 @@ pub fn update_idle_period_resolution(
 pub fn get_idle_period(conn: &Connection, id: i64) -> AppResult<IdlePeriod> {
     let mut stmt = conn
         .prepare("SELECT id, start_time, end_time, resolution FROM idle_periods WHERE id = ?1")
-        .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        .map_err(db_err("Failed to prepare query"))?;
 
     let period = stmt
         .query_row(rusqlite::params![id], |row| {
@@ -34,7 +37,7 @@ pub fn get_idle_period(conn: &Connection, id: i64) -> AppResult<IdlePeriod> {
                 resolution: row.get(3)?,
             })
         })
-        .map_err(|e| format!("Failed to get idle period: {}", e))?;
+        .map_err(db_err("Failed to get idle period"))?;
 
     Ok(period)
 }
@@ -61,15 +64,24 @@ pub fn resolve_idle_period_with_action(
         "merged" => {
             // Merge with target entry (extend its end_time)
             if let Some(target_id) = resolution.target_entry_id {
-                conn.execute(
-                    "UPDATE time_entries SET end_time = ?1 WHERE id = ?2",
-                    rusqlite::params![period.end_time, target_id],
-                )
-                .map_err(|e| format!("Failed to merge idle period: {}", e))?;
+                with_source(RevisionSource::IdleMerge, || {
+                    journal::track(conn, journal::TIME_ENTRIES, target_id, || {
+                        conn.execute(
+                            "UPDATE time_entries SET end_time = ?1 WHERE id = ?2 AND deleted_at IS NULL",
+                            rusqlite::params![period.end_time, target_id],
+                        )
+                        .map_err(db_err("Failed to merge idle period"))?;
+                        Ok(())
+                    })
+                })?;
 
                 update_idle_period_resolution(conn, resolution.id, "merged")?;
             } else {
-                return Err("target_entry_id required for 'merged' resolution".to_string());
+                return Err(AppError::validation(
+                    "missing_target_entry",
+                    "target_entry_id required for 'merged' resolution",
+                )
+                .with_field("target_entry_id"));
             }
         }
         "labeled" => {
@@ -82,19 +94,28 @@ pub fn resolve_idle_period_with_action(
                     label: label.clone(),
                     color: None,
                     category_id: None,
+                    notes: None,
+                    project_id: None,
                 };
 
-                crate::data::time_entries::create_time_entry_impl(conn, &entry_input)?;
+                with_source(RevisionSource::IdleMerge, || {
+                    crate::data::time_entries::create_time_entry_impl(conn, &entry_input)
+                })?;
                 update_idle_period_resolution(conn, resolution.id, "labeled")?;
             } else {
-                return Err("new_entry_label required for 'labeled' resolution".to_string());
+                return Err(AppError::validation(
+                    "missing_entry_label",
+                    "new_entry_label required for 'labeled' resolution",
+                )
+                .with_field("new_entry_label"));
             }
         }
         _ => {
-            return Err(format!(
-                "Invalid resolution type: {}",
-                resolution.resolution
-            ));
+            return Err(AppError::validation(
+                "invalid_resolution",
+                format!("Invalid resolution type: {}", resolution.resolution),
+            )
+            .with_field("resolution"));
         }
     }
 
@@ -102,11 +123,62 @@ pub fn resolve_idle_period_with_action(
     get_idle_period(conn, resolution.id)
 }
 
+/// Delete up to `limit` of the oldest idle periods that ended before `cutoff_timestamp`.
+pub fn delete_idle_periods_before(
+    conn: &Connection,
+    cutoff_timestamp: i64,
+    limit: usize,
+) -> AppResult<usize> {
+    let deleted = conn
+        .execute(
+            "DELETE FROM idle_periods WHERE id IN (
+                 SELECT id FROM idle_periods WHERE end_time < ?1 ORDER BY end_time LIMIT ?2
+             )",
+            rusqlite::params![cutoff_timestamp, limit as i64],
+        )
+        .map_err(db_err("Failed to delete old idle periods"))?;
+
+    Ok(deleted)
+}
+
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use crate::data::database::{enable_foreign_keys, run_migrations};
+    use crate::data::revisions::get_time_entry_revisions_impl;
+
     #[test]
     fn test_idle_period_crud() {
         // This would require a test database setup
         // Placeholder for future implementation
     }
+
+    #[test]
+    fn test_labeled_resolution_records_idle_source() {
+        let conn = Connection::open_in_memory().unwrap();
+        enable_foreign_keys(&conn).unwrap();
+        run_migrations(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO idle_periods (start_time, end_time) VALUES (1000, 2000)",
+            [],
+        )
+        .unwrap();
+
+        resolve_idle_period_with_action(
+            &conn,
+            &IdlePeriodResolution {
+                id: conn.last_insert_rowid(),
+                resolution: "labeled".to_string(),
+                target_entry_id: None,
+                new_entry_label: Some("Meeting".to_string()),
+            },
+        )
+        .unwrap();
+
+        let entry_id: i64 = conn
+            .query_row("SELECT id FROM time_entries", [], |row| row.get(0))
+            .unwrap();
+        let revisions = get_time_entry_revisions_impl(&conn, entry_id).unwrap();
+        assert_eq!(revisions[0].source, RevisionSource::IdleMerge);
+    }
 }
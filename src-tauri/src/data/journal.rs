@@ -0,0 +1,777 @@
+use crate::data::database::{row_as_json, table_columns};
+use crate::data::error::db_err;
+use crate::data::revisions;
+use crate::data::time_entries::find_overlapping_entry_ids;
+use crate::data::timer;
+use crate::data::{AppError, AppResult};
+use crate::types::{JournalChange, JournalReplay, RevisionSource, UndoState};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::cell::Cell;
+
+pub const TIME_ENTRIES: &str = "time_entries";
+pub const CATEGORIES: &str = "categories";
+pub const ENTRY_TEMPLATES: &str = "entry_templates";
+
+/// Undo history is capped at this many steps.
+const MAX_GROUPS: i64 = 200;
+
+/// Consecutive drag updates of the same row closer together than this
+/// collapse into one undo step, so a timeline drag undoes as a whole.
+const COALESCE_WINDOW_MS: i64 = 1500;
+
+thread_local! {
+    /// Group shared by every change recorded inside [`grouped`].
+    static ACTIVE_GROUP: Cell<Option<i64>> = const { Cell::new(None) };
+    /// Set inside [`untracked`].
+    static UNTRACKED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Current state of row `id` in `table`, or `None` if it does not exist.
+pub fn snapshot(conn: &Connection, table: &str, id: i64) -> AppResult<Option<String>> {
+    row_as_json(conn, table, id)
+}
+
+/// Record that row `id` of `table` went from `before` to `after`.
+///
+/// `None` on either side means the row did not exist. Recording a change
+/// discards anything that could have been redone. Time entry changes also
+/// land in the entry's revision history, even inside [`untracked`].
+pub fn record(
+    conn: &Connection,
+    table: &str,
+    id: i64,
+    before: Option<String>,
+    after: Option<String>,
+) -> AppResult<()> {
+    if before == after {
+        return Ok(());
+    }
+
+    if table == TIME_ENTRIES {
+        revisions::record(conn, id, before.as_deref(), after.as_deref())?;
+    }
+    if UNTRACKED.with(Cell::get) {
+        return Ok(());
+    }
+
+    conn.execute("DELETE FROM operation_journal WHERE undone = 1", [])
+        .map_err(db_err("Failed to clear redo history"))?;
+
+    let now = chrono::Utc::now().timestamp_millis();
+    let group_id = match ACTIVE_GROUP.with(Cell::get) {
+        Some(group_id) => group_id,
+        None => {
+            if before.is_some()
+                && after.is_some()
+                && revisions::current_source() == RevisionSource::Drag
+                && coalesce(conn, table, id, &after, now)?
+            {
+                return Ok(());
+            }
+            next_group_id(conn)?
+        }
+    };
+
+    conn.execute(
+        "INSERT INTO operation_journal (group_id, entity, entity_id, before_json, after_json, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![group_id, table, id, before, after, now],
+    )
+    .map_err(db_err("Failed to record operation"))?;
+
+    conn.execute(
+        "DELETE FROM operation_journal WHERE group_id <= ?1",
+        [group_id - MAX_GROUPS],
+    )
+    .map_err(db_err("Failed to trim operation journal"))?;
+
+    Ok(())
+}
+
+/// Run `f` and record the change it makes to row `id` of `table`.
+pub fn track<R, F>(conn: &Connection, table: &str, id: i64, f: F) -> AppResult<R>
+where
+    F: FnOnce() -> AppResult<R>,
+{
+    let before = snapshot(conn, table, id)?;
+    let result = f()?;
+    let after = snapshot(conn, table, id)?;
+    record(conn, table, id, before, after)?;
+    Ok(result)
+}
+
+/// Run `f` so that every change it records is undone and redone as one step.
+///
+/// Nested calls join the outermost group.
+pub fn grouped<R, F>(conn: &Connection, f: F) -> AppResult<R>
+where
+    F: FnOnce() -> AppResult<R>,
+{
+    if ACTIVE_GROUP.with(Cell::get).is_some() {
+        return f();
+    }
+
+    let group_id = next_group_id(conn)?;
+    ACTIVE_GROUP.with(|g| g.set(Some(group_id)));
+    let _guard = GroupGuard;
+    f()
+}
+
+/// Ends the active group when [`grouped`] returns or unwinds, so a panic in
+/// `f` never leaves later changes on this thread joining a stale group.
+struct GroupGuard;
+
+impl Drop for GroupGuard {
+    fn drop(&mut self) {
+        ACTIVE_GROUP.with(|g| g.set(None));
+    }
+}
+
+/// Run `f` without recording its changes for undo, for changes the app makes
+/// on its own. The user's undo and redo history is left as it was.
+pub fn untracked<R, F>(f: F) -> AppResult<R>
+where
+    F: FnOnce() -> AppResult<R>,
+{
+    let previous = UNTRACKED.with(|u| u.replace(true));
+    let _guard = UntrackedGuard(previous);
+    f()
+}
+
+/// Restores the previous [`untracked`] state when it returns or unwinds.
+struct UntrackedGuard(bool);
+
+impl Drop for UntrackedGuard {
+    fn drop(&mut self) {
+        UNTRACKED.with(|u| u.set(self.0));
+    }
+}
+
+/// Drop history for rows that were permanently deleted.
+pub fn forget(conn: &Connection, table: &str, ids: &[i64]) -> AppResult<()> {
+    for id in ids {
+        conn.execute(
+            "DELETE FROM operation_journal WHERE entity = ?1 AND entity_id = ?2",
+            params![table, id],
+        )
+        .map_err(db_err("Failed to clear operation history"))?;
+    }
+    Ok(())
+}
+
+pub fn undo_state(conn: &Connection) -> AppResult<UndoState> {
+    conn.query_row(
+        "SELECT
+             EXISTS(SELECT 1 FROM operation_journal WHERE undone = 0),
+             EXISTS(SELECT 1 FROM operation_journal WHERE undone = 1)",
+        [],
+        |row| {
+            Ok(UndoState {
+                can_undo: row.get(0)?,
+                can_redo: row.get(1)?,
+            })
+        },
+    )
+    .map_err(db_err("Failed to read undo state"))
+}
+
+/// Revert the most recent step. Returns `None` when there is nothing to undo.
+pub fn undo(conn: &Connection) -> AppResult<Option<JournalReplay>> {
+    let group_id: Option<i64> = conn
+        .query_row(
+            "SELECT MAX(group_id) FROM operation_journal WHERE undone = 0",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(db_err("Failed to find operation to undo"))?;
+
+    match group_id {
+        Some(group_id) => replay(conn, group_id, Direction::Undo).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Re-apply the most recently undone step. Returns `None` when there is nothing to redo.
+pub fn redo(conn: &Connection) -> AppResult<Option<JournalReplay>> {
+    let group_id: Option<i64> = conn
+        .query_row(
+            "SELECT MIN(group_id) FROM operation_journal WHERE undone = 1",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(db_err("Failed to find operation to redo"))?;
+
+    match group_id {
+        Some(group_id) => replay(conn, group_id, Direction::Redo).map(Some),
+        None => Ok(None),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Direction {
+    Undo,
+    Redo,
+}
+
+impl Direction {
+    fn name(self) -> &'static str {
+        match self {
+            Direction::Undo => "undo",
+            Direction::Redo => "redo",
+        }
+    }
+}
+
+struct JournalRow {
+    entity: String,
+    entity_id: i64,
+    before: Option<String>,
+    after: Option<String>,
+}
+
+/// Apply one group inside a transaction, validating time entries afterwards.
+fn replay(conn: &Connection, group_id: i64, direction: Direction) -> AppResult<JournalReplay> {
+    let tx = conn
+        .unchecked_transaction()
+        .map_err(db_err("Failed to start transaction"))?;
+
+    let mut rows = group_rows(&tx, group_id)?;
+    if direction == Direction::Undo {
+        rows.reverse();
+    }
+
+    for row in &rows {
+        let target = match direction {
+            Direction::Undo => row.before.as_deref(),
+            Direction::Redo => row.after.as_deref(),
+        };
+        if row.entity == TIME_ENTRIES {
+            let current = snapshot(&tx, TIME_ENTRIES, row.entity_id)?;
+            apply_snapshot(&tx, &row.entity, row.entity_id, target)?;
+            let applied = snapshot(&tx, TIME_ENTRIES, row.entity_id)?;
+            let source = match direction {
+                Direction::Undo => RevisionSource::Undo,
+                Direction::Redo => RevisionSource::Redo,
+            };
+            revisions::with_source(source, || {
+                revisions::record(&tx, row.entity_id, current.as_deref(), applied.as_deref())
+            })?;
+        } else {
+            apply_snapshot(&tx, &row.entity, row.entity_id, target)?;
+        }
+    }
+
+    for row in rows.iter().filter(|r| r.entity == TIME_ENTRIES) {
+        validate_time_entry(&tx, row.entity_id)?;
+    }
+
+    tx.execute(
+        "UPDATE operation_journal SET undone = ?1 WHERE group_id = ?2",
+        params![direction == Direction::Undo, group_id],
+    )
+    .map_err(db_err("Failed to update operation journal"))?;
+
+    tx.commit()
+        .map_err(|e| AppError::from(e).context(format!("Failed to commit {}", direction.name())))?;
+
+    let mut changes: Vec<JournalChange> = Vec::new();
+    for row in rows {
+        let change = JournalChange {
+            entity: row.entity,
+            entity_id: row.entity_id,
+        };
+        if !changes.contains(&change) {
+            changes.push(change);
+        }
+    }
+
+    Ok(JournalReplay { group_id, changes })
+}
+
+fn group_rows(conn: &Connection, group_id: i64) -> AppResult<Vec<JournalRow>> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT entity, entity_id, before_json, after_json FROM operation_journal
+             WHERE group_id = ?1
+             ORDER BY id",
+        )
+        .map_err(db_err("Failed to prepare journal query"))?;
+
+    let rows = stmt
+        .query_map([group_id], |row| {
+            Ok(JournalRow {
+                entity: row.get(0)?,
+                entity_id: row.get(1)?,
+                before: row.get(2)?,
+                after: row.get(3)?,
+            })
+        })
+        .map_err(db_err("Failed to query operation journal"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db_err("Failed to read operation journal"))?;
+
+    Ok(rows)
+}
+
+/// Make row `id` of `table` match `snapshot`, deleting it when `None`.
+///
+/// A time entry is moved to the trash rather than deleted, so its tags,
+/// recurrence link and revisions are still there when the create is redone.
+fn apply_snapshot(
+    conn: &Connection,
+    table: &str,
+    id: i64,
+    snapshot: Option<&str>,
+) -> AppResult<()> {
+    let Some(snapshot) = snapshot else {
+        if table == TIME_ENTRIES {
+            timer::clear_running_timer(conn, id)?;
+            conn.execute(
+                "UPDATE time_entries SET deleted_at = ?1 WHERE id = ?2 AND deleted_at IS NULL",
+                params![chrono::Utc::now().timestamp_millis(), id],
+            )
+            .map_err(|e| {
+                AppError::from(e).context(format!("Failed to remove time entry {}", id))
+            })?;
+            return Ok(());
+        }
+        conn.execute(&format!("DELETE FROM \"{}\" WHERE id = ?1", table), [id])
+            .map_err(|e| {
+                AppError::from(e).context(format!("Failed to remove {} row {}", table, id))
+            })?;
+        return Ok(());
+    };
+
+    // Upsert rather than REPLACE so ON DELETE actions on dependants never fire.
+    let columns = table_columns(conn, "main", table)?;
+    let names = columns
+        .iter()
+        .map(|c| format!("\"{}\"", c))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let values = columns
+        .iter()
+        .map(|c| format!("json_extract(?1, '$.{}')", c))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let updates = columns
+        .iter()
+        .filter(|c| c.as_str() != "id")
+        .map(|c| format!("\"{}\" = excluded.\"{}\"", c, c))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    conn.execute(
+        &format!(
+            "INSERT INTO \"{}\" ({}) VALUES ({}) ON CONFLICT(id) DO UPDATE SET {}",
+            table, names, values, updates
+        ),
+        [snapshot],
+    )
+    .map_err(|e| AppError::from(e).with_field(table))?;
+
+    Ok(())
+}
+
+/// Apply the same rules as `update_time_entry_impl` to a replayed entry.
+fn validate_time_entry(conn: &Connection, id: i64) -> AppResult<()> {
+    let live: Option<(i64, i64)> = conn
+        .query_row(
+            "SELECT start_time, end_time FROM time_entries WHERE id = ?1 AND deleted_at IS NULL",
+            [id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+        .map_err(|e| AppError::from(e).context(format!("Failed to validate time entry {}", id)))?;
+
+    let Some((start_time, end_time)) = live else {
+        return Ok(());
+    };
+
+    let overlapping = find_overlapping_entry_ids(conn, start_time, end_time, Some(id))?;
+    if !overlapping.is_empty() {
+        return Err(AppError::overlap(overlapping));
+    }
+
+    Ok(())
+}
+
+fn next_group_id(conn: &Connection) -> AppResult<i64> {
+    conn.query_row(
+        "SELECT COALESCE(MAX(group_id), 0) + 1 FROM operation_journal",
+        [],
+        |row| row.get(0),
+    )
+    .map_err(db_err("Failed to allocate operation group"))
+}
+
+/// Fold this update into the previous step if it is a recent lone update of the same row.
+fn coalesce(
+    conn: &Connection,
+    table: &str,
+    id: i64,
+    after: &Option<String>,
+    now: i64,
+) -> AppResult<bool> {
+    let latest: Option<(i64, i64, String, i64, bool, i64)> = conn
+        .query_row(
+            "SELECT j.id, j.group_id, j.entity, j.entity_id,
+                    j.before_json IS NOT NULL AND j.after_json IS NOT NULL,
+                    j.created_at
+             FROM operation_journal j
+             WHERE j.undone = 0
+               AND (SELECT COUNT(*) FROM operation_journal g WHERE g.group_id = j.group_id) = 1
+               AND j.group_id = (SELECT MAX(group_id) FROM operation_journal)
+             ORDER BY j.id DESC
+             LIMIT 1",
+            [],
+            |row| {
+                Ok((
+                    row.get(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                    row.get(5)?,
+                ))
+            },
+        )
+        .optional()
+        .map_err(db_err("Failed to read operation journal"))?;
+
+    let Some((row_id, _, entity, entity_id, is_update, created_at)) = latest else {
+        return Ok(false);
+    };
+    if entity != table || entity_id != id || !is_update || now - created_at > COALESCE_WINDOW_MS {
+        return Ok(false);
+    }
+
+    conn.execute(
+        "UPDATE operation_journal SET after_json = ?1, created_at = ?2 WHERE id = ?3",
+        params![after, now, row_id],
+    )
+    .map_err(db_err("Failed to update operation journal"))?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::categories::{create_category_impl, delete_category_impl};
+    use crate::data::database::{enable_foreign_keys, run_migrations};
+    use crate::data::recurrence::{
+        create_entry_template_impl, delete_entry_template_impl, get_entry_templates_impl,
+    };
+    use crate::data::tags::{create_tag_impl, get_time_entry_tags_impl, set_time_entry_tags_impl};
+    use crate::data::time_entries::{
+        create_time_entry_impl, delete_time_entry_impl, get_time_entries_by_range_impl,
+        update_time_entry_impl,
+    };
+    use crate::types::{
+        CategoryInput, EntryTemplateInput, TagInput, TimeEntryInput, TimeEntryUpdate,
+    };
+
+    fn setup_test_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        enable_foreign_keys(&conn).unwrap();
+        run_migrations(&conn).unwrap();
+        conn
+    }
+
+    fn create(conn: &Connection, start_time: i64, end_time: i64, label: &str) -> i64 {
+        create_time_entry_impl(
+            conn,
+            &TimeEntryInput {
+                start_time,
+                end_time,
+                label: label.to_string(),
+                color: None,
+                category_id: None,
+                notes: None,
+                project_id: None,
+            },
+        )
+        .unwrap()
+        .id
+    }
+
+    fn move_to(conn: &Connection, id: i64, start_time: i64, end_time: i64) {
+        let updates = TimeEntryUpdate {
+            start_time: Some(start_time),
+            end_time: Some(end_time),
+            label: None,
+            color: None,
+            category_id: None,
+            notes: None,
+            project_id: None,
+        };
+        update_time_entry_impl(conn, id, &updates).unwrap();
+    }
+
+    fn labels(conn: &Connection) -> Vec<(String, i64)> {
+        get_time_entries_by_range_impl(conn, 0, i64::MAX)
+            .unwrap()
+            .into_iter()
+            .map(|e| (e.label, e.start_time))
+            .collect()
+    }
+
+    /// Backdate every journal row so the next update starts a new step.
+    fn age_journal(conn: &Connection) {
+        conn.execute("UPDATE operation_journal SET created_at = 0", [])
+            .unwrap();
+    }
+
+    #[test]
+    fn test_undo_redo_create_update_delete() {
+        let conn = setup_test_db();
+        let id = create(&conn, 1000, 2000, "Task");
+        age_journal(&conn);
+        move_to(&conn, id, 3000, 4000);
+        age_journal(&conn);
+        delete_time_entry_impl(&conn, id).unwrap();
+
+        assert!(labels(&conn).is_empty());
+        undo(&conn).unwrap().unwrap();
+        assert_eq!(labels(&conn), vec![("Task".to_string(), 3000)]);
+        undo(&conn).unwrap().unwrap();
+        assert_eq!(labels(&conn), vec![("Task".to_string(), 1000)]);
+        let replay = undo(&conn).unwrap().unwrap();
+        assert_eq!(replay.changes[0].entity_id, id);
+        assert!(labels(&conn).is_empty());
+        assert!(undo(&conn).unwrap().is_none());
+
+        redo(&conn).unwrap().unwrap();
+        redo(&conn).unwrap().unwrap();
+        assert_eq!(labels(&conn), vec![("Task".to_string(), 3000)]);
+
+        let state = undo_state(&conn).unwrap();
+        assert!(state.can_undo && state.can_redo);
+
+        // A new change discards the redo history
+        create(&conn, 10_000, 11_000, "Other");
+        assert!(redo(&conn).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_undo_create_keeps_entry_links_for_redo() {
+        let conn = setup_test_db();
+        let id = create(&conn, 1000, 2000, "Task");
+        let tag = create_tag_impl(
+            &conn,
+            &TagInput {
+                name: "deep".to_string(),
+                color: None,
+            },
+        )
+        .unwrap();
+        set_time_entry_tags_impl(&conn, id, &[tag.id]).unwrap();
+
+        undo(&conn).unwrap().unwrap();
+        assert!(labels(&conn).is_empty());
+        let trashed: Option<i64> = conn
+            .query_row(
+                "SELECT deleted_at FROM time_entries WHERE id = ?1",
+                [id],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert!(trashed.is_some());
+
+        redo(&conn).unwrap().unwrap();
+        assert_eq!(labels(&conn), vec![("Task".to_string(), 1000)]);
+        assert_eq!(get_time_entry_tags_impl(&conn, id).unwrap(), vec![tag]);
+    }
+
+    #[test]
+    fn test_group_ends_when_closure_panics() {
+        let conn = setup_test_db();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            grouped(&conn, || -> AppResult<()> { panic!("boom") })
+        }));
+        assert!(result.is_err());
+        assert_eq!(ACTIVE_GROUP.with(Cell::get), None);
+    }
+
+    #[test]
+    fn test_drag_updates_coalesce_into_one_step() {
+        let conn = setup_test_db();
+        let id = create(&conn, 1000, 2000, "Dragged");
+        age_journal(&conn);
+
+        revisions::with_source(RevisionSource::Drag, || {
+            for offset in 1..=5 {
+                move_to(&conn, id, 1000 + offset * 100, 2000 + offset * 100);
+            }
+        });
+
+        undo(&conn).unwrap().unwrap();
+        assert_eq!(labels(&conn), vec![("Dragged".to_string(), 1000)]);
+    }
+
+    #[test]
+    fn test_quick_manual_edits_stay_separate_steps() {
+        let conn = setup_test_db();
+        let id = create(&conn, 1000, 2000, "Draft");
+        age_journal(&conn);
+
+        let rename = TimeEntryUpdate {
+            start_time: None,
+            end_time: None,
+            label: Some("Renamed".to_string()),
+            color: None,
+            category_id: None,
+            notes: None,
+            project_id: None,
+        };
+        update_time_entry_impl(&conn, id, &rename).unwrap();
+        move_to(&conn, id, 1500, 2500);
+
+        undo(&conn).unwrap().unwrap();
+        assert_eq!(labels(&conn), vec![("Renamed".to_string(), 1000)]);
+        undo(&conn).unwrap().unwrap();
+        assert_eq!(labels(&conn), vec![("Draft".to_string(), 1000)]);
+    }
+
+    #[test]
+    fn test_undo_respects_overlap_validation() {
+        let conn = setup_test_db();
+        let id = create(&conn, 1000, 2000, "Moved");
+        age_journal(&conn);
+        move_to(&conn, id, 5000, 6000);
+
+        // Something outside the journal now occupies the old slot.
+        conn.execute(
+            "INSERT INTO time_entries (start_time, end_time, label) VALUES (1500, 2500, 'Blocker')",
+            [],
+        )
+        .unwrap();
+
+        let error = undo(&conn).unwrap_err();
+        assert_eq!(error.code, "time_entry_overlap");
+        assert_eq!(
+            labels(&conn),
+            vec![("Blocker".to_string(), 1500), ("Moved".to_string(), 5000)]
+        );
+        assert!(undo_state(&conn).unwrap().can_undo);
+    }
+
+    #[test]
+    fn test_undo_category_delete_restores_assignments() {
+        let conn = setup_test_db();
+        let category = create_category_impl(
+            &conn,
+            &CategoryInput {
+                name: "Work".to_string(),
+                color: "#123456".to_string(),
+            },
+        )
+        .unwrap();
+        let entry = create_time_entry_impl(
+            &conn,
+            &TimeEntryInput {
+                start_time: 1000,
+                end_time: 2000,
+                label: "Task".to_string(),
+                color: None,
+                category_id: Some(category.id),
+                notes: None,
+                project_id: None,
+            },
+        )
+        .unwrap();
+        let template = |category_id| EntryTemplateInput {
+            label: "Standup".to_string(),
+            color: None,
+            category_id,
+            duration_ms: 15 * 60 * 1000,
+        };
+        create_entry_template_impl(&conn, &template(Some(category.id))).unwrap();
+        let deleted_template =
+            create_entry_template_impl(&conn, &template(Some(category.id))).unwrap();
+        let template_categories = |conn: &Connection| -> Vec<Option<i64>> {
+            get_entry_templates_impl(conn)
+                .unwrap()
+                .into_iter()
+                .map(|t| t.category_id)
+                .collect()
+        };
+
+        delete_category_impl(&conn, category.id).unwrap();
+        assert_eq!(template_categories(&conn), vec![None, None]);
+        // Deleted since, so undo leaves it deleted
+        delete_entry_template_impl(&conn, deleted_template.id).unwrap();
+        undo(&conn).unwrap().unwrap();
+        assert_eq!(template_categories(&conn), vec![Some(category.id)]);
+
+        let restored: Option<i64> = conn
+            .query_row(
+                "SELECT category_id FROM time_entries WHERE id = ?1",
+                [entry.id],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(restored, Some(category.id));
+
+        redo(&conn).unwrap().unwrap();
+        let cleared: Option<i64> = conn
+            .query_row(
+                "SELECT category_id FROM time_entries WHERE id = ?1",
+                [entry.id],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(cleared, None);
+        assert_eq!(template_categories(&conn), vec![None]);
+    }
+
+    #[test]
+    fn test_failed_category_delete_rolls_back() {
+        let conn = setup_test_db();
+        let category = create_category_impl(
+            &conn,
+            &CategoryInput {
+                name: "Work".to_string(),
+                color: "#123456".to_string(),
+            },
+        )
+        .unwrap();
+        let entry = create_time_entry_impl(
+            &conn,
+            &TimeEntryInput {
+                start_time: 1000,
+                end_time: 2000,
+                label: "Task".to_string(),
+                color: None,
+                category_id: Some(category.id),
+                notes: None,
+                project_id: None,
+            },
+        )
+        .unwrap();
+
+        // Journaling fails after the DELETE has run
+        conn.execute_batch(
+            "CREATE TEMP TRIGGER fail_journal BEFORE INSERT ON operation_journal
+             BEGIN SELECT RAISE(ABORT, 'journal unavailable'); END;",
+        )
+        .unwrap();
+        assert!(delete_category_impl(&conn, category.id).is_err());
+        conn.execute_batch("DROP TRIGGER fail_journal").unwrap();
+
+        let category_id: Option<i64> = conn
+            .query_row(
+                "SELECT category_id FROM time_entries WHERE id = ?1",
+                [entry.id],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(category_id, Some(category.id));
+        assert_eq!(
+            crate::data::categories::get_categories_impl(&conn)
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+}
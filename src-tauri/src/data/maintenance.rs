@@ -0,0 +1,307 @@
+use crate::data::error::db_err;
+use crate::data::{AppError, AppResult};
+use crate::types::{DatabaseStats, MaintenanceReport, TableRowCount};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::path::Path;
+
+/// `maintenance_log` task for the hourly WAL checkpoint.
+pub const CHECKPOINT_TASK: &str = "checkpoint";
+/// `maintenance_log` task for the daily optimize + incremental vacuum.
+pub const MAINTENANCE_TASK: &str = "maintenance";
+
+/// Free pages returned to the filesystem per write, so capture is never
+/// blocked behind one long vacuum.
+pub const VACUUM_BATCH_PAGES: i64 = 1000;
+
+const DAY_MS: i64 = 24 * 60 * 60 * 1000;
+
+/// Runs one maintenance step on the writer connection.
+pub type StepWriter<'a> =
+    &'a dyn Fn(&mut dyn FnMut(&Connection) -> AppResult<i64>) -> AppResult<i64>;
+
+/// Daily maintenance runs outside the idle window once it is this overdue,
+/// for machines that are never on during idle hours.
+const MAINTENANCE_OVERDUE_MS: i64 = 7 * DAY_MS;
+
+/// Switch the database to `auto_vacuum = INCREMENTAL`.
+///
+/// The mode of an existing database only changes with a full `VACUUM`, so
+/// this rebuilds the file once; later runs see the mode already set.
+pub fn enable_incremental_auto_vacuum(conn: &Connection) -> AppResult<()> {
+    set_auto_vacuum(conn, 2, "INCREMENTAL")
+}
+
+pub fn disable_auto_vacuum(conn: &Connection) -> AppResult<()> {
+    set_auto_vacuum(conn, 0, "NONE")
+}
+
+fn set_auto_vacuum(conn: &Connection, mode: i64, name: &str) -> AppResult<()> {
+    let current: i64 = conn
+        .query_row("PRAGMA auto_vacuum", [], |row| row.get(0))
+        .map_err(db_err("Failed to read auto_vacuum"))?;
+    if current == mode {
+        return Ok(());
+    }
+
+    conn.execute_batch(&format!("PRAGMA auto_vacuum = {}; VACUUM;", name))
+        .map_err(|e| AppError::from(e).context(format!("Failed to set auto_vacuum to {}", name)))
+}
+
+/// Whether the daily optimize + vacuum should run now.
+///
+/// Runs once per day inside the idle window `[idle_start_hour, idle_end_hour)`
+/// (which may wrap past midnight), or at any hour once it is a week overdue.
+pub fn maintenance_due(
+    idle_start_hour: u32,
+    idle_end_hour: u32,
+    local_hour: u32,
+    now: i64,
+    last_run_at: Option<i64>,
+) -> bool {
+    let Some(last_run_at) = last_run_at else {
+        return true;
+    };
+    if now - last_run_at >= MAINTENANCE_OVERDUE_MS {
+        return true;
+    }
+
+    let in_idle_window = if idle_start_hour <= idle_end_hour {
+        (idle_start_hour..idle_end_hour).contains(&local_hour)
+    } else {
+        local_hour >= idle_start_hour || local_hour < idle_end_hour
+    };
+    // Slightly under a day so a run at 02:05 does not push tomorrow's past the window.
+    in_idle_window && now - last_run_at >= DAY_MS - 2 * 60 * 60 * 1000
+}
+
+/// `PRAGMA wal_checkpoint(TRUNCATE)`; returns `(busy, wal_pages, checkpointed_pages)`.
+///
+/// `busy` means a reader held the WAL open and it could not be truncated.
+pub fn checkpoint(conn: &Connection, now: i64) -> AppResult<(bool, i64, i64)> {
+    // Logged first so the log write itself is checkpointed too
+    record_task(conn, CHECKPOINT_TASK, now)?;
+    let (busy, log, checkpointed): (i64, i64, i64) = conn
+        .query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        })
+        .map_err(db_err("Failed to checkpoint WAL"))?;
+
+    Ok((busy != 0, log, checkpointed))
+}
+
+/// `PRAGMA optimize`, refreshing planner statistics where they are stale.
+pub fn optimize(conn: &Connection) -> AppResult<()> {
+    conn.execute_batch("PRAGMA optimize;")
+        .map_err(db_err("Failed to optimize database"))?;
+    Ok(())
+}
+
+/// Release up to `max_pages` free pages; returns how many were released.
+pub fn incremental_vacuum(conn: &Connection, max_pages: i64) -> AppResult<i64> {
+    let before = freelist_count(conn)?;
+    // Each step of the pragma releases one page, so step it to completion.
+    let mut stmt = conn
+        .prepare(&format!("PRAGMA incremental_vacuum({})", max_pages))
+        .map_err(db_err("Failed to prepare incremental vacuum"))?;
+    let mut rows = stmt
+        .query([])
+        .map_err(db_err("Failed to run incremental vacuum"))?;
+    while rows
+        .next()
+        .map_err(db_err("Failed to run incremental vacuum"))?
+        .is_some()
+    {}
+    drop(rows);
+    Ok(before - freelist_count(conn)?)
+}
+
+/// Optimize, then vacuum in batches of [`VACUUM_BATCH_PAGES`], then
+/// checkpoint so the truncated file is what ends up on disk.
+///
+/// `write` runs one step on the writer connection.
+pub fn run_maintenance(write: StepWriter, now: i64) -> AppResult<MaintenanceReport> {
+    write(&mut |conn| optimize(conn).map(|_| 0))?;
+
+    let mut freed_pages = 0;
+    loop {
+        let freed = write(&mut |conn| incremental_vacuum(conn, VACUUM_BATCH_PAGES))?;
+        freed_pages += freed;
+        if freed < VACUUM_BATCH_PAGES {
+            break;
+        }
+    }
+
+    let mut report = MaintenanceReport {
+        optimized: true,
+        freed_pages,
+        wal_checkpoint_busy: false,
+        wal_pages_checkpointed: 0,
+    };
+    write(&mut |conn| {
+        record_task(conn, MAINTENANCE_TASK, now)?;
+        let (busy, _, checkpointed) = checkpoint(conn, now)?;
+        report.wal_checkpoint_busy = busy;
+        report.wal_pages_checkpointed = checkpointed;
+        Ok(0)
+    })?;
+
+    Ok(report)
+}
+
+/// When `task` last completed.
+pub fn last_run(conn: &Connection, task: &str) -> AppResult<Option<i64>> {
+    let last = conn
+        .query_row(
+            "SELECT last_run_at FROM maintenance_log WHERE task = ?1",
+            params![task],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(db_err("Failed to read maintenance log"))?;
+    Ok(last)
+}
+
+fn record_task(conn: &Connection, task: &str, now: i64) -> AppResult<()> {
+    conn.execute(
+        "INSERT INTO maintenance_log (task, last_run_at) VALUES (?1, ?2)
+         ON CONFLICT(task) DO UPDATE SET last_run_at = excluded.last_run_at",
+        params![task, now],
+    )
+    .map_err(db_err("Failed to record maintenance"))?;
+    Ok(())
+}
+
+/// Size, page and row statistics for the database at `db_path`.
+pub fn database_stats(conn: &Connection, db_path: &Path) -> AppResult<DatabaseStats> {
+    let pragma = |name: &str| -> AppResult<i64> {
+        conn.query_row(&format!("PRAGMA {}", name), [], |row| row.get(0))
+            .map_err(|e| AppError::from(e).context(format!("Failed to read {}", name)))
+    };
+    let file_size = |path: &Path| std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+
+    let auto_vacuum = match pragma("auto_vacuum")? {
+        1 => "full",
+        2 => "incremental",
+        _ => "none",
+    };
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT name FROM sqlite_master
+             WHERE type = 'table' AND name NOT LIKE 'sqlite_%'
+             ORDER BY name",
+        )
+        .map_err(db_err("Failed to prepare table list query"))?;
+    let table_names = stmt
+        .query_map([], |row| row.get::<_, String>(0))
+        .map_err(db_err("Failed to list tables"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db_err("Failed to collect tables"))?;
+
+    let mut tables = Vec::with_capacity(table_names.len());
+    for name in table_names {
+        let row_count: i64 = conn
+            .query_row(&format!("SELECT COUNT(*) FROM \"{}\"", name), [], |row| {
+                row.get(0)
+            })
+            .map_err(|e| AppError::from(e).context(format!("Failed to count rows of {}", name)))?;
+        tables.push(TableRowCount { name, row_count });
+    }
+
+    let mut wal_path = db_path.as_os_str().to_owned();
+    wal_path.push("-wal");
+
+    Ok(DatabaseStats {
+        file_size_bytes: file_size(db_path),
+        wal_size_bytes: file_size(Path::new(&wal_path)),
+        page_size: pragma("page_size")?,
+        page_count: pragma("page_count")?,
+        freelist_count: pragma("freelist_count")?,
+        auto_vacuum: auto_vacuum.to_string(),
+        tables,
+        last_checkpoint_at: last_run(conn, CHECKPOINT_TASK)?,
+        last_maintenance_at: last_run(conn, MAINTENANCE_TASK)?,
+    })
+}
+
+fn freelist_count(conn: &Connection) -> AppResult<i64> {
+    let count = conn
+        .query_row("PRAGMA freelist_count", [], |row| row.get(0))
+        .map_err(db_err("Failed to read freelist_count"))?;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const HOUR_MS: i64 = 60 * 60 * 1000;
+
+    fn file_database(dir: &TempDir) -> (Connection, std::path::PathBuf) {
+        let path = dir.path().join("database.db");
+        let conn = Connection::open(&path).unwrap();
+        crate::data::database::run_migrations(&conn).unwrap();
+        conn.pragma_update(None, "journal_mode", "WAL").unwrap();
+        (conn, path)
+    }
+
+    #[test]
+    fn test_migration_enables_incremental_auto_vacuum() {
+        let dir = TempDir::new().unwrap();
+        let (conn, path) = file_database(&dir);
+
+        let stats = database_stats(&conn, &path).unwrap();
+        assert_eq!(stats.auto_vacuum, "incremental");
+        assert!(stats.tables.iter().any(|t| t.name == "time_entries"));
+        assert_eq!(stats.last_maintenance_at, None);
+    }
+
+    #[test]
+    fn test_maintenance_frees_pages_and_truncates_wal() {
+        let dir = TempDir::new().unwrap();
+        let (conn, path) = file_database(&dir);
+
+        let title = "x".repeat(500);
+        for i in 0..2000 {
+            conn.execute(
+                "INSERT INTO window_activity (timestamp, window_title, process_name)
+                 VALUES (?1, ?2, 'code.exe')",
+                params![i, title],
+            )
+            .unwrap();
+        }
+        conn.execute("DELETE FROM window_activity", []).unwrap();
+        assert!(freelist_count(&conn).unwrap() > 0);
+
+        let write = |step: &mut dyn FnMut(&Connection) -> AppResult<i64>| step(&conn);
+        let report = run_maintenance(&write, 5000).unwrap();
+
+        assert!(report.optimized);
+        assert!(report.freed_pages > 0);
+        assert!(!report.wal_checkpoint_busy);
+        let stats = database_stats(&conn, &path).unwrap();
+        assert_eq!(stats.freelist_count, 0);
+        assert_eq!(stats.wal_size_bytes, 0);
+        assert_eq!(stats.last_maintenance_at, Some(5000));
+        assert_eq!(stats.last_checkpoint_at, Some(5000));
+    }
+
+    #[test]
+    fn test_maintenance_due() {
+        let day = DAY_MS;
+        // Never run: due at any hour
+        assert!(maintenance_due(2, 6, 14, day, None));
+        // Ran yesterday: only inside the window
+        assert!(maintenance_due(2, 6, 3, 2 * day, Some(day)));
+        assert!(!maintenance_due(2, 6, 14, 2 * day, Some(day)));
+        // Ran an hour ago
+        assert!(!maintenance_due(2, 6, 3, day + HOUR_MS, Some(day)));
+        // Window wrapping midnight
+        assert!(maintenance_due(22, 4, 23, 2 * day, Some(day)));
+        assert!(maintenance_due(22, 4, 1, 2 * day, Some(day)));
+        assert!(!maintenance_due(22, 4, 12, 2 * day, Some(day)));
+        // A week overdue: outside the window too
+        assert!(maintenance_due(2, 6, 14, 8 * day, Some(day)));
+    }
+}
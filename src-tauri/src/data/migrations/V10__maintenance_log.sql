@@ -0,0 +1,5 @@
+-- When each maintenance task last completed
+CREATE TABLE IF NOT EXISTS maintenance_log (
+    task TEXT PRIMARY KEY,
+    last_run_at INTEGER NOT NULL
+);
@@ -0,0 +1,7 @@
+-- The entry the timer is counting, if any. The entry's end_time follows the
+-- heartbeat while it runs; last_seen_at is when the app last confirmed it.
+CREATE TABLE IF NOT EXISTS running_timer (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    entry_id INTEGER NOT NULL REFERENCES time_entries(id) ON DELETE CASCADE,
+    last_seen_at INTEGER NOT NULL
+);
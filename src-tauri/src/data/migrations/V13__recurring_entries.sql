@@ -0,0 +1,26 @@
+-- Reusable presets for time entries
+CREATE TABLE IF NOT EXISTS entry_templates (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    label TEXT NOT NULL,
+    color TEXT,
+    category_id INTEGER REFERENCES categories(id) ON DELETE SET NULL,
+    duration_ms INTEGER NOT NULL
+);
+
+-- A template repeated on a schedule. `rule` is an RRULE subset, `start_minute`
+-- the local time of day and `starts_on` the local date (YYYY-MM-DD) it begins.
+CREATE TABLE IF NOT EXISTS recurring_series (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    template_id INTEGER NOT NULL REFERENCES entry_templates(id) ON DELETE CASCADE,
+    rule TEXT NOT NULL,
+    start_minute INTEGER NOT NULL,
+    starts_on TEXT NOT NULL
+);
+
+-- Days a series has already produced an entry, so deleting that entry sticks
+CREATE TABLE IF NOT EXISTS recurring_occurrences (
+    series_id INTEGER NOT NULL REFERENCES recurring_series(id) ON DELETE CASCADE,
+    occurs_on TEXT NOT NULL,
+    entry_id INTEGER REFERENCES time_entries(id) ON DELETE SET NULL,
+    PRIMARY KEY (series_id, occurs_on)
+);
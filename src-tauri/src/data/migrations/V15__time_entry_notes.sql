@@ -0,0 +1,2 @@
+-- Free-form markdown describing what was done during an entry
+ALTER TABLE time_entries ADD COLUMN notes TEXT;
@@ -0,0 +1,22 @@
+-- Who the work is billed to. Rates are in minor units (cents) per hour.
+CREATE TABLE IF NOT EXISTS clients (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+    hourly_rate_cents INTEGER,
+    currency TEXT NOT NULL
+);
+
+-- A rate or currency left NULL falls back to the client's
+CREATE TABLE IF NOT EXISTS projects (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    client_id INTEGER NOT NULL REFERENCES clients(id),
+    name TEXT NOT NULL COLLATE NOCASE,
+    hourly_rate_cents INTEGER,
+    currency TEXT,
+    billable INTEGER NOT NULL DEFAULT 1,
+    UNIQUE (client_id, name)
+);
+
+ALTER TABLE time_entries ADD COLUMN project_id INTEGER REFERENCES projects(id);
+
+CREATE INDEX IF NOT EXISTS idx_time_entries_project ON time_entries(project_id);
@@ -0,0 +1,11 @@
+-- Rows removed by the integrity repair, kept as JSON for manual recovery
+CREATE TABLE IF NOT EXISTS quarantined_rows (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    source_table TEXT NOT NULL,
+    source_id INTEGER NOT NULL,
+    reason TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    quarantined_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_quarantined_rows_source ON quarantined_rows(source_table, source_id);
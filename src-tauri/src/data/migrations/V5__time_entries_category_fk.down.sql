@@ -0,0 +1,19 @@
+-- Restore the V2 shape of time_entries (plain reference, no referential action)
+CREATE TABLE time_entries_old (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    start_time INTEGER NOT NULL,
+    end_time INTEGER NOT NULL,
+    label TEXT NOT NULL,
+    color TEXT,
+    category_id INTEGER REFERENCES categories(id)
+);
+
+INSERT INTO time_entries_old (id, start_time, end_time, label, color, category_id)
+SELECT id, start_time, end_time, label, color, category_id FROM time_entries;
+
+DROP TABLE time_entries;
+ALTER TABLE time_entries_old RENAME TO time_entries;
+
+CREATE INDEX IF NOT EXISTS idx_time_entries_start_time ON time_entries(start_time);
+CREATE INDEX IF NOT EXISTS idx_time_entries_end_time ON time_entries(end_time);
+CREATE INDEX IF NOT EXISTS idx_time_entries_label ON time_entries(label);
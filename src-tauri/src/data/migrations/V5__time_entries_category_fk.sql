@@ -0,0 +1,23 @@
+-- Rebuild time_entries so deleting a category clears references declaratively
+CREATE TABLE time_entries_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    start_time INTEGER NOT NULL,
+    end_time INTEGER NOT NULL,
+    label TEXT NOT NULL,
+    color TEXT,
+    category_id INTEGER REFERENCES categories(id) ON DELETE SET NULL
+);
+
+-- References to categories that no longer exist are dropped on the way over
+INSERT INTO time_entries_new (id, start_time, end_time, label, color, category_id)
+SELECT id, start_time, end_time, label, color,
+       CASE WHEN category_id IN (SELECT id FROM categories) THEN category_id END
+FROM time_entries;
+
+DROP TABLE time_entries;
+ALTER TABLE time_entries_new RENAME TO time_entries;
+
+CREATE INDEX IF NOT EXISTS idx_time_entries_start_time ON time_entries(start_time);
+CREATE INDEX IF NOT EXISTS idx_time_entries_end_time ON time_entries(end_time);
+CREATE INDEX IF NOT EXISTS idx_time_entries_label ON time_entries(label);
+CREATE INDEX IF NOT EXISTS idx_time_entries_category_id ON time_entries(category_id);
@@ -0,0 +1,4 @@
+-- Soft delete: trashed entries keep their row until purged
+ALTER TABLE time_entries ADD COLUMN deleted_at INTEGER;
+
+CREATE INDEX IF NOT EXISTS idx_time_entries_deleted_at ON time_entries(deleted_at);
@@ -0,0 +1,15 @@
+-- Undo/redo history: row snapshots before and after each change.
+-- Changes sharing a group_id are undone and redone together.
+CREATE TABLE IF NOT EXISTS operation_journal (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    group_id INTEGER NOT NULL,
+    entity TEXT NOT NULL,
+    entity_id INTEGER NOT NULL,
+    before_json TEXT,
+    after_json TEXT,
+    undone INTEGER NOT NULL DEFAULT 0,
+    created_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_operation_journal_group_id ON operation_journal(group_id);
+CREATE INDEX IF NOT EXISTS idx_operation_journal_entity ON operation_journal(entity, entity_id);
@@ -0,0 +1,12 @@
+-- Audit trail of every change to a time entry
+CREATE TABLE IF NOT EXISTS time_entry_revisions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    entry_id INTEGER NOT NULL,
+    changed_at INTEGER NOT NULL,
+    action TEXT NOT NULL,
+    source TEXT NOT NULL,
+    before_json TEXT,
+    after_json TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_time_entry_revisions_entry_id ON time_entry_revisions(entry_id, changed_at);
@@ -0,0 +1,24 @@
+-- Expand segments back into one sample per second
+CREATE TABLE IF NOT EXISTS process_samples (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp INTEGER NOT NULL,
+    process_name TEXT NOT NULL,
+    day_id INTEGER NOT NULL,
+    UNIQUE(timestamp)
+);
+
+CREATE INDEX IF NOT EXISTS idx_process_samples_timestamp ON process_samples(timestamp);
+CREATE INDEX IF NOT EXISTS idx_process_samples_day_id ON process_samples(day_id);
+CREATE INDEX IF NOT EXISTS idx_process_samples_process_name ON process_samples(process_name);
+
+WITH RECURSIVE seconds(timestamp, process_name, end_time) AS (
+    SELECT start_time, process_name, end_time FROM process_segments
+    UNION ALL
+    SELECT timestamp + 1000, process_name, end_time FROM seconds
+    WHERE timestamp + 1000 < end_time
+)
+INSERT OR REPLACE INTO process_samples (timestamp, process_name, day_id)
+SELECT timestamp, process_name, CAST(strftime('%Y%m%d', timestamp / 1000, 'unixepoch') AS INTEGER)
+FROM seconds;
+
+DROP TABLE process_segments;
@@ -0,0 +1,32 @@
+-- Run-length process history: one row per uninterrupted stretch of a foreground process
+CREATE TABLE IF NOT EXISTS process_segments (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    start_time INTEGER NOT NULL,
+    end_time INTEGER NOT NULL,
+    process_name TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_process_segments_start_time ON process_segments(start_time);
+CREATE INDEX IF NOT EXISTS idx_process_segments_end_time ON process_segments(end_time);
+
+-- Collapse consecutive samples of the same process. A sample covers one second;
+-- samples more than 5 seconds apart start a new segment.
+INSERT INTO process_segments (start_time, end_time, process_name)
+SELECT MIN(timestamp), MAX(timestamp) + 1000, process_name
+FROM (
+    SELECT timestamp, process_name, SUM(starts_run) OVER (ORDER BY timestamp) AS run
+    FROM (
+        SELECT timestamp, process_name,
+               CASE
+                   WHEN LAG(process_name) OVER w = process_name
+                        AND timestamp - LAG(timestamp) OVER w <= 5000 THEN 0
+                   ELSE 1
+               END AS starts_run
+        FROM process_samples
+        WINDOW w AS (ORDER BY timestamp)
+    )
+)
+GROUP BY run
+ORDER BY MIN(timestamp);
+
+DROP TABLE process_samples;
@@ -1,107 +1,638 @@
+pub mod backup;
+pub mod bulk_edit;
 pub mod categories;
 pub mod database;
+pub mod diagnostics;
+pub mod encryption;
+pub mod error;
 pub mod export;
 pub mod idle;
-pub mod process_samples;
+pub mod journal;
+pub mod maintenance;
+pub mod overlap;
+pub mod pool;
+pub mod process_segments;
+pub mod projects;
+pub mod recurrence;
+pub mod retention;
+pub mod revisions;
 pub mod screenshot;
 pub mod search;
+pub mod tags;
 pub mod time_entries;
+pub mod timer;
 pub mod window_activity;
 
 // Re-export internal functions for use within the crate
 pub use screenshot::{get_screenshot_near_time, insert_screenshot};
 pub use window_activity::insert_window_activities_batch;
-pub use process_samples::{delete_process_samples_before, insert_process_sample};
+pub use process_segments::record_process_sample;
+
+pub use error::AppError;
 
 use once_cell::sync::Lazy;
+use pool::DbPool;
 use rusqlite::Connection;
-use std::sync::Mutex;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
 
-pub type AppResult<T> = std::result::Result<T, String>;
+pub type AppResult<T> = std::result::Result<T, AppError>;
 
-static DB_CONN: Lazy<Mutex<Option<Connection>>> = Lazy::new(|| Mutex::new(None));
+static DB_POOL: Lazy<RwLock<Option<Arc<DbPool>>>> = Lazy::new(|| RwLock::new(None));
+/// Why the database could not be opened at startup, reported by commands.
+static STARTUP_ERROR: Lazy<RwLock<Option<AppError>>> = Lazy::new(|| RwLock::new(None));
 
 pub fn init_database() -> AppResult<()> {
-    let conn = database::init_database()?;
-    let mut db = DB_CONN
-        .lock()
+    let db_path = database::get_database_path()?;
+    let writer = database::init_database()?;
+    let pool = DbPool::open(writer, &db_path)?;
+    let mut db = DB_POOL
+        .write()
         .map_err(|e| format!("Failed to lock database: {}", e))?;
-    *db = Some(conn);
+    *db = Some(Arc::new(pool));
+    drop(db);
+    set_startup_error(None);
+
+    if let Err(e) = settle_running_timer() {
+        eprintln!("Failed to apply the timer startup policy: {}", e);
+    }
+    Ok(())
+}
+
+/// Resume or stop a timer left running by the last session, per the timer settings.
+fn settle_running_timer() -> AppResult<()> {
+    let policy = crate::app_settings::load_timer_settings()?.on_startup;
+    let now = chrono::Utc::now().timestamp_millis();
+    if let Some(entry) = with_db_write(|conn| timer::settle_on_startup(conn, policy, now))? {
+        println!("Stopped timer left running on time entry {}", entry.id);
+    }
     Ok(())
 }
 
-pub fn with_db<F, R>(f: F) -> AppResult<R>
+/// Drop the pool so the database file can be replaced.
+///
+/// The pool is taken out of `DB_POOL` first, so new commands fail fast instead
+/// of blocking on the lock while this waits for in-flight commands holding the
+/// pool to finish. If they do not within `timeout`, the pool is put back and an
+/// error is returned.
+fn close_database(timeout: Duration) -> AppResult<()> {
+    let Some(mut pool) = DB_POOL
+        .write()
+        .map_err(|e| format!("Failed to lock database: {}", e))?
+        .take()
+    else {
+        return Ok(());
+    };
+
+    let deadline = Instant::now() + timeout;
+    loop {
+        match Arc::try_unwrap(pool) {
+            Ok(pool) => {
+                drop(pool);
+                return Ok(());
+            }
+            Err(shared) if Instant::now() < deadline => {
+                pool = shared;
+                std::thread::sleep(Duration::from_millis(20));
+            }
+            Err(shared) => {
+                let mut db = DB_POOL
+                    .write()
+                    .map_err(|e| format!("Failed to lock database: {}", e))?;
+                if db.is_none() {
+                    *db = Some(shared);
+                }
+                return Err(AppError::new(
+                    error::ErrorKind::Conflict,
+                    "database_busy",
+                    "Database is still in use; try again",
+                ));
+            }
+        }
+    }
+}
+
+/// Try to repair the database file after `init_database` failed at startup.
+///
+/// Only a file SQLite reports as corrupt or not a database is repaired, and
+/// never an encrypted one, where a wrong key looks the same. Any other
+/// failure, such as a schema from a newer version, is returned unchanged.
+pub fn repair_database_on_startup(
+    init_error: AppError,
+) -> AppResult<crate::types::IntegrityReport> {
+    let db_path = database::get_database_path()?;
+    let encrypted = crate::app_settings::load_encryption_settings()?.enabled;
+    if encrypted || !diagnostics::is_corrupt_database_file(&db_path) {
+        return Err(init_error);
+    }
+    diagnostics::repair_database_file(&db_path, &screenshot_file_exists)
+}
+
+/// Remember why startup could not open the database so commands report it
+/// instead of a bare "Database not initialized".
+pub fn set_startup_error(error: Option<AppError>) {
+    if let Ok(mut startup_error) = STARTUP_ERROR.write() {
+        *startup_error = error;
+    }
+}
+
+/// Paths that cannot be resolved count as present so repair never drops rows on a guess.
+fn screenshot_file_exists(stored_path: &str) -> bool {
+    crate::app_settings::resolve_screenshot_file_path(stored_path)
+        .map(|path| path.exists())
+        .unwrap_or(true)
+}
+
+fn current_pool() -> AppResult<Arc<DbPool>> {
+    let db = DB_POOL
+        .read()
+        .map_err(|e| format!("Failed to lock database: {}", e))?;
+    db.as_ref().cloned().ok_or_else(|| {
+        if crate::crypto::is_locked() {
+            database_locked()
+        } else if let Some(error) = STARTUP_ERROR.read().ok().and_then(|e| e.clone()) {
+            error
+        } else {
+            AppError::from("Database not initialized")
+        }
+    })
+}
+
+/// Run `f` on the single writer connection. Use for anything that mutates.
+pub fn with_db_write<F, R>(f: F) -> AppResult<R>
 where
     F: FnOnce(&Connection) -> AppResult<R>,
 {
-    let db = DB_CONN
-        .lock()
-        .map_err(|e| format!("Failed to lock database: {}", e))?;
-    let conn = db.as_ref().ok_or("Database not initialized")?;
-    f(conn)
+    current_pool()?.write(f)
 }
 
-// Tauri commands - ALL must use with_db wrapper
+/// Run `f` on a pooled read-only connection; does not wait for the writer.
+pub fn with_db_read<F, R>(f: F) -> AppResult<R>
+where
+    F: FnOnce(&Connection) -> AppResult<R>,
+{
+    current_pool()?.read(f)
+}
+
+// Tauri commands - ALL must use with_db_read / with_db_write
 #[tauri::command]
 pub async fn get_time_entries(date: i64) -> AppResult<Vec<crate::types::TimeEntry>> {
-    with_db(|conn| time_entries::get_time_entries_impl(conn, date))
+    with_db_read(|conn| time_entries::get_time_entries_impl(conn, date))
 }
 
 #[tauri::command]
 pub async fn get_time_entries_by_range(
     start_time: i64,
     end_time: i64,
+    tag_ids: Option<Vec<i64>>,
 ) -> AppResult<Vec<crate::types::TimeEntry>> {
-    with_db(|conn| time_entries::get_time_entries_by_range_impl(conn, start_time, end_time))
+    with_db_read(|conn| match tag_ids.as_deref() {
+        Some(tag_ids) if !tag_ids.is_empty() => {
+            tags::get_time_entries_by_range_with_tags_impl(conn, start_time, end_time, tag_ids)
+        }
+        _ => time_entries::get_time_entries_by_range_impl(conn, start_time, end_time),
+    })
 }
 
 #[tauri::command]
 pub async fn create_time_entry(
     entry: crate::types::TimeEntryInput,
 ) -> AppResult<crate::types::TimeEntry> {
-    with_db(|conn| time_entries::create_time_entry_impl(conn, &entry))
+    with_db_write(|conn| time_entries::create_time_entry_impl(conn, &entry))
 }
 
 #[tauri::command]
 pub async fn update_time_entry(
     id: i64,
     updates: crate::types::TimeEntryUpdate,
+    source: Option<crate::types::EditSource>,
 ) -> AppResult<crate::types::TimeEntry> {
-    with_db(|conn| time_entries::update_time_entry_impl(conn, id, &updates))
+    let source = source.unwrap_or(crate::types::EditSource::Manual).into();
+    with_db_write(|conn| {
+        revisions::with_source(source, || {
+            time_entries::update_time_entry_impl(conn, id, &updates)
+        })
+    })
+}
+
+/// Create an entry, resolving overlaps with `strategy`; returns every entry it changed.
+#[tauri::command]
+pub async fn create_time_entry_with_strategy_cmd(
+    entry: crate::types::TimeEntryInput,
+    strategy: crate::types::OverlapStrategy,
+) -> AppResult<crate::types::OverlapResolution> {
+    with_db_write(|conn| overlap::create_time_entry_with_strategy(conn, &entry, strategy))
+}
+
+/// Update an entry, resolving overlaps with `strategy`; returns every entry it changed.
+#[tauri::command]
+pub async fn update_time_entry_with_strategy_cmd(
+    id: i64,
+    updates: crate::types::TimeEntryUpdate,
+    strategy: crate::types::OverlapStrategy,
+    source: Option<crate::types::EditSource>,
+) -> AppResult<crate::types::OverlapResolution> {
+    let source = source.unwrap_or(crate::types::EditSource::Manual).into();
+    with_db_write(|conn| {
+        revisions::with_source(source, || {
+            overlap::update_time_entry_with_strategy(conn, id, &updates, strategy)
+        })
+    })
 }
 
 #[tauri::command]
 pub async fn delete_time_entry(id: i64) -> AppResult<()> {
-    with_db(|conn| time_entries::delete_time_entry_impl(conn, id))
+    with_db_write(|conn| time_entries::delete_time_entry_impl(conn, id))
+}
+
+/// Cut entry `id` in two at `at`; the second half may get its own label and category.
+#[tauri::command]
+pub async fn split_time_entry_cmd(
+    id: i64,
+    at: i64,
+    new_label: Option<String>,
+    new_category_id: Option<i64>,
+) -> AppResult<crate::types::TimeEntrySplit> {
+    with_db_write(|conn| {
+        time_entries::split_time_entry_impl(conn, id, at, new_label.as_deref(), new_category_id)
+    })
+}
+
+/// Merge entries `ids` into one; `absorb_gaps` bridges the time between them.
+#[tauri::command]
+pub async fn merge_time_entries_cmd(
+    ids: Vec<i64>,
+    label: Option<String>,
+    category_id: Option<i64>,
+    absorb_gaps: Option<bool>,
+) -> AppResult<crate::types::TimeEntry> {
+    with_db_write(|conn| {
+        time_entries::merge_time_entries_impl(
+            conn,
+            &ids,
+            label.as_deref(),
+            category_id,
+            absorb_gaps.unwrap_or(false),
+        )
+    })
 }
 
+/// Entries a bulk edit would touch, before and after. Changes nothing.
 #[tauri::command]
-pub async fn search_activities_cmd(query: String) -> AppResult<Vec<crate::types::SearchResult>> {
-    with_db(|conn| search::search_activities_impl(conn, &query))
+pub async fn preview_bulk_edit_cmd(
+    filter: crate::types::TimeEntryFilter,
+    patch: crate::types::TimeEntryPatch,
+) -> AppResult<Vec<crate::types::BulkEditChange>> {
+    // Needs the writer: the preview runs the edit and rolls it back
+    with_db_write(|conn| bulk_edit::preview_bulk_edit(conn, &filter, &patch))
 }
 
 #[tauri::command]
-pub async fn search_activities_by_range_cmd(query: String, start_time: i64, end_time: i64) -> AppResult<Vec<crate::types::SearchResult>> {
-    with_db(|conn| {
-        search::search_activities_by_date_impl(conn, &query, start_time, end_time)
+pub async fn bulk_edit_time_entries_cmd(
+    filter: crate::types::TimeEntryFilter,
+    patch: crate::types::TimeEntryPatch,
+) -> AppResult<Vec<crate::types::BulkEditChange>> {
+    with_db_write(|conn| bulk_edit::apply_bulk_edit(conn, &filter, &patch))
+}
+
+/// Working time between `start_time` and `end_time` with no entry, at least `min_gap_ms` long.
+#[tauri::command]
+pub async fn get_untracked_gaps_cmd(
+    start_time: i64,
+    end_time: i64,
+    min_gap_ms: Option<i64>,
+) -> AppResult<Vec<crate::types::UntrackedGap>> {
+    let hours = crate::app_settings::load_working_hours_settings()?;
+    let windows = time_entries::working_windows(&chrono::Local, start_time, end_time, &hours);
+    let min_gap_ms = min_gap_ms.unwrap_or(5 * 60 * 1000);
+    with_db_read(|conn| {
+        time_entries::get_untracked_gaps_impl(conn, start_time, end_time, min_gap_ms, &windows)
     })
 }
 
+/// How many local days ahead recurring series create their entries.
+const RECURRING_HORIZON_DAYS: u32 = 7;
+
+#[tauri::command]
+pub async fn list_entry_templates_cmd() -> AppResult<Vec<crate::types::EntryTemplate>> {
+    with_db_read(recurrence::get_entry_templates_impl)
+}
+
+#[tauri::command]
+pub async fn create_entry_template_cmd(
+    template: crate::types::EntryTemplateInput,
+) -> AppResult<crate::types::EntryTemplate> {
+    with_db_write(|conn| recurrence::create_entry_template_impl(conn, &template))
+}
+
+#[tauri::command]
+pub async fn update_entry_template_cmd(
+    id: i64,
+    template: crate::types::EntryTemplateInput,
+) -> AppResult<crate::types::EntryTemplate> {
+    with_db_write(|conn| recurrence::update_entry_template_impl(conn, id, &template))
+}
+
+#[tauri::command]
+pub async fn delete_entry_template_cmd(id: i64) -> AppResult<()> {
+    with_db_write(|conn| recurrence::delete_entry_template_impl(conn, id))
+}
+
+#[tauri::command]
+pub async fn list_recurring_series_cmd() -> AppResult<Vec<crate::types::RecurringSeries>> {
+    with_db_read(recurrence::get_recurring_series_impl)
+}
+
+#[tauri::command]
+pub async fn create_recurring_series_cmd(
+    series: crate::types::RecurringSeriesInput,
+) -> AppResult<crate::types::RecurringSeries> {
+    with_db_write(|conn| recurrence::create_recurring_series_impl(conn, &series))
+}
+
+/// Reschedule a series; upcoming entries it created are recreated on the new schedule.
+#[tauri::command]
+pub async fn update_recurring_series_cmd(
+    id: i64,
+    series: crate::types::RecurringSeriesInput,
+) -> AppResult<crate::types::RecurringSeries> {
+    let now = chrono::Utc::now().timestamp_millis();
+    with_db_write(|conn| recurrence::update_recurring_series_impl(conn, id, &series, now))
+}
+
+#[tauri::command]
+pub async fn delete_recurring_series_cmd(id: i64, delete_upcoming: Option<bool>) -> AppResult<()> {
+    let now = chrono::Utc::now().timestamp_millis();
+    with_db_write(|conn| {
+        recurrence::delete_recurring_series_impl(conn, id, delete_upcoming.unwrap_or(false), now)
+    })
+}
+
+#[tauri::command]
+pub async fn materialize_recurring_entries_cmd() -> AppResult<crate::types::MaterializeReport> {
+    materialize_recurring_entries()
+}
+
+/// Create the entries recurring series are due for over the coming days.
+pub fn materialize_recurring_entries() -> AppResult<crate::types::MaterializeReport> {
+    let now = chrono::Utc::now().timestamp_millis();
+    with_db_write(|conn| {
+        recurrence::materialize_recurring_entries_impl(
+            conn,
+            &chrono::Local,
+            now,
+            RECURRING_HORIZON_DAYS,
+        )
+    })
+}
+
+#[tauri::command]
+pub async fn start_timer_cmd(
+    start: crate::types::TimerStart,
+) -> AppResult<crate::types::RunningTimer> {
+    let now = chrono::Utc::now().timestamp_millis();
+    with_db_write(|conn| timer::start_timer_impl(conn, &start, now))
+}
+
+/// Start counting existing entry `id` again.
+#[tauri::command]
+pub async fn resume_timer_cmd(id: i64) -> AppResult<crate::types::RunningTimer> {
+    let now = chrono::Utc::now().timestamp_millis();
+    with_db_write(|conn| timer::resume_timer_impl(conn, id, now))
+}
+
+/// Stop the timer at `end_time`, defaulting to now.
+#[tauri::command]
+pub async fn stop_timer_cmd(end_time: Option<i64>) -> AppResult<crate::types::TimeEntry> {
+    let end_time = end_time.unwrap_or_else(|| chrono::Utc::now().timestamp_millis());
+    with_db_write(|conn| timer::stop_timer_impl(conn, end_time))
+}
+
+#[tauri::command]
+pub async fn get_running_timer_cmd() -> AppResult<Option<crate::types::RunningTimer>> {
+    with_db_read(timer::get_running_timer_impl)
+}
+
+/// Extend the running entry up to now; a no-op while nothing runs.
+pub fn timer_heartbeat() -> AppResult<bool> {
+    let now = chrono::Utc::now().timestamp_millis();
+    with_db_write(|conn| timer::heartbeat(conn, now))
+}
+
+#[tauri::command]
+pub async fn get_trashed_time_entries_cmd() -> AppResult<Vec<crate::types::TrashedTimeEntry>> {
+    with_db_read(time_entries::get_trashed_time_entries_impl)
+}
+
+#[tauri::command]
+pub async fn restore_time_entry_cmd(id: i64) -> AppResult<crate::types::TimeEntry> {
+    with_db_write(|conn| time_entries::restore_time_entry_impl(conn, id))
+}
+
+/// Permanently delete the given trashed entries, or everything in the trash.
+#[tauri::command]
+pub async fn purge_trash_cmd(ids: Option<Vec<i64>>) -> AppResult<usize> {
+    with_db_write(|conn| time_entries::purge_trashed_time_entries_impl(conn, ids.as_deref()))
+}
+
+/// Purge trashed entries older than the configured retention period.
+pub fn purge_expired_trash() -> AppResult<usize> {
+    let settings = crate::app_settings::load_trash_settings()?;
+    if settings.retention_days == 0 {
+        return Ok(0);
+    }
+
+    let retention_ms = i64::from(settings.retention_days) * 24 * 60 * 60 * 1000;
+    let cutoff = chrono::Utc::now().timestamp_millis() - retention_ms;
+    with_db_write(|conn| time_entries::purge_trash_before(conn, cutoff))
+}
+
+/// Revert the latest change to time entries or categories; `None` if there is nothing to undo.
+#[tauri::command]
+pub async fn undo_cmd() -> AppResult<Option<crate::types::JournalReplay>> {
+    with_db_write(journal::undo)
+}
+
+#[tauri::command]
+pub async fn redo_cmd() -> AppResult<Option<crate::types::JournalReplay>> {
+    with_db_write(journal::redo)
+}
+
+#[tauri::command]
+pub async fn get_undo_state_cmd() -> AppResult<crate::types::UndoState> {
+    with_db_read(journal::undo_state)
+}
+
+/// Change history of one time entry, newest first.
+#[tauri::command]
+pub async fn get_time_entry_revisions_cmd(
+    entry_id: i64,
+) -> AppResult<Vec<crate::types::TimeEntryRevision>> {
+    with_db_read(|conn| revisions::get_time_entry_revisions_impl(conn, entry_id))
+}
+
+#[tauri::command]
+pub async fn revert_time_entry_cmd(
+    entry_id: i64,
+    revision_id: i64,
+) -> AppResult<crate::types::TimeEntryState> {
+    with_db_write(|conn| revisions::revert_time_entry_to_revision_impl(conn, entry_id, revision_id))
+}
+
+#[tauri::command]
+pub async fn search_activities_cmd(
+    query: String,
+    tag_ids: Option<Vec<i64>>,
+) -> AppResult<Vec<crate::types::SearchResult>> {
+    with_db_read(|conn| {
+        search::search_activities_impl(conn, &query, &tag_ids.unwrap_or_default())
+    })
+}
+
+#[tauri::command]
+pub async fn search_activities_by_range_cmd(
+    query: String,
+    start_time: i64,
+    end_time: i64,
+    tag_ids: Option<Vec<i64>>,
+) -> AppResult<Vec<crate::types::SearchResult>> {
+    with_db_read(|conn| {
+        search::search_activities_by_date_impl(
+            conn,
+            &query,
+            start_time,
+            end_time,
+            &tag_ids.unwrap_or_default(),
+        )
+    })
+}
+
+#[tauri::command]
+pub async fn get_tags_cmd() -> AppResult<Vec<crate::types::Tag>> {
+    with_db_read(tags::get_tags_impl)
+}
+
+#[tauri::command]
+pub async fn create_tag_cmd(tag: crate::types::TagInput) -> AppResult<crate::types::Tag> {
+    with_db_write(|conn| tags::create_tag_impl(conn, &tag))
+}
+
+#[tauri::command]
+pub async fn update_tag_cmd(id: i64, tag: crate::types::TagInput) -> AppResult<crate::types::Tag> {
+    with_db_write(|conn| tags::update_tag_impl(conn, id, &tag))
+}
+
+#[tauri::command]
+pub async fn delete_tag_cmd(id: i64) -> AppResult<()> {
+    with_db_write(|conn| tags::delete_tag_impl(conn, id))
+}
+
+#[tauri::command]
+pub async fn get_time_entry_tags_cmd(entry_id: i64) -> AppResult<Vec<crate::types::Tag>> {
+    with_db_read(|conn| tags::get_time_entry_tags_impl(conn, entry_id))
+}
+
+/// Replace the tags on an entry.
+#[tauri::command]
+pub async fn set_time_entry_tags_cmd(
+    entry_id: i64,
+    tag_ids: Vec<i64>,
+) -> AppResult<Vec<crate::types::Tag>> {
+    with_db_write(|conn| tags::set_time_entry_tags_impl(conn, entry_id, &tag_ids))
+}
+
+/// Tag assignments of the entries in a range, for rendering a timeline.
+#[tauri::command]
+pub async fn get_time_entry_tags_by_range_cmd(
+    start_time: i64,
+    end_time: i64,
+) -> AppResult<Vec<crate::types::TimeEntryTag>> {
+    with_db_read(|conn| tags::get_time_entry_tags_by_range_impl(conn, start_time, end_time))
+}
+
+/// Tracked time per tag in a range, most used first.
+#[tauri::command]
+pub async fn get_tag_totals_cmd(
+    start_time: i64,
+    end_time: i64,
+) -> AppResult<Vec<crate::types::TagTotal>> {
+    with_db_read(|conn| tags::get_tag_totals_by_range_impl(conn, start_time, end_time))
+}
+
+#[tauri::command]
+pub async fn get_clients_cmd() -> AppResult<Vec<crate::types::Client>> {
+    with_db_read(projects::get_clients_impl)
+}
+
+#[tauri::command]
+pub async fn create_client_cmd(
+    client: crate::types::ClientInput,
+) -> AppResult<crate::types::Client> {
+    with_db_write(|conn| projects::create_client_impl(conn, &client))
+}
+
+#[tauri::command]
+pub async fn update_client_cmd(
+    id: i64,
+    client: crate::types::ClientInput,
+) -> AppResult<crate::types::Client> {
+    with_db_write(|conn| projects::update_client_impl(conn, id, &client))
+}
+
+#[tauri::command]
+pub async fn delete_client_cmd(id: i64) -> AppResult<()> {
+    with_db_write(|conn| projects::delete_client_impl(conn, id))
+}
+
+#[tauri::command]
+pub async fn get_projects_cmd() -> AppResult<Vec<crate::types::Project>> {
+    with_db_read(projects::get_projects_impl)
+}
+
+#[tauri::command]
+pub async fn create_project_cmd(
+    project: crate::types::ProjectInput,
+) -> AppResult<crate::types::Project> {
+    with_db_write(|conn| projects::create_project_impl(conn, &project))
+}
+
+#[tauri::command]
+pub async fn update_project_cmd(
+    id: i64,
+    project: crate::types::ProjectInput,
+) -> AppResult<crate::types::Project> {
+    with_db_write(|conn| projects::update_project_impl(conn, id, &project))
+}
+
+#[tauri::command]
+pub async fn delete_project_cmd(id: i64) -> AppResult<()> {
+    with_db_write(|conn| projects::delete_project_impl(conn, id))
+}
+
+/// Billable hours and amounts per project and client in a range.
+#[tauri::command]
+pub async fn get_billing_summary_cmd(
+    start_time: i64,
+    end_time: i64,
+) -> AppResult<crate::types::BillingSummary> {
+    with_db_read(|conn| projects::get_billing_summary_impl(conn, start_time, end_time))
+}
+
 #[tauri::command]
 pub async fn export_data_cmd() -> AppResult<crate::types::ExportData> {
-    with_db(export::export_data_impl)
+    with_db_read(export::export_data_impl)
 }
 
 #[tauri::command]
 pub async fn get_categories() -> AppResult<Vec<crate::types::Category>> {
-    with_db(categories::get_categories_impl)
+    with_db_read(categories::get_categories_impl)
 }
 
 #[tauri::command]
 pub async fn create_category(
     category: crate::types::CategoryInput,
 ) -> AppResult<crate::types::Category> {
-    with_db(|conn| categories::create_category_impl(conn, &category))
+    with_db_write(|conn| categories::create_category_impl(conn, &category))
 }
 
 #[tauri::command]
@@ -109,17 +640,17 @@ pub async fn update_category(
     id: i64,
     category: crate::types::CategoryInput,
 ) -> AppResult<crate::types::Category> {
-    with_db(|conn| categories::update_category_impl(conn, id, &category))
+    with_db_write(|conn| categories::update_category_impl(conn, id, &category))
 }
 
 #[tauri::command]
 pub async fn delete_category(id: i64) -> AppResult<()> {
-    with_db(|conn| categories::delete_category_impl(conn, id))
+    with_db_write(|conn| categories::delete_category_impl(conn, id))
 }
 
 #[tauri::command]
 pub async fn get_screenshot_timestamps_for_day(date: i64) -> AppResult<Vec<i64>> {
-    with_db(|conn| {
+    with_db_read(|conn| {
         let start_of_day = date;
         let end_of_day = date + 86400000;
         screenshot::get_screenshot_timestamps_for_day(conn, start_of_day, end_of_day)
@@ -127,10 +658,505 @@ pub async fn get_screenshot_timestamps_for_day(date: i64) -> AppResult<Vec<i64>>
 }
 
 #[tauri::command]
-pub async fn get_process_samples_for_day(date: i64) -> AppResult<Vec<crate::types::ProcessSample>> {
-    with_db(|conn| {
+pub async fn get_process_segments_for_day(
+    date: i64,
+) -> AppResult<Vec<crate::types::ProcessSegment>> {
+    with_db_read(|conn| {
         let start_of_day = date;
         let end_of_day = date + 86400000;
-        process_samples::get_process_samples_for_day(conn, start_of_day, end_of_day)
+        process_segments::get_process_segments_by_range(conn, start_of_day, end_of_day)
+    })
+}
+
+/// Delete captured data older than the configured retention periods.
+pub fn run_retention() -> AppResult<crate::types::RetentionReport> {
+    let settings = crate::app_settings::load_retention_settings()?;
+    let now = chrono::Utc::now().timestamp_millis();
+    let cutoffs = retention::RetentionCutoffs::from_settings(&settings, now);
+    retention::run_retention(
+        &cutoffs,
+        &|batch| with_db_write(|conn| batch(conn)),
+        &remove_screenshot_file,
+    )
+}
+
+#[tauri::command]
+pub async fn run_retention_cmd() -> AppResult<crate::types::RetentionReport> {
+    run_retention()
+}
+
+/// Dry run of the retention job for `settings`, or the saved settings when omitted.
+#[tauri::command]
+pub async fn preview_retention_cmd(
+    settings: Option<crate::app_settings::RetentionSettings>,
+) -> AppResult<crate::types::RetentionReport> {
+    let settings = match settings {
+        Some(settings) => settings.normalized(),
+        None => crate::app_settings::load_retention_settings()?,
+    };
+    let now = chrono::Utc::now().timestamp_millis();
+    let cutoffs = retention::RetentionCutoffs::from_settings(&settings, now);
+    with_db_read(|conn| retention::preview_retention(conn, &cutoffs, &screenshot_file_size))
+}
+
+/// Delete a screenshot file and return its size; a file already gone counts as deleted.
+fn remove_screenshot_file(stored_path: &str) -> Result<u64, String> {
+    let path = crate::app_settings::resolve_screenshot_file_path(stored_path)?;
+    match std::fs::metadata(&path) {
+        Ok(metadata) => {
+            std::fs::remove_file(&path).map_err(|e| format!("Failed to delete file: {}", e))?;
+            Ok(metadata.len())
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(0),
+        Err(e) => Err(format!("Failed to read file: {}", e)),
+    }
+}
+
+fn screenshot_file_size(stored_path: &str) -> u64 {
+    crate::app_settings::resolve_screenshot_file_path(stored_path)
+        .and_then(|path| std::fs::metadata(path).map_err(|e| e.to_string()))
+        .map(|metadata| metadata.len())
+        .unwrap_or(0)
+}
+
+/// Hourly maintenance tick: a WAL checkpoint, plus optimize and incremental
+/// vacuum when they are due. Returns the report of a full run.
+pub fn run_scheduled_maintenance() -> AppResult<Option<crate::types::MaintenanceReport>> {
+    use chrono::Timelike;
+
+    let settings = crate::app_settings::load_maintenance_settings()?;
+    if !settings.enabled {
+        return Ok(None);
+    }
+
+    let now = chrono::Utc::now().timestamp_millis();
+    let last_run =
+        with_db_read(|conn| maintenance::last_run(conn, maintenance::MAINTENANCE_TASK))?;
+    if maintenance::maintenance_due(
+        settings.idle_start_hour,
+        settings.idle_end_hour,
+        chrono::Local::now().hour(),
+        now,
+        last_run,
+    ) {
+        return run_database_maintenance(now).map(Some);
+    }
+
+    let (busy, _, _) = with_db_write(|conn| maintenance::checkpoint(conn, now))?;
+    if busy {
+        eprintln!("WAL checkpoint could not complete; readers were active");
+    }
+    Ok(None)
+}
+
+fn run_database_maintenance(now: i64) -> AppResult<crate::types::MaintenanceReport> {
+    maintenance::run_maintenance(&|step| with_db_write(|conn| step(conn)), now)
+}
+
+#[tauri::command]
+pub async fn run_database_maintenance_cmd() -> AppResult<crate::types::MaintenanceReport> {
+    run_database_maintenance(chrono::Utc::now().timestamp_millis())
+}
+
+#[tauri::command]
+pub async fn get_database_stats_cmd() -> AppResult<crate::types::DatabaseStats> {
+    let db_path = database::get_database_path()?;
+    with_db_read(|conn| maintenance::database_stats(conn, &db_path))
+}
+
+/// How often the background scheduler takes a rotated backup.
+const SCHEDULED_BACKUP_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Take a rotated backup if scheduled backups are enabled and the newest one is a day old.
+pub fn run_scheduled_backup() -> AppResult<Option<crate::types::BackupInfo>> {
+    let settings = crate::app_settings::load_backup_settings()?;
+    if !settings.enabled {
+        return Ok(None);
+    }
+
+    let dir = crate::app_settings::resolve_backup_dir(&settings)?;
+    let now = chrono::Utc::now().timestamp_millis();
+    if !backup::is_backup_due(&dir, now, SCHEDULED_BACKUP_INTERVAL)? {
+        return Ok(None);
+    }
+
+    with_db_read(|conn| backup::create_rotated_backup(conn, &dir, settings.keep_count)).map(Some)
+}
+
+#[tauri::command]
+pub async fn backup_database_cmd(
+    destination: Option<String>,
+) -> AppResult<crate::types::BackupInfo> {
+    match destination {
+        Some(path) => {
+            with_db_read(|conn| backup::backup_to_file(conn, std::path::Path::new(&path)))
+        }
+        None => {
+            let settings = crate::app_settings::load_backup_settings()?;
+            let dir = crate::app_settings::resolve_backup_dir(&settings)?;
+            with_db_read(|conn| backup::create_rotated_backup(conn, &dir, settings.keep_count))
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn list_backups_cmd() -> AppResult<Vec<crate::types::BackupInfo>> {
+    let settings = crate::app_settings::load_backup_settings()?;
+    backup::list_backups(&crate::app_settings::resolve_backup_dir(&settings)?)
+}
+
+/// Replace the live database with `path` after validating it.
+///
+/// A safety copy of the current database is written to the backup folder
+/// first (only the last few are kept), then the pool is closed, the file
+/// swapped and the pool reopened (which also migrates an older backup up to
+/// the current schema).
+#[tauri::command]
+pub async fn restore_database_cmd(path: String) -> AppResult<crate::types::BackupInfo> {
+    let backup_path = std::path::PathBuf::from(&path);
+    let keys = crate::crypto::current_keys()?;
+    let checked = backup::validate_backup(&backup_path, keys.as_deref())?;
+
+    let settings = crate::app_settings::load_backup_settings()?;
+    let dir = crate::app_settings::resolve_backup_dir(&settings)?;
+    let safety_copy = with_db_read(|conn| backup::create_pre_restore_backup(conn, &dir))?;
+
+    let db_path = database::get_database_path()?;
+    // A backup from before encryption was turned on is encrypted on the way in.
+    let staged = match keys.as_deref() {
+        Some(keys) if !checked.encrypted => {
+            let staged = db_path.with_extension("restoring-encrypted");
+            backup::encrypted_copy(&backup_path, &staged, keys)?;
+            Some(staged)
+        }
+        _ => None,
+    };
+
+    close_database(Duration::from_secs(10))?;
+    let swapped =
+        backup::replace_database_file(staged.as_deref().unwrap_or(&backup_path), &db_path);
+    if let Some(staged) = &staged {
+        let _ = std::fs::remove_file(staged);
+    }
+    // Reopen even if the swap failed so the app keeps working on the old file.
+    init_database()?;
+    swapped?;
+
+    Ok(safety_copy)
+}
+
+#[tauri::command]
+pub async fn list_profiles_cmd() -> AppResult<crate::types::ProfileList> {
+    Ok(crate::profiles::list_profiles(&crate::app_paths::data_root()?)?)
+}
+
+#[tauri::command]
+pub async fn create_profile_cmd(name: String) -> AppResult<crate::types::Profile> {
+    let now = chrono::Utc::now().timestamp_millis();
+    crate::profiles::create_profile(&crate::app_paths::data_root()?, &name, now)
+}
+
+/// Close the current profile's database and open the one of profile `id`.
+///
+/// An encrypted profile stays locked until `unlock_database_cmd`. If the new
+/// database cannot be opened the previous profile is reactivated.
+#[tauri::command]
+pub async fn switch_profile_cmd(id: String) -> AppResult<crate::types::ProfileList> {
+    let data_root = crate::app_paths::data_root()?;
+    let previous = crate::profiles::list_profiles(&data_root)?.active;
+    if previous != id {
+        close_database(Duration::from_secs(10))?;
+        if let Err(e) = activate_profile(&data_root, &id) {
+            activate_profile(&data_root, &previous)?;
+            return Err(e);
+        }
+    }
+    Ok(crate::profiles::list_profiles(&data_root)?)
+}
+
+/// Delete an inactive profile together with its database and screenshots.
+#[tauri::command]
+pub async fn delete_profile_cmd(id: String) -> AppResult<()> {
+    crate::profiles::delete_profile(&crate::app_paths::data_root()?, &id)
+}
+
+fn activate_profile(data_root: &std::path::Path, id: &str) -> AppResult<()> {
+    let dir = crate::profiles::select_profile(data_root, id)?;
+    let root = (id != crate::profiles::DEFAULT_PROFILE_ID).then_some(dir);
+    crate::app_paths::set_profile_root(root)?;
+
+    // Encryption settings are per profile
+    crate::crypto::init_from_settings()?;
+    if crate::crypto::is_locked() {
+        return Ok(());
+    }
+    init_database()
+}
+
+fn database_locked() -> AppError {
+    AppError::new(
+        error::ErrorKind::Conflict,
+        "database_locked",
+        "The database is encrypted; unlock it with the passphrase first",
+    )
+}
+
+#[tauri::command]
+pub async fn check_database_integrity_cmd(
+    repair: bool,
+) -> AppResult<crate::types::IntegrityReport> {
+    if repair {
+        with_db_write(|conn| diagnostics::run_diagnostics(conn, true, &screenshot_file_exists))
+    } else {
+        with_db_read(|conn| diagnostics::run_diagnostics(conn, false, &screenshot_file_exists))
+    }
+}
+
+#[tauri::command]
+pub async fn get_encryption_status_cmd() -> AppResult<crate::types::EncryptionStatus> {
+    let settings = crate::app_settings::load_encryption_settings()?;
+    Ok(crate::types::EncryptionStatus {
+        enabled: settings.enabled,
+        unlocked: !crate::crypto::is_locked(),
     })
 }
+
+/// Derive the keys from `passphrase` and open the encrypted database.
+#[tauri::command]
+pub async fn unlock_database_cmd(passphrase: String) -> AppResult<crate::types::EncryptionStatus> {
+    if crate::crypto::is_locked() {
+        let settings = crate::app_settings::load_encryption_settings()?;
+        let (keys, settled) = unlock_keys(&settings, &passphrase)?;
+        crate::crypto::set_keys(Some(Arc::new(keys)))?;
+        if let Err(e) = init_database() {
+            crate::crypto::init_from_settings()?;
+            return Err(e);
+        }
+        if let Some(settled) = settled {
+            crate::app_settings::save_encryption_settings(settled)?;
+        }
+    }
+    get_encryption_status_cmd().await
+}
+
+/// Encrypt the database and all screenshots with a key derived from `passphrase`.
+///
+/// Once the database is encrypted, calling this again with the same passphrase
+/// retries the screenshots that were left unconverted.
+#[tauri::command]
+pub async fn enable_encryption_cmd(
+    passphrase: String,
+) -> AppResult<crate::types::EncryptionReport> {
+    let current = crate::app_settings::load_encryption_settings()?;
+    let keys = if current.enabled {
+        Arc::new(verify_passphrase(&current, &passphrase)?)
+    } else {
+        validate_new_passphrase(&passphrase)?;
+        let (keys, settings) = new_encryption_keys(&passphrase)?;
+        convert_database(None, Some(keys.clone()), settings)?;
+        keys
+    };
+
+    with_db_read(|conn| {
+        encryption::reencrypt_screenshots(conn, None, Some(&keys), &resolve_screenshot_path)
+    })
+}
+
+/// Re-encrypt the database and screenshots under a new passphrase.
+#[tauri::command]
+pub async fn change_encryption_passphrase_cmd(
+    current_passphrase: String,
+    new_passphrase: String,
+) -> AppResult<crate::types::EncryptionReport> {
+    let previous = crate::app_settings::load_encryption_settings()?;
+    let old_keys = verify_passphrase(&previous, &current_passphrase)?;
+    validate_new_passphrase(&new_passphrase)?;
+    let (keys, settings) = new_encryption_keys(&new_passphrase)?;
+
+    let db_path = database::get_database_path()?;
+    close_database(Duration::from_secs(10))?;
+    // Keep the old salt next to the new one until the rekey is done, so
+    // whichever key the file ends up under can still be derived after a crash.
+    crate::app_settings::save_encryption_settings(crate::app_settings::EncryptionSettings {
+        previous_salt: previous.salt.clone(),
+        previous_check: previous.check.clone(),
+        ..settings.clone()
+    })?;
+    let rekeyed = Connection::open(&db_path)
+        .map_err(|e| AppError::from(format!("Failed to open database: {}", e)))
+        .and_then(|conn| {
+            crate::crypto::key_connection(&conn, &old_keys)?;
+            encryption::rekey_database(&conn, &keys)
+        });
+    if let Err(e) = rekeyed {
+        crate::app_settings::save_encryption_settings(previous)?;
+        crate::crypto::set_keys(Some(Arc::new(old_keys)))?;
+        init_database()?;
+        return Err(e);
+    }
+
+    crate::app_settings::save_encryption_settings(settings)?;
+    crate::crypto::set_keys(Some(keys.clone()))?;
+    init_database()?;
+
+    with_db_read(|conn| {
+        encryption::reencrypt_screenshots(
+            conn,
+            Some(&old_keys),
+            Some(&keys),
+            &resolve_screenshot_path,
+        )
+    })
+}
+
+/// Decrypt the database and screenshots and turn encryption off.
+#[tauri::command]
+pub async fn disable_encryption_cmd(
+    passphrase: String,
+) -> AppResult<crate::types::EncryptionReport> {
+    let previous = crate::app_settings::load_encryption_settings()?;
+    let old_keys = verify_passphrase(&previous, &passphrase)?;
+
+    convert_database(
+        Some(&old_keys),
+        None,
+        crate::app_settings::EncryptionSettings::default(),
+    )?;
+
+    with_db_read(|conn| {
+        encryption::reencrypt_screenshots(conn, Some(&old_keys), None, &resolve_screenshot_path)
+    })
+}
+
+fn verify_passphrase(
+    settings: &crate::app_settings::EncryptionSettings,
+    passphrase: &str,
+) -> AppResult<crate::crypto::KeyMaterial> {
+    let (true, Some(salt), Some(check)) = (settings.enabled, &settings.salt, &settings.check)
+    else {
+        return Err(AppError::validation(
+            "encryption_not_enabled",
+            "Encryption is not enabled",
+        ));
+    };
+
+    let keys = crate::crypto::KeyMaterial::derive(passphrase, salt)?;
+    if !keys.matches(check) {
+        return Err(
+            AppError::validation("wrong_passphrase", "Incorrect passphrase")
+                .with_field("passphrase"),
+        );
+    }
+    Ok(keys)
+}
+
+/// Keys for `passphrase` at unlock.
+///
+/// After a passphrase change that was cut short, the settings still carry the
+/// previous salt and either passphrase is accepted. The settings that match
+/// the key the database turned out to be under are returned, to be saved once
+/// it opens.
+fn unlock_keys(
+    settings: &crate::app_settings::EncryptionSettings,
+    passphrase: &str,
+) -> AppResult<(
+    crate::crypto::KeyMaterial,
+    Option<crate::app_settings::EncryptionSettings>,
+)> {
+    let current = verify_passphrase(settings, passphrase);
+    let (Some(salt), Some(check)) = (&settings.previous_salt, &settings.previous_check) else {
+        return current.map(|keys| (keys, None));
+    };
+
+    let settled = match current {
+        Ok(keys) => (keys, settings.salt.clone(), settings.check.clone()),
+        Err(e) => {
+            let keys = crate::crypto::KeyMaterial::derive(passphrase, salt)?;
+            if !keys.matches(check) {
+                return Err(e);
+            }
+            (keys, Some(salt.clone()), Some(check.clone()))
+        }
+    };
+    let (keys, salt, check) = settled;
+    Ok((
+        keys,
+        Some(crate::app_settings::EncryptionSettings {
+            enabled: true,
+            salt,
+            check,
+            ..Default::default()
+        }),
+    ))
+}
+
+fn validate_new_passphrase(passphrase: &str) -> AppResult<()> {
+    if passphrase.trim().is_empty() {
+        return Err(
+            AppError::validation("empty_passphrase", "Passphrase cannot be empty")
+                .with_field("passphrase"),
+        );
+    }
+    Ok(())
+}
+
+fn new_encryption_keys(
+    passphrase: &str,
+) -> AppResult<(
+    Arc<crate::crypto::KeyMaterial>,
+    crate::app_settings::EncryptionSettings,
+)> {
+    let salt = crate::crypto::generate_salt();
+    let keys = crate::crypto::KeyMaterial::derive(passphrase, &salt)?;
+    let settings = crate::app_settings::EncryptionSettings {
+        enabled: true,
+        salt: Some(salt),
+        check: Some(keys.check_value()?),
+        ..Default::default()
+    };
+    Ok((Arc::new(keys), settings))
+}
+
+/// Swap the database for a copy encrypted with `target` (plaintext when `None`)
+/// and reopen the pool with the new keys.
+fn convert_database(
+    current: Option<&crate::crypto::KeyMaterial>,
+    target: Option<Arc<crate::crypto::KeyMaterial>>,
+    settings: crate::app_settings::EncryptionSettings,
+) -> AppResult<()> {
+    let db_path = database::get_database_path()?;
+    let staged = db_path.with_extension("converting");
+
+    // Close first so nothing written after the export is lost in the swap.
+    close_database(Duration::from_secs(10))?;
+    let exported = Connection::open(&db_path)
+        .map_err(|e| AppError::from(format!("Failed to open database: {}", e)))
+        .and_then(|conn| {
+            if let Some(keys) = current {
+                crate::crypto::key_connection(&conn, keys)?;
+            }
+            encryption::export_database(&conn, &staged, target.as_deref())
+        });
+    if let Err(e) = exported {
+        let _ = std::fs::remove_file(&staged);
+        init_database()?;
+        return Err(e);
+    }
+
+    let previous = crate::app_settings::load_encryption_settings()?;
+    crate::app_settings::save_encryption_settings(settings)?;
+    let swapped = backup::replace_database_file(&staged, &db_path);
+    let _ = std::fs::remove_file(&staged);
+    if let Err(e) = swapped {
+        crate::app_settings::save_encryption_settings(previous)?;
+        init_database()?;
+        return Err(e);
+    }
+
+    crate::crypto::set_keys(target)?;
+    init_database()
+}
+
+fn resolve_screenshot_path(stored_path: &str) -> Result<std::path::PathBuf, String> {
+    crate::app_settings::resolve_screenshot_file_path(stored_path)
+}
@@ -0,0 +1,459 @@
+use crate::data::error::db_err;
+use crate::data::journal;
+use crate::data::tags::copy_time_entry_tags;
+use crate::data::time_entries::{
+    create_time_entry_impl, delete_time_entry_impl, find_overlapping_entry_ids,
+    get_time_entries_by_range_impl, get_time_entry_by_id, update_time_entry_impl,
+    update_time_entry_vacating,
+};
+use crate::data::timer;
+use crate::data::{AppError, AppResult};
+use crate::types::{
+    OverlapResolution, OverlapStrategy, TimeEntry, TimeEntryInput, TimeEntryUpdate,
+};
+use rusqlite::Connection;
+
+/// Create `entry`, first making room for it with `strategy`. One undo step.
+pub fn create_time_entry_with_strategy(
+    conn: &Connection,
+    entry: &TimeEntryInput,
+    strategy: OverlapStrategy,
+) -> AppResult<OverlapResolution> {
+    in_transaction(conn, |conn| {
+        let (modified, removed) =
+            make_room(conn, entry.start_time, entry.end_time, None, strategy)?;
+        let entry = create_time_entry_impl(conn, entry)?;
+        Ok(OverlapResolution {
+            entry,
+            modified,
+            removed,
+        })
+    })
+}
+
+/// Update entry `id`, first making room for its new range with `strategy`. One undo step.
+pub fn update_time_entry_with_strategy(
+    conn: &Connection,
+    id: i64,
+    updates: &TimeEntryUpdate,
+    strategy: OverlapStrategy,
+) -> AppResult<OverlapResolution> {
+    in_transaction(conn, |conn| {
+        let existing = get_time_entry_by_id(conn, id)?;
+        let (modified, removed) = if updates.start_time.is_some() || updates.end_time.is_some() {
+            make_room(
+                conn,
+                updates.start_time.unwrap_or(existing.start_time),
+                updates.end_time.unwrap_or(existing.end_time),
+                Some(id),
+                strategy,
+            )?
+        } else {
+            (Vec::new(), Vec::new())
+        };
+        let entry = update_time_entry_impl(conn, id, updates)?;
+        Ok(OverlapResolution {
+            entry,
+            modified,
+            removed,
+        })
+    })
+}
+
+/// Run `f` in a transaction as one undo step. Any failure leaves the neighbors untouched.
+fn in_transaction<F>(conn: &Connection, f: F) -> AppResult<OverlapResolution>
+where
+    F: FnOnce(&Connection) -> AppResult<OverlapResolution>,
+{
+    let tx = conn
+        .unchecked_transaction()
+        .map_err(db_err("Failed to start transaction"))?;
+    let resolution = journal::grouped(&tx, || f(&tx))?;
+    tx.commit()
+        .map_err(db_err("Failed to commit overlap resolution"))?;
+    Ok(resolution)
+}
+
+/// Clear `[start_time, end_time)` of other entries according to `strategy`.
+///
+/// Returns the neighbors changed or created, and the ids of those trashed.
+/// `Reject` changes nothing and leaves the overlap check to the caller. Only
+/// `Split` accepts a neighbor enclosing the range; the others would drop its
+/// tail, so they fail with `time_entry_overlap` naming it.
+///
+/// The running entry is never moved, trimmed or trashed: its end follows the
+/// timer heartbeat, which would stretch it back over the new range. Touching
+/// it fails with `time_entry_overlap` naming it; stop the timer first.
+fn make_room(
+    conn: &Connection,
+    start_time: i64,
+    end_time: i64,
+    exclude_id: Option<i64>,
+    strategy: OverlapStrategy,
+) -> AppResult<(Vec<TimeEntry>, Vec<i64>)> {
+    if strategy == OverlapStrategy::Reject || end_time <= start_time {
+        return Ok((Vec::new(), Vec::new()));
+    }
+    let running = timer::running_entry_id(conn)?.filter(|&id| Some(id) != exclude_id);
+
+    let overlapping = find_overlapping_entry_ids(conn, start_time, end_time, exclude_id)?;
+    if let Some(running) = running.filter(|id| overlapping.contains(id)) {
+        return Err(AppError::overlap(vec![running]));
+    }
+    let neighbors = overlapping
+        .into_iter()
+        .map(|id| get_time_entry_by_id(conn, id))
+        .collect::<AppResult<Vec<_>>>()?;
+    if strategy != OverlapStrategy::Split {
+        let enclosing: Vec<i64> = neighbors
+            .iter()
+            .filter(|n| n.start_time < start_time && n.end_time > end_time)
+            .map(|n| n.id)
+            .collect();
+        if !enclosing.is_empty() {
+            return Err(AppError::overlap(enclosing));
+        }
+    }
+
+    let mut modified = Vec::new();
+    let mut removed = Vec::new();
+    for neighbor in neighbors {
+        let id = neighbor.id;
+        let keeps_head = neighbor.start_time < start_time;
+        let keeps_tail = neighbor.end_time > end_time;
+
+        match (keeps_head, keeps_tail) {
+            (true, _) => {
+                modified.push(update_time_entry_impl(
+                    conn,
+                    id,
+                    &times(None, Some(start_time)),
+                )?);
+                // Only `Split` gets here with an enclosing neighbor
+                if keeps_tail {
+                    let tail = create_time_entry_impl(
+                        conn,
+                        &TimeEntryInput {
+                            start_time: end_time,
+                            end_time: neighbor.end_time,
+                            label: neighbor.label,
+                            color: neighbor.color,
+                            category_id: neighbor.category_id,
+                            notes: neighbor.notes,
+                            project_id: neighbor.project_id,
+                        },
+                    )?;
+                    copy_time_entry_tags(conn, id, tail.id)?;
+                    modified.push(tail);
+                }
+            }
+            // Moved out of the way below
+            (false, _) if strategy == OverlapStrategy::PushLater => {}
+            (false, false) => {
+                delete_time_entry_impl(conn, id)?;
+                removed.push(id);
+            }
+            (false, true) => {
+                modified.push(update_time_entry_impl(
+                    conn,
+                    id,
+                    &times(Some(end_time), None),
+                )?);
+            }
+        }
+    }
+
+    if strategy == OverlapStrategy::PushLater {
+        modified.extend(push_later(conn, start_time, end_time, exclude_id, running)?);
+    }
+    Ok((modified, removed))
+}
+
+/// Move every entry starting inside `[start_time, end_time)` to after
+/// `end_time`, pushing later ones along, keeping durations and order.
+///
+/// The entry being updated (`exclude_id`) is left where it is, but its old
+/// range counts as free: it moves to `[start_time, end_time)` right after.
+fn push_later(
+    conn: &Connection,
+    start_time: i64,
+    end_time: i64,
+    exclude_id: Option<i64>,
+    running: Option<i64>,
+) -> AppResult<Vec<TimeEntry>> {
+    let mut cursor = end_time;
+    let mut moves = Vec::new();
+    for entry in get_time_entries_by_range_impl(conn, start_time, i64::MAX)? {
+        if Some(entry.id) == exclude_id || entry.start_time < start_time {
+            continue;
+        }
+        if entry.start_time >= cursor {
+            break;
+        }
+        if Some(entry.id) == running {
+            return Err(AppError::overlap(vec![entry.id]));
+        }
+        let shift = cursor - entry.start_time;
+        cursor = entry.end_time + shift;
+        moves.push((entry.id, entry.start_time + shift, cursor));
+    }
+
+    // Latest first, so each entry moves into space already vacated
+    let mut moved = Vec::with_capacity(moves.len());
+    for (id, start_time, end_time) in moves.into_iter().rev() {
+        moved.push(update_time_entry_vacating(
+            conn,
+            id,
+            &times(Some(start_time), Some(end_time)),
+            exclude_id,
+        )?);
+    }
+    moved.reverse();
+    Ok(moved)
+}
+
+fn times(start_time: Option<i64>, end_time: Option<i64>) -> TimeEntryUpdate {
+    TimeEntryUpdate {
+        start_time,
+        end_time,
+        label: None,
+        color: None,
+        category_id: None,
+        notes: None,
+        project_id: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::database::{enable_foreign_keys, run_migrations};
+
+    fn setup_test_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        enable_foreign_keys(&conn).unwrap();
+        run_migrations(&conn).unwrap();
+        conn
+    }
+
+    fn input(start_time: i64, end_time: i64, label: &str) -> TimeEntryInput {
+        TimeEntryInput {
+            start_time,
+            end_time,
+            label: label.to_string(),
+            color: None,
+            category_id: None,
+            notes: None,
+            project_id: None,
+        }
+    }
+
+    fn ranges(conn: &Connection) -> Vec<(i64, i64, String)> {
+        get_time_entries_by_range_impl(conn, 0, i64::MAX)
+            .unwrap()
+            .into_iter()
+            .map(|e| (e.start_time, e.end_time, e.label))
+            .collect()
+    }
+
+    fn range(start_time: i64, end_time: i64, label: &str) -> (i64, i64, String) {
+        (start_time, end_time, label.to_string())
+    }
+
+    #[test]
+    fn test_running_entry_is_left_alone() {
+        let conn = setup_test_db();
+        let timer = timer::start_timer_impl(
+            &conn,
+            &crate::types::TimerStart {
+                start_time: Some(1000),
+                label: "Running".to_string(),
+                color: None,
+                category_id: None,
+            },
+            5000,
+        )
+        .unwrap();
+
+        for strategy in [
+            OverlapStrategy::Trim,
+            OverlapStrategy::Split,
+            OverlapStrategy::PushLater,
+        ] {
+            let error = create_time_entry_with_strategy(&conn, &input(500, 3000, "New"), strategy)
+                .unwrap_err();
+            assert_eq!(error.code, "time_entry_overlap");
+            assert_eq!(
+                error.details.unwrap().conflicting_entry_ids,
+                vec![timer.entry.id]
+            );
+        }
+        assert_eq!(ranges(&conn), vec![range(1000, 5000, "Running")]);
+        assert_eq!(
+            timer::running_entry_id(&conn).unwrap(),
+            Some(timer.entry.id)
+        );
+    }
+
+    #[test]
+    fn test_reject_keeps_neighbors() {
+        let conn = setup_test_db();
+        create_time_entry_impl(&conn, &input(1000, 3000, "A")).unwrap();
+
+        let error = create_time_entry_with_strategy(
+            &conn,
+            &input(2000, 4000, "New"),
+            OverlapStrategy::Reject,
+        )
+        .unwrap_err();
+        assert_eq!(error.code, "time_entry_overlap");
+        assert_eq!(ranges(&conn), vec![range(1000, 3000, "A")]);
+    }
+
+    #[test]
+    fn test_trim_and_split() {
+        let conn = setup_test_db();
+        create_time_entry_impl(&conn, &input(0, 2000, "Head")).unwrap();
+        let covered = create_time_entry_impl(&conn, &input(2000, 3000, "Covered")).unwrap();
+        create_time_entry_impl(&conn, &input(3000, 6000, "Tail")).unwrap();
+
+        let resolution = create_time_entry_with_strategy(
+            &conn,
+            &input(1000, 4000, "New"),
+            OverlapStrategy::Trim,
+        )
+        .unwrap();
+        assert_eq!(resolution.removed, vec![covered.id]);
+        assert_eq!(resolution.modified.len(), 2);
+        assert_eq!(
+            ranges(&conn),
+            vec![
+                range(0, 1000, "Head"),
+                range(1000, 4000, "New"),
+                range(4000, 6000, "Tail"),
+            ]
+        );
+
+        // Split keeps both sides of an enclosing neighbor
+        create_time_entry_with_strategy(
+            &conn,
+            &input(4500, 5000, "Inside"),
+            OverlapStrategy::Split,
+        )
+        .unwrap();
+        assert_eq!(
+            ranges(&conn)[2..],
+            [
+                range(4000, 4500, "Tail"),
+                range(4500, 5000, "Inside"),
+                range(5000, 6000, "Tail"),
+            ]
+        );
+
+        // Each strategy call undoes as one step, neighbors included
+        journal::undo(&conn).unwrap();
+        assert_eq!(
+            ranges(&conn),
+            vec![
+                range(0, 1000, "Head"),
+                range(1000, 4000, "New"),
+                range(4000, 6000, "Tail"),
+            ]
+        );
+        journal::undo(&conn).unwrap();
+        assert_eq!(
+            ranges(&conn),
+            vec![
+                range(0, 2000, "Head"),
+                range(2000, 3000, "Covered"),
+                range(3000, 6000, "Tail"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_push_later_cascades() {
+        let conn = setup_test_db();
+        create_time_entry_impl(&conn, &input(1000, 2000, "A")).unwrap();
+        create_time_entry_impl(&conn, &input(2000, 3500, "B")).unwrap();
+        create_time_entry_impl(&conn, &input(5000, 6000, "C")).unwrap();
+        create_time_entry_impl(&conn, &input(9000, 9500, "D")).unwrap();
+
+        let resolution = create_time_entry_with_strategy(
+            &conn,
+            &input(1500, 4000, "New"),
+            OverlapStrategy::PushLater,
+        )
+        .unwrap();
+
+        // "A" started first, so it is cut back rather than moved behind "New"
+        assert_eq!(resolution.modified.len(), 3);
+        assert_eq!(
+            ranges(&conn),
+            vec![
+                range(1000, 1500, "A"),
+                range(1500, 4000, "New"),
+                range(4000, 5500, "B"),
+                range(5500, 6500, "C"),
+                range(9000, 9500, "D"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_enclosing_neighbor_needs_split() {
+        let conn = setup_test_db();
+        let outer = create_time_entry_impl(&conn, &input(0, 6000, "Outer")).unwrap();
+
+        for strategy in [OverlapStrategy::Trim, OverlapStrategy::PushLater] {
+            let error = create_time_entry_with_strategy(&conn, &input(1000, 2000, "New"), strategy)
+                .unwrap_err();
+            assert_eq!(error.code, "time_entry_overlap");
+            assert_eq!(error.details.unwrap().conflicting_entry_ids, vec![outer.id]);
+        }
+        assert_eq!(ranges(&conn), vec![range(0, 6000, "Outer")]);
+    }
+
+    #[test]
+    fn test_update_with_strategy() {
+        let conn = setup_test_db();
+        let moving = create_time_entry_impl(&conn, &input(0, 1000, "Moving")).unwrap();
+        create_time_entry_impl(&conn, &input(1000, 3000, "Next")).unwrap();
+
+        let resolution = update_time_entry_with_strategy(
+            &conn,
+            moving.id,
+            &times(None, Some(2000)),
+            OverlapStrategy::Trim,
+        )
+        .unwrap();
+
+        assert_eq!(resolution.entry.end_time, 2000);
+        assert_eq!(
+            ranges(&conn),
+            vec![range(0, 2000, "Moving"), range(2000, 3000, "Next")]
+        );
+
+        // Dragged earlier over its neighbor, which lands in the vacated range
+        create_time_entry_impl(&conn, &input(5000, 5500, "Neighbor")).unwrap();
+        let dragged = create_time_entry_impl(&conn, &input(5500, 6500, "Dragged")).unwrap();
+        let resolution = update_time_entry_with_strategy(
+            &conn,
+            dragged.id,
+            &times(Some(4900), Some(5900)),
+            OverlapStrategy::PushLater,
+        )
+        .unwrap();
+        assert_eq!(resolution.modified.len(), 1);
+        assert_eq!(
+            ranges(&conn)[2..],
+            [range(4900, 5900, "Dragged"), range(5900, 6400, "Neighbor")]
+        );
+
+        journal::undo(&conn).unwrap();
+        assert_eq!(
+            ranges(&conn)[2..],
+            [range(5000, 5500, "Neighbor"), range(5500, 6500, "Dragged")]
+        );
+    }
+}
@@ -0,0 +1,169 @@
+use crate::data::error::db_err;
+use crate::data::{AppError, AppResult};
+use rusqlite::{Connection, OpenFlags};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+/// Number of read-only connections kept open next to the writer.
+pub const READ_POOL_SIZE: usize = 4;
+
+/// One dedicated writer plus a small pool of read-only WAL connections.
+///
+/// SQLite in WAL mode lets readers proceed while a write transaction is open,
+/// so timeline queries, search and export never wait behind the samplers.
+pub struct DbPool {
+    writer: Mutex<Connection>,
+    readers: Vec<Mutex<Connection>>,
+    next_reader: AtomicUsize,
+}
+
+impl DbPool {
+    /// Wrap an already-migrated writer and open `READ_POOL_SIZE` readers on `db_path`.
+    pub fn open(writer: Connection, db_path: &Path) -> AppResult<Self> {
+        let readers = (0..READ_POOL_SIZE)
+            .map(|_| open_read_only_connection(db_path).map(Mutex::new))
+            .collect::<AppResult<Vec<_>>>()?;
+
+        Ok(Self {
+            writer: Mutex::new(writer),
+            readers,
+            next_reader: AtomicUsize::new(0),
+        })
+    }
+
+    pub fn write<F, R>(&self, f: F) -> AppResult<R>
+    where
+        F: FnOnce(&Connection) -> AppResult<R>,
+    {
+        let conn = self
+            .writer
+            .lock()
+            .map_err(|e| format!("Failed to lock database writer: {}", e))?;
+        f(&conn)
+    }
+
+    pub fn read<F, R>(&self, f: F) -> AppResult<R>
+    where
+        F: FnOnce(&Connection) -> AppResult<R>,
+    {
+        let conn = self.acquire_reader()?;
+        f(&conn)
+    }
+
+    /// Take the first idle reader, starting round-robin; block on one if all are busy.
+    fn acquire_reader(&self) -> AppResult<MutexGuard<'_, Connection>> {
+        let count = self.readers.len();
+        let start = self.next_reader.fetch_add(1, Ordering::Relaxed) % count;
+
+        for offset in 0..count {
+            if let Ok(conn) = self.readers[(start + offset) % count].try_lock() {
+                return Ok(conn);
+            }
+        }
+
+        self.readers[start]
+            .lock()
+            .map_err(|e| AppError::from(format!("Failed to lock database reader: {}", e)))
+    }
+}
+
+fn open_read_only_connection(db_path: &Path) -> AppResult<Connection> {
+    let conn = Connection::open_with_flags(
+        db_path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )
+    .map_err(db_err("Failed to open read-only connection"))?;
+
+    crate::crypto::apply_database_key(&conn)?;
+    crate::data::database::enable_foreign_keys(&conn)?;
+
+    conn.busy_timeout(std::time::Duration::from_secs(5))
+        .map_err(db_err("Failed to set busy timeout"))?;
+
+    Ok(conn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn open_test_pool(dir: &tempfile::TempDir) -> DbPool {
+        let db_path = dir.path().join("pool.db");
+        let writer = Connection::open(&db_path).unwrap();
+        writer.pragma_update(None, "journal_mode", "WAL").unwrap();
+        writer
+            .execute_batch("CREATE TABLE items (id INTEGER PRIMARY KEY, name TEXT NOT NULL);")
+            .unwrap();
+        DbPool::open(writer, &db_path).unwrap()
+    }
+
+    #[test]
+    fn test_readers_are_read_only() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let pool = open_test_pool(&dir);
+
+        let result = pool.read(|conn| {
+            conn.execute("INSERT INTO items (name) VALUES ('x')", [])
+                .map_err(AppError::from)
+        });
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_read_not_blocked_by_open_write_transaction() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let pool = Arc::new(open_test_pool(&dir));
+
+        pool.write(|conn| {
+            conn.execute("INSERT INTO items (name) VALUES ('committed')", [])
+                .map_err(AppError::from)
+        })
+        .unwrap();
+
+        pool.write(|conn| {
+            let tx = conn.unchecked_transaction().map_err(AppError::from)?;
+            tx.execute("INSERT INTO items (name) VALUES ('pending')", [])
+                .map_err(AppError::from)?;
+
+            // While the writer is held mid-transaction, a reader on another
+            // thread still sees the last committed snapshot.
+            let reader_pool = Arc::clone(&pool);
+            let count = std::thread::spawn(move || {
+                reader_pool.read(|conn| {
+                    conn.query_row("SELECT COUNT(*) FROM items", [], |row| row.get::<_, i64>(0))
+                        .map_err(AppError::from)
+                })
+            })
+            .join()
+            .unwrap()?;
+            assert_eq!(count, 1);
+
+            tx.commit().map_err(AppError::from)
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn test_concurrent_readers() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let pool = Arc::new(open_test_pool(&dir));
+
+        let handles: Vec<_> = (0..READ_POOL_SIZE * 2)
+            .map(|_| {
+                let pool = Arc::clone(&pool);
+                std::thread::spawn(move || {
+                    pool.read(|conn| {
+                        conn.query_row("SELECT COUNT(*) FROM items", [], |row| row.get::<_, i64>(0))
+                            .map_err(AppError::from)
+                    })
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            assert_eq!(handle.join().unwrap().unwrap(), 0);
+        }
+    }
+}
@@ -0,0 +1,239 @@
+use crate::data::error::db_err;
+use crate::data::AppResult;
+use crate::types::{ProcessSegment, ProcessTotal};
+use rusqlite::{params, Connection, OptionalExtension};
+
+/// Length of time one sample stands for.
+const SAMPLE_MS: i64 = 1000;
+
+/// A sample this close after the end of the latest segment of the same
+/// process extends it; anything later (sleep, a stalled sampler) starts a
+/// new segment so the gap stays visible.
+const MAX_SAMPLE_GAP_MS: i64 = 4000;
+
+/// Record that `process_name` was in the foreground for the second starting at `timestamp`.
+///
+/// Extends the latest segment in place while the foreground process is
+/// unchanged, otherwise starts a new one.
+pub fn record_process_sample(
+    conn: &Connection,
+    timestamp: i64,
+    process_name: &str,
+) -> AppResult<()> {
+    let latest: Option<(i64, i64, i64, String)> = conn
+        .query_row(
+            "SELECT id, start_time, end_time, process_name FROM process_segments
+             ORDER BY end_time DESC, id DESC
+             LIMIT 1",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )
+        .optional()
+        .map_err(db_err("Failed to read latest process segment"))?;
+
+    let sample_end = timestamp + SAMPLE_MS;
+
+    if let Some((id, start_time, end_time, latest_name)) = latest {
+        if sample_end <= end_time && timestamp >= start_time && latest_name == process_name {
+            // Same second sampled twice
+            return Ok(());
+        }
+
+        if latest_name == process_name
+            && timestamp >= start_time
+            && timestamp - end_time <= MAX_SAMPLE_GAP_MS
+        {
+            conn.execute(
+                "UPDATE process_segments SET end_time = ?1 WHERE id = ?2",
+                params![sample_end.max(end_time), id],
+            )
+            .map_err(db_err("Failed to extend process segment"))?;
+            return Ok(());
+        }
+
+        if timestamp < end_time && timestamp > start_time {
+            // A different process took over inside the latest segment's last second
+            conn.execute(
+                "UPDATE process_segments SET end_time = ?1 WHERE id = ?2",
+                params![timestamp, id],
+            )
+            .map_err(db_err("Failed to trim process segment"))?;
+        }
+    }
+
+    conn.execute(
+        "INSERT INTO process_segments (start_time, end_time, process_name) VALUES (?1, ?2, ?3)",
+        params![timestamp, sample_end, process_name],
+    )
+    .map_err(db_err("Failed to insert process segment"))?;
+
+    Ok(())
+}
+
+/// Segments overlapping `[start_time, end_time)`, clipped to that range.
+pub fn get_process_segments_by_range(
+    conn: &Connection,
+    start_time: i64,
+    end_time: i64,
+) -> AppResult<Vec<ProcessSegment>> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT MAX(start_time, ?1), MIN(end_time, ?2), process_name
+             FROM process_segments
+             WHERE end_time > ?1 AND start_time < ?2
+             ORDER BY start_time",
+        )
+        .map_err(db_err("Failed to prepare process segments query"))?;
+
+    let rows = stmt
+        .query_map(params![start_time, end_time], |row| {
+            Ok(ProcessSegment {
+                start_time: row.get(0)?,
+                end_time: row.get(1)?,
+                process_name: row.get(2)?,
+            })
+        })
+        .map_err(db_err("Failed to query process segments"))?;
+
+    let segments = rows
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db_err("Failed to collect process segments"))?;
+
+    Ok(segments)
+}
+
+/// The `limit` processes with the most time in `[start_time, end_time)`, most first.
+pub fn get_process_totals_by_range(
+    conn: &Connection,
+    start_time: i64,
+    end_time: i64,
+    limit: usize,
+) -> AppResult<Vec<ProcessTotal>> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT process_name, SUM(MIN(end_time, ?2) - MAX(start_time, ?1)) AS duration
+             FROM process_segments
+             WHERE end_time > ?1 AND start_time < ?2
+             GROUP BY process_name
+             ORDER BY duration DESC, process_name
+             LIMIT ?3",
+        )
+        .map_err(db_err("Failed to prepare process totals query"))?;
+
+    let totals = stmt
+        .query_map(params![start_time, end_time, limit as i64], |row| {
+            Ok(ProcessTotal {
+                process_name: row.get(0)?,
+                duration_ms: row.get(1)?,
+            })
+        })
+        .map_err(db_err("Failed to query process totals"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db_err("Failed to collect process totals"))?;
+
+    Ok(totals)
+}
+
+/// Delete up to `limit` of the oldest segments that ended before `cutoff_timestamp`.
+pub fn delete_process_segments_before(
+    conn: &Connection,
+    cutoff_timestamp: i64,
+    limit: usize,
+) -> AppResult<usize> {
+    let deleted = conn
+        .execute(
+            "DELETE FROM process_segments WHERE id IN (
+                 SELECT id FROM process_segments WHERE end_time < ?1 ORDER BY end_time LIMIT ?2
+             )",
+            params![cutoff_timestamp, limit as i64],
+        )
+        .map_err(db_err("Failed to delete old process segments"))?;
+
+    Ok(deleted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup_test_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::data::database::run_migrations(&conn).unwrap();
+        conn
+    }
+
+    fn segments(conn: &Connection) -> Vec<(i64, i64, String)> {
+        get_process_segments_by_range(conn, 0, i64::MAX)
+            .unwrap()
+            .into_iter()
+            .map(|s| (s.start_time, s.end_time, s.process_name))
+            .collect()
+    }
+
+    #[test]
+    fn test_samples_extend_segment_in_place() {
+        let conn = setup_test_db();
+        for second in 0..60 {
+            record_process_sample(&conn, 10_000 + second * 1000, "code.exe").unwrap();
+        }
+        // Duplicate sample of the last second is a no-op
+        record_process_sample(&conn, 69_000, "code.exe").unwrap();
+
+        assert_eq!(
+            segments(&conn),
+            vec![(10_000, 70_000, "code.exe".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_process_switch_and_gap_start_new_segments() {
+        let conn = setup_test_db();
+        record_process_sample(&conn, 1000, "code.exe").unwrap();
+        record_process_sample(&conn, 2000, "code.exe").unwrap();
+        record_process_sample(&conn, 3000, "chrome.exe").unwrap();
+        // Machine slept for a minute
+        record_process_sample(&conn, 63_000, "chrome.exe").unwrap();
+        // A slightly late sample still extends the segment
+        record_process_sample(&conn, 66_000, "chrome.exe").unwrap();
+
+        assert_eq!(
+            segments(&conn),
+            vec![
+                (1000, 3000, "code.exe".to_string()),
+                (3000, 4000, "chrome.exe".to_string()),
+                (63_000, 67_000, "chrome.exe".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_range_query_clips_segments() {
+        let conn = setup_test_db();
+        for second in 0..10 {
+            record_process_sample(&conn, second * 1000, "code.exe").unwrap();
+        }
+
+        let clipped = get_process_segments_by_range(&conn, 2500, 5000).unwrap();
+        assert_eq!(clipped.len(), 1);
+        assert_eq!((clipped[0].start_time, clipped[0].end_time), (2500, 5000));
+        assert!(get_process_segments_by_range(&conn, 10_000, 20_000)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_delete_process_segments_before() {
+        let conn = setup_test_db();
+        record_process_sample(&conn, 1000, "old.exe").unwrap();
+        record_process_sample(&conn, 10_000, "older.exe").unwrap();
+        record_process_sample(&conn, 100_000, "new.exe").unwrap();
+
+        assert_eq!(delete_process_segments_before(&conn, 50_000, 1).unwrap(), 1);
+        assert_eq!(delete_process_segments_before(&conn, 50_000, 1).unwrap(), 1);
+        assert_eq!(delete_process_segments_before(&conn, 50_000, 1).unwrap(), 0);
+        assert_eq!(
+            segments(&conn),
+            vec![(100_000, 101_000, "new.exe".to_string())]
+        );
+    }
+}
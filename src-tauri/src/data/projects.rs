@@ -0,0 +1,632 @@
+use crate::data::error::{db_err, ErrorDetails, ErrorKind};
+use crate::data::{AppError, AppResult};
+use crate::types::{
+    BillingSummary, Client, ClientBilling, ClientInput, Project, ProjectBilling, ProjectInput,
+};
+use rusqlite::{params, Connection, OptionalExtension};
+
+const MS_PER_HOUR: i64 = 60 * 60 * 1000;
+
+pub fn get_clients_impl(conn: &Connection) -> AppResult<Vec<Client>> {
+    let mut stmt = conn
+        .prepare("SELECT id, name, hourly_rate_cents, currency FROM clients ORDER BY name")
+        .map_err(db_err("Failed to prepare clients query"))?;
+
+    let clients = stmt
+        .query_map([], |row| {
+            Ok(Client {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                hourly_rate_cents: row.get(2)?,
+                currency: row.get(3)?,
+            })
+        })
+        .map_err(db_err("Failed to query clients"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db_err("Failed to map client"))?;
+
+    Ok(clients)
+}
+
+pub fn create_client_impl(conn: &Connection, client: &ClientInput) -> AppResult<Client> {
+    let client = validate_client(conn, client, None)?;
+
+    conn.execute(
+        "INSERT INTO clients (name, hourly_rate_cents, currency) VALUES (?, ?, ?)",
+        params![client.name, client.hourly_rate_cents, client.currency],
+    )
+    .map_err(db_err("Failed to insert client"))?;
+
+    Ok(Client {
+        id: conn.last_insert_rowid(),
+        ..client
+    })
+}
+
+/// Projects without their own rate or currency follow the change.
+pub fn update_client_impl(conn: &Connection, id: i64, client: &ClientInput) -> AppResult<Client> {
+    let client = validate_client(conn, client, Some(id))?;
+
+    let rows_affected = conn
+        .execute(
+            "UPDATE clients SET name = ?1, hourly_rate_cents = ?2, currency = ?3 WHERE id = ?4",
+            params![client.name, client.hourly_rate_cents, client.currency, id],
+        )
+        .map_err(db_err("Failed to update client"))?;
+
+    if rows_affected == 0 {
+        return Err(client_not_found(id));
+    }
+
+    Ok(Client { id, ..client })
+}
+
+/// Only clients without projects can be deleted.
+pub fn delete_client_impl(conn: &Connection, id: i64) -> AppResult<()> {
+    let has_projects: bool = conn
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM projects WHERE client_id = ?)",
+            params![id],
+            |row| row.get(0),
+        )
+        .map_err(db_err("Failed to look up client projects"))?;
+    if has_projects {
+        return Err(AppError::new(
+            ErrorKind::Conflict,
+            "client_has_projects",
+            "Delete or move the client's projects first",
+        ));
+    }
+
+    let rows_affected = conn
+        .execute("DELETE FROM clients WHERE id = ?", params![id])
+        .map_err(db_err("Failed to delete client"))?;
+
+    if rows_affected == 0 {
+        return Err(client_not_found(id));
+    }
+
+    Ok(())
+}
+
+pub fn get_projects_impl(conn: &Connection) -> AppResult<Vec<Project>> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT p.id, p.client_id, p.name, p.hourly_rate_cents, p.currency, p.billable
+             FROM projects p
+             JOIN clients c ON c.id = p.client_id
+             ORDER BY c.name, p.name",
+        )
+        .map_err(db_err("Failed to prepare projects query"))?;
+
+    let projects = stmt
+        .query_map([], map_project)
+        .map_err(db_err("Failed to query projects"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db_err("Failed to map project"))?;
+
+    Ok(projects)
+}
+
+pub fn create_project_impl(conn: &Connection, project: &ProjectInput) -> AppResult<Project> {
+    let project = validate_project(conn, project, None)?;
+
+    conn.execute(
+        "INSERT INTO projects (client_id, name, hourly_rate_cents, currency, billable)
+         VALUES (?, ?, ?, ?, ?)",
+        params![
+            project.client_id,
+            project.name,
+            project.hourly_rate_cents,
+            project.currency,
+            project.billable
+        ],
+    )
+    .map_err(db_err("Failed to insert project"))?;
+
+    Ok(Project {
+        id: conn.last_insert_rowid(),
+        ..project
+    })
+}
+
+pub fn update_project_impl(
+    conn: &Connection,
+    id: i64,
+    project: &ProjectInput,
+) -> AppResult<Project> {
+    let project = validate_project(conn, project, Some(id))?;
+
+    let rows_affected = conn
+        .execute(
+            "UPDATE projects
+             SET client_id = ?1, name = ?2, hourly_rate_cents = ?3, currency = ?4, billable = ?5
+             WHERE id = ?6",
+            params![
+                project.client_id,
+                project.name,
+                project.hourly_rate_cents,
+                project.currency,
+                project.billable,
+                id
+            ],
+        )
+        .map_err(db_err("Failed to update project"))?;
+
+    if rows_affected == 0 {
+        return Err(project_not_found(id));
+    }
+
+    Ok(Project { id, ..project })
+}
+
+/// Only projects no entry refers to, including trashed ones, can be deleted, so
+/// undoing an entry change never points it at a missing project.
+pub fn delete_project_impl(conn: &Connection, id: i64) -> AppResult<()> {
+    let mut stmt = conn
+        .prepare("SELECT id FROM time_entries WHERE project_id = ? ORDER BY start_time")
+        .map_err(db_err("Failed to prepare project usage query"))?;
+    let entry_ids = stmt
+        .query_map(params![id], |row| row.get::<_, i64>(0))
+        .map_err(db_err("Failed to query project usage"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db_err("Failed to query project usage"))?;
+    if !entry_ids.is_empty() {
+        return Err(AppError::new(
+            ErrorKind::Conflict,
+            "project_in_use",
+            format!("{} time entries belong to this project", entry_ids.len()),
+        )
+        .with_details(ErrorDetails {
+            conflicting_entry_ids: entry_ids,
+            ..Default::default()
+        }));
+    }
+
+    let rows_affected = conn
+        .execute("DELETE FROM projects WHERE id = ?", params![id])
+        .map_err(db_err("Failed to delete project"))?;
+
+    if rows_affected == 0 {
+        return Err(project_not_found(id));
+    }
+
+    Ok(())
+}
+
+/// Reject references to projects that do not exist.
+pub fn ensure_project_exists(conn: &Connection, id: i64) -> AppResult<()> {
+    let exists: bool = conn
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM projects WHERE id = ?)",
+            params![id],
+            |row| row.get(0),
+        )
+        .map_err(db_err("Failed to look up project"))?;
+
+    if !exists {
+        return Err(AppError::validation(
+            "unknown_project",
+            format!("Project {} does not exist", id),
+        )
+        .with_field("project_id"));
+    }
+
+    Ok(())
+}
+
+/// Billable time and amounts per project and per client within
+/// `[start_time, end_time)`.
+///
+/// Entries crossing the range edges count only their part inside it. Projects
+/// marked not billable are left out; time at no rate is listed with a zero amount.
+/// Amounts are rounded to the nearest minor unit per project.
+pub fn get_billing_summary_impl(
+    conn: &Connection,
+    start_time: i64,
+    end_time: i64,
+) -> AppResult<BillingSummary> {
+    if end_time <= start_time {
+        return Err(AppError::validation(
+            "invalid_time_range",
+            "end_time must be greater than start_time",
+        )
+        .with_field("end_time"));
+    }
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT p.id, p.name, c.id, c.name,
+                    SUM(MIN(e.end_time, ?2) - MAX(e.start_time, ?1)),
+                    COALESCE(p.hourly_rate_cents, c.hourly_rate_cents),
+                    COALESCE(p.currency, c.currency)
+             FROM time_entries e
+             JOIN projects p ON p.id = e.project_id
+             JOIN clients c ON c.id = p.client_id
+             WHERE e.deleted_at IS NULL AND e.start_time < ?2 AND e.end_time > ?1
+               AND p.billable = 1
+             GROUP BY p.id
+             ORDER BY c.name, p.name",
+        )
+        .map_err(db_err("Failed to prepare billing query"))?;
+
+    let projects = stmt
+        .query_map(params![start_time, end_time], |row| {
+            let billable_ms: i64 = row.get(4)?;
+            let hourly_rate_cents: Option<i64> = row.get(5)?;
+            Ok(ProjectBilling {
+                project_id: row.get(0)?,
+                project_name: row.get(1)?,
+                client_id: row.get(2)?,
+                client_name: row.get(3)?,
+                billable_ms,
+                hourly_rate_cents,
+                currency: row.get(6)?,
+                amount_cents: amount_cents(billable_ms, hourly_rate_cents.unwrap_or(0)),
+            })
+        })
+        .map_err(db_err("Failed to query billing"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db_err("Failed to collect billing"))?;
+
+    let mut clients: Vec<ClientBilling> = Vec::new();
+    for project in &projects {
+        match clients
+            .iter_mut()
+            .find(|c| c.client_id == project.client_id && c.currency == project.currency)
+        {
+            Some(client) => {
+                client.billable_ms += project.billable_ms;
+                client.amount_cents += project.amount_cents;
+            }
+            None => clients.push(ClientBilling {
+                client_id: project.client_id,
+                client_name: project.client_name.clone(),
+                currency: project.currency.clone(),
+                billable_ms: project.billable_ms,
+                amount_cents: project.amount_cents,
+            }),
+        }
+    }
+
+    Ok(BillingSummary { projects, clients })
+}
+
+/// `hourly_rate_cents` for `duration_ms`, rounded half up.
+fn amount_cents(duration_ms: i64, hourly_rate_cents: i64) -> i64 {
+    let exact = i128::from(duration_ms) * i128::from(hourly_rate_cents);
+    let hour = i128::from(MS_PER_HOUR);
+    ((exact + hour / 2) / hour) as i64
+}
+
+fn validate_client(conn: &Connection, client: &ClientInput, id: Option<i64>) -> AppResult<Client> {
+    let name = client.name.trim();
+    if name.is_empty() {
+        return Err(
+            AppError::validation("empty_client_name", "Client name cannot be empty")
+                .with_field("name"),
+        );
+    }
+    validate_rate(client.hourly_rate_cents)?;
+    let currency = normalize_currency(&client.currency)?;
+
+    let taken: Option<i64> = conn
+        .query_row(
+            "SELECT id FROM clients WHERE name = ?1 COLLATE NOCASE AND id IS NOT ?2",
+            params![name, id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(db_err("Failed to look up client"))?;
+    if taken.is_some() {
+        return Err(AppError::new(
+            ErrorKind::Conflict,
+            "client_name_taken",
+            format!("A client named '{}' already exists", name),
+        )
+        .with_field("name"));
+    }
+
+    Ok(Client {
+        id: id.unwrap_or_default(),
+        name: name.to_string(),
+        hourly_rate_cents: client.hourly_rate_cents,
+        currency,
+    })
+}
+
+fn validate_project(
+    conn: &Connection,
+    project: &ProjectInput,
+    id: Option<i64>,
+) -> AppResult<Project> {
+    let name = project.name.trim();
+    if name.is_empty() {
+        return Err(
+            AppError::validation("empty_project_name", "Project name cannot be empty")
+                .with_field("name"),
+        );
+    }
+    validate_rate(project.hourly_rate_cents)?;
+    let currency = project
+        .currency
+        .as_deref()
+        .map(normalize_currency)
+        .transpose()?;
+
+    let client_exists: bool = conn
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM clients WHERE id = ?)",
+            params![project.client_id],
+            |row| row.get(0),
+        )
+        .map_err(db_err("Failed to look up client"))?;
+    if !client_exists {
+        return Err(AppError::validation(
+            "unknown_client",
+            format!("Client {} does not exist", project.client_id),
+        )
+        .with_field("client_id"));
+    }
+
+    let taken: Option<i64> = conn
+        .query_row(
+            "SELECT id FROM projects WHERE client_id = ?1 AND name = ?2 COLLATE NOCASE AND id IS NOT ?3",
+            params![project.client_id, name, id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(db_err("Failed to look up project"))?;
+    if taken.is_some() {
+        return Err(AppError::new(
+            ErrorKind::Conflict,
+            "project_name_taken",
+            format!("The client already has a project named '{}'", name),
+        )
+        .with_field("name"));
+    }
+
+    Ok(Project {
+        id: id.unwrap_or_default(),
+        client_id: project.client_id,
+        name: name.to_string(),
+        hourly_rate_cents: project.hourly_rate_cents,
+        currency,
+        billable: project.billable,
+    })
+}
+
+fn validate_rate(hourly_rate_cents: Option<i64>) -> AppResult<()> {
+    if hourly_rate_cents.is_some_and(|rate| rate < 0) {
+        return Err(
+            AppError::validation("invalid_rate", "hourly_rate_cents cannot be negative")
+                .with_field("hourly_rate_cents"),
+        );
+    }
+    Ok(())
+}
+
+/// Uppercase three-letter ISO 4217 code.
+fn normalize_currency(currency: &str) -> AppResult<String> {
+    let currency = currency.trim().to_ascii_uppercase();
+    if currency.len() != 3 || !currency.chars().all(|c| c.is_ascii_uppercase()) {
+        return Err(AppError::validation(
+            "invalid_currency",
+            "Currency must be a three-letter code such as USD",
+        )
+        .with_field("currency"));
+    }
+    Ok(currency)
+}
+
+fn map_project(row: &rusqlite::Row) -> rusqlite::Result<Project> {
+    Ok(Project {
+        id: row.get(0)?,
+        client_id: row.get(1)?,
+        name: row.get(2)?,
+        hourly_rate_cents: row.get(3)?,
+        currency: row.get(4)?,
+        billable: row.get(5)?,
+    })
+}
+
+fn client_not_found(id: i64) -> AppError {
+    AppError::not_found("client_not_found", format!("Client {} not found", id))
+}
+
+fn project_not_found(id: i64) -> AppError {
+    AppError::not_found("project_not_found", format!("Project {} not found", id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::database::{enable_foreign_keys, run_migrations};
+    use crate::data::time_entries::{
+        create_time_entry_impl, delete_time_entry_impl, update_time_entry_impl,
+    };
+    use crate::types::{TimeEntryInput, TimeEntryUpdate};
+
+    fn setup_test_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        enable_foreign_keys(&conn).unwrap();
+        run_migrations(&conn).unwrap();
+        conn
+    }
+
+    fn client(conn: &Connection, name: &str, rate: Option<i64>, currency: &str) -> Client {
+        create_client_impl(
+            conn,
+            &ClientInput {
+                name: name.to_string(),
+                hourly_rate_cents: rate,
+                currency: currency.to_string(),
+            },
+        )
+        .unwrap()
+    }
+
+    fn project(conn: &Connection, client_id: i64, name: &str, rate: Option<i64>) -> Project {
+        create_project_impl(
+            conn,
+            &ProjectInput {
+                client_id,
+                name: name.to_string(),
+                hourly_rate_cents: rate,
+                currency: None,
+                billable: true,
+            },
+        )
+        .unwrap()
+    }
+
+    fn entry(conn: &Connection, start_time: i64, end_time: i64, project_id: i64) -> i64 {
+        create_time_entry_impl(
+            conn,
+            &TimeEntryInput {
+                start_time,
+                end_time,
+                label: "Work".to_string(),
+                color: None,
+                category_id: None,
+                notes: None,
+                project_id: Some(project_id),
+            },
+        )
+        .unwrap()
+        .id
+    }
+
+    #[test]
+    fn test_client_and_project_validation() {
+        let conn = setup_test_db();
+        let acme = client(&conn, "Acme", Some(10000), "usd");
+        assert_eq!(acme.currency, "USD");
+
+        let error = create_client_impl(
+            &conn,
+            &ClientInput {
+                name: "ACME".to_string(),
+                hourly_rate_cents: None,
+                currency: "USD".to_string(),
+            },
+        )
+        .unwrap_err();
+        assert_eq!(error.code, "client_name_taken");
+
+        let error = create_client_impl(
+            &conn,
+            &ClientInput {
+                name: "Globex".to_string(),
+                hourly_rate_cents: Some(-1),
+                currency: "USD".to_string(),
+            },
+        )
+        .unwrap_err();
+        assert_eq!(error.code, "invalid_rate");
+
+        let site = project(&conn, acme.id, "Website", None);
+        let error = create_project_impl(
+            &conn,
+            &ProjectInput {
+                client_id: acme.id + 1,
+                name: "Website".to_string(),
+                hourly_rate_cents: None,
+                currency: Some("dollars".to_string()),
+                billable: true,
+            },
+        )
+        .unwrap_err();
+        assert_eq!(error.code, "invalid_currency");
+
+        // Entries pin their project; the client keeps its projects
+        let id = entry(&conn, 0, 1000, site.id);
+        assert_eq!(
+            delete_client_impl(&conn, acme.id).unwrap_err().code,
+            "client_has_projects"
+        );
+        delete_time_entry_impl(&conn, id).unwrap();
+        let error = delete_project_impl(&conn, site.id).unwrap_err();
+        assert_eq!(error.code, "project_in_use");
+        assert_eq!(error.details.unwrap().conflicting_entry_ids, vec![id]);
+
+        let error = update_time_entry_impl(
+            &conn,
+            entry(&conn, 1000, 2000, site.id),
+            &TimeEntryUpdate {
+                start_time: None,
+                end_time: None,
+                label: None,
+                color: None,
+                category_id: None,
+                notes: None,
+                project_id: Some(Some(site.id + 1)),
+            },
+        )
+        .unwrap_err();
+        assert_eq!(error.code, "unknown_project");
+    }
+
+    #[test]
+    fn test_billing_summary() {
+        let conn = setup_test_db();
+        let acme = client(&conn, "Acme", Some(10000), "USD");
+        let inherits = project(&conn, acme.id, "Support", None);
+        let overrides = project(&conn, acme.id, "Website", Some(15000));
+        let internal = create_project_impl(
+            &conn,
+            &ProjectInput {
+                client_id: acme.id,
+                name: "Internal".to_string(),
+                hourly_rate_cents: None,
+                currency: None,
+                billable: false,
+            },
+        )
+        .unwrap();
+        let globex = client(&conn, "Globex", None, "EUR");
+        let unpriced = project(&conn, globex.id, "Audit", None);
+
+        // 1.5h of support, half of it before the range starts
+        entry(&conn, 0, 3 * MS_PER_HOUR, inherits.id);
+        entry(&conn, 3 * MS_PER_HOUR, 5 * MS_PER_HOUR, overrides.id);
+        entry(&conn, 5 * MS_PER_HOUR, 6 * MS_PER_HOUR, internal.id);
+        entry(&conn, 6 * MS_PER_HOUR, 7 * MS_PER_HOUR, unpriced.id);
+
+        let summary =
+            get_billing_summary_impl(&conn, 3 * MS_PER_HOUR / 2, 10 * MS_PER_HOUR).unwrap();
+        let projects: Vec<(&str, i64, Option<i64>, i64)> = summary
+            .projects
+            .iter()
+            .map(|p| {
+                (
+                    p.project_name.as_str(),
+                    p.billable_ms,
+                    p.hourly_rate_cents,
+                    p.amount_cents,
+                )
+            })
+            .collect();
+        assert_eq!(
+            projects,
+            vec![
+                ("Support", 3 * MS_PER_HOUR / 2, Some(10000), 15000),
+                ("Website", 2 * MS_PER_HOUR, Some(15000), 30000),
+                ("Audit", MS_PER_HOUR, None, 0),
+            ]
+        );
+
+        assert_eq!(summary.clients.len(), 2);
+        assert_eq!(summary.clients[0].client_name, "Acme");
+        assert_eq!(summary.clients[0].billable_ms, 7 * MS_PER_HOUR / 2);
+        assert_eq!(summary.clients[0].amount_cents, 45000);
+        assert_eq!(summary.clients[1].currency, "EUR");
+    }
+
+    #[test]
+    fn test_amount_rounding() {
+        // One minute at 100.00/h is 1.6666...
+        assert_eq!(amount_cents(60 * 1000, 10000), 167);
+        assert_eq!(amount_cents(0, 10000), 0);
+    }
+}
@@ -0,0 +1,957 @@
+use crate::data::categories::ensure_category_exists;
+use crate::data::error::db_err;
+use crate::data::journal;
+use crate::data::time_entries::{
+    create_planned_time_entry_impl, delete_time_entry_impl, local_minute_millis,
+};
+use crate::data::{AppError, AppResult};
+use crate::types::{
+    EntryTemplate, EntryTemplateInput, MaterializeReport, Recurrence, RecurringSeries,
+    RecurringSeriesInput, TimeEntryInput,
+};
+use chrono::{Datelike, NaiveDate, TimeZone, Weekday};
+use rusqlite::{params, Connection, OptionalExtension};
+
+const MINUTES_PER_DAY: u32 = 24 * 60;
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Frequency {
+    Daily,
+    Weekly,
+}
+
+/// The supported subset of an RFC 5545 RRULE.
+#[derive(Debug, Clone, PartialEq)]
+struct Rule {
+    frequency: Frequency,
+    interval: u32,
+    /// Monday first; empty means every day (daily) or the weekday of `starts_on` (weekly).
+    by_day: Vec<Weekday>,
+    count: Option<u32>,
+    until: Option<NaiveDate>,
+}
+
+impl Rule {
+    fn parse(text: &str) -> AppResult<Rule> {
+        let text = text.trim();
+        let text = text.strip_prefix("RRULE:").unwrap_or(text);
+        let mut frequency = None;
+        let mut rule = Rule {
+            frequency: Frequency::Daily,
+            interval: 1,
+            by_day: Vec::new(),
+            count: None,
+            until: None,
+        };
+
+        for part in text.split(';').filter(|part| !part.is_empty()) {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| invalid_rule(format!("Expected KEY=VALUE, got '{}'", part)))?;
+            match key.to_ascii_uppercase().as_str() {
+                "FREQ" => {
+                    frequency = Some(match value.to_ascii_uppercase().as_str() {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        _ => return Err(invalid_rule(format!("Unsupported FREQ '{}'", value))),
+                    })
+                }
+                "INTERVAL" => {
+                    rule.interval = value
+                        .parse()
+                        .ok()
+                        .filter(|interval| *interval > 0)
+                        .ok_or_else(|| invalid_rule(format!("Invalid INTERVAL '{}'", value)))?
+                }
+                "BYDAY" => {
+                    rule.by_day = value
+                        .split(',')
+                        .map(parse_weekday)
+                        .collect::<AppResult<Vec<_>>>()?
+                }
+                "COUNT" => {
+                    rule.count = Some(
+                        value
+                            .parse()
+                            .ok()
+                            .filter(|count| *count > 0)
+                            .ok_or_else(|| invalid_rule(format!("Invalid COUNT '{}'", value)))?,
+                    )
+                }
+                "UNTIL" => {
+                    // Only the date matters; a time part such as T235959Z is ignored
+                    let date = value.get(..8).unwrap_or(value);
+                    rule.until = Some(
+                        NaiveDate::parse_from_str(date, "%Y%m%d")
+                            .map_err(|_| invalid_rule(format!("Invalid UNTIL '{}'", value)))?,
+                    )
+                }
+                _ => return Err(invalid_rule(format!("Unsupported rule part '{}'", key))),
+            }
+        }
+
+        rule.frequency = frequency.ok_or_else(|| invalid_rule("FREQ is required"))?;
+        if rule.count.is_some() && rule.until.is_some() {
+            return Err(invalid_rule("COUNT and UNTIL cannot both be set"));
+        }
+        rule.by_day.sort_by_key(Weekday::num_days_from_monday);
+        rule.by_day.dedup();
+        Ok(rule)
+    }
+
+    fn from_recurrence(recurrence: &Recurrence) -> AppResult<Rule> {
+        let weekly = |by_day| Rule {
+            frequency: Frequency::Weekly,
+            interval: 1,
+            by_day,
+            count: None,
+            until: None,
+        };
+        match recurrence {
+            Recurrence::Daily => Rule::parse("FREQ=DAILY"),
+            Recurrence::Weekdays => Ok(weekly(vec![
+                Weekday::Mon,
+                Weekday::Tue,
+                Weekday::Wed,
+                Weekday::Thu,
+                Weekday::Fri,
+            ])),
+            Recurrence::Weekly { days } => {
+                if days.is_empty() {
+                    return Err(invalid_rule("A weekly recurrence needs at least one day"));
+                }
+                let mut by_day = days
+                    .iter()
+                    .map(|&day| match day {
+                        1..=7 => Ok(Weekday::try_from(day as u8 - 1).unwrap_or(Weekday::Mon)),
+                        _ => Err(invalid_rule(format!("Invalid weekday {}", day))),
+                    })
+                    .collect::<AppResult<Vec<_>>>()?;
+                by_day.sort_by_key(Weekday::num_days_from_monday);
+                by_day.dedup();
+                Ok(weekly(by_day))
+            }
+            Recurrence::Rrule { rule } => Rule::parse(rule),
+        }
+    }
+
+    fn to_rrule(&self) -> String {
+        let mut parts = vec![match self.frequency {
+            Frequency::Daily => "FREQ=DAILY".to_string(),
+            Frequency::Weekly => "FREQ=WEEKLY".to_string(),
+        }];
+        if self.interval > 1 {
+            parts.push(format!("INTERVAL={}", self.interval));
+        }
+        if !self.by_day.is_empty() {
+            let days: Vec<&str> = self.by_day.iter().map(|day| weekday_code(*day)).collect();
+            parts.push(format!("BYDAY={}", days.join(",")));
+        }
+        if let Some(count) = self.count {
+            parts.push(format!("COUNT={}", count));
+        }
+        if let Some(until) = self.until {
+            parts.push(format!("UNTIL={}", until.format("%Y%m%d")));
+        }
+        parts.join(";")
+    }
+
+    /// Whether the pattern matches `date`, ignoring COUNT and UNTIL.
+    fn matches(&self, starts_on: NaiveDate, date: NaiveDate) -> bool {
+        if date < starts_on {
+            return false;
+        }
+        let interval = i64::from(self.interval);
+        match self.frequency {
+            Frequency::Daily => {
+                (date - starts_on).num_days() % interval == 0
+                    && (self.by_day.is_empty() || self.by_day.contains(&date.weekday()))
+            }
+            Frequency::Weekly => {
+                let week_of = |d: NaiveDate| {
+                    d - chrono::Duration::days(i64::from(d.weekday().num_days_from_monday()))
+                };
+                let weeks = (week_of(date) - week_of(starts_on)).num_days() / 7;
+                let on_day = if self.by_day.is_empty() {
+                    date.weekday() == starts_on.weekday()
+                } else {
+                    self.by_day.contains(&date.weekday())
+                };
+                weeks % interval == 0 && on_day
+            }
+        }
+    }
+
+    fn occurs_on(&self, starts_on: NaiveDate, date: NaiveDate) -> bool {
+        if !self.matches(starts_on, date) || self.until.is_some_and(|until| date > until) {
+            return false;
+        }
+        match self.count {
+            Some(count) => self.occurrences_before(starts_on, date) < i64::from(count),
+            None => true,
+        }
+    }
+
+    /// How many days in `[starts_on, date)` match the pattern, without walking them.
+    fn occurrences_before(&self, starts_on: NaiveDate, date: NaiveDate) -> i64 {
+        if date <= starts_on {
+            return 0;
+        }
+        let interval = i64::from(self.interval);
+        match self.frequency {
+            Frequency::Daily => {
+                // Every `interval`th day, whose weekdays repeat every 7 of them
+                let candidates = ((date - starts_on).num_days() + interval - 1) / interval;
+                if self.by_day.is_empty() {
+                    return candidates;
+                }
+                let on_day = |m: i64| {
+                    let day = starts_on + chrono::Duration::days(m * interval);
+                    self.by_day.contains(&day.weekday())
+                };
+                let per_cycle = (0..7).filter(|&m| on_day(m)).count() as i64;
+                let rest = (0..candidates % 7).filter(|&m| on_day(m)).count() as i64;
+                candidates / 7 * per_cycle + rest
+            }
+            Frequency::Weekly => {
+                let days = if self.by_day.is_empty() {
+                    vec![starts_on.weekday()]
+                } else {
+                    self.by_day.clone()
+                };
+                let in_week = |from: u32, to: u32| {
+                    days.iter()
+                        .filter(|d| (from..to).contains(&d.num_days_from_monday()))
+                        .count() as i64
+                };
+                let first = starts_on.weekday().num_days_from_monday();
+                let last = date.weekday().num_days_from_monday();
+                // Weeks from the Monday of `starts_on` to the Monday of `date`
+                let weeks =
+                    ((date - starts_on).num_days() + i64::from(first) - i64::from(last)) / 7;
+                if weeks == 0 {
+                    return in_week(first, last);
+                }
+                // The first and last weeks are partial; the ones between count in full
+                let mut total = in_week(first, 7) + (weeks - 1) / interval * days.len() as i64;
+                if weeks % interval == 0 {
+                    total += in_week(0, last);
+                }
+                total
+            }
+        }
+    }
+}
+
+/// Normalize `recurrence` to the RRULE text stored on a series.
+pub fn normalize_recurrence(recurrence: &Recurrence) -> AppResult<String> {
+    Rule::from_recurrence(recurrence).map(|rule| rule.to_rrule())
+}
+
+pub fn get_entry_templates_impl(conn: &Connection) -> AppResult<Vec<EntryTemplate>> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, label, color, category_id, duration_ms FROM entry_templates
+             ORDER BY label",
+        )
+        .map_err(db_err("Failed to prepare entry templates query"))?;
+
+    let templates = stmt
+        .query_map([], |row| {
+            Ok(EntryTemplate {
+                id: row.get(0)?,
+                label: row.get(1)?,
+                color: row.get(2)?,
+                category_id: row.get(3)?,
+                duration_ms: row.get(4)?,
+            })
+        })
+        .map_err(db_err("Failed to query entry templates"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db_err("Failed to map entry template"))?;
+
+    Ok(templates)
+}
+
+pub fn create_entry_template_impl(
+    conn: &Connection,
+    template: &EntryTemplateInput,
+) -> AppResult<EntryTemplate> {
+    validate_template(conn, template)?;
+
+    conn.execute(
+        "INSERT INTO entry_templates (label, color, category_id, duration_ms) VALUES (?, ?, ?, ?)",
+        params![
+            template.label,
+            template.color,
+            template.category_id,
+            template.duration_ms
+        ],
+    )
+    .map_err(db_err("Failed to insert entry template"))?;
+
+    get_entry_template_by_id(conn, conn.last_insert_rowid())
+}
+
+/// Series using the template pick up the change for occurrences not yet created.
+///
+/// Template edits are not undoable, so the template drops out of earlier undo
+/// steps, e.g. a category delete, rather than have them overwrite this edit.
+pub fn update_entry_template_impl(
+    conn: &Connection,
+    id: i64,
+    template: &EntryTemplateInput,
+) -> AppResult<EntryTemplate> {
+    validate_template(conn, template)?;
+
+    let rows_affected = conn
+        .execute(
+            "UPDATE entry_templates SET label = ?1, color = ?2, category_id = ?3, duration_ms = ?4
+             WHERE id = ?5",
+            params![
+                template.label,
+                template.color,
+                template.category_id,
+                template.duration_ms,
+                id
+            ],
+        )
+        .map_err(db_err("Failed to update entry template"))?;
+
+    if rows_affected == 0 {
+        return Err(template_not_found(id));
+    }
+    journal::forget(conn, journal::ENTRY_TEMPLATES, &[id])?;
+
+    get_entry_template_by_id(conn, id)
+}
+
+/// Deletes the template's series too. Entries already created are kept.
+pub fn delete_entry_template_impl(conn: &Connection, id: i64) -> AppResult<()> {
+    let rows_affected = conn
+        .execute("DELETE FROM entry_templates WHERE id = ?", params![id])
+        .map_err(db_err("Failed to delete entry template"))?;
+
+    if rows_affected == 0 {
+        return Err(template_not_found(id));
+    }
+    // Undoing an earlier category delete must not bring it back
+    journal::forget(conn, journal::ENTRY_TEMPLATES, &[id])
+}
+
+pub fn get_recurring_series_impl(conn: &Connection) -> AppResult<Vec<RecurringSeries>> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, template_id, rule, start_minute, starts_on FROM recurring_series
+             ORDER BY id",
+        )
+        .map_err(db_err("Failed to prepare recurring series query"))?;
+
+    let series = stmt
+        .query_map([], map_series)
+        .map_err(db_err("Failed to query recurring series"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db_err("Failed to map recurring series"))?;
+
+    Ok(series)
+}
+
+pub fn create_recurring_series_impl(
+    conn: &Connection,
+    series: &RecurringSeriesInput,
+) -> AppResult<RecurringSeries> {
+    let rule = validate_series(conn, series)?;
+
+    conn.execute(
+        "INSERT INTO recurring_series (template_id, rule, start_minute, starts_on)
+         VALUES (?, ?, ?, ?)",
+        params![
+            series.template_id,
+            rule,
+            series.start_minute,
+            series.starts_on
+        ],
+    )
+    .map_err(db_err("Failed to insert recurring series"))?;
+
+    get_series_by_id(conn, conn.last_insert_rowid())
+}
+
+/// Change a series from `now` on.
+///
+/// Upcoming entries it already created are trashed so the next materialization
+/// recreates them under the new schedule; past entries are left alone.
+pub fn update_recurring_series_impl(
+    conn: &Connection,
+    id: i64,
+    series: &RecurringSeriesInput,
+    now: i64,
+) -> AppResult<RecurringSeries> {
+    let rule = validate_series(conn, series)?;
+    get_series_by_id(conn, id)?;
+
+    let tx = conn
+        .unchecked_transaction()
+        .map_err(db_err("Failed to start transaction"))?;
+    journal::untracked(|| remove_upcoming(&tx, id, now))?;
+    tx.execute(
+        "UPDATE recurring_series SET template_id = ?1, rule = ?2, start_minute = ?3, starts_on = ?4
+         WHERE id = ?5",
+        params![
+            series.template_id,
+            rule,
+            series.start_minute,
+            series.starts_on,
+            id
+        ],
+    )
+    .map_err(db_err("Failed to update recurring series"))?;
+    tx.commit()
+        .map_err(db_err("Failed to commit recurring series update"))?;
+
+    get_series_by_id(conn, id)
+}
+
+/// Stop a series. With `delete_upcoming`, entries it created that start at or
+/// after `now` go to the trash.
+pub fn delete_recurring_series_impl(
+    conn: &Connection,
+    id: i64,
+    delete_upcoming: bool,
+    now: i64,
+) -> AppResult<()> {
+    let tx = conn
+        .unchecked_transaction()
+        .map_err(db_err("Failed to start transaction"))?;
+    if delete_upcoming {
+        journal::untracked(|| remove_upcoming(&tx, id, now))?;
+    }
+    let rows_affected = tx
+        .execute("DELETE FROM recurring_series WHERE id = ?", params![id])
+        .map_err(db_err("Failed to delete recurring series"))?;
+
+    if rows_affected == 0 {
+        return Err(series_not_found(id));
+    }
+
+    tx.commit()
+        .map_err(db_err("Failed to commit recurring series deletion"))?;
+    Ok(())
+}
+
+/// Create the entries every series is due for from `now` through `horizon_days`
+/// local days ahead.
+///
+/// The entries are not recorded for undo, so the background run never touches
+/// the user's undo and redo history. Slots that would overlap an existing entry
+/// are skipped and tried again on the next run; a running timer only counts up
+/// to `now`. Days that already produced an entry are never filled twice, even if
+/// that entry was since deleted.
+pub fn materialize_recurring_entries_impl<Tz: TimeZone>(
+    conn: &Connection,
+    tz: &Tz,
+    now: i64,
+    horizon_days: u32,
+) -> AppResult<MaterializeReport> {
+    let today = tz
+        .timestamp_millis_opt(now)
+        .earliest()
+        .map(|t| t.naive_local().date())
+        .ok_or_else(|| format!("Invalid timestamp {}", now))?;
+    let templates = get_entry_templates_impl(conn)?;
+
+    let tx = conn
+        .unchecked_transaction()
+        .map_err(db_err("Failed to start transaction"))?;
+    let report = journal::untracked(|| {
+        let mut report = MaterializeReport::default();
+        for series in get_recurring_series_impl(&tx)? {
+            let Some(template) = templates.iter().find(|t| t.id == series.template_id) else {
+                continue;
+            };
+            let rule = Rule::parse(&series.rule)?;
+            let starts_on = parse_date(&series.starts_on)?;
+
+            for day in today.iter_days().take(horizon_days as usize + 1) {
+                if !rule.occurs_on(starts_on, day) {
+                    continue;
+                }
+                let occurs_on = day.format(DATE_FORMAT).to_string();
+                if has_occurrence(&tx, series.id, &occurs_on)? {
+                    continue;
+                }
+                let start_time = local_minute_millis(tz, day, series.start_minute);
+                if start_time < now {
+                    continue;
+                }
+
+                let created = create_planned_time_entry_impl(
+                    &tx,
+                    &TimeEntryInput {
+                        start_time,
+                        end_time: start_time + template.duration_ms,
+                        label: template.label.clone(),
+                        color: template.color.clone(),
+                        category_id: template.category_id,
+                        notes: None,
+                        project_id: None,
+                    },
+                    now,
+                );
+                match created {
+                    Ok(entry) => {
+                        tx.execute(
+                            "INSERT INTO recurring_occurrences (series_id, occurs_on, entry_id)
+                             VALUES (?, ?, ?)",
+                            params![series.id, occurs_on, entry.id],
+                        )
+                        .map_err(db_err("Failed to record occurrence"))?;
+                        report.created.push(entry);
+                    }
+                    Err(e) if e.code == "time_entry_overlap" => report.skipped += 1,
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        Ok(report)
+    })?;
+    tx.commit()
+        .map_err(db_err("Failed to commit recurring entries"))?;
+
+    Ok(report)
+}
+
+/// Trash the live entries series `id` created that start at or after `now`, and
+/// forget those days so they can be filled again.
+fn remove_upcoming(conn: &Connection, id: i64, now: i64) -> AppResult<()> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT o.occurs_on, e.id FROM recurring_occurrences o
+             JOIN time_entries e ON e.id = o.entry_id
+             WHERE o.series_id = ? AND e.start_time >= ? AND e.deleted_at IS NULL",
+        )
+        .map_err(db_err("Failed to prepare upcoming occurrences query"))?;
+    let upcoming = stmt
+        .query_map(params![id, now], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+        })
+        .map_err(db_err("Failed to query upcoming occurrences"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db_err("Failed to map occurrence"))?;
+
+    for (occurs_on, entry_id) in upcoming {
+        delete_time_entry_impl(conn, entry_id)?;
+        conn.execute(
+            "DELETE FROM recurring_occurrences WHERE series_id = ? AND occurs_on = ?",
+            params![id, occurs_on],
+        )
+        .map_err(db_err("Failed to forget occurrence"))?;
+    }
+
+    Ok(())
+}
+
+fn has_occurrence(conn: &Connection, series_id: i64, occurs_on: &str) -> AppResult<bool> {
+    let exists = conn
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM recurring_occurrences WHERE series_id = ? AND occurs_on = ?)",
+            params![series_id, occurs_on],
+            |row| row.get(0),
+        )
+        .map_err(db_err("Failed to look up occurrence"))?;
+    Ok(exists)
+}
+
+fn validate_template(conn: &Connection, template: &EntryTemplateInput) -> AppResult<()> {
+    if template.label.trim().is_empty() {
+        return Err(
+            AppError::validation("empty_label", "Label cannot be empty").with_field("label")
+        );
+    }
+    if template.duration_ms <= 0 {
+        return Err(AppError::validation(
+            "invalid_duration",
+            "duration_ms must be greater than zero",
+        )
+        .with_field("duration_ms"));
+    }
+    if let Some(category_id) = template.category_id {
+        ensure_category_exists(conn, category_id)?;
+    }
+    Ok(())
+}
+
+/// Check `series` and return its normalized rule.
+fn validate_series(conn: &Connection, series: &RecurringSeriesInput) -> AppResult<String> {
+    get_entry_template_by_id(conn, series.template_id)?;
+    if series.start_minute >= MINUTES_PER_DAY {
+        return Err(AppError::validation(
+            "invalid_start_minute",
+            "start_minute must be between 0 and 1439",
+        )
+        .with_field("start_minute"));
+    }
+    parse_date(&series.starts_on)?;
+    normalize_recurrence(&series.recurrence)
+}
+
+fn parse_date(value: &str) -> AppResult<NaiveDate> {
+    NaiveDate::parse_from_str(value, DATE_FORMAT).map_err(|_| {
+        AppError::validation(
+            "invalid_date",
+            format!("Expected a YYYY-MM-DD date, got '{}'", value),
+        )
+        .with_field("starts_on")
+    })
+}
+
+fn parse_weekday(code: &str) -> AppResult<Weekday> {
+    match code.trim().to_ascii_uppercase().as_str() {
+        "MO" => Ok(Weekday::Mon),
+        "TU" => Ok(Weekday::Tue),
+        "WE" => Ok(Weekday::Wed),
+        "TH" => Ok(Weekday::Thu),
+        "FR" => Ok(Weekday::Fri),
+        "SA" => Ok(Weekday::Sat),
+        "SU" => Ok(Weekday::Sun),
+        _ => Err(invalid_rule(format!("Unsupported BYDAY value '{}'", code))),
+    }
+}
+
+fn weekday_code(day: Weekday) -> &'static str {
+    match day {
+        Weekday::Mon => "MO",
+        Weekday::Tue => "TU",
+        Weekday::Wed => "WE",
+        Weekday::Thu => "TH",
+        Weekday::Fri => "FR",
+        Weekday::Sat => "SA",
+        Weekday::Sun => "SU",
+    }
+}
+
+fn map_series(row: &rusqlite::Row) -> rusqlite::Result<RecurringSeries> {
+    Ok(RecurringSeries {
+        id: row.get(0)?,
+        template_id: row.get(1)?,
+        rule: row.get(2)?,
+        start_minute: row.get(3)?,
+        starts_on: row.get(4)?,
+    })
+}
+
+fn get_entry_template_by_id(conn: &Connection, id: i64) -> AppResult<EntryTemplate> {
+    conn.query_row(
+        "SELECT id, label, color, category_id, duration_ms FROM entry_templates WHERE id = ?",
+        params![id],
+        |row| {
+            Ok(EntryTemplate {
+                id: row.get(0)?,
+                label: row.get(1)?,
+                color: row.get(2)?,
+                category_id: row.get(3)?,
+                duration_ms: row.get(4)?,
+            })
+        },
+    )
+    .optional()
+    .map_err(db_err("Failed to query entry template"))?
+    .ok_or_else(|| template_not_found(id))
+}
+
+fn get_series_by_id(conn: &Connection, id: i64) -> AppResult<RecurringSeries> {
+    conn.query_row(
+        "SELECT id, template_id, rule, start_minute, starts_on FROM recurring_series WHERE id = ?",
+        params![id],
+        map_series,
+    )
+    .optional()
+    .map_err(db_err("Failed to query recurring series"))?
+    .ok_or_else(|| series_not_found(id))
+}
+
+fn invalid_rule(message: impl Into<String>) -> AppError {
+    AppError::validation("invalid_recurrence_rule", message).with_field("recurrence")
+}
+
+fn template_not_found(id: i64) -> AppError {
+    AppError::not_found(
+        "entry_template_not_found",
+        format!("Entry template {} not found", id),
+    )
+}
+
+fn series_not_found(id: i64) -> AppError {
+    AppError::not_found(
+        "recurring_series_not_found",
+        format!("Recurring series {} not found", id),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::database::{enable_foreign_keys, run_migrations};
+    use crate::data::time_entries::{
+        create_time_entry_impl, get_time_entries_by_range_impl, get_time_entry_by_id,
+        update_time_entry_impl,
+    };
+    use crate::data::timer;
+    use crate::types::{TimeEntryUpdate, TimerStart};
+    use chrono::Utc;
+
+    const MINUTE_MS: i64 = 60 * 1000;
+    const DAY_MS: i64 = 24 * 60 * MINUTE_MS;
+
+    fn setup_test_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        enable_foreign_keys(&conn).unwrap();
+        run_migrations(&conn).unwrap();
+        conn
+    }
+
+    fn date(value: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(value, DATE_FORMAT).unwrap()
+    }
+
+    fn template(conn: &Connection) -> EntryTemplate {
+        create_entry_template_impl(
+            conn,
+            &EntryTemplateInput {
+                label: "Standup".to_string(),
+                color: None,
+                category_id: None,
+                duration_ms: 15 * MINUTE_MS,
+            },
+        )
+        .unwrap()
+    }
+
+    fn series(template_id: i64, recurrence: Recurrence) -> RecurringSeriesInput {
+        RecurringSeriesInput {
+            template_id,
+            recurrence,
+            start_minute: 9 * 60,
+            // A Monday
+            starts_on: "2024-01-01".to_string(),
+        }
+    }
+
+    fn midnight(value: &str) -> i64 {
+        date(value)
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+            .and_utc()
+            .timestamp_millis()
+    }
+
+    #[test]
+    fn test_rules() {
+        assert_eq!(
+            normalize_recurrence(&Recurrence::Weekdays).unwrap(),
+            "FREQ=WEEKLY;BYDAY=MO,TU,WE,TH,FR"
+        );
+        assert_eq!(
+            normalize_recurrence(&Recurrence::Weekly {
+                days: vec![3, 1, 3]
+            })
+            .unwrap(),
+            "FREQ=WEEKLY;BYDAY=MO,WE"
+        );
+        assert_eq!(
+            normalize_recurrence(&Recurrence::Rrule {
+                rule: "RRULE:freq=weekly;byday=fr,tu;interval=2;until=20240131T235959Z".to_string()
+            })
+            .unwrap(),
+            "FREQ=WEEKLY;INTERVAL=2;BYDAY=TU,FR;UNTIL=20240131"
+        );
+        for rule in ["FREQ=MONTHLY", "INTERVAL=2", "FREQ=DAILY;BYDAY=XX"] {
+            let error = normalize_recurrence(&Recurrence::Rrule {
+                rule: rule.to_string(),
+            })
+            .unwrap_err();
+            assert_eq!(error.code, "invalid_recurrence_rule");
+        }
+
+        let starts_on = date("2024-01-01");
+        let dates = |rule: &str| -> Vec<u32> {
+            starts_on
+                .iter_days()
+                .take(21)
+                .filter(|day| Rule::parse(rule).unwrap().occurs_on(starts_on, *day))
+                .map(|day| day.day())
+                .collect()
+        };
+        assert_eq!(
+            dates("FREQ=WEEKLY;INTERVAL=2;BYDAY=TU,FR"),
+            vec![2, 5, 16, 19]
+        );
+        assert_eq!(dates("FREQ=DAILY;INTERVAL=3;COUNT=4"), vec![1, 4, 7, 10]);
+        assert_eq!(dates("FREQ=WEEKLY;UNTIL=20240110"), vec![1, 8]);
+    }
+
+    #[test]
+    fn test_occurrences_before_matches_walking_the_days() {
+        let starts_on = date("2024-01-03");
+        for rule in [
+            "FREQ=DAILY",
+            "FREQ=DAILY;INTERVAL=3",
+            "FREQ=DAILY;INTERVAL=7;BYDAY=WE",
+            "FREQ=DAILY;INTERVAL=2;BYDAY=MO,TH,SU",
+            "FREQ=WEEKLY",
+            "FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,FR",
+            "FREQ=WEEKLY;INTERVAL=3;BYDAY=TU,WE,SU",
+        ] {
+            let rule = Rule::parse(rule).unwrap();
+            let mut walked = 0;
+            for day in starts_on.iter_days().take(120) {
+                assert_eq!(
+                    rule.occurrences_before(starts_on, day),
+                    walked,
+                    "{:?}",
+                    rule
+                );
+                if rule.matches(starts_on, day) {
+                    walked += 1;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_materialize_skips_overlaps_and_fills_once() {
+        let conn = setup_test_db();
+        let template = template(&conn);
+        create_recurring_series_impl(&conn, &series(template.id, Recurrence::Weekdays)).unwrap();
+        // Tuesday's slot is taken
+        let tuesday_slot = midnight("2024-01-02") + 9 * 60 * MINUTE_MS;
+        create_time_entry_impl(
+            &conn,
+            &TimeEntryInput {
+                start_time: tuesday_slot,
+                end_time: tuesday_slot + 30 * MINUTE_MS,
+                label: "Dentist".to_string(),
+                color: None,
+                category_id: None,
+                notes: None,
+                project_id: None,
+            },
+        )
+        .unwrap();
+
+        let now = midnight("2024-01-01");
+        let report = materialize_recurring_entries_impl(&conn, &Utc, now, 6).unwrap();
+        // Monday, Wednesday to Friday; the weekend has no occurrences
+        assert_eq!(report.created.len(), 4);
+        assert_eq!(report.skipped, 1);
+        assert_eq!(report.created[0].start_time, now + 9 * 60 * MINUTE_MS);
+        assert_eq!(report.created[0].label, "Standup");
+
+        // Deleting a created entry does not bring it back
+        delete_time_entry_impl(&conn, report.created[0].id).unwrap();
+        let again = materialize_recurring_entries_impl(&conn, &Utc, now, 6).unwrap();
+        assert!(again.created.is_empty());
+        assert_eq!(again.skipped, 1);
+
+        // Only the user's own changes are undone; the redo survives later runs
+        journal::undo(&conn).unwrap();
+        assert!(get_time_entries_by_range_impl(&conn, now, now + 7 * DAY_MS)
+            .unwrap()
+            .iter()
+            .any(|e| e.id == report.created[0].id));
+        materialize_recurring_entries_impl(&conn, &Utc, now, 6).unwrap();
+        assert!(journal::undo_state(&conn).unwrap().can_redo);
+        journal::undo(&conn).unwrap();
+        assert!(!journal::undo_state(&conn).unwrap().can_undo);
+        let remaining = get_time_entries_by_range_impl(&conn, now, now + 7 * DAY_MS).unwrap();
+        assert_eq!(remaining.len(), 4);
+    }
+
+    #[test]
+    fn test_timer_runs_alongside_series() {
+        let conn = setup_test_db();
+        let template = template(&conn);
+        create_recurring_series_impl(&conn, &series(template.id, Recurrence::Daily)).unwrap();
+        let monday = midnight("2024-01-01");
+        materialize_recurring_entries_impl(&conn, &Utc, monday, 2).unwrap();
+
+        // Started at 08:00, before Monday's 09:00 standup
+        let eight = monday + 8 * 60 * MINUTE_MS;
+        let timer = timer::start_timer_impl(
+            &conn,
+            &TimerStart {
+                start_time: Some(eight),
+                label: "Email".to_string(),
+                color: None,
+                category_id: None,
+            },
+            eight + 10 * MINUTE_MS,
+        )
+        .unwrap();
+
+        // Later days are still filled while it runs
+        let report =
+            materialize_recurring_entries_impl(&conn, &Utc, eight + 20 * MINUTE_MS, 3).unwrap();
+        assert_eq!(report.created.len(), 1);
+        assert_eq!(report.skipped, 0);
+
+        // Edits to the running entry ignore the planned entries after it
+        let rename = TimeEntryUpdate {
+            start_time: None,
+            end_time: None,
+            label: Some("Inbox".to_string()),
+            color: None,
+            category_id: None,
+            notes: None,
+            project_id: None,
+        };
+        update_time_entry_impl(&conn, timer.entry.id, &rename).unwrap();
+        journal::undo(&conn).unwrap();
+        journal::redo(&conn).unwrap();
+        assert_eq!(
+            get_time_entry_by_id(&conn, timer.entry.id).unwrap().label,
+            "Inbox"
+        );
+
+        // Reaching the standup stops the timer there
+        assert!(!timer::heartbeat(&conn, eight + 70 * MINUTE_MS).unwrap());
+        let entries = get_time_entries_by_range_impl(&conn, eight, eight + 1).unwrap();
+        assert_eq!(entries[0].id, timer.entry.id);
+        assert_eq!(entries[0].end_time, eight + 60 * MINUTE_MS);
+    }
+
+    #[test]
+    fn test_update_and_delete_series() {
+        let conn = setup_test_db();
+        let template = template(&conn);
+        let created =
+            create_recurring_series_impl(&conn, &series(template.id, Recurrence::Daily)).unwrap();
+        let monday = midnight("2024-01-01");
+        materialize_recurring_entries_impl(&conn, &Utc, monday, 2).unwrap();
+
+        // From Tuesday noon on, the series runs at 10:00 instead
+        let now = monday + DAY_MS + 12 * 60 * MINUTE_MS;
+        let mut input = series(template.id, Recurrence::Daily);
+        input.start_minute = 10 * 60;
+        update_recurring_series_impl(&conn, created.id, &input, now).unwrap();
+        materialize_recurring_entries_impl(&conn, &Utc, now, 1).unwrap();
+        let starts: Vec<i64> = get_time_entries_by_range_impl(&conn, monday, i64::MAX)
+            .unwrap()
+            .into_iter()
+            .map(|e| (e.start_time - monday) % DAY_MS / (60 * MINUTE_MS))
+            .collect();
+        assert_eq!(starts, vec![9, 9, 10]);
+
+        delete_recurring_series_impl(&conn, created.id, true, now).unwrap();
+        assert_eq!(
+            get_time_entries_by_range_impl(&conn, monday, i64::MAX)
+                .unwrap()
+                .len(),
+            2
+        );
+        assert!(get_recurring_series_impl(&conn).unwrap().is_empty());
+        let error = delete_recurring_series_impl(&conn, created.id, false, now).unwrap_err();
+        assert_eq!(error.code, "recurring_series_not_found");
+    }
+}
@@ -0,0 +1,335 @@
+use crate::app_settings::RetentionSettings;
+use crate::data::error::db_err;
+use crate::data::{idle, process_segments, screenshot, window_activity, AppResult};
+use crate::types::RetentionReport;
+use rusqlite::{params, Connection};
+
+/// Rows deleted per write, so capture never waits long on the writer.
+pub const RETENTION_BATCH_SIZE: usize = 500;
+
+const DAY_MS: i64 = 24 * 60 * 60 * 1000;
+
+/// Runs one batch on the writer connection and returns how many rows it deleted.
+pub type BatchWriter<'a> =
+    &'a dyn Fn(&mut dyn FnMut(&Connection) -> AppResult<usize>) -> AppResult<usize>;
+
+/// Per-kind timestamps before which data is removed; `None` keeps that kind forever.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RetentionCutoffs {
+    pub process_segments: Option<i64>,
+    pub window_activity: Option<i64>,
+    pub screenshots: Option<i64>,
+    pub idle_periods: Option<i64>,
+}
+
+impl RetentionCutoffs {
+    pub fn from_settings(settings: &RetentionSettings, now: i64) -> Self {
+        let cutoff = |days: u32| (days > 0).then(|| now - i64::from(days) * DAY_MS);
+        Self {
+            process_segments: cutoff(settings.process_segments_days),
+            window_activity: cutoff(settings.window_activity_days),
+            screenshots: cutoff(settings.screenshots_days),
+            idle_periods: cutoff(settings.idle_periods_days),
+        }
+    }
+}
+
+/// Count what [`run_retention`] would remove without deleting anything.
+pub fn preview_retention(
+    conn: &Connection,
+    cutoffs: &RetentionCutoffs,
+    file_size: &dyn Fn(&str) -> u64,
+) -> AppResult<RetentionReport> {
+    let mut report = RetentionReport {
+        dry_run: true,
+        ..RetentionReport::default()
+    };
+
+    if let Some(cutoff) = cutoffs.process_segments {
+        report.process_segments = count_rows(
+            conn,
+            "SELECT COUNT(*) FROM process_segments WHERE end_time < ?1",
+            cutoff,
+        )?;
+    }
+    if let Some(cutoff) = cutoffs.window_activity {
+        report.window_activity = count_rows(
+            conn,
+            "SELECT COUNT(*) FROM window_activity WHERE timestamp < ?1",
+            cutoff,
+        )?;
+    }
+    if let Some(cutoff) = cutoffs.idle_periods {
+        report.idle_periods = count_rows(
+            conn,
+            "SELECT COUNT(*) FROM idle_periods WHERE end_time < ?1",
+            cutoff,
+        )?;
+    }
+    if let Some(cutoff) = cutoffs.screenshots {
+        let mut after_id = 0;
+        loop {
+            let batch =
+                screenshot::get_screenshots_before(conn, cutoff, after_id, RETENTION_BATCH_SIZE)?;
+            for (id, path) in &batch {
+                after_id = *id;
+                report.screenshots += 1;
+                report.screenshot_bytes += file_size(path);
+            }
+            if batch.len() < RETENTION_BATCH_SIZE {
+                break;
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+/// Delete everything older than `cutoffs`, one batch per `write` call.
+///
+/// Screenshots take two writes per batch: one selects the rows, the next
+/// deletes those whose files were removed in between, so file I/O never holds
+/// the writer. A file that cannot be deleted keeps its row so the next run
+/// retries it; a row left behind by a failed write finds its file already gone.
+pub fn run_retention(
+    cutoffs: &RetentionCutoffs,
+    write: BatchWriter,
+    remove_file: &dyn Fn(&str) -> Result<u64, String>,
+) -> AppResult<RetentionReport> {
+    let mut report = RetentionReport::default();
+
+    if let Some(cutoff) = cutoffs.process_segments {
+        report.process_segments = delete_in_batches(write, |conn| {
+            process_segments::delete_process_segments_before(conn, cutoff, RETENTION_BATCH_SIZE)
+        })?;
+    }
+    if let Some(cutoff) = cutoffs.window_activity {
+        report.window_activity = delete_in_batches(write, |conn| {
+            window_activity::delete_window_activities_before(conn, cutoff, RETENTION_BATCH_SIZE)
+        })?;
+    }
+    if let Some(cutoff) = cutoffs.idle_periods {
+        report.idle_periods = delete_in_batches(write, |conn| {
+            idle::delete_idle_periods_before(conn, cutoff, RETENTION_BATCH_SIZE)
+        })?;
+    }
+    if let Some(cutoff) = cutoffs.screenshots {
+        let mut after_id = 0;
+        loop {
+            let mut batch = Vec::new();
+            write(&mut |conn| {
+                batch = screenshot::get_screenshots_before(
+                    conn,
+                    cutoff,
+                    after_id,
+                    RETENTION_BATCH_SIZE,
+                )?;
+                Ok(batch.len())
+            })?;
+            let batch_len = batch.len();
+
+            let mut removed = Vec::with_capacity(batch_len);
+            for (id, path) in batch {
+                after_id = id;
+                match remove_file(&path) {
+                    Ok(bytes) => {
+                        removed.push(id);
+                        report.screenshot_bytes += bytes;
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to delete screenshot {}: {}", path, e);
+                        report.screenshot_files_failed.push(path);
+                    }
+                }
+            }
+            if !removed.is_empty() {
+                report.screenshots +=
+                    write(&mut |conn| screenshot::delete_screenshots(conn, &removed))?;
+            }
+            if batch_len < RETENTION_BATCH_SIZE {
+                break;
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+fn delete_in_batches<F>(write: BatchWriter, mut delete: F) -> AppResult<usize>
+where
+    F: FnMut(&Connection) -> AppResult<usize>,
+{
+    let mut total = 0;
+    loop {
+        let deleted = write(&mut delete)?;
+        total += deleted;
+        if deleted < RETENTION_BATCH_SIZE {
+            return Ok(total);
+        }
+    }
+}
+
+fn count_rows(conn: &Connection, sql: &str, cutoff: i64) -> AppResult<usize> {
+    let count: i64 = conn
+        .query_row(sql, params![cutoff], |row| row.get(0))
+        .map_err(db_err("Failed to count expired rows"))?;
+    Ok(count as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    fn setup_test_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::data::database::run_migrations(&conn).unwrap();
+        conn
+    }
+
+    fn count(conn: &Connection, table: &str) -> i64 {
+        conn.query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| {
+            row.get(0)
+        })
+        .unwrap()
+    }
+
+    fn seed(conn: &Connection, dir: &TempDir) {
+        for i in 0..1200 {
+            conn.execute(
+                "INSERT INTO window_activity (timestamp, window_title, process_name)
+                 VALUES (?1, 'Editor', 'code.exe')",
+                params![i * 1000],
+            )
+            .unwrap();
+        }
+        conn.execute_batch(
+            "INSERT INTO process_segments (start_time, end_time, process_name)
+                 VALUES (0, 5000, 'old.exe'), (2000000, 2005000, 'new.exe');
+             INSERT INTO idle_periods (start_time, end_time) VALUES (100, 200), (2000000, 2100000);",
+        )
+        .unwrap();
+
+        for (timestamp, name) in [(1000, "a.webp"), (2000, "b.webp"), (2000000, "c.webp")] {
+            let path = dir.path().join(name);
+            std::fs::write(&path, b"RIFF-image").unwrap();
+            crate::data::insert_screenshot(conn, timestamp, &path.to_string_lossy()).unwrap();
+        }
+    }
+
+    fn cutoffs(at: i64) -> RetentionCutoffs {
+        RetentionCutoffs {
+            process_segments: Some(at),
+            window_activity: Some(at),
+            screenshots: Some(at),
+            idle_periods: Some(at),
+        }
+    }
+
+    fn remove(stored: &str) -> Result<u64, String> {
+        let path = PathBuf::from(stored);
+        let size = std::fs::metadata(&path).map_err(|e| e.to_string())?.len();
+        std::fs::remove_file(&path).map_err(|e| e.to_string())?;
+        Ok(size)
+    }
+
+    #[test]
+    fn test_cutoffs_from_settings() {
+        let settings = RetentionSettings {
+            process_segments_days: 30,
+            window_activity_days: 0,
+            screenshots_days: 1,
+            idle_periods_days: 0,
+        };
+        let cutoffs = RetentionCutoffs::from_settings(&settings, 100 * DAY_MS);
+
+        assert_eq!(cutoffs.process_segments, Some(70 * DAY_MS));
+        assert_eq!(cutoffs.window_activity, None);
+        assert_eq!(cutoffs.screenshots, Some(99 * DAY_MS));
+        assert_eq!(cutoffs.idle_periods, None);
+    }
+
+    #[test]
+    fn test_preview_matches_run() {
+        let dir = TempDir::new().unwrap();
+        let conn = setup_test_db();
+        seed(&conn, &dir);
+
+        let preview = preview_retention(&conn, &cutoffs(1_000_000), &|path| {
+            std::fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+        })
+        .unwrap();
+        assert!(preview.dry_run);
+        assert_eq!(preview.window_activity, 1000);
+        assert_eq!(preview.screenshots, 2);
+        assert_eq!(preview.screenshot_bytes, 20);
+        // Preview leaves everything in place
+        assert_eq!(count(&conn, "window_activity"), 1200);
+        assert!(dir.path().join("a.webp").exists());
+
+        let batches = Cell::new(0);
+        let writing = Cell::new(false);
+        let write = |batch: &mut dyn FnMut(&Connection) -> AppResult<usize>| {
+            batches.set(batches.get() + 1);
+            writing.set(true);
+            let result = batch(&conn);
+            writing.set(false);
+            result
+        };
+        let report = run_retention(&cutoffs(1_000_000), &write, &|path| {
+            // Files are deleted without holding the writer
+            assert!(!writing.get());
+            remove(path)
+        })
+        .unwrap();
+
+        assert_eq!(
+            report,
+            RetentionReport {
+                dry_run: false,
+                ..preview
+            }
+        );
+        // 1000 window rows take three batches, screenshots a select and a
+        // delete, the other kinds one each
+        assert_eq!(batches.get(), 7);
+        assert_eq!(count(&conn, "window_activity"), 200);
+        assert_eq!(count(&conn, "process_segments"), 1);
+        assert_eq!(count(&conn, "idle_periods"), 1);
+        assert_eq!(count(&conn, "screenshots"), 1);
+        assert!(!dir.path().join("a.webp").exists());
+        assert!(dir.path().join("c.webp").exists());
+    }
+
+    #[test]
+    fn test_failed_file_keeps_row() {
+        let dir = TempDir::new().unwrap();
+        let conn = setup_test_db();
+        seed(&conn, &dir);
+        let locked = dir.path().join("b.webp").to_string_lossy().to_string();
+
+        let write = |batch: &mut dyn FnMut(&Connection) -> AppResult<usize>| batch(&conn);
+        let report = run_retention(
+            &RetentionCutoffs {
+                screenshots: Some(1_000_000),
+                ..RetentionCutoffs::default()
+            },
+            &write,
+            &|path| {
+                if path == locked {
+                    Err("file in use".to_string())
+                } else {
+                    remove(path)
+                }
+            },
+        )
+        .unwrap();
+
+        assert_eq!(report.screenshots, 1);
+        assert_eq!(report.screenshot_files_failed, vec![locked]);
+        assert_eq!(count(&conn, "screenshots"), 2);
+        assert_eq!(count(&conn, "window_activity"), 1200);
+    }
+}
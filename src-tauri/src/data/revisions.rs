@@ -0,0 +1,410 @@
+use crate::data::error::db_err;
+use crate::data::journal;
+use crate::data::time_entries::apply_time_entry_state;
+use crate::data::{AppError, AppResult};
+use crate::types::{RevisionSource, TimeEntryRevision, TimeEntryState};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::cell::Cell;
+
+/// Successive drag revisions of the same entry closer together than this
+/// are stored as one, so moving a block leaves a single history row.
+const DRAG_COALESCE_WINDOW_MS: i64 = 1500;
+
+thread_local! {
+    /// Source attributed to revisions recorded on this thread.
+    static CURRENT_SOURCE: Cell<RevisionSource> = const { Cell::new(RevisionSource::Manual) };
+}
+
+/// Run `f` with every revision it records attributed to `source`.
+pub fn with_source<R, F>(source: RevisionSource, f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    let previous = CURRENT_SOURCE.with(|s| s.replace(source));
+    let _guard = SourceGuard(previous);
+    f()
+}
+
+/// Restores the previous source when [`with_source`] returns or unwinds.
+struct SourceGuard(RevisionSource);
+
+impl Drop for SourceGuard {
+    fn drop(&mut self) {
+        CURRENT_SOURCE.with(|s| s.set(self.0));
+    }
+}
+
+/// Source attributed to changes recorded on this thread right now.
+pub fn current_source() -> RevisionSource {
+    CURRENT_SOURCE.with(Cell::get)
+}
+
+/// Append a revision for time entry `entry_id` going from `before` to `after`.
+///
+/// Both sides are row snapshots as produced by [`journal::snapshot`].
+pub fn record(
+    conn: &Connection,
+    entry_id: i64,
+    before: Option<&str>,
+    after: Option<&str>,
+) -> AppResult<()> {
+    if before == after {
+        return Ok(());
+    }
+
+    let source = current_source();
+    let action = action_for(
+        before.map(parse_state).transpose()?,
+        after.map(parse_state).transpose()?,
+    );
+    let now = chrono::Utc::now().timestamp_millis();
+
+    if source == RevisionSource::Drag
+        && action == "update"
+        && coalesce_drag(conn, entry_id, after, now)?
+    {
+        return Ok(());
+    }
+
+    conn.execute(
+        "INSERT INTO time_entry_revisions (entry_id, changed_at, action, source, before_json, after_json)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![entry_id, now, action, source.as_str(), before, after],
+    )
+    .map_err(db_err("Failed to record time entry revision"))?;
+
+    Ok(())
+}
+
+/// History of one time entry, newest first.
+pub fn get_time_entry_revisions_impl(
+    conn: &Connection,
+    entry_id: i64,
+) -> AppResult<Vec<TimeEntryRevision>> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, entry_id, changed_at, action, source, before_json, after_json
+             FROM time_entry_revisions
+             WHERE entry_id = ?1
+             ORDER BY changed_at DESC, id DESC",
+        )
+        .map_err(db_err("Failed to prepare revisions query"))?;
+
+    let rows = stmt
+        .query_map([entry_id], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, i64>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, String>(4)?,
+                row.get::<_, Option<String>>(5)?,
+                row.get::<_, Option<String>>(6)?,
+            ))
+        })
+        .map_err(db_err("Failed to query revisions"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db_err("Failed to read revisions"))?;
+
+    rows.into_iter()
+        .map(
+            |(id, entry_id, changed_at, action, source, before, after)| {
+                Ok(TimeEntryRevision {
+                    id,
+                    entry_id,
+                    changed_at,
+                    action,
+                    source: parse_source(&source)?,
+                    before: before.as_deref().map(parse_state).transpose()?,
+                    after: after.as_deref().map(parse_state).transpose()?,
+                })
+            },
+        )
+        .collect()
+}
+
+/// Bring a time entry back to the state it had right after `revision_id`.
+///
+/// Goes through the usual validation, so a revert that would overlap another
+/// entry is rejected. The revert itself is recorded as a new revision and is
+/// undoable like any other change.
+pub fn revert_time_entry_to_revision_impl(
+    conn: &Connection,
+    entry_id: i64,
+    revision_id: i64,
+) -> AppResult<TimeEntryState> {
+    let target: Option<Option<String>> = conn
+        .query_row(
+            "SELECT after_json FROM time_entry_revisions WHERE id = ?1 AND entry_id = ?2",
+            params![revision_id, entry_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(db_err("Failed to fetch revision"))?;
+
+    let Some(target) = target else {
+        return Err(AppError::not_found(
+            "revision_not_found",
+            format!(
+                "Revision {} of time entry {} not found",
+                revision_id, entry_id
+            ),
+        ));
+    };
+    let Some(target) = target else {
+        return Err(AppError::validation(
+            "revision_without_state",
+            format!("Revision {} has no state to revert to", revision_id),
+        ));
+    };
+    let state = parse_state(&target)?;
+
+    let tx = conn
+        .unchecked_transaction()
+        .map_err(db_err("Failed to start transaction"))?;
+
+    with_source(RevisionSource::Revert, || {
+        journal::grouped(&tx, || apply_time_entry_state(&tx, entry_id, &state))
+    })?;
+
+    tx.commit().map_err(db_err("Failed to commit revert"))?;
+
+    Ok(state)
+}
+
+fn action_for(before: Option<TimeEntryState>, after: Option<TimeEntryState>) -> &'static str {
+    match (before, after) {
+        (None, _) => "create",
+        (Some(_), None) => "delete",
+        (Some(before), Some(after)) => match (before.deleted_at, after.deleted_at) {
+            (None, Some(_)) => "delete",
+            (Some(_), None) => "restore",
+            _ => "update",
+        },
+    }
+}
+
+/// Fold a drag step into the entry's latest revision if that was a recent drag too.
+fn coalesce_drag(
+    conn: &Connection,
+    entry_id: i64,
+    after: Option<&str>,
+    now: i64,
+) -> AppResult<bool> {
+    let latest: Option<(i64, String, String, i64)> = conn
+        .query_row(
+            "SELECT id, action, source, changed_at FROM time_entry_revisions
+             WHERE entry_id = ?1
+             ORDER BY id DESC
+             LIMIT 1",
+            [entry_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )
+        .optional()
+        .map_err(db_err("Failed to read revisions"))?;
+
+    let Some((id, action, source, changed_at)) = latest else {
+        return Ok(false);
+    };
+    if action != "update"
+        || source != RevisionSource::Drag.as_str()
+        || now - changed_at > DRAG_COALESCE_WINDOW_MS
+    {
+        return Ok(false);
+    }
+
+    conn.execute(
+        "UPDATE time_entry_revisions SET after_json = ?1, changed_at = ?2 WHERE id = ?3",
+        params![after, now, id],
+    )
+    .map_err(db_err("Failed to update revision"))?;
+    Ok(true)
+}
+
+fn parse_state(json: &str) -> AppResult<TimeEntryState> {
+    serde_json::from_str(json)
+        .map_err(|e| format!("Failed to parse time entry revision: {}", e).into())
+}
+
+fn parse_source(source: &str) -> AppResult<RevisionSource> {
+    serde_json::from_value(serde_json::Value::String(source.to_string()))
+        .map_err(|e| format!("Unknown revision source '{}': {}", source, e).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::database::{enable_foreign_keys, run_migrations};
+    use crate::data::time_entries::{
+        create_time_entry_impl, delete_time_entry_impl, restore_time_entry_impl,
+        update_time_entry_impl,
+    };
+    use crate::types::{TimeEntryInput, TimeEntryUpdate};
+
+    fn setup_test_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        enable_foreign_keys(&conn).unwrap();
+        run_migrations(&conn).unwrap();
+        conn
+    }
+
+    fn create(conn: &Connection, start_time: i64, end_time: i64, label: &str) -> i64 {
+        create_time_entry_impl(
+            conn,
+            &TimeEntryInput {
+                start_time,
+                end_time,
+                label: label.to_string(),
+                color: None,
+                category_id: None,
+                notes: None,
+                project_id: None,
+            },
+        )
+        .unwrap()
+        .id
+    }
+
+    fn update(conn: &Connection, id: i64, range: Option<(i64, i64)>, label: Option<&str>) {
+        let updates = TimeEntryUpdate {
+            start_time: range.map(|r| r.0),
+            end_time: range.map(|r| r.1),
+            label: label.map(str::to_string),
+            color: None,
+            category_id: None,
+            notes: None,
+            project_id: None,
+        };
+        update_time_entry_impl(conn, id, &updates).unwrap();
+    }
+
+    fn actions(conn: &Connection, id: i64) -> Vec<(String, RevisionSource)> {
+        get_time_entry_revisions_impl(conn, id)
+            .unwrap()
+            .into_iter()
+            .map(|r| (r.action, r.source))
+            .collect()
+    }
+
+    #[test]
+    fn test_history_records_each_change() {
+        let conn = setup_test_db();
+        let id = create(&conn, 1000, 2000, "Draft");
+        update(&conn, id, None, Some("Final"));
+        delete_time_entry_impl(&conn, id).unwrap();
+        restore_time_entry_impl(&conn, id).unwrap();
+
+        assert_eq!(
+            actions(&conn, id),
+            vec![
+                ("restore".to_string(), RevisionSource::Manual),
+                ("delete".to_string(), RevisionSource::Manual),
+                ("update".to_string(), RevisionSource::Manual),
+                ("create".to_string(), RevisionSource::Manual),
+            ]
+        );
+
+        let revisions = get_time_entry_revisions_impl(&conn, id).unwrap();
+        let rename = &revisions[2];
+        assert_eq!(rename.before.as_ref().unwrap().label, "Draft");
+        assert_eq!(rename.after.as_ref().unwrap().label, "Final");
+    }
+
+    #[test]
+    fn test_drag_updates_collapse_into_one_revision() {
+        let conn = setup_test_db();
+        let id = create(&conn, 1000, 2000, "Dragged");
+
+        with_source(RevisionSource::Drag, || {
+            for offset in 1..=5 {
+                update(
+                    &conn,
+                    id,
+                    Some((1000 + offset * 100, 2000 + offset * 100)),
+                    None,
+                );
+            }
+        });
+
+        let revisions = get_time_entry_revisions_impl(&conn, id).unwrap();
+        assert_eq!(revisions.len(), 2);
+        assert_eq!(revisions[0].source, RevisionSource::Drag);
+        assert_eq!(revisions[0].before.as_ref().unwrap().start_time, 1000);
+        assert_eq!(revisions[0].after.as_ref().unwrap().start_time, 1500);
+    }
+
+    #[test]
+    fn test_revert_to_revision() {
+        let conn = setup_test_db();
+        let id = create(&conn, 1000, 2000, "Original");
+        update(&conn, id, Some((1500, 2500)), Some("Changed"));
+        delete_time_entry_impl(&conn, id).unwrap();
+
+        let created = get_time_entry_revisions_impl(&conn, id)
+            .unwrap()
+            .into_iter()
+            .find(|r| r.action == "create")
+            .unwrap();
+
+        let state = revert_time_entry_to_revision_impl(&conn, id, created.id).unwrap();
+        assert_eq!(state.label, "Original");
+
+        let (start_time, label, deleted_at): (i64, String, Option<i64>) = conn
+            .query_row(
+                "SELECT start_time, label, deleted_at FROM time_entries WHERE id = ?1",
+                [id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!(
+            (start_time, label.as_str(), deleted_at),
+            (1000, "Original", None)
+        );
+        assert_eq!(
+            actions(&conn, id)[0],
+            ("restore".to_string(), RevisionSource::Revert)
+        );
+
+        // The revert is a single undo step
+        journal::undo(&conn).unwrap().unwrap();
+        let deleted_at: Option<i64> = conn
+            .query_row(
+                "SELECT deleted_at FROM time_entries WHERE id = ?1",
+                [id],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert!(deleted_at.is_some());
+        assert_eq!(
+            actions(&conn, id)[0],
+            ("delete".to_string(), RevisionSource::Undo)
+        );
+    }
+
+    #[test]
+    fn test_revert_rejects_overlap_and_unknown_revision() {
+        let conn = setup_test_db();
+        let id = create(&conn, 1000, 2000, "Moved");
+        update(&conn, id, Some((5000, 6000)), None);
+        create(&conn, 1500, 2500, "Blocker");
+
+        let created = get_time_entry_revisions_impl(&conn, id)
+            .unwrap()
+            .pop()
+            .unwrap();
+        let error = revert_time_entry_to_revision_impl(&conn, id, created.id).unwrap_err();
+        assert_eq!(error.code, "time_entry_overlap");
+
+        let error = revert_time_entry_to_revision_impl(&conn, id, 9999).unwrap_err();
+        assert_eq!(error.code, "revision_not_found");
+    }
+
+    #[test]
+    fn test_source_resets_when_closure_panics() {
+        let result = std::panic::catch_unwind(|| {
+            with_source(RevisionSource::Import, || panic!("boom"));
+        });
+        assert!(result.is_err());
+        assert_eq!(CURRENT_SOURCE.with(Cell::get), RevisionSource::Manual);
+    }
+}
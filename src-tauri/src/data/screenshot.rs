@@ -1,3 +1,4 @@
+use crate::data::error::{db_err, io_err};
 use crate::data::AppResult;
 use rusqlite::{Connection, OptionalExtension};
 use std::path::Path;
@@ -17,7 +18,7 @@ pub fn save_screenshot(
 ) -> AppResult<std::path::PathBuf> {
     let screenshot_dir = get_screenshot_path(base_path);
     std::fs::create_dir_all(&screenshot_dir)
-        .map_err(|e| format!("Failed to create screenshots directory: {}", e))?;
+        .map_err(io_err("Failed to create screenshots directory"))?;
 
     let filename = format!("{}.png", timestamp);
     let file_path = screenshot_dir.join(&filename);
@@ -37,8 +38,7 @@ pub fn save_screenshot(
         file_path
     };
 
-    std::fs::write(&file_path, data)
-        .map_err(|e| format!("Failed to write screenshot file: {}", e))?;
+    std::fs::write(&file_path, data).map_err(io_err("Failed to write screenshot file"))?;
 
     Ok(file_path)
 }
@@ -51,7 +51,7 @@ pub fn insert_screenshot(conn: &Connection, timestamp: i64, file_path: &str) ->
         "INSERT INTO screenshots (timestamp, file_path, day_id) VALUES (?1, ?2, ?3)",
         rusqlite::params![timestamp, file_path, day_id],
     )
-    .map_err(|e| format!("Failed to insert screenshot: {}", e))?;
+    .map_err(db_err("Failed to insert screenshot"))?;
 
     Ok(conn.last_insert_rowid())
 }
@@ -72,14 +72,14 @@ pub fn get_screenshot_near_time(
              ORDER BY ABS(timestamp - ?3)
              LIMIT 1",
         )
-        .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        .map_err(db_err("Failed to prepare query"))?;
 
     let result = stmt
         .query_row(rusqlite::params![start_time, end_time, timestamp], |row| {
             row.get::<_, String>(0)
         })
         .optional()
-        .map_err(|e| format!("Failed to query screenshot: {}", e))?;
+        .map_err(db_err("Failed to query screenshot"))?;
 
     Ok(result)
 }
@@ -91,15 +91,15 @@ pub fn get_screenshots_for_day(conn: &Connection, day_id: i32) -> AppResult<Vec<
         .prepare(
             "SELECT timestamp, file_path FROM screenshots WHERE day_id = ?1 ORDER BY timestamp",
         )
-        .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        .map_err(db_err("Failed to prepare query"))?;
 
     let screenshots = stmt
         .query_map(rusqlite::params![day_id], |row| {
             Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
         })
-        .map_err(|e| format!("Failed to query screenshots: {}", e))?
+        .map_err(db_err("Failed to query screenshots"))?
         .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| format!("Failed to collect screenshots: {}", e))?;
+        .map_err(db_err("Failed to collect screenshots"))?;
 
     Ok(screenshots)
 }
@@ -116,21 +116,69 @@ pub fn get_screenshot_timestamps_for_day(
              WHERE timestamp >= ?1 AND timestamp < ?2
              ORDER BY timestamp",
         )
-        .map_err(|e| format!("Failed to prepare screenshot timestamps query: {}", e))?;
+        .map_err(db_err("Failed to prepare screenshot timestamps query"))?;
 
     let rows = stmt
         .query_map(rusqlite::params![start_of_day, end_of_day], |row| {
             row.get::<_, i64>(0)
         })
-        .map_err(|e| format!("Failed to query screenshot timestamps: {}", e))?;
+        .map_err(db_err("Failed to query screenshot timestamps"))?;
 
     let timestamps = rows
         .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| format!("Failed to collect screenshot timestamps: {}", e))?;
+        .map_err(db_err("Failed to collect screenshot timestamps"))?;
 
     Ok(timestamps)
 }
 
+/// Up to `limit` screenshots taken before `cutoff_timestamp` with an id above
+/// `after_id`, as `(id, file_path)` in id order.
+pub fn get_screenshots_before(
+    conn: &Connection,
+    cutoff_timestamp: i64,
+    after_id: i64,
+    limit: usize,
+) -> AppResult<Vec<(i64, String)>> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, file_path FROM screenshots
+             WHERE timestamp < ?1 AND id > ?2
+             ORDER BY id
+             LIMIT ?3",
+        )
+        .map_err(db_err("Failed to prepare old screenshots query"))?;
+
+    let screenshots = stmt
+        .query_map(
+            rusqlite::params![cutoff_timestamp, after_id, limit as i64],
+            |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)),
+        )
+        .map_err(db_err("Failed to query old screenshots"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db_err("Failed to collect old screenshots"))?;
+
+    Ok(screenshots)
+}
+
+/// Delete screenshot records by id. Does not touch the image files.
+pub fn delete_screenshots(conn: &Connection, ids: &[i64]) -> AppResult<usize> {
+    let tx = conn
+        .unchecked_transaction()
+        .map_err(db_err("Failed to start transaction"))?;
+
+    let mut deleted = 0;
+    for id in ids {
+        deleted += tx
+            .execute("DELETE FROM screenshots WHERE id = ?1", rusqlite::params![id])
+            .map_err(db_err("Failed to delete screenshot"))?;
+    }
+
+    tx.commit()
+        .map_err(db_err("Failed to commit transaction"))?;
+
+    Ok(deleted)
+}
+
 /// Convert timestamp (milliseconds) to day_id (YYYYMMDD integer)
 fn timestamp_to_day_id(timestamp: i64) -> i32 {
     use chrono::{DateTime, Datelike, Utc};
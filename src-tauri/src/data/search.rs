@@ -1,43 +1,18 @@
+use crate::data::error::db_err;
+use crate::data::tags;
+use crate::data::{AppError, AppResult};
 use rusqlite::Connection;
 
-/// Search activities by keyword
+/// Search activities by keyword, limited to time entries carrying every tag in
+/// `tag_ids` when any are given. Time entries match on their label or notes.
+///
+/// Window activity has no tags, so a tag filter leaves only time entries.
 pub fn search_activities_impl(
     conn: &Connection,
     query: &str,
-) -> Result<Vec<crate::types::SearchResult>, String> {
-    if query.len() < 2 {
-        return Err("Query must be at least 2 characters".to_string());
-    }
-
-    let search_pattern = format!("%{}%", query);
-
-    let mut stmt = conn.prepare(
-        "SELECT 'time_entry' as type, start_time as timestamp, label as title, NULL as process_name
-         FROM time_entries WHERE label LIKE ?
-         UNION ALL
-         SELECT 'window_activity' as type, timestamp, window_title as title, process_name
-         FROM window_activity WHERE window_title LIKE ?
-         ORDER BY timestamp DESC LIMIT 100"
-    )
-    .map_err(|e| format!("Failed to prepare search query: {}", e))?;
-
-    let result_iter = stmt
-        .query_map(rusqlite::params![&search_pattern, &search_pattern], |row| {
-            Ok(crate::types::SearchResult {
-                r#type: row.get(0)?,
-                timestamp: row.get(1)?,
-                title: row.get(2)?,
-                process_name: row.get(3)?,
-            })
-        })
-        .map_err(|e| format!("Failed to execute search: {}", e))?;
-
-    let mut results = Vec::new();
-    for result in result_iter {
-        results.push(result.map_err(|e| format!("Failed to map search result: {}", e))?);
-    }
-
-    Ok(results)
+    tag_ids: &[i64],
+) -> AppResult<Vec<crate::types::SearchResult>> {
+    search(conn, query, None, tag_ids)
 }
 
 /// Search activities by keyword within a specific date range
@@ -46,25 +21,72 @@ pub fn search_activities_by_date_impl(
     query: &str,
     start_of_day: i64,
     end_of_day: i64,
-) -> Result<Vec<crate::types::SearchResult>, String> {
+    tag_ids: &[i64],
+) -> AppResult<Vec<crate::types::SearchResult>> {
+    search(conn, query, Some((start_of_day, end_of_day)), tag_ids)
+}
+
+fn search(
+    conn: &Connection,
+    query: &str,
+    range: Option<(i64, i64)>,
+    tag_ids: &[i64],
+) -> AppResult<Vec<crate::types::SearchResult>> {
     if query.len() < 2 {
-        return Err("Query must be at least 2 characters".to_string());
+        return Err(query_too_short());
     }
 
     let search_pattern = format!("%{}%", query);
+    let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+
+    let mut entry_conditions = vec![
+        "e.deleted_at IS NULL".to_string(),
+        "(e.label LIKE ? OR e.notes LIKE ?)".to_string(),
+    ];
+    params.push(Box::new(search_pattern.clone()));
+    params.push(Box::new(search_pattern.clone()));
+    if let Some((start, end)) = range {
+        entry_conditions.push("e.start_time >= ? AND e.start_time < ?".to_string());
+        params.push(Box::new(start));
+        params.push(Box::new(end));
+    }
+    if !tag_ids.is_empty() {
+        let (condition, tag_ids) = tags::has_all_tags_condition("e.id", tag_ids);
+        entry_conditions.push(condition);
+        params.extend(
+            tag_ids
+                .into_iter()
+                .map(|id| Box::new(id) as Box<dyn rusqlite::ToSql>),
+        );
+    }
 
-    let mut stmt = conn.prepare(
-        "SELECT 'time_entry' as type, start_time as timestamp, label as title, NULL as process_name
-         FROM time_entries WHERE label LIKE ? AND start_time >= ? AND start_time < ?
+    let mut sql = format!(
+        "SELECT 'time_entry' as type, e.start_time as timestamp, e.label as title, NULL as process_name
+         FROM time_entries e WHERE {}",
+        entry_conditions.join(" AND ")
+    );
+    if tag_ids.is_empty() {
+        sql.push_str(
+            "
          UNION ALL
          SELECT 'window_activity' as type, timestamp, window_title as title, process_name
-         FROM window_activity WHERE window_title LIKE ? AND timestamp >= ? AND timestamp < ?
-         ORDER BY timestamp DESC LIMIT 100"
-    )
-    .map_err(|e| format!("Failed to prepare search query: {}", e))?;
+         FROM window_activity WHERE window_title LIKE ?",
+        );
+        params.push(Box::new(search_pattern));
+        if let Some((start, end)) = range {
+            sql.push_str(" AND timestamp >= ? AND timestamp < ?");
+            params.push(Box::new(start));
+            params.push(Box::new(end));
+        }
+    }
+    sql.push_str("\n         ORDER BY timestamp DESC LIMIT 100");
+
+    let mut stmt = conn
+        .prepare(&sql)
+        .map_err(db_err("Failed to prepare search query"))?;
 
     let result_iter = stmt
-        .query_map(rusqlite::params![&search_pattern, start_of_day, end_of_day, &search_pattern, start_of_day, end_of_day], |row| {
+        .query_map(rusqlite::params_from_iter(params.iter().map(|p| p.as_ref())), |row| {
             Ok(crate::types::SearchResult {
                 r#type: row.get(0)?,
                 timestamp: row.get(1)?,
@@ -72,42 +94,45 @@ pub fn search_activities_by_date_impl(
                 process_name: row.get(3)?,
             })
         })
-        .map_err(|e| format!("Failed to execute search: {}", e))?;
+        .map_err(db_err("Failed to execute search"))?;
 
     let mut results = Vec::new();
     for result in result_iter {
-        results.push(result.map_err(|e| format!("Failed to map search result: {}", e))?);
+        results.push(result.map_err(db_err("Failed to map search result"))?);
     }
 
     Ok(results)
 }
 
+fn query_too_short() -> AppError {
+    AppError::validation("query_too_short", "Query must be at least 2 characters")
+        .with_field("query")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     fn setup_test_db() -> Connection {
         let conn = Connection::open_in_memory().unwrap();
-        conn.execute_batch(include_str!("migrations/V1__initial_schema.sql"))
-            .unwrap();
+        crate::data::database::run_migrations(&conn).unwrap();
         conn
     }
 
     #[test]
     fn test_search_minimum_length() {
         let conn = setup_test_db();
-        let result = search_activities_impl(&conn, "a");
+        let result = search_activities_impl(&conn, "a", &[]);
         assert!(result.is_err());
-        assert_eq!(
-            result.unwrap_err(),
-            "Query must be at least 2 characters".to_string()
-        );
+        let error = result.unwrap_err();
+        assert_eq!(error.code, "query_too_short");
+        assert_eq!(error.message, "Query must be at least 2 characters");
     }
 
     #[test]
     fn test_search_empty_results() {
         let conn = setup_test_db();
-        let result = search_activities_impl(&conn, "nothing").unwrap();
+        let result = search_activities_impl(&conn, "nothing", &[]).unwrap();
         assert!(result.is_empty());
     }
 
@@ -121,7 +146,7 @@ mod tests {
         )
         .unwrap();
 
-        let result = search_activities_impl(&conn, "project").unwrap();
+        let result = search_activities_impl(&conn, "project", &[]).unwrap();
 
         assert_eq!(result.len(), 1);
         assert_eq!(result[0].r#type, "time_entry");
@@ -139,7 +164,7 @@ mod tests {
         )
         .unwrap();
 
-        let result = search_activities_impl(&conn, "VS Code").unwrap();
+        let result = search_activities_impl(&conn, "VS Code", &[]).unwrap();
 
         assert_eq!(result.len(), 1);
         assert_eq!(result[0].r#type, "window_activity");
@@ -163,7 +188,7 @@ mod tests {
         )
         .unwrap();
 
-        let result = search_activities_impl(&conn, "Code").unwrap();
+        let result = search_activities_impl(&conn, "Code", &[]).unwrap();
 
         assert_eq!(result.len(), 2);
     }
@@ -178,7 +203,7 @@ mod tests {
         )
         .unwrap();
 
-        let result = search_activities_impl(&conn, "meeting").unwrap();
+        let result = search_activities_impl(&conn, "meeting", &[]).unwrap();
         assert_eq!(result.len(), 1);
     }
 
@@ -198,10 +223,88 @@ mod tests {
         )
         .unwrap();
 
-        let result = search_activities_impl(&conn, "task").unwrap();
+        let result = search_activities_impl(&conn, "task", &[]).unwrap();
 
         assert_eq!(result.len(), 2);
         assert_eq!(result[0].title, "Second task"); // newest first
         assert_eq!(result[1].title, "First task");
     }
+
+    #[test]
+    fn test_search_skips_trashed_entries() {
+        let conn = setup_test_db();
+
+        conn.execute(
+            "INSERT INTO time_entries (start_time, end_time, label, deleted_at) VALUES (?1, ?2, ?3, ?4)",
+            rusqlite::params![1000, 2000, "Deleted project", 5000],
+        )
+        .unwrap();
+
+        assert!(search_activities_impl(&conn, "project", &[]).unwrap().is_empty());
+        assert!(search_activities_by_date_impl(&conn, "project", 0, 10000, &[])
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_search_matches_notes() {
+        let conn = setup_test_db();
+
+        conn.execute(
+            "INSERT INTO time_entries (start_time, end_time, label, notes) VALUES (?1, ?2, ?3, ?4)",
+            rusqlite::params![1000, 2000, "Deep work", "- Fixed the **flaky** upload test"],
+        )
+        .unwrap();
+
+        let result = search_activities_impl(&conn, "flaky", &[]).unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].title, "Deep work");
+    }
+
+    #[test]
+    fn test_search_filters_by_tags() {
+        let conn = setup_test_db();
+        let tag = |name: &str| {
+            tags::create_tag_impl(
+                &conn,
+                &crate::types::TagInput {
+                    name: name.to_string(),
+                    color: None,
+                },
+            )
+            .unwrap()
+        };
+        let billable = tag("Billable");
+        let meeting = tag("Meeting");
+
+        for (start, label) in [(1000, "Project sync"), (3000, "Project work")] {
+            conn.execute(
+                "INSERT INTO time_entries (start_time, end_time, label) VALUES (?1, ?2, ?3)",
+                rusqlite::params![start, start + 1000, label],
+            )
+            .unwrap();
+        }
+        conn.execute(
+            "INSERT INTO window_activity (timestamp, window_title, process_name) VALUES (?1, ?2, ?3)",
+            rusqlite::params![1500, "project.md", "code.exe"],
+        )
+        .unwrap();
+        tags::set_time_entry_tags_impl(&conn, 1, &[billable.id, meeting.id]).unwrap();
+        tags::set_time_entry_tags_impl(&conn, 2, &[billable.id]).unwrap();
+
+        let titles = |results: Vec<crate::types::SearchResult>| -> Vec<String> {
+            results.into_iter().map(|r| r.title).collect()
+        };
+        let search = |tag_ids: &[i64]| search_activities_impl(&conn, "project", tag_ids).unwrap();
+        assert_eq!(search(&[]).len(), 3);
+        assert_eq!(titles(search(&[billable.id])), vec!["Project work", "Project sync"]);
+        assert_eq!(titles(search(&[billable.id, meeting.id])), vec!["Project sync"]);
+        assert_eq!(
+            titles(search(&[meeting.id, billable.id, meeting.id])),
+            vec!["Project sync"]
+        );
+        let in_range =
+            search_activities_by_date_impl(&conn, "project", 2000, 5000, &[billable.id]).unwrap();
+        assert_eq!(titles(in_range), vec!["Project work"]);
+    }
 }
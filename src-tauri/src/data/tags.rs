@@ -0,0 +1,438 @@
+use crate::data::error::{db_err, ErrorKind};
+use crate::data::time_entries::get_time_entry_by_id;
+use crate::data::{AppError, AppResult};
+use crate::types::{Tag, TagInput, TagTotal, TimeEntry, TimeEntryTag};
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension};
+
+pub fn get_tags_impl(conn: &Connection) -> AppResult<Vec<Tag>> {
+    let mut stmt = conn
+        .prepare("SELECT id, name, color FROM tags ORDER BY name")
+        .map_err(db_err("Failed to prepare tags query"))?;
+
+    let tags = stmt
+        .query_map([], map_tag)
+        .map_err(db_err("Failed to query tags"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db_err("Failed to map tag"))?;
+
+    Ok(tags)
+}
+
+pub fn create_tag_impl(conn: &Connection, tag: &TagInput) -> AppResult<Tag> {
+    let name = validate_name(conn, tag, None)?;
+
+    conn.execute(
+        "INSERT INTO tags (name, color) VALUES (?, ?)",
+        params![name, tag.color],
+    )
+    .map_err(db_err("Failed to insert tag"))?;
+
+    Ok(Tag {
+        id: conn.last_insert_rowid(),
+        name,
+        color: tag.color.clone(),
+    })
+}
+
+pub fn update_tag_impl(conn: &Connection, id: i64, tag: &TagInput) -> AppResult<Tag> {
+    let name = validate_name(conn, tag, Some(id))?;
+
+    let rows_affected = conn
+        .execute(
+            "UPDATE tags SET name = ?1, color = ?2 WHERE id = ?3",
+            params![name, tag.color, id],
+        )
+        .map_err(db_err("Failed to update tag"))?;
+
+    if rows_affected == 0 {
+        return Err(tag_not_found(id));
+    }
+
+    Ok(Tag {
+        id,
+        name,
+        color: tag.color.clone(),
+    })
+}
+
+/// Removes the tag from every entry carrying it.
+pub fn delete_tag_impl(conn: &Connection, id: i64) -> AppResult<()> {
+    let rows_affected = conn
+        .execute("DELETE FROM tags WHERE id = ?", params![id])
+        .map_err(db_err("Failed to delete tag"))?;
+
+    if rows_affected == 0 {
+        return Err(tag_not_found(id));
+    }
+
+    Ok(())
+}
+
+pub fn get_time_entry_tags_impl(conn: &Connection, entry_id: i64) -> AppResult<Vec<Tag>> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT t.id, t.name, t.color FROM tags t
+             JOIN time_entry_tags et ON et.tag_id = t.id
+             WHERE et.entry_id = ?
+             ORDER BY t.name",
+        )
+        .map_err(db_err("Failed to prepare entry tags query"))?;
+
+    let tags = stmt
+        .query_map(params![entry_id], map_tag)
+        .map_err(db_err("Failed to query entry tags"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db_err("Failed to map tag"))?;
+
+    Ok(tags)
+}
+
+/// Replace the tags on entry `entry_id` with `tag_ids`.
+pub fn set_time_entry_tags_impl(
+    conn: &Connection,
+    entry_id: i64,
+    tag_ids: &[i64],
+) -> AppResult<Vec<Tag>> {
+    get_time_entry_by_id(conn, entry_id)?;
+    for &tag_id in tag_ids {
+        ensure_tag_exists(conn, tag_id)?;
+    }
+
+    let tx = conn
+        .unchecked_transaction()
+        .map_err(db_err("Failed to start transaction"))?;
+    tx.execute(
+        "DELETE FROM time_entry_tags WHERE entry_id = ?",
+        params![entry_id],
+    )
+    .map_err(db_err("Failed to clear entry tags"))?;
+    for &tag_id in tag_ids {
+        tx.execute(
+            "INSERT OR IGNORE INTO time_entry_tags (entry_id, tag_id) VALUES (?, ?)",
+            params![entry_id, tag_id],
+        )
+        .map_err(db_err("Failed to tag entry"))?;
+    }
+    tx.commit().map_err(db_err("Failed to commit entry tags"))?;
+
+    get_time_entry_tags_impl(conn, entry_id)
+}
+
+/// Give entry `to_id` the same tags as `from_id`, e.g. the new half of a split.
+pub fn copy_time_entry_tags(conn: &Connection, from_id: i64, to_id: i64) -> AppResult<()> {
+    conn.execute(
+        "INSERT OR IGNORE INTO time_entry_tags (entry_id, tag_id)
+         SELECT ?2, tag_id FROM time_entry_tags WHERE entry_id = ?1",
+        params![from_id, to_id],
+    )
+    .map_err(db_err("Failed to copy entry tags"))?;
+    Ok(())
+}
+
+/// Tag assignments of live entries overlapping `[start_time, end_time)`.
+pub fn get_time_entry_tags_by_range_impl(
+    conn: &Connection,
+    start_time: i64,
+    end_time: i64,
+) -> AppResult<Vec<TimeEntryTag>> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT et.entry_id, et.tag_id FROM time_entry_tags et
+             JOIN time_entries e ON e.id = et.entry_id
+             WHERE e.deleted_at IS NULL AND e.start_time < ? AND e.end_time > ?
+             ORDER BY e.start_time, et.tag_id",
+        )
+        .map_err(db_err("Failed to prepare entry tags query"))?;
+
+    let assignments = stmt
+        .query_map(params![end_time, start_time], |row| {
+            Ok(TimeEntryTag {
+                entry_id: row.get(0)?,
+                tag_id: row.get(1)?,
+            })
+        })
+        .map_err(db_err("Failed to query entry tags"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db_err("Failed to map entry tag"))?;
+
+    Ok(assignments)
+}
+
+/// Entries overlapping the range that carry every tag in `tag_ids`.
+pub fn get_time_entries_by_range_with_tags_impl(
+    conn: &Connection,
+    start_time: i64,
+    end_time: i64,
+    tag_ids: &[i64],
+) -> AppResult<Vec<TimeEntry>> {
+    if end_time <= start_time {
+        return Err(AppError::validation(
+            "invalid_time_range",
+            "end_time must be greater than start_time",
+        )
+        .with_field("end_time"));
+    }
+
+    let (tags_condition, tag_ids) = has_all_tags_condition("e.id", tag_ids);
+    let sql = format!(
+        "SELECT e.id, e.start_time, e.end_time, e.label, e.color, e.category_id, e.notes, e.project_id
+         FROM time_entries e
+         WHERE e.deleted_at IS NULL AND e.start_time < ? AND e.end_time > ? AND {}
+         ORDER BY e.start_time",
+        tags_condition
+    );
+    let mut stmt = conn
+        .prepare(&sql)
+        .map_err(db_err("Failed to prepare tagged range query"))?;
+
+    let mut params: Vec<i64> = vec![end_time, start_time];
+    params.extend(tag_ids);
+    let entries = stmt
+        .query_map(params_from_iter(params), |row| {
+            Ok(TimeEntry {
+                id: row.get(0)?,
+                start_time: row.get(1)?,
+                end_time: row.get(2)?,
+                label: row.get(3)?,
+                color: row.get(4)?,
+                category_id: row.get(5)?,
+                notes: row.get(6)?,
+                project_id: row.get(7)?,
+            })
+        })
+        .map_err(db_err("Failed to query tagged time entries"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db_err("Failed to map row"))?;
+
+    Ok(entries)
+}
+
+/// Tracked time per tag within `[start_time, end_time)`, most used first.
+///
+/// Entries crossing the range edges count only their part inside it. An entry
+/// with several tags counts toward each of them.
+pub fn get_tag_totals_by_range_impl(
+    conn: &Connection,
+    start_time: i64,
+    end_time: i64,
+) -> AppResult<Vec<TagTotal>> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT t.id, t.name, t.color,
+                    SUM(MIN(e.end_time, ?2) - MAX(e.start_time, ?1)) AS duration_ms,
+                    COUNT(*)
+             FROM tags t
+             JOIN time_entry_tags et ON et.tag_id = t.id
+             JOIN time_entries e ON e.id = et.entry_id
+             WHERE e.deleted_at IS NULL AND e.start_time < ?2 AND e.end_time > ?1
+             GROUP BY t.id
+             ORDER BY duration_ms DESC, t.name",
+        )
+        .map_err(db_err("Failed to prepare tag totals query"))?;
+
+    let totals = stmt
+        .query_map(params![start_time, end_time], |row| {
+            Ok(TagTotal {
+                tag_id: row.get(0)?,
+                name: row.get(1)?,
+                color: row.get(2)?,
+                duration_ms: row.get(3)?,
+                entry_count: row.get(4)?,
+            })
+        })
+        .map_err(db_err("Failed to query tag totals"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db_err("Failed to collect tag totals"))?;
+
+    Ok(totals)
+}
+
+/// SQL condition that the entry id in `entry_column` carries every tag in
+/// `tag_ids`, and the ids to bind in its place. Repeated ids count once.
+pub fn has_all_tags_condition(entry_column: &str, tag_ids: &[i64]) -> (String, Vec<i64>) {
+    let mut ids = tag_ids.to_vec();
+    ids.sort_unstable();
+    ids.dedup();
+    if ids.is_empty() {
+        return ("1".to_string(), ids);
+    }
+    let condition = format!(
+        "(SELECT COUNT(*) FROM time_entry_tags et
+          WHERE et.entry_id = {} AND et.tag_id IN ({})) = {}",
+        entry_column,
+        vec!["?"; ids.len()].join(", "),
+        ids.len()
+    );
+    (condition, ids)
+}
+
+fn validate_name(conn: &Connection, tag: &TagInput, id: Option<i64>) -> AppResult<String> {
+    let name = tag.name.trim();
+    if name.is_empty() {
+        return Err(
+            AppError::validation("empty_tag_name", "Tag name cannot be empty").with_field("name"),
+        );
+    }
+
+    let taken: Option<i64> = conn
+        .query_row(
+            "SELECT id FROM tags WHERE name = ?1 COLLATE NOCASE AND id IS NOT ?2",
+            params![name, id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(db_err("Failed to look up tag"))?;
+    if taken.is_some() {
+        return Err(AppError::new(
+            ErrorKind::Conflict,
+            "tag_name_taken",
+            format!("A tag named '{}' already exists", name),
+        )
+        .with_field("name"));
+    }
+
+    Ok(name.to_string())
+}
+
+fn ensure_tag_exists(conn: &Connection, id: i64) -> AppResult<()> {
+    let exists: bool = conn
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM tags WHERE id = ?)",
+            params![id],
+            |row| row.get(0),
+        )
+        .map_err(db_err("Failed to look up tag"))?;
+
+    if !exists {
+        return Err(
+            AppError::validation("unknown_tag", format!("Tag {} does not exist", id))
+                .with_field("tag_ids"),
+        );
+    }
+    Ok(())
+}
+
+fn map_tag(row: &rusqlite::Row) -> rusqlite::Result<Tag> {
+    Ok(Tag {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        color: row.get(2)?,
+    })
+}
+
+fn tag_not_found(id: i64) -> AppError {
+    AppError::not_found("tag_not_found", format!("Tag {} not found", id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::database::{enable_foreign_keys, run_migrations};
+    use crate::data::time_entries::{create_time_entry_impl, delete_time_entry_impl};
+    use crate::types::TimeEntryInput;
+
+    fn setup_test_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        enable_foreign_keys(&conn).unwrap();
+        run_migrations(&conn).unwrap();
+        conn
+    }
+
+    fn tag(conn: &Connection, name: &str) -> Tag {
+        create_tag_impl(
+            conn,
+            &TagInput {
+                name: name.to_string(),
+                color: None,
+            },
+        )
+        .unwrap()
+    }
+
+    fn entry(conn: &Connection, start_time: i64, end_time: i64, label: &str) -> TimeEntry {
+        create_time_entry_impl(
+            conn,
+            &TimeEntryInput {
+                start_time,
+                end_time,
+                label: label.to_string(),
+                color: None,
+                category_id: None,
+                notes: None,
+                project_id: None,
+            },
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_tag_crud() {
+        let conn = setup_test_db();
+        let billable = tag(&conn, " Billable ");
+        assert_eq!(billable.name, "Billable");
+
+        let error = create_tag_impl(
+            &conn,
+            &TagInput {
+                name: "billable".to_string(),
+                color: None,
+            },
+        )
+        .unwrap_err();
+        assert_eq!(error.code, "tag_name_taken");
+
+        // Renaming a tag to itself in another case is fine
+        let renamed = update_tag_impl(
+            &conn,
+            billable.id,
+            &TagInput {
+                name: "BILLABLE".to_string(),
+                color: Some("#00FF00".to_string()),
+            },
+        )
+        .unwrap();
+        assert_eq!(get_tags_impl(&conn).unwrap(), vec![renamed]);
+
+        let work = entry(&conn, 1000, 2000, "Work");
+        set_time_entry_tags_impl(&conn, work.id, &[billable.id]).unwrap();
+        delete_tag_impl(&conn, billable.id).unwrap();
+        assert!(get_time_entry_tags_impl(&conn, work.id).unwrap().is_empty());
+
+        let error = set_time_entry_tags_impl(&conn, work.id, &[billable.id]).unwrap_err();
+        assert_eq!(error.code, "unknown_tag");
+    }
+
+    #[test]
+    fn test_range_filter_and_totals() {
+        let conn = setup_test_db();
+        let client = tag(&conn, "Client A");
+        let meeting = tag(&conn, "Meeting");
+        let kickoff = entry(&conn, 0, 2000, "Kickoff");
+        let build = entry(&conn, 2000, 5000, "Build");
+        let trashed = entry(&conn, 5000, 6000, "Trashed");
+        set_time_entry_tags_impl(&conn, kickoff.id, &[client.id, meeting.id]).unwrap();
+        set_time_entry_tags_impl(&conn, build.id, &[client.id]).unwrap();
+        set_time_entry_tags_impl(&conn, trashed.id, &[meeting.id]).unwrap();
+        delete_time_entry_impl(&conn, trashed.id).unwrap();
+
+        let labels = |tag_ids: &[i64]| -> Vec<String> {
+            get_time_entries_by_range_with_tags_impl(&conn, 0, 10000, tag_ids)
+                .unwrap()
+                .into_iter()
+                .map(|e| e.label)
+                .collect()
+        };
+        assert_eq!(labels(&[client.id]), vec!["Kickoff", "Build"]);
+        assert_eq!(labels(&[client.id, meeting.id]), vec!["Kickoff"]);
+        assert_eq!(labels(&[client.id, client.id]), vec!["Kickoff", "Build"]);
+        assert_eq!(labels(&[]).len(), 2);
+
+        // Clipped to the range; the trashed entry does not count
+        let totals = get_tag_totals_by_range_impl(&conn, 1000, 10000).unwrap();
+        let totals: Vec<(&str, i64, i64)> = totals
+            .iter()
+            .map(|t| (t.name.as_str(), t.duration_ms, t.entry_count))
+            .collect();
+        assert_eq!(totals, vec![("Client A", 4000, 2), ("Meeting", 1000, 1)]);
+    }
+}
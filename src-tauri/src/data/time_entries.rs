@@ -1,22 +1,40 @@
-use crate::data::AppResult;
-use crate::types::{TimeEntry, TimeEntryInput, TimeEntryUpdate};
+use crate::app_settings::WorkingHoursSettings;
+use crate::data::categories::ensure_category_exists;
+use crate::data::error::{db_err, ErrorDetails, ErrorKind};
+use crate::data::journal;
+use crate::data::process_segments::get_process_totals_by_range;
+use crate::data::projects::ensure_project_exists;
+use crate::data::tags;
+use crate::data::timer;
+use crate::data::window_activity::get_window_title_counts;
+use crate::data::{AppError, AppResult};
+use crate::types::{
+    TimeEntry, TimeEntryInput, TimeEntrySplit, TimeEntryState, TimeEntryUpdate, TrashedTimeEntry,
+    UntrackedGap,
+};
+use chrono::{Datelike, NaiveDate, TimeZone};
 use rusqlite::{params, Connection};
 
 #[cfg(test)]
 #[path = "time_entries_tests.rs"]
 mod tests;
 
+/// Processes and window titles listed for each untracked gap.
+const GAP_TOP_PROCESSES: usize = 3;
+const GAP_TOP_WINDOW_TITLES: usize = 5;
+
 pub fn get_time_entries_impl(conn: &Connection, date: i64) -> AppResult<Vec<TimeEntry>> {
     let start_of_day = date;
     let end_of_day = date + 86400000;
 
     let mut stmt = conn
         .prepare(
-            "SELECT id, start_time, end_time, label, color, category_id FROM time_entries 
-         WHERE start_time >= ? AND start_time < ? 
+            "SELECT id, start_time, end_time, label, color, category_id, notes, project_id
+         FROM time_entries
+         WHERE deleted_at IS NULL AND start_time >= ? AND start_time < ? 
          ORDER BY start_time",
         )
-        .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        .map_err(db_err("Failed to prepare query"))?;
 
     let entry_iter = stmt
         .query_map(params![start_of_day, end_of_day], |row| {
@@ -27,13 +45,15 @@ pub fn get_time_entries_impl(conn: &Connection, date: i64) -> AppResult<Vec<Time
                 label: row.get(3)?,
                 color: row.get(4)?,
                 category_id: row.get(5)?,
+                notes: row.get(6)?,
+                project_id: row.get(7)?,
             })
         })
-        .map_err(|e| format!("Failed to query time entries: {}", e))?;
+        .map_err(db_err("Failed to query time entries"))?;
 
     let mut entries = Vec::new();
     for entry in entry_iter {
-        entries.push(entry.map_err(|e| format!("Failed to map row: {}", e))?);
+        entries.push(entry.map_err(db_err("Failed to map row"))?);
     }
 
     Ok(entries)
@@ -45,16 +65,17 @@ pub fn get_time_entries_by_range_impl(
     end_time: i64,
 ) -> AppResult<Vec<TimeEntry>> {
     if end_time <= start_time {
-        return Err("end_time must be greater than start_time".to_string());
+        return Err(invalid_time_range());
     }
 
     let mut stmt = conn
         .prepare(
-            "SELECT id, start_time, end_time, label, color, category_id FROM time_entries
-         WHERE start_time < ? AND end_time > ?
+            "SELECT id, start_time, end_time, label, color, category_id, notes, project_id
+         FROM time_entries
+         WHERE deleted_at IS NULL AND start_time < ? AND end_time > ?
          ORDER BY start_time",
         )
-        .map_err(|e| format!("Failed to prepare range query: {}", e))?;
+        .map_err(db_err("Failed to prepare range query"))?;
 
     let entry_iter = stmt
         .query_map(params![end_time, start_time], |row| {
@@ -65,53 +86,87 @@ pub fn get_time_entries_by_range_impl(
                 label: row.get(3)?,
                 color: row.get(4)?,
                 category_id: row.get(5)?,
+                notes: row.get(6)?,
+                project_id: row.get(7)?,
             })
         })
-        .map_err(|e| format!("Failed to query time entries by range: {}", e))?;
+        .map_err(db_err("Failed to query time entries by range"))?;
 
     let mut entries = Vec::new();
     for entry in entry_iter {
-        entries.push(entry.map_err(|e| format!("Failed to map row: {}", e))?);
+        entries.push(entry.map_err(db_err("Failed to map row"))?);
     }
 
     Ok(entries)
 }
 
 pub fn create_time_entry_impl(conn: &Connection, entry: &TimeEntryInput) -> AppResult<TimeEntry> {
+    insert_time_entry(conn, entry, None)
+}
+
+/// Create an entry planned ahead of time.
+///
+/// The running entry only blocks it up to `now`; the timer stops by itself
+/// when it reaches the planned entry.
+pub fn create_planned_time_entry_impl(
+    conn: &Connection,
+    entry: &TimeEntryInput,
+    now: i64,
+) -> AppResult<TimeEntry> {
+    insert_time_entry(conn, entry, Some(now))
+}
+
+fn insert_time_entry(
+    conn: &Connection,
+    entry: &TimeEntryInput,
+    running_until: Option<i64>,
+) -> AppResult<TimeEntry> {
     if entry.end_time <= entry.start_time {
-        return Err("end_time must be greater than start_time".to_string());
+        return Err(invalid_time_range());
     }
 
     if entry.label.trim().is_empty() {
-        return Err("Label cannot be empty".to_string());
+        return Err(empty_label());
     }
 
-    let overlap_count: i64 = conn
-        .query_row(
-            "SELECT COUNT(1) FROM time_entries
-             WHERE start_time < ?1 AND end_time > ?2",
-            params![entry.end_time, entry.start_time],
-            |row| row.get(0),
-        )
-        .map_err(|e| format!("Failed to check overlapping entries: {}", e))?;
+    if let Some(category_id) = entry.category_id {
+        ensure_category_exists(conn, category_id)?;
+    }
+
+    if let Some(project_id) = entry.project_id {
+        ensure_project_exists(conn, project_id)?;
+    }
 
-    if overlap_count > 0 {
-        return Err("Time entry overlaps with an existing entry".to_string());
+    let overlapping =
+        overlapping_entry_ids(conn, entry.start_time, entry.end_time, None, running_until)?;
+    if !overlapping.is_empty() {
+        return Err(AppError::overlap(overlapping));
     }
 
+    let notes = non_blank(entry.notes.as_deref());
     conn.execute(
-        "INSERT INTO time_entries (start_time, end_time, label, color, category_id) VALUES (?, ?, ?, ?, ?)",
+        "INSERT INTO time_entries (start_time, end_time, label, color, category_id, notes, project_id)
+         VALUES (?, ?, ?, ?, ?, ?, ?)",
         params![
             entry.start_time,
             entry.end_time,
             &entry.label,
             &entry.color,
-            entry.category_id
+            entry.category_id,
+            notes,
+            entry.project_id
         ],
     )
-    .map_err(|e| format!("Failed to insert time entry: {}", e))?;
+    .map_err(db_err("Failed to insert time entry"))?;
 
     let id = conn.last_insert_rowid();
+    journal::record(
+        conn,
+        journal::TIME_ENTRIES,
+        id,
+        None,
+        journal::snapshot(conn, journal::TIME_ENTRIES, id)?,
+    )?;
 
     Ok(TimeEntry {
         id,
@@ -120,6 +175,8 @@ pub fn create_time_entry_impl(conn: &Connection, entry: &TimeEntryInput) -> AppR
         label: entry.label.clone(),
         color: entry.color.clone(),
         category_id: entry.category_id,
+        notes: notes.map(str::to_string),
+        project_id: entry.project_id,
     })
 }
 
@@ -127,27 +184,41 @@ pub fn update_time_entry_impl(
     conn: &Connection,
     id: i64,
     updates: &TimeEntryUpdate,
+) -> AppResult<TimeEntry> {
+    update_time_entry_vacating(conn, id, updates, None)
+}
+
+/// Like [`update_time_entry_impl`], but entry `vacating_id` does not count as
+/// an overlap: the caller moves it out of the way in the same transaction.
+pub fn update_time_entry_vacating(
+    conn: &Connection,
+    id: i64,
+    updates: &TimeEntryUpdate,
+    vacating_id: Option<i64>,
 ) -> AppResult<TimeEntry> {
     let existing = get_time_entry_by_id(conn, id)?;
     let next_start_time = updates.start_time.unwrap_or(existing.start_time);
     let next_end_time = updates.end_time.unwrap_or(existing.end_time);
 
     if next_end_time <= next_start_time {
-        return Err("end_time must be greater than start_time".to_string());
+        return Err(invalid_time_range());
     }
 
-    if updates.start_time.is_some() || updates.end_time.is_some() {
-        let overlap_count: i64 = conn
-            .query_row(
-                "SELECT COUNT(1) FROM time_entries
-                 WHERE id != ?1 AND start_time < ?2 AND end_time > ?3",
-                params![id, next_end_time, next_start_time],
-                |row| row.get(0),
-            )
-            .map_err(|e| format!("Failed to check overlapping entries: {}", e))?;
+    if updates.end_time.is_some() && timer::running_entry_id(conn)? == Some(id) {
+        return Err(AppError::new(
+            ErrorKind::Conflict,
+            "timer_running",
+            "Stop the timer before changing the end of its entry",
+        )
+        .with_field("end_time"));
+    }
 
-        if overlap_count > 0 {
-            return Err("Time entry overlaps with an existing entry".to_string());
+    if updates.start_time.is_some() || updates.end_time.is_some() {
+        let mut overlapping =
+            find_overlapping_entry_ids(conn, next_start_time, next_end_time, Some(id))?;
+        overlapping.retain(|&other| Some(other) != vacating_id);
+        if !overlapping.is_empty() {
+            return Err(AppError::overlap(overlapping));
         }
     }
 
@@ -166,7 +237,7 @@ pub fn update_time_entry_impl(
 
     if let Some(ref label) = updates.label {
         if label.trim().is_empty() {
-            return Err("Label cannot be empty".to_string());
+            return Err(empty_label());
         }
         set_clauses.push("label = ?");
         params.push(Box::new(label.clone()));
@@ -178,12 +249,28 @@ pub fn update_time_entry_impl(
     }
 
     if let Some(category_id) = updates.category_id {
+        if let Some(category_id) = category_id {
+            ensure_category_exists(conn, category_id)?;
+        }
         set_clauses.push("category_id = ?");
         params.push(Box::new(category_id));
     }
 
+    if let Some(ref notes) = updates.notes {
+        set_clauses.push("notes = ?");
+        params.push(Box::new(non_blank(Some(notes)).map(str::to_string)));
+    }
+
+    if let Some(project_id) = updates.project_id {
+        if let Some(project_id) = project_id {
+            ensure_project_exists(conn, project_id)?;
+        }
+        set_clauses.push("project_id = ?");
+        params.push(Box::new(project_id));
+    }
+
     if set_clauses.is_empty() {
-        return Err("No updates provided".to_string());
+        return Err(AppError::validation("no_updates", "No updates provided"));
     }
 
     let sql = format!(
@@ -196,27 +283,589 @@ pub fn update_time_entry_impl(
     let mut all_params = params_refs;
     all_params.push(&id);
 
-    conn.execute(&sql, rusqlite::params_from_iter(all_params))
-        .map_err(|e| format!("Failed to update time entry: {}", e))?;
+    journal::track(conn, journal::TIME_ENTRIES, id, || {
+        conn.execute(&sql, rusqlite::params_from_iter(all_params))
+            .map_err(db_err("Failed to update time entry"))?;
+        Ok(())
+    })?;
 
     get_time_entry_by_id(conn, id)
 }
 
+/// Cut entry `id` at `at` into two back-to-back entries, as one undo step.
+///
+/// The second half keeps the label, color, category and notes unless overridden.
+/// Splitting the running entry keeps the timer running on the second half.
+pub fn split_time_entry_impl(
+    conn: &Connection,
+    id: i64,
+    at: i64,
+    new_label: Option<&str>,
+    new_category_id: Option<i64>,
+) -> AppResult<TimeEntrySplit> {
+    let existing = get_time_entry_by_id(conn, id)?;
+    if at <= existing.start_time || at >= existing.end_time {
+        return Err(AppError::validation(
+            "invalid_split_point",
+            "The split point must fall strictly inside the entry",
+        )
+        .with_field("at"));
+    }
+
+    let running = timer::running_entry_id(conn)? == Some(id);
+
+    let tx = conn
+        .unchecked_transaction()
+        .map_err(db_err("Failed to start transaction"))?;
+
+    let split = journal::grouped(&tx, || {
+        if running {
+            timer::clear_running_timer(&tx, id)?;
+        }
+        let first = update_time_entry_impl(
+            &tx,
+            id,
+            &TimeEntryUpdate {
+                start_time: None,
+                end_time: Some(at),
+                label: None,
+                color: None,
+                category_id: None,
+                notes: None,
+                project_id: None,
+            },
+        )?;
+        let second = create_time_entry_impl(
+            &tx,
+            &TimeEntryInput {
+                start_time: at,
+                end_time: existing.end_time,
+                label: new_label.unwrap_or(&existing.label).to_string(),
+                color: existing.color.clone(),
+                category_id: new_category_id.or(existing.category_id),
+                notes: existing.notes.clone(),
+                project_id: existing.project_id,
+            },
+        )?;
+        tags::copy_time_entry_tags(&tx, id, second.id)?;
+        if running {
+            timer::set_running(&tx, second.id, existing.end_time)?;
+        }
+        Ok(TimeEntrySplit { first, second })
+    })?;
+
+    tx.commit().map_err(db_err("Failed to commit split"))?;
+
+    Ok(split)
+}
+
+/// Combine entries `ids` into the earliest of them, as one undo step.
+///
+/// The merged entry spans from the first start to the last end. Gaps between
+/// the entries are only bridged with `absorb_gaps`; either way no other entry
+/// may lie inside the span. The others go to the trash; their notes are
+/// appended to the merged entry's.
+pub fn merge_time_entries_impl(
+    conn: &Connection,
+    ids: &[i64],
+    label: Option<&str>,
+    category_id: Option<i64>,
+    absorb_gaps: bool,
+) -> AppResult<TimeEntry> {
+    let mut entries = Vec::new();
+    for &id in ids {
+        if !entries.iter().any(|e: &TimeEntry| e.id == id) {
+            entries.push(get_time_entry_by_id(conn, id)?);
+        }
+    }
+    if entries.len() < 2 {
+        return Err(AppError::validation(
+            "merge_needs_two_entries",
+            "Select at least two entries to merge",
+        )
+        .with_field("ids"));
+    }
+    entries.sort_by_key(|e| e.start_time);
+
+    let start_time = entries[0].start_time;
+    let end_time = entries
+        .iter()
+        .map(|e| e.end_time)
+        .max()
+        .unwrap_or(start_time);
+
+    if !absorb_gaps {
+        let mut covered_until = start_time;
+        for entry in &entries {
+            if entry.start_time > covered_until {
+                return Err(AppError::validation(
+                    "merge_has_gaps",
+                    "The entries are not back to back; allow absorbing the gaps to merge them",
+                )
+                .with_field("ids"));
+            }
+            covered_until = covered_until.max(entry.end_time);
+        }
+    }
+
+    let swallowed: Vec<i64> = find_overlapping_entry_ids(conn, start_time, end_time, None)?
+        .into_iter()
+        .filter(|id| !entries.iter().any(|e| e.id == *id))
+        .collect();
+    if !swallowed.is_empty() {
+        return Err(AppError::new(
+            ErrorKind::Conflict,
+            "merge_swallows_entry",
+            "The merged entry would cover entries that are not part of the merge",
+        )
+        .with_details(ErrorDetails {
+            conflicting_entry_ids: swallowed,
+            ..Default::default()
+        }));
+    }
+
+    let survivor = entries[0].id;
+    let running = timer::running_entry_id(conn)?.filter(|id| entries.iter().any(|e| e.id == *id));
+
+    let tx = conn
+        .unchecked_transaction()
+        .map_err(db_err("Failed to start transaction"))?;
+
+    let merged = journal::grouped(&tx, || {
+        if let Some(running) = running {
+            timer::clear_running_timer(&tx, running)?;
+        }
+        for entry in &entries[1..] {
+            tags::copy_time_entry_tags(&tx, entry.id, survivor)?;
+            delete_time_entry_impl(&tx, entry.id)?;
+        }
+        let merged = update_time_entry_impl(
+            &tx,
+            survivor,
+            &TimeEntryUpdate {
+                start_time: None,
+                end_time: Some(end_time),
+                label: label.map(str::to_string),
+                color: None,
+                category_id: category_id.map(Some),
+                notes: merged_notes(&entries),
+                project_id: None,
+            },
+        )?;
+        if running.is_some() {
+            timer::set_running(&tx, survivor, end_time)?;
+        }
+        Ok(merged)
+    })?;
+
+    tx.commit().map_err(db_err("Failed to commit merge"))?;
+
+    Ok(merged)
+}
+
+/// Move an entry to the trash. It stays restorable until purged.
+///
+/// Trashing the running entry stops the timer.
 pub fn delete_time_entry_impl(conn: &Connection, id: i64) -> AppResult<()> {
-    let rows_affected = conn
-        .execute("DELETE FROM time_entries WHERE id = ?", params![id])
-        .map_err(|e| format!("Failed to delete time entry: {}", e))?;
+    timer::clear_running_timer(conn, id)?;
+    journal::track(conn, journal::TIME_ENTRIES, id, || {
+        let rows_affected = conn
+            .execute(
+                "UPDATE time_entries SET deleted_at = ? WHERE id = ? AND deleted_at IS NULL",
+                params![chrono::Utc::now().timestamp_millis(), id],
+            )
+            .map_err(db_err("Failed to delete time entry"))?;
+
+        if rows_affected == 0 {
+            return Err(entry_not_found(id));
+        }
+
+        Ok(())
+    })
+}
+
+/// Trashed entries, most recently deleted first.
+pub fn get_trashed_time_entries_impl(conn: &Connection) -> AppResult<Vec<TrashedTimeEntry>> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, start_time, end_time, label, color, category_id, deleted_at, notes,
+                project_id
+         FROM time_entries
+         WHERE deleted_at IS NOT NULL
+         ORDER BY deleted_at DESC, id DESC",
+        )
+        .map_err(db_err("Failed to prepare trash query"))?;
+
+    let entry_iter = stmt
+        .query_map([], |row| {
+            Ok(TrashedTimeEntry {
+                entry: TimeEntry {
+                    id: row.get(0)?,
+                    start_time: row.get(1)?,
+                    end_time: row.get(2)?,
+                    label: row.get(3)?,
+                    color: row.get(4)?,
+                    category_id: row.get(5)?,
+                    notes: row.get(7)?,
+                    project_id: row.get(8)?,
+                },
+                deleted_at: row.get(6)?,
+            })
+        })
+        .map_err(db_err("Failed to query trash"))?;
 
-    if rows_affected == 0 {
-        return Err("Time entry not found".to_string());
+    let mut entries = Vec::new();
+    for entry in entry_iter {
+        entries.push(entry.map_err(db_err("Failed to map row"))?);
     }
 
-    Ok(())
+    Ok(entries)
 }
 
-fn get_time_entry_by_id(conn: &Connection, id: i64) -> AppResult<TimeEntry> {
+/// Take an entry out of the trash. Fails if its slot has been filled since.
+pub fn restore_time_entry_impl(conn: &Connection, id: i64) -> AppResult<TimeEntry> {
+    let (start_time, end_time): (i64, i64) = conn
+        .query_row(
+            "SELECT start_time, end_time FROM time_entries WHERE id = ? AND deleted_at IS NOT NULL",
+            params![id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => trashed_entry_not_found(id),
+            other => AppError::from(other).context("Failed to fetch trashed time entry"),
+        })?;
+
+    let overlapping = find_overlapping_entry_ids(conn, start_time, end_time, Some(id))?;
+    if !overlapping.is_empty() {
+        return Err(AppError::overlap(overlapping));
+    }
+
+    journal::track(conn, journal::TIME_ENTRIES, id, || {
+        conn.execute(
+            "UPDATE time_entries SET deleted_at = NULL WHERE id = ?",
+            params![id],
+        )
+        .map_err(db_err("Failed to restore time entry"))?;
+        Ok(())
+    })?;
+
+    get_time_entry_by_id(conn, id)
+}
+
+/// Overwrite every stored field of entry `id`, trashed or not, with `state`.
+///
+/// Applies the same checks as create/update; the overlap check is skipped
+/// when `state` puts the entry in the trash.
+pub fn apply_time_entry_state(conn: &Connection, id: i64, state: &TimeEntryState) -> AppResult<()> {
+    if state.end_time <= state.start_time {
+        return Err(invalid_time_range());
+    }
+    if state.label.trim().is_empty() {
+        return Err(empty_label());
+    }
+    if let Some(category_id) = state.category_id {
+        ensure_category_exists(conn, category_id)?;
+    }
+    if let Some(project_id) = state.project_id {
+        ensure_project_exists(conn, project_id)?;
+    }
+    if state.deleted_at.is_none() {
+        let overlapping =
+            find_overlapping_entry_ids(conn, state.start_time, state.end_time, Some(id))?;
+        if !overlapping.is_empty() {
+            return Err(AppError::overlap(overlapping));
+        }
+    }
+
+    journal::track(conn, journal::TIME_ENTRIES, id, || {
+        let rows_affected = conn
+            .execute(
+                "UPDATE time_entries
+                 SET start_time = ?1, end_time = ?2, label = ?3, color = ?4, category_id = ?5, deleted_at = ?6,
+                     notes = ?7, project_id = ?8
+                 WHERE id = ?9",
+                params![
+                    state.start_time,
+                    state.end_time,
+                    &state.label,
+                    &state.color,
+                    state.category_id,
+                    state.deleted_at,
+                    &state.notes,
+                    state.project_id,
+                    id
+                ],
+            )
+            .map_err(db_err("Failed to apply time entry state"))?;
+
+        if rows_affected == 0 {
+            return Err(entry_not_found(id));
+        }
+
+        Ok(())
+    })
+}
+
+/// Permanently delete trashed entries: the given ids, or the whole trash when `None`.
+pub fn purge_trashed_time_entries_impl(conn: &Connection, ids: Option<&[i64]>) -> AppResult<usize> {
+    let purged = match ids {
+        None => delete_returning_ids(
+            conn,
+            "DELETE FROM time_entries WHERE deleted_at IS NOT NULL RETURNING id",
+            [],
+        )?,
+        Some(ids) => {
+            let mut purged = Vec::new();
+            for id in ids {
+                purged.extend(delete_returning_ids(
+                    conn,
+                    "DELETE FROM time_entries WHERE id = ? AND deleted_at IS NOT NULL RETURNING id",
+                    params![id],
+                )?);
+            }
+            purged
+        }
+    };
+
+    journal::forget(conn, journal::TIME_ENTRIES, &purged)?;
+    Ok(purged.len())
+}
+
+/// Permanently delete entries that were trashed before `cutoff`.
+pub fn purge_trash_before(conn: &Connection, cutoff: i64) -> AppResult<usize> {
+    let purged = delete_returning_ids(
+        conn,
+        "DELETE FROM time_entries WHERE deleted_at IS NOT NULL AND deleted_at < ? RETURNING id",
+        params![cutoff],
+    )?;
+
+    journal::forget(conn, journal::TIME_ENTRIES, &purged)?;
+    Ok(purged.len())
+}
+
+fn delete_returning_ids<P: rusqlite::Params>(
+    conn: &Connection,
+    sql: &str,
+    params: P,
+) -> AppResult<Vec<i64>> {
+    let mut stmt = conn
+        .prepare(sql)
+        .map_err(db_err("Failed to prepare purge"))?;
+
+    let ids = stmt
+        .query_map(params, |row| row.get(0))
+        .map_err(db_err("Failed to purge trash"))?
+        .collect::<Result<Vec<i64>, _>>()
+        .map_err(db_err("Failed to purge trash"))?;
+
+    Ok(ids)
+}
+
+/// Ids of entries intersecting `[start_time, end_time)`, optionally ignoring `exclude_id`.
+///
+/// The running entry has no end yet: it intersects every range past its
+/// start. When it is `exclude_id`, the range checked reaches at least its last
+/// heartbeat; entries planned past that stop the timer once it gets there.
+pub fn find_overlapping_entry_ids(
+    conn: &Connection,
+    start_time: i64,
+    end_time: i64,
+    exclude_id: Option<i64>,
+) -> AppResult<Vec<i64>> {
+    overlapping_entry_ids(conn, start_time, end_time, exclude_id, None)
+}
+
+/// Like [`find_overlapping_entry_ids`], but with `running_until` the running
+/// entry only counts as reaching that time.
+fn overlapping_entry_ids(
+    conn: &Connection,
+    start_time: i64,
+    end_time: i64,
+    exclude_id: Option<i64>,
+    running_until: Option<i64>,
+) -> AppResult<Vec<i64>> {
+    let end_time = match exclude_id {
+        Some(id) if timer::running_entry_id(conn)? == Some(id) => {
+            end_time.max(get_time_entry_by_id(conn, id)?.end_time)
+        }
+        _ => end_time,
+    };
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT id FROM time_entries
+             WHERE deleted_at IS NULL
+               AND start_time < ?1
+               AND (end_time > ?2
+                    OR (id IN (SELECT entry_id FROM running_timer) AND (?4 IS NULL OR ?4 > ?2)))
+               AND (?3 IS NULL OR id != ?3)
+             ORDER BY start_time",
+        )
+        .map_err(db_err("Failed to prepare overlap query"))?;
+
+    let ids = stmt
+        .query_map(
+            params![end_time, start_time, exclude_id, running_until],
+            |row| row.get(0),
+        )
+        .map_err(db_err("Failed to check overlapping entries"))?
+        .collect::<Result<Vec<i64>, _>>()
+        .map_err(db_err("Failed to collect overlapping entries"))?;
+
+    Ok(ids)
+}
+
+/// Working time in `[start_time, end_time)` not covered by any entry.
+///
+/// `working_windows` are the spans that count as working time, in order.
+/// Gaps shorter than `min_gap_ms` are left out; the rest are annotated with
+/// the processes and window titles captured during them.
+pub fn get_untracked_gaps_impl(
+    conn: &Connection,
+    start_time: i64,
+    end_time: i64,
+    min_gap_ms: i64,
+    working_windows: &[(i64, i64)],
+) -> AppResult<Vec<UntrackedGap>> {
+    let mut entries = get_time_entries_by_range_impl(conn, start_time, end_time)?;
+    // The running entry covers everything up to now, not just to its last heartbeat
+    if let Some(running) = timer::running_entry_id(conn)? {
+        for entry in entries.iter_mut().filter(|e| e.id == running) {
+            entry.end_time = i64::MAX;
+        }
+    }
+
+    let mut gaps = Vec::new();
+    for &(window_start, window_end) in working_windows {
+        let window_start = window_start.max(start_time);
+        let window_end = window_end.min(end_time);
+        let mut cursor = window_start;
+        for entry in &entries {
+            if entry.end_time <= cursor || entry.start_time >= window_end {
+                continue;
+            }
+            if entry.start_time > cursor {
+                gaps.push((cursor, entry.start_time));
+            }
+            cursor = cursor.max(entry.end_time);
+        }
+        if cursor < window_end {
+            gaps.push((cursor, window_end));
+        }
+    }
+
+    gaps.into_iter()
+        .filter(|(start, end)| end - start >= min_gap_ms.max(1))
+        .map(|(start, end)| {
+            Ok(UntrackedGap {
+                start_time: start,
+                end_time: end,
+                processes: get_process_totals_by_range(conn, start, end, GAP_TOP_PROCESSES)?,
+                window_titles: get_window_title_counts(conn, start, end, GAP_TOP_WINDOW_TITLES)?,
+            })
+        })
+        .collect()
+}
+
+/// Spans of `[start_time, end_time)` inside `hours`, with days and hours read in `tz`.
+pub fn working_windows<Tz: TimeZone>(
+    tz: &Tz,
+    start_time: i64,
+    end_time: i64,
+    hours: &WorkingHoursSettings,
+) -> Vec<(i64, i64)> {
+    let local_date = |timestamp: i64| {
+        tz.timestamp_millis_opt(timestamp)
+            .single()
+            .map(|t| t.naive_local().date())
+    };
+    let (Some(mut day), Some(last_day)) = (local_date(start_time), local_date(end_time)) else {
+        return Vec::new();
+    };
+
+    let mut windows = Vec::new();
+    while day <= last_day {
+        if hours.days.contains(&day.weekday().number_from_monday()) {
+            let open = local_minute_millis(tz, day, hours.start_hour * 60).max(start_time);
+            let close = local_minute_millis(tz, day, hours.end_hour * 60).min(end_time);
+            if open < close {
+                windows.push((open, close));
+            }
+        }
+        let Some(next) = day.succ_opt() else {
+            break;
+        };
+        day = next;
+    }
+    windows
+}
+
+/// `minute` minutes after midnight (1440 meaning the next midnight) on `day` in `tz`.
+pub fn local_minute_millis<Tz: TimeZone>(tz: &Tz, day: NaiveDate, minute: u32) -> i64 {
+    let midnight = day.and_hms_opt(0, 0, 0).unwrap_or_default();
+    let local = midnight + chrono::Duration::minutes(minute as i64);
+    // Hours skipped by a DST change start at the next hour that exists
+    tz.from_local_datetime(&local)
+        .earliest()
+        .or_else(|| {
+            tz.from_local_datetime(&(local + chrono::Duration::hours(1)))
+                .earliest()
+        })
+        .map(|t| t.timestamp_millis())
+        .unwrap_or_else(|| local.and_utc().timestamp_millis())
+}
+
+/// Notes of all `entries` one after the other, or `None` when only the first has any.
+fn merged_notes(entries: &[TimeEntry]) -> Option<String> {
+    if entries[1..]
+        .iter()
+        .all(|e| non_blank(e.notes.as_deref()).is_none())
+    {
+        return None;
+    }
+    let notes: Vec<&str> = entries
+        .iter()
+        .filter_map(|e| non_blank(e.notes.as_deref()))
+        .collect();
+    Some(notes.join("\n\n"))
+}
+
+/// Notes with only whitespace are stored as none.
+fn non_blank(notes: Option<&str>) -> Option<&str> {
+    notes.filter(|notes| !notes.trim().is_empty())
+}
+
+fn invalid_time_range() -> AppError {
+    AppError::validation(
+        "invalid_time_range",
+        "end_time must be greater than start_time",
+    )
+    .with_field("end_time")
+}
+
+fn empty_label() -> AppError {
+    AppError::validation("empty_label", "Label cannot be empty").with_field("label")
+}
+
+fn trashed_entry_not_found(id: i64) -> AppError {
+    AppError::not_found(
+        "trashed_time_entry_not_found",
+        format!("Time entry {} is not in the trash", id),
+    )
+}
+
+fn entry_not_found(id: i64) -> AppError {
+    AppError::not_found(
+        "time_entry_not_found",
+        format!("Time entry {} not found", id),
+    )
+}
+
+pub fn get_time_entry_by_id(conn: &Connection, id: i64) -> AppResult<TimeEntry> {
     conn.query_row(
-        "SELECT id, start_time, end_time, label, color, category_id FROM time_entries WHERE id = ?",
+        "SELECT id, start_time, end_time, label, color, category_id, notes, project_id
+         FROM time_entries
+         WHERE id = ? AND deleted_at IS NULL",
         params![id],
         |row| {
             Ok(TimeEntry {
@@ -226,8 +875,13 @@ fn get_time_entry_by_id(conn: &Connection, id: i64) -> AppResult<TimeEntry> {
                 label: row.get(3)?,
                 color: row.get(4)?,
                 category_id: row.get(5)?,
+                notes: row.get(6)?,
+                project_id: row.get(7)?,
             })
         },
     )
-    .map_err(|e| format!("Failed to fetch time entry: {}", e))
+    .map_err(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => entry_not_found(id),
+        other => AppError::from(other).context("Failed to fetch time entry"),
+    })
 }
@@ -1,34 +1,22 @@
 #[cfg(test)]
 mod cases {
+    use crate::data::categories::{create_category_impl, delete_category_impl};
+    use crate::data::database::{enable_foreign_keys, run_migrations};
+    use crate::data::error::ErrorKind;
     use crate::data::time_entries::{
-        create_time_entry_impl, delete_time_entry_impl, get_time_entries_impl,
-        update_time_entry_impl, TimeEntryInput, TimeEntryUpdate,
+        create_time_entry_impl, delete_time_entry_impl, get_time_entries_by_range_impl,
+        get_time_entries_impl, get_trashed_time_entries_impl, get_untracked_gaps_impl,
+        merge_time_entries_impl, purge_trash_before, purge_trashed_time_entries_impl,
+        restore_time_entry_impl, split_time_entry_impl, update_time_entry_impl, working_windows,
+        TimeEntryInput, TimeEntryUpdate,
     };
+    use crate::types::CategoryInput;
     use rusqlite::Connection;
 
     fn setup_test_db() -> Connection {
         let conn = Connection::open_in_memory().unwrap();
-
-        // Create tables
-        conn.execute(
-            "CREATE TABLE time_entries (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                start_time INTEGER NOT NULL,
-                end_time INTEGER NOT NULL,
-                label TEXT NOT NULL,
-                color TEXT,
-                category_id INTEGER
-            )",
-            [],
-        )
-        .unwrap();
-
-        conn.execute(
-            "CREATE INDEX idx_time_entries_start_time ON time_entries(start_time)",
-            [],
-        )
-        .unwrap();
-
+        enable_foreign_keys(&conn).unwrap();
+        run_migrations(&conn).unwrap();
         conn
     }
 
@@ -42,6 +30,8 @@ mod cases {
             label: "Test Task".to_string(),
             color: Some("#4CAF50".to_string()),
             category_id: None,
+            notes: None,
+            project_id: None,
         };
 
         let result = create_time_entry_impl(&conn, &input);
@@ -65,12 +55,17 @@ mod cases {
             label: "Invalid Task".to_string(),
             color: None,
             category_id: None,
+            notes: None,
+            project_id: None,
         };
 
         let result = create_time_entry_impl(&conn, &input);
         assert!(result.is_err());
-        assert!(result
-            .unwrap_err()
+        let error = result.unwrap_err();
+        assert_eq!(error.kind, ErrorKind::Validation);
+        assert_eq!(error.code, "invalid_time_range");
+        assert!(error
+            .message
             .contains("end_time must be greater than start_time"));
     }
 
@@ -85,6 +80,8 @@ mod cases {
             label: "Task 1".to_string(),
             color: None,
             category_id: None,
+            notes: None,
+            project_id: None,
         };
         create_time_entry_impl(&conn, &input1).unwrap();
 
@@ -94,6 +91,8 @@ mod cases {
             label: "Task 2".to_string(),
             color: None,
             category_id: None,
+            notes: None,
+            project_id: None,
         };
         create_time_entry_impl(&conn, &input2).unwrap();
 
@@ -119,6 +118,8 @@ mod cases {
             label: "Original Task".to_string(),
             color: None,
             category_id: None,
+            notes: None,
+            project_id: None,
         };
         let entry = create_time_entry_impl(&conn, &input).unwrap();
 
@@ -129,6 +130,8 @@ mod cases {
             label: Some("Updated Task".to_string()),
             color: Some("#FF5733".to_string()),
             category_id: None,
+            notes: None,
+            project_id: None,
         };
 
         let result = update_time_entry_impl(&conn, entry.id, &updates);
@@ -150,6 +153,8 @@ mod cases {
             label: "Task to Delete".to_string(),
             color: None,
             category_id: None,
+            notes: None,
+            project_id: None,
         };
         let entry = create_time_entry_impl(&conn, &input).unwrap();
 
@@ -173,11 +178,15 @@ mod cases {
             label: "".to_string(), // Empty label
             color: None,
             category_id: None,
+            notes: None,
+            project_id: None,
         };
 
         let result = create_time_entry_impl(&conn, &input);
         assert!(result.is_err());
-        assert!(result.unwrap_err().contains("Label cannot be empty"));
+        let error = result.unwrap_err();
+        assert_eq!(error.kind, ErrorKind::Validation);
+        assert_eq!(error.details.unwrap().field.as_deref(), Some("label"));
     }
 
     #[test]
@@ -191,8 +200,10 @@ mod cases {
             label: "Task 1".to_string(),
             color: None,
             category_id: None,
+            notes: None,
+            project_id: None,
         };
-        create_time_entry_impl(&conn, &input1).unwrap();
+        let first = create_time_entry_impl(&conn, &input1).unwrap();
 
         // Try to create overlapping entry (10:30 - 11:30)
         let input2 = TimeEntryInput {
@@ -201,11 +212,16 @@ mod cases {
             label: "Task 2".to_string(),
             color: None,
             category_id: None,
+            notes: None,
+            project_id: None,
         };
 
         let result = create_time_entry_impl(&conn, &input2);
         assert!(result.is_err());
-        assert!(result.unwrap_err().contains("overlaps"));
+        let error = result.unwrap_err();
+        assert_eq!(error.kind, ErrorKind::Conflict);
+        assert_eq!(error.code, "time_entry_overlap");
+        assert_eq!(error.details.unwrap().conflicting_entry_ids, vec![first.id]);
     }
 
     #[test]
@@ -219,6 +235,8 @@ mod cases {
             label: "Task 1".to_string(),
             color: None,
             category_id: None,
+            notes: None,
+            project_id: None,
         };
         create_time_entry_impl(&conn, &input1).unwrap();
 
@@ -229,6 +247,8 @@ mod cases {
             label: "Task 2".to_string(),
             color: None,
             category_id: None,
+            notes: None,
+            project_id: None,
         };
 
         let result = create_time_entry_impl(&conn, &input2);
@@ -246,6 +266,8 @@ mod cases {
             label: "Long Running".to_string(),
             color: None,
             category_id: None,
+            notes: None,
+            project_id: None,
         };
         create_time_entry_impl(&conn, &long_running).unwrap();
 
@@ -256,10 +278,377 @@ mod cases {
             label: "Next Day Task".to_string(),
             color: None,
             category_id: None,
+            notes: None,
+            project_id: None,
         };
 
         let result = create_time_entry_impl(&conn, &next_day_overlap);
         assert!(result.is_err());
-        assert!(result.unwrap_err().contains("overlaps"));
+        assert_eq!(result.unwrap_err().kind, ErrorKind::Conflict);
+    }
+
+    #[test]
+    fn test_missing_entry_is_not_found() {
+        let conn = setup_test_db();
+
+        let updates = TimeEntryUpdate {
+            start_time: None,
+            end_time: None,
+            label: Some("Renamed".to_string()),
+            color: None,
+            category_id: None,
+            notes: None,
+            project_id: None,
+        };
+
+        let error = update_time_entry_impl(&conn, 42, &updates).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::NotFound);
+        assert_eq!(error.code, "time_entry_not_found");
+
+        let error = delete_time_entry_impl(&conn, 42).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_unknown_category_is_rejected() {
+        let conn = setup_test_db();
+
+        let input = TimeEntryInput {
+            start_time: 1000,
+            end_time: 2000,
+            label: "Task".to_string(),
+            color: None,
+            category_id: Some(42),
+            notes: None,
+            project_id: None,
+        };
+        let error = create_time_entry_impl(&conn, &input).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::Validation);
+        assert_eq!(error.code, "unknown_category");
+
+        let entry = create_time_entry_impl(
+            &conn,
+            &TimeEntryInput {
+                category_id: None,
+                ..input
+            },
+        )
+        .unwrap();
+        let updates = TimeEntryUpdate {
+            start_time: None,
+            end_time: None,
+            label: None,
+            color: None,
+            category_id: Some(Some(42)),
+            notes: None,
+            project_id: None,
+        };
+        let error = update_time_entry_impl(&conn, entry.id, &updates).unwrap_err();
+        assert_eq!(error.code, "unknown_category");
+    }
+
+    #[test]
+    fn test_deleting_category_clears_entries() {
+        let conn = setup_test_db();
+        let category = create_category_impl(
+            &conn,
+            &CategoryInput {
+                name: "Work".to_string(),
+                color: "#123456".to_string(),
+            },
+        )
+        .unwrap();
+
+        let entry = create_time_entry_impl(
+            &conn,
+            &TimeEntryInput {
+                start_time: 1000,
+                end_time: 2000,
+                label: "Task".to_string(),
+                color: None,
+                category_id: Some(category.id),
+                notes: None,
+                project_id: None,
+            },
+        )
+        .unwrap();
+
+        delete_category_impl(&conn, category.id).unwrap();
+
+        let entries = get_time_entries_impl(&conn, 0).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].id, entry.id);
+        assert_eq!(entries[0].category_id, None);
+    }
+
+    fn entry_input(start_time: i64, end_time: i64, label: &str) -> TimeEntryInput {
+        TimeEntryInput {
+            start_time,
+            end_time,
+            label: label.to_string(),
+            color: None,
+            category_id: None,
+            notes: None,
+            project_id: None,
+        }
+    }
+
+    #[test]
+    fn test_deleted_entry_moves_to_trash() {
+        let conn = setup_test_db();
+        let entry = create_time_entry_impl(&conn, &entry_input(1000, 2000, "Trashed")).unwrap();
+
+        delete_time_entry_impl(&conn, entry.id).unwrap();
+
+        assert!(get_time_entries_by_range_impl(&conn, 0, 5000)
+            .unwrap()
+            .is_empty());
+        let trash = get_trashed_time_entries_impl(&conn).unwrap();
+        assert_eq!(trash.len(), 1);
+        assert_eq!(trash[0].entry.id, entry.id);
+
+        // Trashed entries cannot be edited or deleted again
+        let updates = TimeEntryUpdate {
+            start_time: None,
+            end_time: None,
+            label: Some("Edited".to_string()),
+            color: None,
+            category_id: None,
+            notes: None,
+            project_id: None,
+        };
+        let error = update_time_entry_impl(&conn, entry.id, &updates).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::NotFound);
+        assert_eq!(
+            delete_time_entry_impl(&conn, entry.id).unwrap_err().kind,
+            ErrorKind::NotFound
+        );
+
+        // ...and no longer block their slot
+        create_time_entry_impl(&conn, &entry_input(1500, 2500, "Replacement")).unwrap();
+    }
+
+    #[test]
+    fn test_restore_time_entry() {
+        let conn = setup_test_db();
+        let entry = create_time_entry_impl(&conn, &entry_input(1000, 2000, "Restored")).unwrap();
+        delete_time_entry_impl(&conn, entry.id).unwrap();
+
+        let blocker = create_time_entry_impl(&conn, &entry_input(1500, 2500, "Blocker")).unwrap();
+        let error = restore_time_entry_impl(&conn, entry.id).unwrap_err();
+        assert_eq!(error.code, "time_entry_overlap");
+        assert_eq!(
+            error.details.unwrap().conflicting_entry_ids,
+            vec![blocker.id]
+        );
+
+        delete_time_entry_impl(&conn, blocker.id).unwrap();
+        let restored = restore_time_entry_impl(&conn, entry.id).unwrap();
+        assert_eq!(restored.label, "Restored");
+        assert_eq!(get_trashed_time_entries_impl(&conn).unwrap().len(), 1);
+
+        let error = restore_time_entry_impl(&conn, entry.id).unwrap_err();
+        assert_eq!(error.code, "trashed_time_entry_not_found");
+    }
+
+    #[test]
+    fn test_purge_trash() {
+        let conn = setup_test_db();
+        let ids: Vec<i64> = (0..3)
+            .map(|i| {
+                let entry =
+                    create_time_entry_impl(&conn, &entry_input(i * 1000, i * 1000 + 500, "Entry"))
+                        .unwrap();
+                delete_time_entry_impl(&conn, entry.id).unwrap();
+                entry.id
+            })
+            .collect();
+        let live = create_time_entry_impl(&conn, &entry_input(9000, 9500, "Live")).unwrap();
+
+        assert_eq!(
+            purge_trashed_time_entries_impl(&conn, Some(&[ids[0], live.id])).unwrap(),
+            1
+        );
+        assert_eq!(purge_trash_before(&conn, 0).unwrap(), 0);
+        assert_eq!(purge_trash_before(&conn, i64::MAX).unwrap(), 2);
+        assert!(get_trashed_time_entries_impl(&conn).unwrap().is_empty());
+        assert_eq!(
+            get_time_entries_by_range_impl(&conn, 0, 10000)
+                .unwrap()
+                .len(),
+            1
+        );
+
+        delete_time_entry_impl(&conn, live.id).unwrap();
+        assert_eq!(purge_trashed_time_entries_impl(&conn, None).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_split_time_entry() {
+        let conn = setup_test_db();
+        let mut input = entry_input(1000, 5000, "Meeting");
+        input.color = Some("#4CAF50".to_string());
+        let entry = create_time_entry_impl(&conn, &input).unwrap();
+
+        for at in [1000, 5000, 7000] {
+            let error = split_time_entry_impl(&conn, entry.id, at, None, None).unwrap_err();
+            assert_eq!(error.code, "invalid_split_point");
+        }
+
+        let split = split_time_entry_impl(&conn, entry.id, 3000, Some("Review"), None).unwrap();
+        assert_eq!(split.first.id, entry.id);
+        assert_eq!((split.first.start_time, split.first.end_time), (1000, 3000));
+        assert_eq!(
+            (split.second.start_time, split.second.end_time),
+            (3000, 5000)
+        );
+        assert_eq!(split.second.label, "Review");
+        assert_eq!(split.second.color.as_deref(), Some("#4CAF50"));
+
+        let error =
+            split_time_entry_impl(&conn, split.second.id, 4000, Some("  "), None).unwrap_err();
+        assert_eq!(error.code, "empty_label");
+        // The failed split left the entry whole
+        assert_eq!(
+            get_time_entries_by_range_impl(&conn, 0, 10000)
+                .unwrap()
+                .len(),
+            2
+        );
+
+        // One undo step restores the original entry
+        crate::data::journal::undo(&conn).unwrap();
+        let entries = get_time_entries_by_range_impl(&conn, 0, 10000).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!((entries[0].start_time, entries[0].end_time), (1000, 5000));
+    }
+
+    #[test]
+    fn test_merge_time_entries() {
+        let conn = setup_test_db();
+        let a = create_time_entry_impl(&conn, &entry_input(1000, 2000, "A")).unwrap();
+        let b = create_time_entry_impl(&conn, &entry_input(2000, 3000, "B")).unwrap();
+        let c = create_time_entry_impl(&conn, &entry_input(4000, 5000, "C")).unwrap();
+
+        let error = merge_time_entries_impl(&conn, &[a.id, a.id], None, None, false).unwrap_err();
+        assert_eq!(error.code, "merge_needs_two_entries");
+        let error = merge_time_entries_impl(&conn, &[a.id, c.id], None, None, false).unwrap_err();
+        assert_eq!(error.code, "merge_has_gaps");
+        // B sits between A and C
+        let error = merge_time_entries_impl(&conn, &[a.id, c.id], None, None, true).unwrap_err();
+        assert_eq!(error.code, "merge_swallows_entry");
+        assert_eq!(error.details.unwrap().conflicting_entry_ids, vec![b.id]);
+
+        let merged =
+            merge_time_entries_impl(&conn, &[c.id, b.id, a.id], Some("All"), None, true).unwrap();
+        assert_eq!(merged.id, a.id);
+        assert_eq!((merged.start_time, merged.end_time), (1000, 5000));
+        assert_eq!(merged.label, "All");
+        assert_eq!(get_trashed_time_entries_impl(&conn).unwrap().len(), 2);
+
+        // One undo step brings all three back
+        crate::data::journal::undo(&conn).unwrap();
+        let entries = get_time_entries_by_range_impl(&conn, 0, 10000).unwrap();
+        assert_eq!(
+            entries.iter().map(|e| e.label.as_str()).collect::<Vec<_>>(),
+            vec!["A", "B", "C"]
+        );
+    }
+
+    #[test]
+    fn test_entry_notes() {
+        let conn = setup_test_db();
+        let mut input = entry_input(1000, 2000, "A");
+        input.notes = Some("- drafted the spec".to_string());
+        let a = create_time_entry_impl(&conn, &input).unwrap();
+        assert_eq!(a.notes.as_deref(), Some("- drafted the spec"));
+
+        let mut input = entry_input(2000, 3000, "B");
+        input.notes = Some("  ".to_string());
+        let b = create_time_entry_impl(&conn, &input).unwrap();
+        assert_eq!(b.notes, None);
+
+        let notes_update = |notes: &str| TimeEntryUpdate {
+            start_time: None,
+            end_time: None,
+            label: None,
+            color: None,
+            category_id: None,
+            notes: Some(notes.to_string()),
+            project_id: None,
+        };
+        let b = update_time_entry_impl(&conn, b.id, &notes_update("**reviewed**")).unwrap();
+        assert_eq!(b.notes.as_deref(), Some("**reviewed**"));
+
+        // Merging keeps everyone's notes, in order
+        let merged = merge_time_entries_impl(&conn, &[a.id, b.id], None, None, false).unwrap();
+        assert_eq!(
+            merged.notes.as_deref(),
+            Some("- drafted the spec\n\n**reviewed**")
+        );
+
+        // An empty string clears them
+        let cleared = update_time_entry_impl(&conn, a.id, &notes_update("")).unwrap();
+        assert_eq!(cleared.notes, None);
+    }
+
+    const HOUR_MS: i64 = 60 * 60 * 1000;
+    // 1970-01-05 00:00 UTC, a Monday
+    const MONDAY: i64 = 4 * 24 * HOUR_MS;
+
+    #[test]
+    fn test_working_windows() {
+        let hours = crate::app_settings::WorkingHoursSettings::default();
+        let windows = working_windows(&chrono::Utc, MONDAY, MONDAY + 7 * 24 * HOUR_MS, &hours);
+        assert_eq!(windows.len(), 5);
+        assert_eq!(windows[0], (MONDAY + 9 * HOUR_MS, MONDAY + 18 * HOUR_MS));
+
+        // Clipped to the range
+        let windows = working_windows(
+            &chrono::Utc,
+            MONDAY + 12 * HOUR_MS,
+            MONDAY + 14 * HOUR_MS,
+            &hours,
+        );
+        assert_eq!(
+            windows,
+            vec![(MONDAY + 12 * HOUR_MS, MONDAY + 14 * HOUR_MS)]
+        );
+    }
+
+    #[test]
+    fn test_untracked_gaps() {
+        let conn = setup_test_db();
+        let at = |hour: i64| MONDAY + hour * HOUR_MS;
+        create_time_entry_impl(&conn, &entry_input(at(8), at(10), "Early")).unwrap();
+        create_time_entry_impl(&conn, &entry_input(at(11), at(17), "Work")).unwrap();
+        conn.execute(
+            "INSERT INTO process_segments (start_time, end_time, process_name)
+             VALUES (?1, ?2, 'mail.exe'), (?3, ?4, 'code.exe')",
+            rusqlite::params![at(10), at(10) + 1000, at(10) + 1000, at(11)],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO window_activity (timestamp, window_title, process_name)
+             VALUES (?1, 'main.rs', 'code.exe')",
+            [at(10) + 60_000],
+        )
+        .unwrap();
+
+        let windows = [(at(9), at(18))];
+        let gaps = get_untracked_gaps_impl(&conn, at(0), at(24), 0, &windows).unwrap();
+        assert_eq!(
+            gaps.iter()
+                .map(|g| (g.start_time, g.end_time))
+                .collect::<Vec<_>>(),
+            vec![(at(10), at(11)), (at(17), at(18))]
+        );
+        assert_eq!(gaps[0].processes[0].process_name, "code.exe");
+        assert_eq!(gaps[0].processes[1].process_name, "mail.exe");
+        assert_eq!(gaps[0].window_titles[0].window_title, "main.rs");
+        assert!(gaps[1].processes.is_empty());
+
+        let gaps = get_untracked_gaps_impl(&conn, at(0), at(24), 2 * HOUR_MS, &windows).unwrap();
+        assert!(gaps.is_empty());
     }
 }
@@ -0,0 +1,477 @@
+// Server-side running timer.
+//
+// A running timer is an ordinary time entry plus the single row in
+// `running_timer` pointing at it. While it runs, the entry claims everything
+// from its start onwards for overlap checks, and its stored `end_time`
+// follows the heartbeat so range queries and exports see it as it grows.
+
+use crate::app_settings::TimerStartupPolicy;
+use crate::data::error::{db_err, ErrorKind};
+use crate::data::journal;
+use crate::data::time_entries::{
+    create_time_entry_impl, find_overlapping_entry_ids, get_time_entry_by_id,
+    update_time_entry_impl,
+};
+use crate::data::{AppError, AppResult};
+use crate::types::{RunningTimer, TimeEntry, TimeEntryInput, TimeEntryUpdate, TimerStart};
+use rusqlite::{params, Connection, OptionalExtension};
+
+/// Id of the entry the timer is counting, ignoring a timer whose entry was trashed.
+pub fn running_entry_id(conn: &Connection) -> AppResult<Option<i64>> {
+    let id = conn
+        .query_row(
+            "SELECT r.entry_id FROM running_timer r
+             JOIN time_entries e ON e.id = r.entry_id
+             WHERE e.deleted_at IS NULL",
+            [],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(db_err("Failed to read running timer"))?;
+    Ok(id)
+}
+
+pub fn get_running_timer_impl(conn: &Connection) -> AppResult<Option<RunningTimer>> {
+    let running: Option<(i64, i64)> = conn
+        .query_row(
+            "SELECT r.entry_id, r.last_seen_at FROM running_timer r
+             JOIN time_entries e ON e.id = r.entry_id
+             WHERE e.deleted_at IS NULL",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+        .map_err(db_err("Failed to read running timer"))?;
+
+    let Some((entry_id, last_seen_at)) = running else {
+        return Ok(None);
+    };
+    Ok(Some(RunningTimer {
+        entry: get_time_entry_by_id(conn, entry_id)?,
+        last_seen_at,
+    }))
+}
+
+/// Create an entry starting at `start.start_time` (default `now`) and start counting it.
+pub fn start_timer_impl(
+    conn: &Connection,
+    start: &TimerStart,
+    now: i64,
+) -> AppResult<RunningTimer> {
+    ensure_not_running(conn)?;
+
+    let start_time = start.start_time.unwrap_or(now);
+    if start_time > now {
+        return Err(AppError::validation(
+            "timer_start_in_future",
+            "A timer cannot start in the future",
+        )
+        .with_field("start_time"));
+    }
+    ensure_open_range_free(conn, start_time, now, None)?;
+
+    let tx = conn
+        .unchecked_transaction()
+        .map_err(db_err("Failed to start transaction"))?;
+    let entry = create_time_entry_impl(
+        &tx,
+        &TimeEntryInput {
+            start_time,
+            end_time: now.max(start_time + 1),
+            label: start.label.clone(),
+            color: start.color.clone(),
+            category_id: start.category_id,
+            notes: None,
+            project_id: None,
+        },
+    )?;
+    set_running(&tx, entry.id, now)?;
+    tx.commit()
+        .map_err(db_err("Failed to commit timer start"))?;
+
+    Ok(RunningTimer {
+        entry,
+        last_seen_at: now,
+    })
+}
+
+/// Start counting an existing entry again, extending it up to `now`.
+pub fn resume_timer_impl(conn: &Connection, id: i64, now: i64) -> AppResult<RunningTimer> {
+    ensure_not_running(conn)?;
+
+    let existing = get_time_entry_by_id(conn, id)?;
+    ensure_open_range_free(conn, existing.start_time, now, Some(id))?;
+
+    let tx = conn
+        .unchecked_transaction()
+        .map_err(db_err("Failed to start transaction"))?;
+    let entry = update_time_entry_impl(
+        &tx,
+        id,
+        &TimeEntryUpdate {
+            start_time: None,
+            end_time: Some(now.max(existing.start_time + 1)),
+            label: None,
+            color: None,
+            category_id: None,
+            notes: None,
+            project_id: None,
+        },
+    )?;
+    set_running(&tx, id, now)?;
+    tx.commit()
+        .map_err(db_err("Failed to commit timer resume"))?;
+
+    Ok(RunningTimer {
+        entry,
+        last_seen_at: now,
+    })
+}
+
+/// Stop the timer and close its entry at `end_time`.
+pub fn stop_timer_impl(conn: &Connection, end_time: i64) -> AppResult<TimeEntry> {
+    let Some(id) = running_entry_id(conn)? else {
+        return Err(AppError::not_found(
+            "timer_not_running",
+            "No timer is running",
+        ));
+    };
+
+    let tx = conn
+        .unchecked_transaction()
+        .map_err(db_err("Failed to start transaction"))?;
+    clear_running_timer(&tx, id)?;
+    let entry = update_time_entry_impl(
+        &tx,
+        id,
+        &TimeEntryUpdate {
+            start_time: None,
+            end_time: Some(end_time),
+            label: None,
+            color: None,
+            category_id: None,
+            notes: None,
+            project_id: None,
+        },
+    )?;
+    tx.commit().map_err(db_err("Failed to commit timer stop"))?;
+
+    Ok(entry)
+}
+
+/// Move the running entry's end up to `now`. Returns whether a timer is running.
+///
+/// Not journaled: the heartbeat is not an edit. Reaching the start of a later
+/// entry stops the timer there, which lands in the entry's revision history but
+/// leaves the user's undo and redo history as it was.
+pub fn heartbeat(conn: &Connection, now: i64) -> AppResult<bool> {
+    let next_start: Option<i64> = conn
+        .query_row(
+            "SELECT MIN(next.start_time) FROM running_timer r
+             JOIN time_entries e ON e.id = r.entry_id AND e.deleted_at IS NULL
+             JOIN time_entries next ON next.deleted_at IS NULL
+                 AND next.id != e.id AND next.start_time > e.start_time",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(db_err("Failed to find next time entry"))?;
+    if let Some(next_start) = next_start.filter(|&next_start| now >= next_start) {
+        journal::untracked(|| stop_timer_impl(conn, next_start))?;
+        return Ok(false);
+    }
+
+    let updated = conn
+        .execute(
+            "UPDATE time_entries SET end_time = MAX(?1, start_time + 1)
+             WHERE deleted_at IS NULL AND id = (SELECT entry_id FROM running_timer)",
+            params![now],
+        )
+        .map_err(db_err("Failed to extend running timer entry"))?;
+
+    if updated == 0 {
+        // Nothing running, or its entry is gone
+        conn.execute("DELETE FROM running_timer", [])
+            .map_err(db_err("Failed to clear running timer"))?;
+        return Ok(false);
+    }
+
+    conn.execute("UPDATE running_timer SET last_seen_at = ?1", params![now])
+        .map_err(db_err("Failed to update running timer"))?;
+    Ok(true)
+}
+
+/// Apply `policy` to a timer left running when the database was last closed.
+///
+/// Returns the entry if the policy stopped it. Like the heartbeat, the stop is
+/// not recorded for undo.
+pub fn settle_on_startup(
+    conn: &Connection,
+    policy: TimerStartupPolicy,
+    now: i64,
+) -> AppResult<Option<TimeEntry>> {
+    let Some(timer) = get_running_timer_impl(conn)? else {
+        // Drop a timer whose entry was trashed
+        heartbeat(conn, now)?;
+        return Ok(None);
+    };
+
+    match policy {
+        TimerStartupPolicy::Resume => {
+            heartbeat(conn, now)?;
+            Ok(None)
+        }
+        TimerStartupPolicy::Stop => {
+            let end_time = timer.last_seen_at.max(timer.entry.start_time + 1);
+            journal::untracked(|| stop_timer_impl(conn, end_time)).map(Some)
+        }
+    }
+}
+
+/// Stop counting entry `id` without touching the entry itself.
+pub fn clear_running_timer(conn: &Connection, id: i64) -> AppResult<()> {
+    conn.execute("DELETE FROM running_timer WHERE entry_id = ?1", params![id])
+        .map_err(db_err("Failed to clear running timer"))?;
+    Ok(())
+}
+
+/// Make entry `id` the one the timer counts.
+pub fn set_running(conn: &Connection, id: i64, now: i64) -> AppResult<()> {
+    conn.execute(
+        "INSERT INTO running_timer (id, entry_id, last_seen_at) VALUES (1, ?1, ?2)
+         ON CONFLICT(id) DO UPDATE SET entry_id = excluded.entry_id, last_seen_at = excluded.last_seen_at",
+        params![id, now],
+    )
+    .map_err(db_err("Failed to start timer"))?;
+    Ok(())
+}
+
+fn ensure_not_running(conn: &Connection) -> AppResult<()> {
+    match running_entry_id(conn)? {
+        Some(id) => Err(AppError::new(
+            ErrorKind::Conflict,
+            "timer_already_running",
+            format!("A timer is already running on time entry {}", id),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// A running entry covers everything from its start up to `now`.
+///
+/// Entries planned later, such as upcoming recurring ones, do not block it:
+/// the heartbeat stops the timer once it reaches them.
+fn ensure_open_range_free(
+    conn: &Connection,
+    start_time: i64,
+    now: i64,
+    exclude_id: Option<i64>,
+) -> AppResult<()> {
+    let end_time = now.max(start_time + 1);
+    let overlapping = find_overlapping_entry_ids(conn, start_time, end_time, exclude_id)?;
+    if !overlapping.is_empty() {
+        return Err(AppError::overlap(overlapping));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::database::{enable_foreign_keys, run_migrations};
+    use crate::data::time_entries::delete_time_entry_impl;
+
+    fn setup_test_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        enable_foreign_keys(&conn).unwrap();
+        run_migrations(&conn).unwrap();
+        conn
+    }
+
+    fn timer_start(label: &str, start_time: Option<i64>) -> TimerStart {
+        TimerStart {
+            start_time,
+            label: label.to_string(),
+            color: None,
+            category_id: None,
+        }
+    }
+
+    fn entry_input(start_time: i64, end_time: i64) -> TimeEntryInput {
+        TimeEntryInput {
+            start_time,
+            end_time,
+            label: "Entry".to_string(),
+            color: None,
+            category_id: None,
+            notes: None,
+            project_id: None,
+        }
+    }
+
+    #[test]
+    fn test_start_heartbeat_and_stop() {
+        let conn = setup_test_db();
+
+        let timer = start_timer_impl(&conn, &timer_start("Writing", Some(1000)), 5000).unwrap();
+        assert_eq!(timer.entry.start_time, 1000);
+        assert_eq!(timer.entry.end_time, 5000);
+
+        let err = start_timer_impl(&conn, &timer_start("Other", None), 6000).unwrap_err();
+        assert_eq!(err.code, "timer_already_running");
+
+        assert!(heartbeat(&conn, 9000).unwrap());
+        let running = get_running_timer_impl(&conn).unwrap().unwrap();
+        assert_eq!(running.entry.end_time, 9000);
+        assert_eq!(running.last_seen_at, 9000);
+
+        let stopped = stop_timer_impl(&conn, 10_000).unwrap();
+        assert_eq!(stopped.end_time, 10_000);
+        assert!(get_running_timer_impl(&conn).unwrap().is_none());
+
+        let err = stop_timer_impl(&conn, 11_000).unwrap_err();
+        assert_eq!(err.code, "timer_not_running");
+    }
+
+    #[test]
+    fn test_running_entry_is_open_ended() {
+        let conn = setup_test_db();
+        let later = create_time_entry_impl(&conn, &entry_input(20_000, 30_000)).unwrap();
+
+        // Would run back over an entry already tracked
+        let err = start_timer_impl(&conn, &timer_start("Late", Some(10_000)), 25_000).unwrap_err();
+        assert_eq!(err.details.unwrap().conflicting_entry_ids, vec![later.id]);
+
+        // A later entry only ends the timer once it is reached
+        let early = start_timer_impl(&conn, &timer_start("Early", Some(1000)), 2000).unwrap();
+        assert!(heartbeat(&conn, 15_000).unwrap());
+        assert!(!heartbeat(&conn, 25_000).unwrap());
+        assert!(get_running_timer_impl(&conn).unwrap().is_none());
+        assert_eq!(
+            get_time_entry_by_id(&conn, early.entry.id)
+                .unwrap()
+                .end_time,
+            20_000
+        );
+
+        let timer = start_timer_impl(&conn, &timer_start("Running", Some(40_000)), 45_000).unwrap();
+
+        // Past the last heartbeat is still taken by the running entry
+        let err = create_time_entry_impl(&conn, &entry_input(60_000, 70_000)).unwrap_err();
+        assert_eq!(
+            err.details.unwrap().conflicting_entry_ids,
+            vec![timer.entry.id]
+        );
+        create_time_entry_impl(&conn, &entry_input(35_000, 40_000)).unwrap();
+
+        let err = update_time_entry_impl(
+            &conn,
+            timer.entry.id,
+            &TimeEntryUpdate {
+                start_time: None,
+                end_time: Some(50_000),
+                label: None,
+                color: None,
+                category_id: None,
+                notes: None,
+                project_id: None,
+            },
+        )
+        .unwrap_err();
+        assert_eq!(err.code, "timer_running");
+    }
+
+    #[test]
+    fn test_automatic_stop_keeps_redo_history() {
+        let conn = setup_test_db();
+        let earlier = create_time_entry_impl(&conn, &entry_input(0, 500)).unwrap();
+        create_time_entry_impl(&conn, &entry_input(20_000, 30_000)).unwrap();
+        start_timer_impl(&conn, &timer_start("Running", Some(1000)), 2000).unwrap();
+
+        let rename = TimeEntryUpdate {
+            start_time: None,
+            end_time: None,
+            label: Some("Renamed".to_string()),
+            color: None,
+            category_id: None,
+            notes: None,
+            project_id: None,
+        };
+        update_time_entry_impl(&conn, earlier.id, &rename).unwrap();
+        journal::undo(&conn).unwrap();
+        assert!(journal::undo_state(&conn).unwrap().can_redo);
+
+        // Reaching the later entry stops the timer without touching redo
+        assert!(!heartbeat(&conn, 25_000).unwrap());
+        assert!(journal::undo_state(&conn).unwrap().can_redo);
+        journal::redo(&conn).unwrap();
+        assert_eq!(
+            get_time_entry_by_id(&conn, earlier.id).unwrap().label,
+            "Renamed"
+        );
+    }
+
+    #[test]
+    fn test_deleting_running_entry_stops_timer() {
+        let conn = setup_test_db();
+        let timer = start_timer_impl(&conn, &timer_start("Running", Some(1000)), 2000).unwrap();
+
+        delete_time_entry_impl(&conn, timer.entry.id).unwrap();
+
+        assert!(get_running_timer_impl(&conn).unwrap().is_none());
+        assert!(!heartbeat(&conn, 3000).unwrap());
+        create_time_entry_impl(&conn, &entry_input(5000, 6000)).unwrap();
+    }
+
+    #[test]
+    fn test_resume_entry() {
+        let conn = setup_test_db();
+        let entry = create_time_entry_impl(&conn, &entry_input(1000, 2000)).unwrap();
+
+        let timer = resume_timer_impl(&conn, entry.id, 8000).unwrap();
+        assert_eq!(timer.entry.id, entry.id);
+        assert_eq!(timer.entry.end_time, 8000);
+        assert_eq!(running_entry_id(&conn).unwrap(), Some(entry.id));
+    }
+
+    #[test]
+    fn test_split_running_entry_keeps_timer_on_second_half() {
+        let conn = setup_test_db();
+        let timer = start_timer_impl(&conn, &timer_start("Running", Some(1000)), 9000).unwrap();
+
+        let split = crate::data::time_entries::split_time_entry_impl(
+            &conn,
+            timer.entry.id,
+            5000,
+            Some("Next"),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(split.first.end_time, 5000);
+        assert_eq!(running_entry_id(&conn).unwrap(), Some(split.second.id));
+        assert_eq!(stop_timer_impl(&conn, 12_000).unwrap().id, split.second.id);
+    }
+
+    #[test]
+    fn test_settle_on_startup() {
+        let conn = setup_test_db();
+        start_timer_impl(&conn, &timer_start("Running", Some(1000)), 2000).unwrap();
+        heartbeat(&conn, 3000).unwrap();
+
+        // App restarted an hour later
+        assert!(
+            settle_on_startup(&conn, TimerStartupPolicy::Resume, 3_603_000)
+                .unwrap()
+                .is_none()
+        );
+        let running = get_running_timer_impl(&conn).unwrap().unwrap();
+        assert_eq!(running.entry.end_time, 3_603_000);
+
+        heartbeat(&conn, 3_700_000).unwrap();
+        let stopped = settle_on_startup(&conn, TimerStartupPolicy::Stop, 9_000_000)
+            .unwrap()
+            .unwrap();
+        assert_eq!(stopped.end_time, 3_700_000);
+        assert!(get_running_timer_impl(&conn).unwrap().is_none());
+    }
+}
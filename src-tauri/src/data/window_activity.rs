@@ -1,5 +1,6 @@
+use crate::data::error::db_err;
 use crate::data::AppResult;
-use crate::types::WindowActivity;
+use crate::types::{WindowActivity, WindowTitleCount};
 use rusqlite::{params, Connection};
 
 /// Insert a single window activity record
@@ -13,7 +14,7 @@ pub fn insert_window_activity(conn: &Connection, activity: &WindowActivity) -> A
             &activity.process_name
         ],
     )
-    .map_err(|e| format!("Failed to insert window activity: {}", e))?;
+    .map_err(db_err("Failed to insert window activity"))?;
 
     Ok(())
 }
@@ -25,7 +26,7 @@ pub fn insert_window_activities_batch(
 ) -> AppResult<()> {
     let tx = conn
         .unchecked_transaction()
-        .map_err(|e| format!("Failed to start transaction: {}", e))?;
+        .map_err(db_err("Failed to start transaction"))?;
 
     for activity in activities {
         tx.execute(
@@ -36,11 +37,11 @@ pub fn insert_window_activities_batch(
                 &activity.process_name
             ],
         )
-        .map_err(|e| format!("Failed to insert window activity: {}", e))?;
+        .map_err(db_err("Failed to insert window activity"))?;
     }
 
     tx.commit()
-        .map_err(|e| format!("Failed to commit transaction: {}", e))?;
+        .map_err(db_err("Failed to commit transaction"))?;
 
     Ok(())
 }
@@ -58,7 +59,7 @@ pub fn get_window_activities(
          WHERE timestamp >= ? AND timestamp < ?
          ORDER BY timestamp",
         )
-        .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        .map_err(db_err("Failed to prepare query"))?;
 
     let activity_iter = stmt
         .query_map(params![start_time, end_time], |row| {
@@ -69,16 +70,66 @@ pub fn get_window_activities(
                 process_name: row.get(3)?,
             })
         })
-        .map_err(|e| format!("Failed to query window activities: {}", e))?;
+        .map_err(db_err("Failed to query window activities"))?;
 
     let mut activities = Vec::new();
     for activity in activity_iter {
-        activities.push(activity.map_err(|e| format!("Failed to map row: {}", e))?);
+        activities.push(activity.map_err(db_err("Failed to map row"))?);
     }
 
     Ok(activities)
 }
 
+/// The `limit` window titles captured most often in `[start_time, end_time)`, most first.
+pub fn get_window_title_counts(
+    conn: &Connection,
+    start_time: i64,
+    end_time: i64,
+    limit: usize,
+) -> AppResult<Vec<WindowTitleCount>> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT window_title, process_name, COUNT(*) AS samples FROM window_activity
+             WHERE timestamp >= ?1 AND timestamp < ?2
+             GROUP BY window_title, process_name
+             ORDER BY samples DESC, window_title
+             LIMIT ?3",
+        )
+        .map_err(db_err("Failed to prepare window title query"))?;
+
+    let counts = stmt
+        .query_map(params![start_time, end_time, limit as i64], |row| {
+            Ok(WindowTitleCount {
+                window_title: row.get(0)?,
+                process_name: row.get(1)?,
+                samples: row.get(2)?,
+            })
+        })
+        .map_err(db_err("Failed to query window titles"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(db_err("Failed to collect window titles"))?;
+
+    Ok(counts)
+}
+
+/// Delete up to `limit` of the oldest records captured before `cutoff_timestamp`.
+pub fn delete_window_activities_before(
+    conn: &Connection,
+    cutoff_timestamp: i64,
+    limit: usize,
+) -> AppResult<usize> {
+    let deleted = conn
+        .execute(
+            "DELETE FROM window_activity WHERE id IN (
+                 SELECT id FROM window_activity WHERE timestamp < ?1 ORDER BY timestamp LIMIT ?2
+             )",
+            params![cutoff_timestamp, limit as i64],
+        )
+        .map_err(db_err("Failed to delete old window activity"))?;
+
+    Ok(deleted)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
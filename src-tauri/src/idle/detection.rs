@@ -6,8 +6,8 @@
 #[allow(dead_code)]
 pub async fn resolve_idle_period(
     resolution: crate::types::IdlePeriodResolution,
-) -> Result<crate::types::IdlePeriod, String> {
-    crate::data::with_db(|conn| {
+) -> crate::data::AppResult<crate::types::IdlePeriod> {
+    crate::data::with_db_write(|conn| {
         crate::data::idle::resolve_idle_period_with_action(conn, &resolution)
     })
 }
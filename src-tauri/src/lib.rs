@@ -1,14 +1,17 @@
 // Library exports for integration tests
 
+pub mod app_paths;
 pub mod capture;
+pub mod crypto;
 pub mod data;
 pub mod idle;
 pub mod app_settings;
+pub mod profiles;
 pub mod types;
 
 // Re-export commonly used types and functions
-pub use data::{init_database, with_db, AppResult};
+pub use data::{init_database, with_db_read, with_db_write, AppError, AppResult};
 pub use types::{
     ExportData, ExportOptions, IdlePeriod, IdlePeriodResolution, ScreenshotInfo, SearchResult,
-    TimeEntry, TimeEntryInput, TimeEntryUpdate, WindowActivity, ProcessSample,
+    TimeEntry, TimeEntryInput, TimeEntryUpdate, WindowActivity, ProcessSegment,
 };
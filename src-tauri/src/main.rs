@@ -1,19 +1,67 @@
 // Prevents additional console window on Windows in release
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod app_paths;
 mod capture;
+mod crypto;
 mod data;
 mod idle;
 mod app_settings;
+mod profiles;
 mod types;
 
 use tauri::Manager;
 
 fn main() {
-    // Initialize database on startup
-    if let Err(e) = data::init_database() {
+    // Resolve --data-dir / --portable before anything touches the data root
+    app_paths::init_from_args(std::env::args());
+
+    if let Err(e) = profiles::init() {
+        eprintln!("Failed to load profiles: {}", e);
+    }
+
+    if let Err(e) = crypto::init_from_settings() {
+        eprintln!("Failed to load encryption settings: {}", e);
+    }
+
+    // --downgrade-schema <version> prepares the database for an older build, then exits
+    if let Some(target) = data::database::parse_downgrade_target(std::env::args()) {
+        match data::database::downgrade_database(&target) {
+            Ok(version) => {
+                println!("Database schema reverted to V{}", version);
+                std::process::exit(0);
+            }
+            Err(e) => {
+                eprintln!("Failed to downgrade database: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // Initialize database on startup; an encrypted one waits for the passphrase
+    if crypto::is_locked() {
+        println!("Database is encrypted; waiting for unlock");
+    } else if let Err(e) = data::init_database() {
         eprintln!("Failed to initialize database: {}", e);
-        std::process::exit(1);
+
+        // Repair a corrupt file; anything else is left for the UI to report
+        match data::repair_database_on_startup(e) {
+            Ok(report) => {
+                if let Some(path) = &report.corrupt_copy_path {
+                    eprintln!("Corrupt database moved to {}", path);
+                }
+                eprintln!("Database repair fixed {} issue(s)", report.issues.len());
+
+                if let Err(e) = data::init_database() {
+                    eprintln!("Failed to initialize database after repair: {}", e);
+                    data::set_startup_error(Some(e));
+                }
+            }
+            Err(e) => {
+                eprintln!("Database not repaired: {}", e);
+                data::set_startup_error(Some(e));
+            }
+        }
     }
 
     // Start background window capture task
@@ -34,10 +82,40 @@ fn main() {
         rt.block_on(start_process_sampling());
     });
 
-    // Cleanup old process samples (startup + hourly)
+    // Apply data retention policies (startup + hourly)
+    std::thread::spawn(|| {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(start_retention_job());
+    });
+
+    // Purge expired trash (startup + hourly)
+    std::thread::spawn(|| {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(start_trash_purge());
+    });
+
+    // WAL checkpoint hourly; optimize + vacuum daily during idle hours
     std::thread::spawn(|| {
         let rt = tokio::runtime::Runtime::new().unwrap();
-        rt.block_on(start_process_samples_cleanup());
+        rt.block_on(start_maintenance_scheduler());
+    });
+
+    // Keep the running timer's entry up to date (every 30 seconds)
+    std::thread::spawn(|| {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(start_timer_heartbeat());
+    });
+
+    // Recurring entries for the coming days (startup + hourly)
+    std::thread::spawn(|| {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(start_recurring_entries());
+    });
+
+    // Daily database backup (checked at startup + hourly)
+    std::thread::spawn(|| {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(start_backup_scheduler());
     });
 
     tauri::Builder::default()
@@ -63,21 +141,95 @@ fn main() {
             data::get_time_entries_by_range,
             data::create_time_entry,
             data::update_time_entry,
+            data::create_time_entry_with_strategy_cmd,
+            data::update_time_entry_with_strategy_cmd,
             data::delete_time_entry,
+            data::split_time_entry_cmd,
+            data::merge_time_entries_cmd,
+            data::preview_bulk_edit_cmd,
+            data::bulk_edit_time_entries_cmd,
+            data::start_timer_cmd,
+            data::resume_timer_cmd,
+            data::stop_timer_cmd,
+            data::get_running_timer_cmd,
+            data::get_untracked_gaps_cmd,
+            data::list_entry_templates_cmd,
+            data::create_entry_template_cmd,
+            data::update_entry_template_cmd,
+            data::delete_entry_template_cmd,
+            data::list_recurring_series_cmd,
+            data::create_recurring_series_cmd,
+            data::update_recurring_series_cmd,
+            data::delete_recurring_series_cmd,
+            data::materialize_recurring_entries_cmd,
+            data::get_trashed_time_entries_cmd,
+            data::restore_time_entry_cmd,
+            data::purge_trash_cmd,
+            data::undo_cmd,
+            data::redo_cmd,
+            data::get_undo_state_cmd,
+            data::get_time_entry_revisions_cmd,
+            data::revert_time_entry_cmd,
             data::get_categories,
             data::create_category,
             data::update_category,
             data::delete_category,
             data::get_screenshot_timestamps_for_day,
-            data::get_process_samples_for_day,
+            data::get_process_segments_for_day,
             data::search_activities_cmd,
             data::search_activities_by_range_cmd,
+            data::get_tags_cmd,
+            data::create_tag_cmd,
+            data::update_tag_cmd,
+            data::delete_tag_cmd,
+            data::get_time_entry_tags_cmd,
+            data::set_time_entry_tags_cmd,
+            data::get_time_entry_tags_by_range_cmd,
+            data::get_tag_totals_cmd,
+            data::get_clients_cmd,
+            data::create_client_cmd,
+            data::update_client_cmd,
+            data::delete_client_cmd,
+            data::get_projects_cmd,
+            data::create_project_cmd,
+            data::update_project_cmd,
+            data::delete_project_cmd,
+            data::get_billing_summary_cmd,
             data::export_data_cmd,
+            data::backup_database_cmd,
+            data::list_backups_cmd,
+            data::restore_database_cmd,
+            data::check_database_integrity_cmd,
+            data::run_retention_cmd,
+            data::preview_retention_cmd,
+            data::run_database_maintenance_cmd,
+            data::get_database_stats_cmd,
+            data::get_encryption_status_cmd,
+            data::unlock_database_cmd,
+            data::enable_encryption_cmd,
+            data::change_encryption_passphrase_cmd,
+            data::disable_encryption_cmd,
+            data::list_profiles_cmd,
+            data::create_profile_cmd,
+            data::switch_profile_cmd,
+            data::delete_profile_cmd,
             capture::screenshot::get_screenshot_for_time,
             app_settings::get_screenshot_settings_cmd,
             app_settings::update_screenshot_settings_cmd,
             app_settings::resolve_screenshot_storage_dir_cmd,
             app_settings::resolve_screenshot_file_path_cmd,
+            app_settings::get_backup_settings_cmd,
+            app_settings::update_backup_settings_cmd,
+            app_settings::get_trash_settings_cmd,
+            app_settings::update_trash_settings_cmd,
+            app_settings::get_retention_settings_cmd,
+            app_settings::update_retention_settings_cmd,
+            app_settings::get_maintenance_settings_cmd,
+            app_settings::update_maintenance_settings_cmd,
+            app_settings::get_timer_settings_cmd,
+            app_settings::update_timer_settings_cmd,
+            app_settings::get_working_hours_settings_cmd,
+            app_settings::update_working_hours_settings_cmd,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -89,15 +241,29 @@ fn main() {
 /// Batches inserts every 5 records (5 minutes) for performance
 async fn start_window_capture() {
     let mut buffer: Vec<capture::WindowActivityCapture> = Vec::new();
+    let mut buffer_profile = app_paths::profile_root().ok();
     const BATCH_SIZE: usize = 5;
 
     loop {
         if let Some(activity) = capture::get_active_window() {
+            // Never write activity captured under one profile into another
+            let profile = app_paths::profile_root().ok();
+            if profile != buffer_profile {
+                if !buffer.is_empty() {
+                    eprintln!(
+                        "Profile switched; dropping {} buffered window activities",
+                        buffer.len()
+                    );
+                }
+                buffer.clear();
+                buffer_profile = profile;
+            }
+
             buffer.push(activity);
 
             if buffer.len() >= BATCH_SIZE {
                 // Batch insert to database
-                if let Err(e) = data::with_db(|conn| {
+                if let Err(e) = data::with_db_write(|conn| {
                     data::insert_window_activities_batch(
                         conn,
                         &buffer
@@ -146,10 +312,10 @@ async fn start_process_sampling() {
     loop {
         if let Some(activity) = capture::get_active_window() {
             let aligned_timestamp = (activity.timestamp / 1000) * 1000;
-            if let Err(e) = data::with_db(|conn| {
-                data::insert_process_sample(conn, aligned_timestamp, &activity.process_name)
+            if let Err(e) = data::with_db_write(|conn| {
+                data::record_process_sample(conn, aligned_timestamp, &activity.process_name)
             }) {
-                eprintln!("Failed to insert process sample: {}", e);
+                eprintln!("Failed to record process sample: {}", e);
             }
         }
 
@@ -157,24 +323,98 @@ async fn start_process_sampling() {
     }
 }
 
-async fn start_process_samples_cleanup() {
-    if let Err(e) = cleanup_old_process_samples() {
-        eprintln!("Failed to cleanup old process samples on startup: {}", e);
+async fn start_retention_job() {
+    loop {
+        match data::run_retention() {
+            Ok(report) => {
+                let removed = report.process_segments
+                    + report.window_activity
+                    + report.screenshots
+                    + report.idle_periods;
+                if removed > 0 {
+                    println!(
+                        "Retention removed {} process segments, {} window activities, {} screenshots ({} bytes), {} idle periods",
+                        report.process_segments,
+                        report.window_activity,
+                        report.screenshots,
+                        report.screenshot_bytes,
+                        report.idle_periods
+                    );
+                }
+                if !report.screenshot_files_failed.is_empty() {
+                    eprintln!(
+                        "Retention could not delete {} screenshot files",
+                        report.screenshot_files_failed.len()
+                    );
+                }
+            }
+            Err(e) => eprintln!("Failed to apply retention policies: {}", e),
+        }
+        tokio::time::sleep(tokio::time::Duration::from_secs(3600)).await;
     }
+}
 
+async fn start_trash_purge() {
     loop {
+        match data::purge_expired_trash() {
+            Ok(0) => {}
+            Ok(purged) => println!("Trash purge removed {} time entries", purged),
+            Err(e) => eprintln!("Failed to purge trash: {}", e),
+        }
         tokio::time::sleep(tokio::time::Duration::from_secs(3600)).await;
-        if let Err(e) = cleanup_old_process_samples() {
-            eprintln!("Failed to cleanup old process samples: {}", e);
+    }
+}
+
+async fn start_maintenance_scheduler() {
+    loop {
+        match data::run_scheduled_maintenance() {
+            Ok(Some(report)) => println!(
+                "Database maintenance freed {} pages, checkpointed {} WAL pages",
+                report.freed_pages, report.wal_pages_checkpointed
+            ),
+            Ok(None) => {}
+            Err(e) => eprintln!("Database maintenance failed: {}", e),
         }
+        tokio::time::sleep(tokio::time::Duration::from_secs(3600)).await;
     }
 }
 
-fn cleanup_old_process_samples() -> Result<(), String> {
-    let cutoff = chrono::Utc::now().timestamp_millis() - (30_i64 * 24 * 60 * 60 * 1000);
-    let deleted = data::with_db(|conn| data::delete_process_samples_before(conn, cutoff))?;
-    if deleted > 0 {
-        println!("Process samples cleanup removed {} old records", deleted);
+async fn start_timer_heartbeat() {
+    loop {
+        // Quiet while an encrypted database waits for its passphrase
+        if let Err(e) = data::timer_heartbeat() {
+            if e.code != "database_locked" {
+                eprintln!("Failed to update running timer: {}", e);
+            }
+        }
+        tokio::time::sleep(tokio::time::Duration::from_secs(30)).await;
+    }
+}
+
+async fn start_recurring_entries() {
+    loop {
+        match data::materialize_recurring_entries() {
+            Ok(report) if !report.created.is_empty() || report.skipped > 0 => println!(
+                "Recurring entries: {} created, {} skipped as overlapping",
+                report.created.len(),
+                report.skipped
+            ),
+            Ok(_) => {}
+            // Quiet while an encrypted database waits for its passphrase
+            Err(e) if e.code == "database_locked" => {}
+            Err(e) => eprintln!("Failed to create recurring entries: {}", e),
+        }
+        tokio::time::sleep(tokio::time::Duration::from_secs(3600)).await;
+    }
+}
+
+async fn start_backup_scheduler() {
+    loop {
+        match data::run_scheduled_backup() {
+            Ok(Some(info)) => println!("Scheduled backup written to {}", info.path),
+            Ok(None) => {}
+            Err(e) => eprintln!("Scheduled backup failed: {}", e),
+        }
+        tokio::time::sleep(tokio::time::Duration::from_secs(3600)).await;
     }
-    Ok(())
 }
@@ -0,0 +1,278 @@
+// Named profiles, each with its own database, settings and screenshots.
+//
+// The default profile keeps using the data root itself so existing installs
+// need no migration; every other profile lives in `profiles/<id>` under the
+// data root. `profiles.json` in the data root lists the profiles and records
+// which one is active.
+
+use crate::data::{AppError, AppResult};
+use crate::types::{Profile, ProfileList};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_PROFILE_ID: &str = "default";
+
+const REGISTRY_FILE_NAME: &str = "profiles.json";
+const PROFILES_DIR_NAME: &str = "profiles";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Registry {
+    active: String,
+    profiles: Vec<Profile>,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self {
+            active: DEFAULT_PROFILE_ID.to_string(),
+            profiles: vec![default_profile()],
+        }
+    }
+}
+
+fn default_profile() -> Profile {
+    Profile {
+        id: DEFAULT_PROFILE_ID.to_string(),
+        name: "Default".to_string(),
+        created_at: 0,
+    }
+}
+
+fn load_registry(data_root: &Path) -> Result<Registry, String> {
+    let path = data_root.join(REGISTRY_FILE_NAME);
+    if !path.exists() {
+        return Ok(Registry::default());
+    }
+
+    let raw = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read profiles file {}: {}", path.display(), e))?;
+    let mut registry: Registry = serde_json::from_str(&raw)
+        .map_err(|e| format!("Failed to parse profiles file {}: {}", path.display(), e))?;
+
+    if !registry.profiles.iter().any(|p| p.id == DEFAULT_PROFILE_ID) {
+        registry.profiles.insert(0, default_profile());
+    }
+    if !registry.profiles.iter().any(|p| p.id == registry.active) {
+        registry.active = DEFAULT_PROFILE_ID.to_string();
+    }
+    Ok(registry)
+}
+
+fn save_registry(data_root: &Path, registry: &Registry) -> Result<(), String> {
+    let path = data_root.join(REGISTRY_FILE_NAME);
+    let serialized = serde_json::to_string_pretty(registry)
+        .map_err(|e| format!("Failed to serialize profiles: {}", e))?;
+    fs::write(&path, serialized)
+        .map_err(|e| format!("Failed to write profiles file {}: {}", path.display(), e))
+}
+
+/// Directory holding the data of profile `id`.
+pub fn profile_dir(data_root: &Path, id: &str) -> PathBuf {
+    if id == DEFAULT_PROFILE_ID {
+        data_root.to_path_buf()
+    } else {
+        data_root.join(PROFILES_DIR_NAME).join(id)
+    }
+}
+
+pub fn list_profiles(data_root: &Path) -> Result<ProfileList, String> {
+    let registry = load_registry(data_root)?;
+    Ok(ProfileList {
+        active: registry.active,
+        profiles: registry.profiles,
+    })
+}
+
+/// Register a new profile named `name` and create its directory.
+pub fn create_profile(data_root: &Path, name: &str, now: i64) -> AppResult<Profile> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(
+            AppError::validation("empty_profile_name", "Profile name cannot be empty")
+                .with_field("name"),
+        );
+    }
+
+    let mut registry = load_registry(data_root)?;
+    if registry
+        .profiles
+        .iter()
+        .any(|p| p.name.eq_ignore_ascii_case(name))
+    {
+        return Err(AppError::validation(
+            "duplicate_profile_name",
+            format!("A profile named '{}' already exists", name),
+        )
+        .with_field("name"));
+    }
+
+    let base = slugify(name);
+    let mut id = base.clone();
+    let mut suffix = 2;
+    while registry.profiles.iter().any(|p| p.id == id) || profile_dir(data_root, &id).exists() {
+        id = format!("{}-{}", base, suffix);
+        suffix += 1;
+    }
+
+    fs::create_dir_all(profile_dir(data_root, &id))
+        .map_err(|e| format!("Failed to create profile directory: {}", e))?;
+
+    let profile = Profile {
+        id,
+        name: name.to_string(),
+        created_at: now,
+    };
+    registry.profiles.push(profile.clone());
+    save_registry(data_root, &registry)?;
+    Ok(profile)
+}
+
+/// Make `id` the active profile and return its directory.
+///
+/// Only records the choice; the caller reopens the database.
+pub fn select_profile(data_root: &Path, id: &str) -> AppResult<PathBuf> {
+    let mut registry = load_registry(data_root)?;
+    if !registry.profiles.iter().any(|p| p.id == id) {
+        return Err(profile_not_found(id));
+    }
+
+    registry.active = id.to_string();
+    save_registry(data_root, &registry)?;
+    Ok(profile_dir(data_root, id))
+}
+
+/// Unregister profile `id` and delete its directory with everything in it.
+pub fn delete_profile(data_root: &Path, id: &str) -> AppResult<()> {
+    let mut registry = load_registry(data_root)?;
+    if id == DEFAULT_PROFILE_ID {
+        return Err(AppError::validation(
+            "default_profile",
+            "The default profile cannot be deleted",
+        ));
+    }
+    if id == registry.active {
+        return Err(AppError::validation(
+            "active_profile",
+            "Switch to another profile before deleting this one",
+        ));
+    }
+    let Some(index) = registry.profiles.iter().position(|p| p.id == id) else {
+        return Err(profile_not_found(id));
+    };
+
+    let dir = profile_dir(data_root, id);
+    if dir.exists() {
+        fs::remove_dir_all(&dir)
+            .map_err(|e| format!("Failed to delete profile directory: {}", e))?;
+    }
+
+    registry.profiles.remove(index);
+    save_registry(data_root, &registry)?;
+    Ok(())
+}
+
+/// Point the app paths at the profile that was active when the app last ran.
+pub fn init() -> Result<(), String> {
+    let data_root = crate::app_paths::data_root()?;
+    let active = load_registry(&data_root)?.active;
+    let root = (active != DEFAULT_PROFILE_ID).then(|| profile_dir(&data_root, &active));
+    crate::app_paths::set_profile_root(root)
+}
+
+fn profile_not_found(id: &str) -> AppError {
+    AppError::not_found(
+        "profile_not_found",
+        format!("Profile '{}' does not exist", id),
+    )
+}
+
+/// Lowercase ASCII directory name for a profile.
+fn slugify(name: &str) -> String {
+    let mut slug = String::new();
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug = slug.trim_end_matches('-');
+
+    if slug.is_empty() || slug == DEFAULT_PROFILE_ID {
+        "profile".to_string()
+    } else {
+        slug.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(slugify("Client Work"), "client-work");
+        assert_eq!(slugify("  ACME / Q3  "), "acme-q3");
+        assert_eq!(slugify("Ünïcødé"), "n-c-d");
+        assert_eq!(slugify("日本"), "profile");
+        assert_eq!(slugify("Default"), "profile");
+    }
+
+    #[test]
+    fn test_create_and_select_profiles() {
+        let root = TempDir::new().unwrap();
+
+        let listed = list_profiles(root.path()).unwrap();
+        assert_eq!(listed.active, DEFAULT_PROFILE_ID);
+        assert_eq!(listed.profiles, vec![default_profile()]);
+
+        let client = create_profile(root.path(), "Client", 10).unwrap();
+        assert_eq!(client.id, "client");
+        assert!(root.path().join("profiles").join("client").is_dir());
+
+        // Same slug, different name
+        let other = create_profile(root.path(), "client!", 20).unwrap();
+        assert_eq!(other.id, "client-2");
+
+        let err = create_profile(root.path(), "CLIENT", 30).unwrap_err();
+        assert_eq!(err.code, "duplicate_profile_name");
+        let err = create_profile(root.path(), "   ", 30).unwrap_err();
+        assert_eq!(err.code, "empty_profile_name");
+
+        let dir = select_profile(root.path(), "client").unwrap();
+        assert_eq!(dir, root.path().join("profiles").join("client"));
+        assert_eq!(list_profiles(root.path()).unwrap().active, "client");
+        assert_eq!(
+            select_profile(root.path(), DEFAULT_PROFILE_ID).unwrap(),
+            root.path()
+        );
+
+        let err = select_profile(root.path(), "missing").unwrap_err();
+        assert_eq!(err.code, "profile_not_found");
+    }
+
+    #[test]
+    fn test_delete_profile() {
+        let root = TempDir::new().unwrap();
+        create_profile(root.path(), "Client", 10).unwrap();
+        let client_dir = profile_dir(root.path(), "client");
+        fs::write(client_dir.join("database.db"), b"db").unwrap();
+
+        let err = delete_profile(root.path(), DEFAULT_PROFILE_ID).unwrap_err();
+        assert_eq!(err.code, "default_profile");
+
+        select_profile(root.path(), "client").unwrap();
+        let err = delete_profile(root.path(), "client").unwrap_err();
+        assert_eq!(err.code, "active_profile");
+
+        select_profile(root.path(), DEFAULT_PROFILE_ID).unwrap();
+        delete_profile(root.path(), "client").unwrap();
+        assert!(!client_dir.exists());
+        assert_eq!(list_profiles(root.path()).unwrap().profiles.len(), 1);
+
+        let err = delete_profile(root.path(), "client").unwrap_err();
+        assert_eq!(err.code, "profile_not_found");
+    }
+}
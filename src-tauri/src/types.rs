@@ -9,6 +9,16 @@ pub struct TimeEntry {
     pub label: String,
     pub color: Option<String>,
     pub category_id: Option<i64>,
+    /// Markdown.
+    pub notes: Option<String>,
+    pub project_id: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct TrashedTimeEntry {
+    #[serde(flatten)]
+    pub entry: TimeEntry,
+    pub deleted_at: i64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Type)]
@@ -18,6 +28,156 @@ pub struct TimeEntryInput {
     pub label: String,
     pub color: Option<String>,
     pub category_id: Option<i64>,
+    #[serde(default)]
+    pub notes: Option<String>,
+    #[serde(default)]
+    pub project_id: Option<i64>,
+}
+
+/// What to do with existing entries a new or moved entry would overlap.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "snake_case")]
+pub enum OverlapStrategy {
+    /// Fail with `time_entry_overlap`.
+    #[default]
+    Reject,
+    /// Cut the overlapping part off each neighbor; neighbors fully covered go
+    /// to the trash. A neighbor enclosing the entry is refused.
+    Trim,
+    /// Like `Trim`, but a neighbor enclosing the entry keeps both parts.
+    Split,
+    /// Cut back a neighbor that started earlier and move the ones starting
+    /// inside the entry to where it ends, pushing later entries along as far as
+    /// needed. A neighbor enclosing the entry is refused.
+    PushLater,
+}
+
+/// A saved entry together with the neighbors its overlap strategy changed.
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct OverlapResolution {
+    pub entry: TimeEntry,
+    /// Neighbors that were shortened, moved or split off, as saved.
+    pub modified: Vec<TimeEntry>,
+    /// Neighbors moved to the trash.
+    pub removed: Vec<i64>,
+}
+
+/// Which time entries a bulk edit applies to. Every given condition must hold.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Type)]
+#[serde(default)]
+pub struct TimeEntryFilter {
+    /// Entries intersecting `[start_time, end_time)`.
+    pub start_time: Option<i64>,
+    pub end_time: Option<i64>,
+    /// Case-insensitive substring of the label.
+    pub label_contains: Option<String>,
+    pub category_id: Option<i64>,
+    /// Entries overlapping time spent in this process.
+    pub process_name: Option<String>,
+}
+
+/// Change applied to every entry a bulk edit matches.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Type)]
+pub struct TimeEntryPatch {
+    pub label: Option<String>,
+    pub color: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_optional_optional_i64")]
+    pub category_id: Option<Option<i64>>,
+    /// Milliseconds to move both start and end by; negative moves earlier.
+    pub shift_ms: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct BulkEditChange {
+    pub before: TimeEntry,
+    pub after: TimeEntry,
+}
+
+/// The two halves of a split time entry.
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct TimeEntrySplit {
+    /// The original entry, now ending at the split point.
+    pub first: TimeEntry,
+    pub second: TimeEntry,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub struct EntryTemplate {
+    pub id: i64,
+    pub label: String,
+    pub color: Option<String>,
+    pub category_id: Option<i64>,
+    pub duration_ms: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct EntryTemplateInput {
+    pub label: String,
+    pub color: Option<String>,
+    pub category_id: Option<i64>,
+    pub duration_ms: i64,
+}
+
+/// When a recurring series repeats.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Recurrence {
+    Daily,
+    /// Monday to Friday.
+    Weekdays,
+    /// Every week on the given days, 1 = Monday through 7 = Sunday.
+    Weekly {
+        days: Vec<u32>,
+    },
+    /// `FREQ=DAILY|WEEKLY` with optional `INTERVAL`, `BYDAY`, `COUNT` and `UNTIL`.
+    Rrule {
+        rule: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub struct RecurringSeries {
+    pub id: i64,
+    pub template_id: i64,
+    /// Normalized RRULE, e.g. `FREQ=WEEKLY;BYDAY=MO,WE`.
+    pub rule: String,
+    /// Local time of day, in minutes after midnight.
+    pub start_minute: u32,
+    /// Local date of the first occurrence, `YYYY-MM-DD`.
+    pub starts_on: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct RecurringSeriesInput {
+    pub template_id: i64,
+    pub recurrence: Recurrence,
+    pub start_minute: u32,
+    pub starts_on: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Type)]
+pub struct MaterializeReport {
+    pub created: Vec<TimeEntry>,
+    /// Occurrences left out because their slot is taken; retried on the next run.
+    pub skipped: usize,
+}
+
+/// The entry the timer is counting.
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct RunningTimer {
+    /// `end_time` is the last heartbeat, not the end.
+    #[serde(flatten)]
+    pub entry: TimeEntry,
+    pub last_seen_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct TimerStart {
+    /// Defaults to now.
+    pub start_time: Option<i64>,
+    pub label: String,
+    pub color: Option<String>,
+    pub category_id: Option<i64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Type)]
@@ -28,6 +188,11 @@ pub struct TimeEntryUpdate {
     pub color: Option<String>,
     #[serde(default, deserialize_with = "deserialize_optional_optional_i64")]
     pub category_id: Option<Option<i64>>,
+    /// An empty string clears the notes.
+    #[serde(default)]
+    pub notes: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_optional_optional_i64")]
+    pub project_id: Option<Option<i64>>,
 }
 
 fn deserialize_optional_optional_i64<'de, D>(
@@ -61,9 +226,138 @@ pub struct WindowActivity {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Type)]
-pub struct ProcessSample {
-    pub timestamp: i64,
+pub struct ProcessSegment {
+    pub start_time: i64,
+    pub end_time: i64,
+    pub process_name: String,
+}
+
+/// Who work is billed to. Rates are in minor units of `currency` per hour.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub struct Client {
+    pub id: i64,
+    pub name: String,
+    pub hourly_rate_cents: Option<i64>,
+    /// ISO 4217 code, e.g. `EUR`.
+    pub currency: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct ClientInput {
+    pub name: String,
+    pub hourly_rate_cents: Option<i64>,
+    pub currency: String,
+}
+
+/// Rate and currency left unset fall back to the client's.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub struct Project {
+    pub id: i64,
+    pub client_id: i64,
+    pub name: String,
+    pub hourly_rate_cents: Option<i64>,
+    pub currency: Option<String>,
+    pub billable: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct ProjectInput {
+    pub client_id: i64,
+    pub name: String,
+    pub hourly_rate_cents: Option<i64>,
+    pub currency: Option<String>,
+    #[serde(default = "default_billable")]
+    pub billable: bool,
+}
+
+fn default_billable() -> bool {
+    true
+}
+
+/// Billable time on one project within some range.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub struct ProjectBilling {
+    pub project_id: i64,
+    pub project_name: String,
+    pub client_id: i64,
+    pub client_name: String,
+    pub billable_ms: i64,
+    /// The project's rate, else the client's; `None` when neither has one.
+    pub hourly_rate_cents: Option<i64>,
+    pub currency: String,
+    pub amount_cents: i64,
+}
+
+/// Billable time for one client in one currency within some range.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub struct ClientBilling {
+    pub client_id: i64,
+    pub client_name: String,
+    pub currency: String,
+    pub billable_ms: i64,
+    pub amount_cents: i64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, Type)]
+pub struct BillingSummary {
+    pub projects: Vec<ProjectBilling>,
+    pub clients: Vec<ClientBilling>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub struct Tag {
+    pub id: i64,
+    pub name: String,
+    pub color: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct TagInput {
+    pub name: String,
+    pub color: Option<String>,
+}
+
+/// One tag on one time entry.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub struct TimeEntryTag {
+    pub entry_id: i64,
+    pub tag_id: i64,
+}
+
+/// Time carrying one tag within some range.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub struct TagTotal {
+    pub tag_id: i64,
+    pub name: String,
+    pub color: Option<String>,
+    pub duration_ms: i64,
+    pub entry_count: i64,
+}
+
+/// Time spent in one process within some range.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub struct ProcessTotal {
+    pub process_name: String,
+    pub duration_ms: i64,
+}
+
+/// How often a window title was captured within some range.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub struct WindowTitleCount {
+    pub window_title: String,
     pub process_name: String,
+    pub samples: i64,
+}
+
+/// Working time not covered by any time entry, with what the computer was doing.
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct UntrackedGap {
+    pub start_time: i64,
+    pub end_time: i64,
+    /// Most used processes first.
+    pub processes: Vec<ProcessTotal>,
+    /// Most captured titles first.
+    pub window_titles: Vec<WindowTitleCount>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Type)]
@@ -121,6 +415,10 @@ pub struct ExportData {
     pub screenshots: Vec<ScreenshotRef>,
     pub window_activities: Vec<WindowActivity>,
     pub idle_periods: Vec<IdlePeriod>,
+    #[serde(default)]
+    pub tags: Vec<Tag>,
+    #[serde(default)]
+    pub time_entry_tags: Vec<TimeEntryTag>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Type)]
@@ -129,6 +427,194 @@ pub struct ScreenshotRef {
     pub file_path: String,
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub struct Profile {
+    /// Directory name under `profiles/`; `default` for the original data root.
+    pub id: String,
+    pub name: String,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct ProfileList {
+    pub active: String,
+    pub profiles: Vec<Profile>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct BackupInfo {
+    pub path: String,
+    pub created_at: i64,
+    pub size_bytes: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct IntegrityIssue {
+    pub kind: String,
+    pub table: String,
+    pub row_id: Option<i64>,
+    pub message: String,
+    pub action: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct IntegrityReport {
+    pub sqlite_ok: bool,
+    pub sqlite_messages: Vec<String>,
+    pub issues: Vec<IntegrityIssue>,
+    pub repaired: bool,
+    pub corrupt_copy_path: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub struct JournalChange {
+    pub entity: String,
+    pub entity_id: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct JournalReplay {
+    pub group_id: i64,
+    pub changes: Vec<JournalChange>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct UndoState {
+    pub can_undo: bool,
+    pub can_redo: bool,
+}
+
+/// What caused a change to a time entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "kebab-case")]
+pub enum RevisionSource {
+    Manual,
+    Drag,
+    IdleMerge,
+    Import,
+    Undo,
+    Redo,
+    Revert,
+}
+
+impl RevisionSource {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RevisionSource::Manual => "manual",
+            RevisionSource::Drag => "drag",
+            RevisionSource::IdleMerge => "idle-merge",
+            RevisionSource::Import => "import",
+            RevisionSource::Undo => "undo",
+            RevisionSource::Redo => "redo",
+            RevisionSource::Revert => "revert",
+        }
+    }
+}
+
+/// How the user edited a time entry; the sources an edit command accepts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Type)]
+#[serde(rename_all = "kebab-case")]
+pub enum EditSource {
+    Manual,
+    Drag,
+}
+
+impl From<EditSource> for RevisionSource {
+    fn from(source: EditSource) -> Self {
+        match source {
+            EditSource::Manual => RevisionSource::Manual,
+            EditSource::Drag => RevisionSource::Drag,
+        }
+    }
+}
+
+/// Stored values of a time entry at one point in its history.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Type)]
+pub struct TimeEntryState {
+    pub start_time: i64,
+    pub end_time: i64,
+    pub label: String,
+    pub color: Option<String>,
+    pub category_id: Option<i64>,
+    pub deleted_at: Option<i64>,
+    #[serde(default)]
+    pub notes: Option<String>,
+    #[serde(default)]
+    pub project_id: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct TimeEntryRevision {
+    pub id: i64,
+    pub entry_id: i64,
+    pub changed_at: i64,
+    /// "create", "update", "delete" or "restore"
+    pub action: String,
+    pub source: RevisionSource,
+    pub before: Option<TimeEntryState>,
+    pub after: Option<TimeEntryState>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct EncryptionStatus {
+    pub enabled: bool,
+    /// False while the passphrase has not been entered this session.
+    pub unlocked: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct EncryptionReport {
+    pub screenshots_converted: usize,
+    /// Stored paths of screenshots that could not be converted.
+    pub screenshots_failed: Vec<String>,
+}
+
+/// Rows removed (or, for a dry run, that would be removed) by the retention job.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, Type)]
+pub struct RetentionReport {
+    pub dry_run: bool,
+    pub process_segments: usize,
+    pub window_activity: usize,
+    pub screenshots: usize,
+    pub idle_periods: usize,
+    /// Disk space of the screenshot files removed.
+    pub screenshot_bytes: u64,
+    /// Stored paths of screenshot files that could not be deleted; their rows are kept.
+    pub screenshot_files_failed: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct MaintenanceReport {
+    pub optimized: bool,
+    /// Free pages handed back to the filesystem by incremental vacuum.
+    pub freed_pages: i64,
+    /// A reader kept the WAL open, so it could not be truncated this time.
+    pub wal_checkpoint_busy: bool,
+    pub wal_pages_checkpointed: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct TableRowCount {
+    pub name: String,
+    pub row_count: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Type)]
+pub struct DatabaseStats {
+    pub file_size_bytes: u64,
+    pub wal_size_bytes: u64,
+    pub page_size: i64,
+    pub page_count: i64,
+    /// Unused pages an incremental vacuum would release.
+    pub freelist_count: i64,
+    /// `none`, `full` or `incremental`
+    pub auto_vacuum: String,
+    pub tables: Vec<TableRowCount>,
+    pub last_checkpoint_at: Option<i64>,
+    /// Last optimize + incremental vacuum.
+    pub last_maintenance_at: Option<i64>,
+}
+
 // specta configuration for TypeScript type generation
 // Note: Type export is handled by specta::specta!() macro
 // which registers all types with the specta type system
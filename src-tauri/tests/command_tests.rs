@@ -34,6 +34,8 @@ mod tests {
             label: "Test Entry".to_string(),
             color: Some("#4CAF50".to_string()),
             category_id: None,
+            notes: None,
+            project_id: None,
         };
 
         // Execute the create_time_entry_impl function
@@ -131,6 +133,8 @@ mod tests {
             label: Some("Updated Label".to_string()),
             color: Some("#2196F3".to_string()),
             category_id: None,
+            notes: None,
+            project_id: None,
         };
 
         // Execute the update_time_entry_impl function
@@ -185,16 +189,25 @@ mod tests {
         let result = delete_time_entry_impl(&conn, entry_id);
         assert!(result.is_ok(), "Delete time entry should succeed");
 
-        // Verify the entry was deleted
+        // Verify the entry was moved to the trash
         let count: i64 = conn
             .query_row(
-                "SELECT COUNT(*) FROM time_entries WHERE id = ?",
+                "SELECT COUNT(*) FROM time_entries WHERE id = ? AND deleted_at IS NULL",
                 [entry_id],
                 |row| row.get(0),
             )
             .expect("Failed to query time entry");
 
         assert_eq!(count, 0, "Entry should be deleted");
+
+        let deleted_at: Option<i64> = conn
+            .query_row(
+                "SELECT deleted_at FROM time_entries WHERE id = ?",
+                [entry_id],
+                |row| row.get(0),
+            )
+            .expect("Trashed entry should still exist");
+        assert!(deleted_at.is_some(), "Entry should be marked as deleted");
     }
 
     #[test]
@@ -224,7 +237,7 @@ mod tests {
         .expect("Failed to insert time entry");
 
         // Execute the search_activities_impl function
-        let result = search_activities_impl(&conn, "work");
+        let result = search_activities_impl(&conn, "work", &[]);
         assert!(result.is_ok(), "Search activities should succeed");
 
         // Verify we got results
@@ -304,6 +317,8 @@ mod tests {
             label: Some("Updated Label".to_string()),
             color: None,
             category_id: None,
+            notes: None,
+            project_id: None,
         };
 
         let result = update_time_entry_impl(&conn, 99999, &update);
@@ -334,6 +349,8 @@ mod tests {
             label: "Invalid Entry".to_string(),
             color: Some("#4CAF50".to_string()),
             category_id: None,
+            notes: None,
+            project_id: None,
         };
 
         let result = create_time_entry_impl(&conn, &input);
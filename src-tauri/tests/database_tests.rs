@@ -7,7 +7,9 @@ mod tests {
     use tempfile::TempDir;
 
     // Import database functions from the main crate
-    use digital_diary::data::database::initialize_database;
+    use digital_diary::data::database::{
+        initialize_database, latest_schema_version, run_migrations,
+    };
 
     #[test]
     fn test_database_initialization() {
@@ -199,4 +201,41 @@ mod tests {
 
         assert_eq!(count, 2, "Should have 2 time entries");
     }
+
+    #[test]
+    fn test_upgrade_v1_database_file_to_latest_schema() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let db_path = temp_dir.path().join("legacy.db");
+
+        // Build a database as the first release shipped it: V1 schema, no version table
+        let conn = Connection::open(&db_path).expect("Failed to create database");
+        conn.execute_batch(include_str!("../src/data/migrations/V1__initial_schema.sql"))
+            .expect("Failed to apply V1 schema");
+        conn.execute(
+            "INSERT INTO time_entries (start_time, end_time, label, color) VALUES (?1, ?2, ?3, ?4)",
+            (1000i64, 2000i64, "Legacy Entry", Some("#4CAF50".to_string())),
+        )
+        .expect("Failed to insert time entry");
+        drop(conn);
+
+        initialize_database(&db_path).expect("Failed to upgrade database");
+
+        let conn = Connection::open(&db_path).expect("Failed to reopen database");
+        let version: i64 = conn
+            .query_row("SELECT MAX(version) FROM schema_migrations", [], |row| {
+                row.get(0)
+            })
+            .expect("Failed to query schema version");
+        assert_eq!(version, latest_schema_version(), "Should reach latest schema");
+
+        let category_id: Option<i64> = conn
+            .query_row("SELECT category_id FROM time_entries WHERE id = 1", [], |row| {
+                row.get(0)
+            })
+            .expect("Legacy entry should survive the upgrade");
+        assert_eq!(category_id, None);
+
+        // Re-running against an up-to-date database is a no-op
+        run_migrations(&conn).expect("Re-running migrations should succeed");
+    }
 }
@@ -1,5 +1,6 @@
 ﻿import React, { useState, useEffect, useRef } from 'react';
 import { useTimelineStore } from '../../services/store';
+import { errorMessage } from '../../services/api';
 import { CategorySelector } from './CategorySelector';
 
 interface TimerInputProps {
@@ -156,7 +157,7 @@ export const TimerInput: React.FC<TimerInputProps> = ({
         setCategoryId(undefined);
       } catch (error) {
         console.error('Failed to stop timer:', error);
-        alert(`停止计时失败：${error}`);
+        alert(`停止计时失败：${errorMessage(error)}`);
       } finally {
         setLoading(false);
       }
@@ -174,7 +175,7 @@ export const TimerInput: React.FC<TimerInputProps> = ({
         });
       } catch (error) {
         console.error('Failed to start timer:', error);
-        alert(`开始计时失败：${error}`);
+        alert(`开始计时失败：${errorMessage(error)}`);
       } finally {
         setLoading(false);
       }
@@ -199,7 +200,7 @@ export const TimerInput: React.FC<TimerInputProps> = ({
       setCategoryId(undefined);
     } catch (error) {
       console.error('Failed to delete running timer entry:', error);
-      alert(`删除当前行为失败：${error}`);
+      alert(`删除当前行为失败：${errorMessage(error)}`);
     } finally {
       setLoading(false);
     }
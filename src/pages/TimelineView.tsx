@@ -9,7 +9,16 @@ import { HoverInsightsTooltip } from '../components/timeline/HoverInsightsToolti
 import { TodaySearchBar } from '../components/search/TodaySearchBar';
 import { ExportButton } from '../components/export/ExportButton';
 import { useTimelineStore } from '../services/store';
-import { api, ScreenshotSettings, TimeEntry, TimeEntryInput, TimeEntryUpdate } from '../services/api';
+import {
+  api,
+  errorMessage,
+  isAppError,
+  EditSource,
+  ScreenshotSettings,
+  TimeEntry,
+  TimeEntryInput,
+  TimeEntryUpdate,
+} from '../services/api';
 import { TimerInput } from '../components/timeline/TimerInput';
 import { checkAndInstallUpdate, relaunchApp, toUpdaterErrorMessage } from '../services/updater';
 
@@ -64,6 +73,12 @@ export const TimelineView: React.FC = () => {
     queryFn: () => api.getTimeEntries(dayTimestamp),
   });
 
+  // The backend owns the running timer; the store only mirrors it for the UI
+  const { data: runningTimer, isFetched: isRunningTimerFetched } = useQuery({
+    queryKey: ['runningTimer'],
+    queryFn: () => api.getRunningTimer(),
+  });
+
   const { data: screenshotSettings } = useQuery({
     queryKey: ['screenshotSettings'],
     queryFn: () => api.getScreenshotSettings(),
@@ -82,16 +97,16 @@ export const TimelineView: React.FC = () => {
     refetchOnWindowFocus: true,
   });
 
-  const { data: processSamples = [] } = useQuery({
-    queryKey: ['processSamples', dayTimestamp],
-    queryFn: () => api.getProcessSamplesForDay(dayTimestamp),
+  const { data: processSegments = [] } = useQuery({
+    queryKey: ['processSegments', dayTimestamp],
+    queryFn: () => api.getProcessSegmentsForDay(dayTimestamp),
     refetchInterval: isSelectedDayToday ? 5000 : false,
     refetchOnWindowFocus: true,
   });
 
-  const sortedProcessSamples = React.useMemo(
-    () => [...processSamples].sort((a, b) => a.timestamp - b.timestamp),
-    [processSamples]
+  const sortedProcessSegments = React.useMemo(
+    () => [...processSegments].sort((a, b) => a.start_time - b.start_time),
+    [processSegments]
   );
 
   const categoryNameMap = React.useMemo(() => {
@@ -106,23 +121,21 @@ export const TimelineView: React.FC = () => {
     }
 
     const buckets = new Map<string, number>();
-    for (let i = 0; i < sortedProcessSamples.length; i += 1) {
-      const sample = sortedProcessSamples[i];
-      const nextTs = sortedProcessSamples[i + 1]?.timestamp ?? (sample.timestamp + 1000);
-      const overlapStart = Math.max(start, sample.timestamp);
-      const overlapEnd = Math.min(end, nextTs);
+    for (const segment of sortedProcessSegments) {
+      const overlapStart = Math.max(start, segment.start_time);
+      const overlapEnd = Math.min(end, segment.end_time);
       if (overlapEnd <= overlapStart) {
         continue;
       }
       const seconds = Math.max(1, Math.round((overlapEnd - overlapStart) / 1000));
-      buckets.set(sample.process_name, (buckets.get(sample.process_name) ?? 0) + seconds);
+      buckets.set(segment.process_name, (buckets.get(segment.process_name) ?? 0) + seconds);
     }
 
     return buckets;
-  }, [sortedProcessSamples]);
+  }, [sortedProcessSegments]);
 
   const processRuns = React.useMemo<ProcessRun[]>(() => {
-    if (sortedProcessSamples.length === 0) {
+    if (sortedProcessSegments.length === 0) {
       return [];
     }
 
@@ -137,10 +150,10 @@ export const TimelineView: React.FC = () => {
 
     const dayEnd = dayTimestamp + 86400000;
     const viewWindowEnd = isSelectedDayToday ? Math.min(dayEnd, Date.now()) : dayEnd;
-    const firstSampleTs = sortedProcessSamples[0].timestamp;
-    const lastSampleTs = sortedProcessSamples[sortedProcessSamples.length - 1].timestamp;
-    const windowStart = Math.max(dayTimestamp, firstSampleTs);
-    const windowEnd = Math.min(viewWindowEnd, lastSampleTs + 1000);
+    const firstSegmentStart = sortedProcessSegments[0].start_time;
+    const lastSegmentEnd = Math.max(...sortedProcessSegments.map((segment) => segment.end_time));
+    const windowStart = Math.max(dayTimestamp, firstSegmentStart);
+    const windowEnd = Math.min(viewWindowEnd, lastSegmentEnd);
 
     if (windowEnd <= windowStart) {
       return [];
@@ -189,7 +202,7 @@ export const TimelineView: React.FC = () => {
       .filter((run): run is ProcessRun => Boolean(run));
 
     return runs.filter((run) => run.endTime > run.startTime);
-  }, [sortedProcessSamples, dayTimestamp, isSelectedDayToday, timeEntries, aggregateProcessUsage]);
+  }, [sortedProcessSegments, dayTimestamp, isSelectedDayToday, timeEntries, aggregateProcessUsage]);
 
   const latestStartableEntry = React.useMemo(() => {
     if (timeEntries.length === 0) {
@@ -229,31 +242,31 @@ export const TimelineView: React.FC = () => {
     },
     onError: (error) => {
       console.error('Failed to create time entry:', error);
-      alert(`创建时间条目失败：${error}`);
+      alert(`创建时间条目失败：${errorMessage(error)}`);
     },
   });
 
   const formatUpdateEntryError = (error: unknown): string => {
-    const message = String(error ?? '');
-    if (message.includes('No updates provided')) {
-      return '未检测到可保存的修改。';
-    }
-    if (message.includes('Time entry overlaps with an existing entry')) {
-      return '时间与现有条目重叠，请调整开始/结束时间。';
-    }
-    if (message.includes('end_time must be greater than start_time')) {
-      return '结束时间必须晚于开始时间。';
+    if (isAppError(error)) {
+      if (error.code === 'no_updates') {
+        return '未检测到可保存的修改。';
+      }
+      if (error.code === 'time_entry_overlap') {
+        return '时间与现有条目重叠，请调整开始/结束时间。';
+      }
+      if (error.code === 'invalid_time_range') {
+        return '结束时间必须晚于开始时间。';
+      }
     }
-    return `更新条目失败：${message}`;
+    return `更新条目失败：${errorMessage(error)}`;
   };
 
-  const isMissingTimeEntryError = (error: unknown): boolean => {
-    const message = String(error ?? '');
-    return message.includes('Failed to fetch time entry') && message.includes('Query returned no rows');
-  };
+  const isMissingTimeEntryError = (error: unknown): boolean =>
+    isAppError(error) && error.kind === 'not_found';
 
   const updateMutation = useMutation({
-    mutationFn: ({ id, updates }: { id: number; updates: TimeEntryUpdate }) => api.updateTimeEntry(id, updates),
+    mutationFn: ({ id, updates, source }: { id: number; updates: TimeEntryUpdate; source?: EditSource }) =>
+      api.updateTimeEntry(id, updates, source),
     onSuccess: () => {
       invalidateEntryDerivedQueries();
       setEditEntryError(null);
@@ -286,13 +299,14 @@ export const TimelineView: React.FC = () => {
     onSuccess: (_, deletedId) => {
       if (activeTimer?.entryId === deletedId) {
         stopTimer();
+        queryClient.invalidateQueries({ queryKey: ['runningTimer'] });
       }
       invalidateEntryDerivedQueries();
       setEditingEntry(null);
     },
     onError: (error) => {
       console.error('Failed to delete time entry:', error);
-      alert(`删除条目失败：${error}`);
+      alert(`删除条目失败：${errorMessage(error)}`);
     },
   });
 
@@ -311,7 +325,7 @@ export const TimelineView: React.FC = () => {
     },
     onError: (error) => {
       console.error('Failed to update screenshot settings:', error);
-      alert(`保存截图设置失败：${error}`);
+      alert(`保存截图设置失败：${errorMessage(error)}`);
     },
   });
 
@@ -348,6 +362,7 @@ export const TimelineView: React.FC = () => {
           start_time: start,
           end_time: end,
         },
+        source: 'drag',
       },
       {
         onError: (error) => {
@@ -372,12 +387,10 @@ export const TimelineView: React.FC = () => {
       return;
     }
 
-    const now = Date.now();
     try {
-      await timerUpdateMutation.mutateAsync({
-        id: entry.id,
-        updates: { end_time: now },
-      });
+      await api.resumeTimer(entry.id);
+      invalidateEntryDerivedQueries();
+      queryClient.invalidateQueries({ queryKey: ['runningTimer'] });
 
       startTimer({
         entryId: entry.id,
@@ -389,18 +402,19 @@ export const TimelineView: React.FC = () => {
       setEditingEntry(null);
     } catch (error) {
       console.error('Failed to start entry:', error);
-      alert(`开始条目失败：${error}`);
+      alert(`开始条目失败：${errorMessage(error)}`);
     }
   };
 
   const handleStartTimer = async (label: string, startTime: number, categoryId?: number): Promise<number> => {
-    const entry = await createMutation.mutateAsync({
+    const timer = await api.startTimer({
       label,
       start_time: startTime,
-      end_time: startTime + 1000,
       category_id: categoryId,
     });
-    return entry.id;
+    invalidateEntryDerivedQueries();
+    queryClient.invalidateQueries({ queryKey: ['runningTimer'] });
+    return timer.id;
   };
 
   const handleStartFromDialog = async (draft: { label: string; categoryId?: number }) => {
@@ -430,15 +444,21 @@ export const TimelineView: React.FC = () => {
       setDialogSource('timeline');
     } catch (error) {
       console.error('Failed to start timer from entry dialog:', error);
-      alert(`开始计时失败：${error}`);
+      alert(`开始计时失败：${errorMessage(error)}`);
     }
   };
 
-  const handleStopTimer = async (entryId: number, endTime: number): Promise<void> => {
-    await timerUpdateMutation.mutateAsync({
-      id: entryId,
-      updates: { end_time: endTime },
-    });
+  const handleStopTimer = async (_entryId: number, endTime: number): Promise<void> => {
+    try {
+      await api.stopTimer(endTime);
+    } catch (error) {
+      // Already stopped elsewhere, e.g. by the startup policy
+      if (!isAppError(error) || error.code !== 'timer_not_running') {
+        throw error;
+      }
+    }
+    invalidateEntryDerivedQueries();
+    queryClient.invalidateQueries({ queryKey: ['runningTimer'] });
   };
 
   const handleDeleteCurrentTimer = async (entryId: number): Promise<void> => {
@@ -825,22 +845,40 @@ export const TimelineView: React.FC = () => {
     };
   }, []);
 
+  useEffect(() => {
+    if (!isRunningTimerFetched) {
+      return;
+    }
+
+    if (!runningTimer) {
+      stopTimer();
+      return;
+    }
+
+    if (activeTimer?.entryId !== runningTimer.id) {
+      startTimer({
+        entryId: runningTimer.id,
+        startTime: runningTimer.start_time,
+        label: runningTimer.label,
+        categoryId: runningTimer.category_id,
+      });
+    }
+  }, [isRunningTimerFetched, runningTimer]);
+
   useEffect(() => {
     if (!activeTimer) {
       return;
     }
 
+    // The backend heartbeat extends the running entry every 30 seconds
     const timerId = window.setInterval(() => {
-      timerUpdateMutation.mutate({
-        id: activeTimer.entryId,
-        updates: { end_time: Date.now() },
-      });
-    }, 3000);
+      invalidateEntryDerivedQueries();
+    }, 30000);
 
     return () => {
       window.clearInterval(timerId);
     };
-  }, [activeTimer?.entryId]);
+  }, [activeTimer?.entryId, invalidateEntryDerivedQueries]);
 
   const navigateDay = (offset: number) => {
     const newDate = new Date(selectedDate);
@@ -8,6 +8,13 @@ export interface TimeEntry {
   label: string;
   color?: string;
   category_id?: number;
+  // Markdown
+  notes?: string;
+  project_id?: number;
+}
+
+export interface TrashedTimeEntry extends TimeEntry {
+  deleted_at: number;
 }
 
 export interface TimeEntryInput {
@@ -16,6 +23,142 @@ export interface TimeEntryInput {
   label: string;
   color?: string;
   category_id?: number;
+  notes?: string;
+  project_id?: number;
+}
+
+// end_time is the last heartbeat while the timer runs
+export interface RunningTimer extends TimeEntry {
+  last_seen_at: number;
+}
+
+export interface TimerStart {
+  // Defaults to now
+  start_time?: number;
+  label: string;
+  color?: string;
+  category_id?: number;
+}
+
+export interface ProcessTotal {
+  process_name: string;
+  duration_ms: number;
+}
+
+export interface WindowTitleCount {
+  window_title: string;
+  process_name: string;
+  samples: number;
+}
+
+// Working time with no entry; processes and titles are most used first
+export interface UntrackedGap {
+  start_time: number;
+  end_time: number;
+  processes: ProcessTotal[];
+  window_titles: WindowTitleCount[];
+}
+
+export interface EntryTemplate {
+  id: number;
+  label: string;
+  color?: string;
+  category_id?: number;
+  duration_ms: number;
+}
+
+export interface EntryTemplateInput {
+  label: string;
+  color?: string;
+  category_id?: number;
+  duration_ms: number;
+}
+
+// Weekly days are 1 = Monday through 7 = Sunday; rrule supports FREQ=DAILY|WEEKLY,
+// INTERVAL, BYDAY, COUNT and UNTIL
+export type Recurrence =
+  | { kind: 'daily' }
+  | { kind: 'weekdays' }
+  | { kind: 'weekly'; days: number[] }
+  | { kind: 'rrule'; rule: string };
+
+export interface RecurringSeries {
+  id: number;
+  template_id: number;
+  // Normalized RRULE, e.g. FREQ=WEEKLY;BYDAY=MO,WE
+  rule: string;
+  // Local minutes after midnight
+  start_minute: number;
+  // YYYY-MM-DD
+  starts_on: string;
+}
+
+export interface RecurringSeriesInput {
+  template_id: number;
+  recurrence: Recurrence;
+  start_minute: number;
+  starts_on: string;
+}
+
+export interface MaterializeReport {
+  created: TimeEntry[];
+  // Occurrences whose slot was taken; retried on the next run
+  skipped: number;
+}
+
+export interface WorkingHoursSettings {
+  start_hour: number;
+  // Exclusive, 1-24
+  end_hour: number;
+  // 1 = Monday through 7 = Sunday
+  days: number[];
+}
+
+export interface TimerSettings {
+  // What to do with a timer still running when the app starts
+  on_startup: 'resume' | 'stop';
+}
+
+// What to do with existing entries a new or moved entry overlaps
+export type OverlapStrategy = 'reject' | 'trim' | 'split' | 'push_later';
+
+export interface OverlapResolution {
+  entry: TimeEntry;
+  // Neighbors shortened, moved or split off, as saved
+  modified: TimeEntry[];
+  // Neighbors moved to the trash
+  removed: number[];
+}
+
+// Every given condition must hold; at least one is required
+export interface TimeEntryFilter {
+  // Entries intersecting [start_time, end_time)
+  start_time?: number;
+  end_time?: number;
+  // Case-insensitive
+  label_contains?: string;
+  category_id?: number;
+  // Entries overlapping time spent in this process
+  process_name?: string;
+}
+
+export interface TimeEntryPatch {
+  label?: string;
+  color?: string;
+  category_id?: number | null;
+  // Moves start and end together; negative moves earlier
+  shift_ms?: number;
+}
+
+export interface BulkEditChange {
+  before: TimeEntry;
+  after: TimeEntry;
+}
+
+export interface TimeEntrySplit {
+  // The original entry, now ending at the split point
+  first: TimeEntry;
+  second: TimeEntry;
 }
 
 export interface TimeEntryUpdate {
@@ -24,6 +167,9 @@ export interface TimeEntryUpdate {
   label?: string;
   color?: string;
   category_id?: number | null;
+  // An empty string clears the notes
+  notes?: string;
+  project_id?: number | null;
 }
 
 export interface Category {
@@ -50,6 +196,249 @@ export interface ScreenshotSettings {
   storage_dir?: string;
 }
 
+export interface BackupSettings {
+  enabled: boolean;
+  directory?: string | null;
+  keep_count: number;
+}
+
+export interface Profile {
+  id: string;
+  name: string;
+  created_at: number;
+}
+
+export interface ProfileList {
+  // id of the profile whose data is currently open
+  active: string;
+  profiles: Profile[];
+}
+
+export interface BackupInfo {
+  path: string;
+  created_at: number;
+  size_bytes: number;
+}
+
+export interface EncryptionStatus {
+  enabled: boolean;
+  // false until the passphrase has been entered this session
+  unlocked: boolean;
+}
+
+export interface EncryptionReport {
+  screenshots_converted: number;
+  screenshots_failed: string[];
+}
+
+export interface IntegrityIssue {
+  // invalid_range | overlap | dangling_category | missing_screenshot_file | foreign_key
+  kind: string;
+  table: string;
+  row_id?: number | null;
+  message: string;
+  // Set when repaired: trimmed | quarantined | cleared_category
+  action?: string | null;
+}
+
+export interface IntegrityReport {
+  sqlite_ok: boolean;
+  sqlite_messages: string[];
+  issues: IntegrityIssue[];
+  repaired: boolean;
+  corrupt_copy_path?: string | null;
+}
+
+export interface TrashSettings {
+  // 0 keeps trashed entries until purged manually
+  retention_days: number;
+}
+
+// Days to keep each kind of captured data; 0 keeps it forever
+export interface RetentionSettings {
+  process_segments_days: number;
+  window_activity_days: number;
+  screenshots_days: number;
+  idle_periods_days: number;
+}
+
+export interface RetentionReport {
+  dry_run: boolean;
+  process_segments: number;
+  window_activity: number;
+  screenshots: number;
+  idle_periods: number;
+  screenshot_bytes: number;
+  // Screenshot files that could not be deleted; their rows are kept
+  screenshot_files_failed: string[];
+}
+
+// Window (local hours) for the daily optimize + vacuum; WAL checkpoints run hourly
+export interface MaintenanceSettings {
+  enabled: boolean;
+  idle_start_hour: number;
+  // Exclusive; may be below idle_start_hour to wrap past midnight
+  idle_end_hour: number;
+}
+
+export interface MaintenanceReport {
+  optimized: boolean;
+  freed_pages: number;
+  // A reader kept the WAL open so it could not be truncated
+  wal_checkpoint_busy: boolean;
+  wal_pages_checkpointed: number;
+}
+
+export interface TableRowCount {
+  name: string;
+  row_count: number;
+}
+
+export interface DatabaseStats {
+  file_size_bytes: number;
+  wal_size_bytes: number;
+  page_size: number;
+  page_count: number;
+  freelist_count: number;
+  auto_vacuum: 'none' | 'full' | 'incremental';
+  tables: TableRowCount[];
+  last_checkpoint_at?: number | null;
+  last_maintenance_at?: number | null;
+}
+
+export interface JournalChange {
+  entity: 'time_entries' | 'categories';
+  entity_id: number;
+}
+
+// Rows touched by an undo/redo step, so views know what to reload
+export interface JournalReplay {
+  group_id: number;
+  changes: JournalChange[];
+}
+
+export interface UndoState {
+  can_undo: boolean;
+  can_redo: boolean;
+}
+
+export type RevisionSource =
+  | 'manual'
+  | 'drag'
+  | 'idle-merge'
+  | 'import'
+  | 'undo'
+  | 'redo'
+  | 'revert';
+
+// Sources an edit from the UI may be recorded with
+export type EditSource = 'manual' | 'drag';
+
+export interface TimeEntryState {
+  start_time: number;
+  end_time: number;
+  label: string;
+  color: string | null;
+  category_id: number | null;
+  deleted_at: number | null;
+  notes?: string | null;
+  project_id?: number | null;
+}
+
+export interface TimeEntryRevision {
+  id: number;
+  entry_id: number;
+  changed_at: number;
+  action: 'create' | 'update' | 'delete' | 'restore';
+  source: RevisionSource;
+  before: TimeEntryState | null;
+  after: TimeEntryState | null;
+}
+
+export interface Tag {
+  id: number;
+  name: string;
+  color?: string;
+}
+
+export interface TagInput {
+  name: string;
+  color?: string;
+}
+
+export interface TimeEntryTag {
+  entry_id: number;
+  tag_id: number;
+}
+
+// Time carrying one tag within a range; an entry with several tags counts for each
+export interface TagTotal {
+  tag_id: number;
+  name: string;
+  color?: string;
+  duration_ms: number;
+  entry_count: number;
+}
+
+// Amounts are in minor units (cents) of `currency`, an ISO 4217 code
+export interface Client {
+  id: number;
+  name: string;
+  hourly_rate_cents?: number;
+  currency: string;
+}
+
+export interface ClientInput {
+  name: string;
+  hourly_rate_cents?: number;
+  currency: string;
+}
+
+// Rate and currency fall back to the client's when unset
+export interface Project {
+  id: number;
+  client_id: number;
+  name: string;
+  hourly_rate_cents?: number;
+  currency?: string;
+  billable: boolean;
+}
+
+export interface ProjectInput {
+  client_id: number;
+  name: string;
+  hourly_rate_cents?: number;
+  currency?: string;
+  // Defaults to true
+  billable?: boolean;
+}
+
+export interface ProjectBilling {
+  project_id: number;
+  project_name: string;
+  client_id: number;
+  client_name: string;
+  billable_ms: number;
+  // Effective rate; null when neither project nor client has one
+  hourly_rate_cents: number | null;
+  currency: string;
+  amount_cents: number;
+}
+
+// One row per client and currency
+export interface ClientBilling {
+  client_id: number;
+  client_name: string;
+  currency: string;
+  billable_ms: number;
+  amount_cents: number;
+}
+
+export interface BillingSummary {
+  projects: ProjectBilling[];
+  clients: ClientBilling[];
+}
+
 export interface SearchResult {
   type: 'time_entry' | 'window_activity';
   timestamp: number;
@@ -57,11 +446,37 @@ export interface SearchResult {
   process_name?: string;
 }
 
-export interface ProcessSample {
-  timestamp: number;
+// Uninterrupted stretch of one foreground process, clipped to the requested day
+export interface ProcessSegment {
+  start_time: number;
+  end_time: number;
   process_name: string;
 }
 
+export type ErrorKind = 'validation' | 'not_found' | 'conflict' | 'database' | 'io' | 'internal';
+
+export interface ErrorDetails {
+  conflicting_entry_ids: number[];
+  field?: string | null;
+}
+
+// Shape of every error returned by data commands
+export interface AppError {
+  kind: ErrorKind;
+  code: string;
+  message: string;
+  details?: ErrorDetails | null;
+}
+
+export const isAppError = (error: unknown): error is AppError =>
+  typeof error === 'object' &&
+  error !== null &&
+  typeof (error as AppError).kind === 'string' &&
+  typeof (error as AppError).message === 'string';
+
+export const errorMessage = (error: unknown): string =>
+  isAppError(error) ? error.message : String(error ?? '');
+
 export interface ExportData {
   version: string;
   exported_at: string;
@@ -79,6 +494,8 @@ export interface ExportData {
     end_time: number;
     resolution?: string;
   }>;
+  tags: Tag[];
+  time_entry_tags: TimeEntryTag[];
 }
 
 // API functions
@@ -87,18 +504,155 @@ export const api = {
   getTimeEntries: (date: number): Promise<TimeEntry[]> =>
     invoke('get_time_entries', { date }),
 
-  getTimeEntriesByRange: (startTime: number, endTime: number): Promise<TimeEntry[]> =>
-    invoke('get_time_entries_by_range', { startTime, endTime }),
+  // With tagIds, only entries carrying every one of those tags
+  getTimeEntriesByRange: (startTime: number, endTime: number, tagIds?: number[]): Promise<TimeEntry[]> =>
+    invoke('get_time_entries_by_range', { startTime, endTime, tagIds }),
 
   createTimeEntry: (entry: TimeEntryInput): Promise<TimeEntry> =>
     invoke('create_time_entry', { entry }),
 
-  updateTimeEntry: (id: number, updates: TimeEntryUpdate): Promise<TimeEntry> =>
-    invoke('update_time_entry', { id, updates }),
+  updateTimeEntry: (
+    id: number,
+    updates: TimeEntryUpdate,
+    source?: EditSource
+  ): Promise<TimeEntry> =>
+    invoke('update_time_entry', { id, updates, source }),
+
+  createTimeEntryWithStrategy: (
+    entry: TimeEntryInput,
+    strategy: OverlapStrategy
+  ): Promise<OverlapResolution> =>
+    invoke('create_time_entry_with_strategy_cmd', { entry, strategy }),
+
+  updateTimeEntryWithStrategy: (
+    id: number,
+    updates: TimeEntryUpdate,
+    strategy: OverlapStrategy,
+    source?: EditSource
+  ): Promise<OverlapResolution> =>
+    invoke('update_time_entry_with_strategy_cmd', { id, updates, strategy, source }),
 
   deleteTimeEntry: (id: number): Promise<void> =>
     invoke('delete_time_entry', { id }),
 
+  // The second half keeps label and category unless given
+  splitTimeEntry: (
+    id: number,
+    at: number,
+    newLabel?: string,
+    newCategoryId?: number
+  ): Promise<TimeEntrySplit> =>
+    invoke('split_time_entry_cmd', { id, at, newLabel, newCategoryId }),
+
+  // Merged into the earliest entry; absorbGaps bridges the time between entries
+  mergeTimeEntries: (
+    ids: number[],
+    label?: string,
+    categoryId?: number,
+    absorbGaps = false
+  ): Promise<TimeEntry> =>
+    invoke('merge_time_entries_cmd', { ids, label, categoryId, absorbGaps }),
+
+  // Bulk edit: the preview changes nothing, the edit is one undo step
+  previewBulkEdit: (filter: TimeEntryFilter, patch: TimeEntryPatch): Promise<BulkEditChange[]> =>
+    invoke('preview_bulk_edit_cmd', { filter, patch }),
+
+  bulkEditTimeEntries: (filter: TimeEntryFilter, patch: TimeEntryPatch): Promise<BulkEditChange[]> =>
+    invoke('bulk_edit_time_entries_cmd', { filter, patch }),
+
+  // minGapMs defaults to 5 minutes
+  getUntrackedGaps: (startTime: number, endTime: number, minGapMs?: number): Promise<UntrackedGap[]> =>
+    invoke('get_untracked_gaps_cmd', { startTime, endTime, minGapMs }),
+
+  getWorkingHoursSettings: (): Promise<WorkingHoursSettings> =>
+    invoke('get_working_hours_settings_cmd'),
+
+  updateWorkingHoursSettings: (settings: WorkingHoursSettings): Promise<WorkingHoursSettings> =>
+    invoke('update_working_hours_settings_cmd', { settings }),
+
+  // Templates and recurring entries
+  listEntryTemplates: (): Promise<EntryTemplate[]> =>
+    invoke('list_entry_templates_cmd'),
+
+  createEntryTemplate: (template: EntryTemplateInput): Promise<EntryTemplate> =>
+    invoke('create_entry_template_cmd', { template }),
+
+  updateEntryTemplate: (id: number, template: EntryTemplateInput): Promise<EntryTemplate> =>
+    invoke('update_entry_template_cmd', { id, template }),
+
+  deleteEntryTemplate: (id: number): Promise<void> =>
+    invoke('delete_entry_template_cmd', { id }),
+
+  listRecurringSeries: (): Promise<RecurringSeries[]> =>
+    invoke('list_recurring_series_cmd'),
+
+  createRecurringSeries: (series: RecurringSeriesInput): Promise<RecurringSeries> =>
+    invoke('create_recurring_series_cmd', { series }),
+
+  // Upcoming entries already created are recreated on the new schedule
+  updateRecurringSeries: (id: number, series: RecurringSeriesInput): Promise<RecurringSeries> =>
+    invoke('update_recurring_series_cmd', { id, series }),
+
+  deleteRecurringSeries: (id: number, deleteUpcoming?: boolean): Promise<void> =>
+    invoke('delete_recurring_series_cmd', { id, deleteUpcoming }),
+
+  materializeRecurringEntries: (): Promise<MaterializeReport> =>
+    invoke('materialize_recurring_entries_cmd'),
+
+  // Running timer
+  startTimer: (start: TimerStart): Promise<RunningTimer> =>
+    invoke('start_timer_cmd', { start }),
+
+  resumeTimer: (id: number): Promise<RunningTimer> =>
+    invoke('resume_timer_cmd', { id }),
+
+  // Omit endTime to stop now
+  stopTimer: (endTime?: number): Promise<TimeEntry> =>
+    invoke('stop_timer_cmd', { endTime }),
+
+  getRunningTimer: (): Promise<RunningTimer | null> =>
+    invoke('get_running_timer_cmd'),
+
+  getTimerSettings: (): Promise<TimerSettings> =>
+    invoke('get_timer_settings_cmd'),
+
+  updateTimerSettings: (settings: TimerSettings): Promise<TimerSettings> =>
+    invoke('update_timer_settings_cmd', { settings }),
+
+  // Undo / redo (null when there is nothing to undo or redo)
+  undo: (): Promise<JournalReplay | null> =>
+    invoke('undo_cmd'),
+
+  redo: (): Promise<JournalReplay | null> =>
+    invoke('redo_cmd'),
+
+  getUndoState: (): Promise<UndoState> =>
+    invoke('get_undo_state_cmd'),
+
+  // Revision history
+  getTimeEntryRevisions: (entryId: number): Promise<TimeEntryRevision[]> =>
+    invoke('get_time_entry_revisions_cmd', { entryId }),
+
+  revertTimeEntry: (entryId: number, revisionId: number): Promise<TimeEntryState> =>
+    invoke('revert_time_entry_cmd', { entryId, revisionId }),
+
+  // Trash
+  getTrashedTimeEntries: (): Promise<TrashedTimeEntry[]> =>
+    invoke('get_trashed_time_entries_cmd'),
+
+  restoreTimeEntry: (id: number): Promise<TimeEntry> =>
+    invoke('restore_time_entry_cmd', { id }),
+
+  // Omit ids to empty the whole trash
+  purgeTrash: (ids?: number[]): Promise<number> =>
+    invoke('purge_trash_cmd', { ids }),
+
+  getTrashSettings: (): Promise<TrashSettings> =>
+    invoke('get_trash_settings_cmd'),
+
+  updateTrashSettings: (settings: TrashSettings): Promise<TrashSettings> =>
+    invoke('update_trash_settings_cmd', { settings }),
+
   // Screenshots
   getScreenshotForTime: (timestamp: number): Promise<ScreenshotInfo> =>
     invoke('get_screenshot_for_time', { timestamp }),
@@ -106,8 +660,8 @@ export const api = {
   getScreenshotTimestampsForDay: (date: number): Promise<number[]> =>
     invoke('get_screenshot_timestamps_for_day', { date }),
 
-  getProcessSamplesForDay: (date: number): Promise<ProcessSample[]> =>
-    invoke('get_process_samples_for_day', { date }),
+  getProcessSegmentsForDay: (date: number): Promise<ProcessSegment[]> =>
+    invoke('get_process_segments_for_day', { date }),
 
   getScreenshotSettings: (): Promise<ScreenshotSettings> =>
     invoke('get_screenshot_settings_cmd'),
@@ -122,16 +676,106 @@ export const api = {
     invoke('resolve_screenshot_file_path_cmd', { storedPath }),
 
   // Search
-  searchActivities: (query: string): Promise<SearchResult[]> =>
-    invoke('search_activities_cmd', { query }),
+  // A tag filter leaves only time entries, since window activity has no tags
+  searchActivities: (query: string, tagIds?: number[]): Promise<SearchResult[]> =>
+    invoke('search_activities_cmd', { query, tagIds }),
 
-  searchActivitiesByRange: (query: string, startTime: number, endTime: number): Promise<SearchResult[]> =>
-    invoke('search_activities_by_range_cmd', { query, startTime, endTime }),
+  searchActivitiesByRange: (
+    query: string,
+    startTime: number,
+    endTime: number,
+    tagIds?: number[]
+  ): Promise<SearchResult[]> =>
+    invoke('search_activities_by_range_cmd', { query, startTime, endTime, tagIds }),
 
   // Export
   exportData: (): Promise<ExportData> =>
     invoke('export_data_cmd'),
 
+  // Backups
+  backupDatabase: (destination?: string): Promise<BackupInfo> =>
+    invoke('backup_database_cmd', { destination }),
+
+  listBackups: (): Promise<BackupInfo[]> =>
+    invoke('list_backups_cmd'),
+
+  // Resolves with the safety copy taken of the database being replaced
+  restoreDatabase: (path: string): Promise<BackupInfo> =>
+    invoke('restore_database_cmd', { path }),
+
+  getBackupSettings: (): Promise<BackupSettings> =>
+    invoke('get_backup_settings_cmd'),
+
+  updateBackupSettings: (settings: BackupSettings): Promise<BackupSettings> =>
+    invoke('update_backup_settings_cmd', { settings }),
+
+  // Profiles
+  listProfiles: (): Promise<ProfileList> =>
+    invoke('list_profiles_cmd'),
+
+  createProfile: (name: string): Promise<Profile> =>
+    invoke('create_profile_cmd', { name }),
+
+  // Reopens the app data against the chosen profile; an encrypted profile
+  // needs unlockDatabase afterwards
+  switchProfile: (id: string): Promise<ProfileList> =>
+    invoke('switch_profile_cmd', { id }),
+
+  // Deletes the profile's database and screenshots; not allowed for the active profile
+  deleteProfile: (id: string): Promise<void> =>
+    invoke('delete_profile_cmd', { id }),
+
+  // Data retention
+  getRetentionSettings: (): Promise<RetentionSettings> =>
+    invoke('get_retention_settings_cmd'),
+
+  updateRetentionSettings: (settings: RetentionSettings): Promise<RetentionSettings> =>
+    invoke('update_retention_settings_cmd', { settings }),
+
+  // Dry run; pass unsaved settings to see what they would remove
+  previewRetention: (settings?: RetentionSettings): Promise<RetentionReport> =>
+    invoke('preview_retention_cmd', { settings }),
+
+  runRetention: (): Promise<RetentionReport> =>
+    invoke('run_retention_cmd'),
+
+  // Database maintenance
+  getDatabaseStats: (): Promise<DatabaseStats> =>
+    invoke('get_database_stats_cmd'),
+
+  runDatabaseMaintenance: (): Promise<MaintenanceReport> =>
+    invoke('run_database_maintenance_cmd'),
+
+  getMaintenanceSettings: (): Promise<MaintenanceSettings> =>
+    invoke('get_maintenance_settings_cmd'),
+
+  updateMaintenanceSettings: (settings: MaintenanceSettings): Promise<MaintenanceSettings> =>
+    invoke('update_maintenance_settings_cmd', { settings }),
+
+  // Diagnostics
+  checkDatabaseIntegrity: (repair = false): Promise<IntegrityReport> =>
+    invoke('check_database_integrity_cmd', { repair }),
+
+  // Encryption at rest
+  getEncryptionStatus: (): Promise<EncryptionStatus> =>
+    invoke('get_encryption_status_cmd'),
+
+  unlockDatabase: (passphrase: string): Promise<EncryptionStatus> =>
+    invoke('unlock_database_cmd', { passphrase }),
+
+  // Calling again with the same passphrase retries screenshots_failed
+  enableEncryption: (passphrase: string): Promise<EncryptionReport> =>
+    invoke('enable_encryption_cmd', { passphrase }),
+
+  changeEncryptionPassphrase: (
+    currentPassphrase: string,
+    newPassphrase: string
+  ): Promise<EncryptionReport> =>
+    invoke('change_encryption_passphrase_cmd', { currentPassphrase, newPassphrase }),
+
+  disableEncryption: (passphrase: string): Promise<EncryptionReport> =>
+    invoke('disable_encryption_cmd', { passphrase }),
+
   // Categories
   getCategories: (): Promise<Category[]> =>
     invoke('get_categories'),
@@ -145,6 +789,60 @@ export const api = {
   deleteCategory: (id: number): Promise<void> =>
     invoke('delete_category', { id }),
 
+  // Tags
+  getTags: (): Promise<Tag[]> =>
+    invoke('get_tags_cmd'),
+
+  createTag: (tag: TagInput): Promise<Tag> =>
+    invoke('create_tag_cmd', { tag }),
+
+  updateTag: (id: number, tag: TagInput): Promise<Tag> =>
+    invoke('update_tag_cmd', { id, tag }),
+
+  deleteTag: (id: number): Promise<void> =>
+    invoke('delete_tag_cmd', { id }),
+
+  getTimeEntryTags: (entryId: number): Promise<Tag[]> =>
+    invoke('get_time_entry_tags_cmd', { entryId }),
+
+  // Replaces the entry's tags
+  setTimeEntryTags: (entryId: number, tagIds: number[]): Promise<Tag[]> =>
+    invoke('set_time_entry_tags_cmd', { entryId, tagIds }),
+
+  getTimeEntryTagsByRange: (startTime: number, endTime: number): Promise<TimeEntryTag[]> =>
+    invoke('get_time_entry_tags_by_range_cmd', { startTime, endTime }),
+
+  getTagTotals: (startTime: number, endTime: number): Promise<TagTotal[]> =>
+    invoke('get_tag_totals_cmd', { startTime, endTime }),
+
+  // Clients & projects
+  getClients: (): Promise<Client[]> =>
+    invoke('get_clients_cmd'),
+
+  createClient: (client: ClientInput): Promise<Client> =>
+    invoke('create_client_cmd', { client }),
+
+  updateClient: (id: number, client: ClientInput): Promise<Client> =>
+    invoke('update_client_cmd', { id, client }),
+
+  deleteClient: (id: number): Promise<void> =>
+    invoke('delete_client_cmd', { id }),
+
+  getProjects: (): Promise<Project[]> =>
+    invoke('get_projects_cmd'),
+
+  createProject: (project: ProjectInput): Promise<Project> =>
+    invoke('create_project_cmd', { project }),
+
+  updateProject: (id: number, project: ProjectInput): Promise<Project> =>
+    invoke('update_project_cmd', { id, project }),
+
+  deleteProject: (id: number): Promise<void> =>
+    invoke('delete_project_cmd', { id }),
+
+  getBillingSummary: (startTime: number, endTime: number): Promise<BillingSummary> =>
+    invoke('get_billing_summary_cmd', { startTime, endTime }),
+
   // Idle
   resolveIdlePeriod: (resolution: {
     id: number;